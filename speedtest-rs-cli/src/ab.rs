@@ -0,0 +1,241 @@
+//! `speedtest-rs ab`: compares two tagged groups of runs with Welch's t-test, to tell a real
+//! improvement from day-to-day noise.
+
+use std::{path::Path, time::Duration};
+
+use color_eyre::eyre;
+use speedtest_rs_core::result::SpeedTestResult;
+
+use crate::{args::HistoryFilter, history::History};
+
+/// Differences with a lower p-value are reported as significant.
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// Extracts a figure of a run, `None` if it was not measured.
+type Metric = fn(&SpeedTestResult) -> Option<f64>;
+
+/// Summary of one metric of a group of runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample variance, zero for a single value.
+    pub variance: f64,
+}
+
+impl Sample {
+    pub fn new(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = if count > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let mid = count / 2;
+        let median = if count.is_multiple_of(2) {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        };
+
+        Some(Self {
+            count,
+            mean,
+            median,
+            variance,
+        })
+    }
+}
+
+/// Two-sided p-value of Welch's t-test for different means, `None` with fewer than two values
+/// in either group.
+pub fn welch_p_value(a: &Sample, b: &Sample) -> Option<f64> {
+    if a.count < 2 || b.count < 2 {
+        return None;
+    }
+    let (va, vb) = (a.variance / a.count as f64, b.variance / b.count as f64);
+    if va + vb == 0.0 {
+        // Identical values in both groups: either the same or certainly different.
+        return Some(if a.mean == b.mean { 1.0 } else { 0.0 });
+    }
+
+    let t = (a.mean - b.mean) / (va + vb).sqrt();
+    let df =
+        (va + vb).powi(2) / (va.powi(2) / (a.count - 1) as f64 + vb.powi(2) / (b.count - 1) as f64);
+    Some(incomplete_beta(df / (df + t * t), df / 2.0, 0.5))
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`.
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+
+    // The continued fraction converges quickly below this point, use the symmetry above it.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_fraction(1.0 - x, b, a) / b
+    }
+}
+
+/// Continued fraction of the incomplete beta function, evaluated with Lentz's method.
+fn beta_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let clamp = |v: f64| if v.abs() < TINY { TINY } else { v };
+
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+    for m in 1..200 {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / clamp(1.0 + even * d);
+        c = clamp(1.0 + even / c);
+        h *= d * c;
+
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / clamp(1.0 + odd * d);
+        c = clamp(1.0 + odd / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    h
+}
+
+/// Lanczos approximation of `ln(Γ(x))` for positive `x`.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    (2.506_628_274_631_000_5 * series / x).ln() + (x + 0.5) * tmp.ln() - tmp
+}
+
+fn metric(results: &[SpeedTestResult], f: Metric) -> Option<Sample> {
+    Sample::new(&results.iter().filter_map(f).collect::<Vec<_>>())
+}
+
+pub fn run(
+    tag_a: &str,
+    tag_b: &str,
+    since: Option<Duration>,
+    history: Option<&Path>,
+) -> eyre::Result<()> {
+    let history = History::open_or_default(history)?;
+    let group = |tag: &str| {
+        history.query(&HistoryFilter {
+            since,
+            tag: Some(tag.to_string()),
+            ..Default::default()
+        })
+    };
+    let (a, b) = (group(tag_a)?, group(tag_b)?);
+    for (tag, runs) in [(tag_a, &a), (tag_b, &b)] {
+        if runs.is_empty() {
+            eyre::bail!("no runs tagged '{tag}', tag runs with --tag {tag}");
+        }
+    }
+
+    println!(
+        "A = {tag_a} ({} runs), B = {tag_b} ({} runs)\n",
+        a.len(),
+        b.len()
+    );
+    println!(
+        "{:<10} {:>10} {:>10} {:>10} {:>10} {:>8}  Difference",
+        "", "Mean A", "Mean B", "Median A", "Median B", "Change"
+    );
+
+    let metrics: [(&str, &str, Metric); 3] = [
+        ("Download", "Mbps", |r| {
+            r.download.map(|d| d.bits_per_second / 1e6)
+        }),
+        ("Upload", "Mbps", |r| {
+            r.upload.map(|u| u.bits_per_second / 1e6)
+        }),
+        ("Latency", "ms", |r| r.idle_latency.map(|l| l.avg_ms)),
+    ];
+    for (label, unit, f) in metrics {
+        let (Some(sa), Some(sb)) = (metric(&a, f), metric(&b, f)) else {
+            println!("{label:<10} not measured in both groups");
+            continue;
+        };
+        let change = if sa.mean != 0.0 {
+            format!("{:+.1}%", (sb.mean - sa.mean) / sa.mean * 100.0)
+        } else {
+            "-".to_string()
+        };
+        let verdict = match welch_p_value(&sa, &sb) {
+            None => "too few runs to tell".to_string(),
+            Some(p) if p < SIGNIFICANCE_LEVEL => format!("significant (p = {p:.3})"),
+            Some(p) => format!("not significant (p = {p:.3})"),
+        };
+        let value = |v: f64| format!("{v:.1} {unit}");
+        println!(
+            "{label:<10} {:>10} {:>10} {:>10} {:>10} {change:>8}  {verdict}",
+            value(sa.mean),
+            value(sb.mean),
+            value(sa.median),
+            value(sb.median),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ab::{Sample, welch_p_value};
+
+    #[test]
+    fn test_sample() {
+        let sample = Sample::new(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!(sample.count, 4);
+        assert_eq!(sample.mean, 2.5);
+        assert_eq!(sample.median, 2.5);
+        assert!((sample.variance - 5.0 / 3.0).abs() < 1e-12);
+        assert_eq!(Sample::new(&[]), None);
+    }
+
+    #[test]
+    fn test_welch_p_value() {
+        let a = Sample::new(&[1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+        let b = Sample::new(&[6.0, 7.0, 8.0, 9.0, 10.0]).unwrap();
+        // t = -5 with 8 degrees of freedom.
+        let p = welch_p_value(&a, &b).unwrap();
+        assert!((p - 0.001_052).abs() < 1e-5, "{p}");
+
+        let c = Sample::new(&[2.0, 4.0, 3.0, 5.0, 1.0]).unwrap();
+        assert!((welch_p_value(&a, &c).unwrap() - 1.0).abs() < 1e-9);
+
+        let single = Sample::new(&[3.0]).unwrap();
+        assert_eq!(welch_p_value(&a, &single), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! `--demo`: drives the TUI from a canned event script without any network access, for
+//! screenshots, recordings and checking layout changes.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::Utc;
+use speedtest_rs_core::{
+    latency::Latency,
+    model::{Client, Server},
+    result::{SpeedTestResult, TransferResult},
+    sampler::Sampler,
+    stage::{self, RaceProbe, Stage, StageEvent},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    app::SimpleConfig,
+    event::{AppEvent, Event, State, Status, TICK_INTERVAL},
+};
+
+/// Length of each transfer phase.
+const TRANSFER_DURATION: Duration = Duration::from_secs(8);
+
+/// Interval the transfer counters are advanced at.
+const TRANSFER_STEP: Duration = Duration::from_millis(100);
+
+const DOWNLOAD_BPS: f64 = 240_000_000.0;
+
+const UPLOAD_BPS: f64 = 38_000_000.0;
+
+/// Canned servers with their race latencies in milliseconds, `None` for a lost probe.
+pub(crate) fn servers() -> Vec<(Server, [Option<u64>; 3])> {
+    let server = |id: &str, sponsor: &str, name: &str, cc: &str, host: &str| {
+        let mut server = Server::from_url(&format!("http://{host}:8080/speedtest/upload.php"))
+            .expect("canned server URL");
+        server.lat = 22.25;
+        server.lon = 114.17;
+        server.name = name.to_string();
+        server.country = name.to_string();
+        server.cc = cc.to_string();
+        server.sponsor = sponsor.to_string();
+        server.id = id.to_string();
+        server
+    };
+
+    vec![
+        (
+            server(
+                "35791",
+                "SmarTone",
+                "Hong Kong",
+                "HK",
+                "speedtest.example.net",
+            ),
+            [Some(14), Some(12), Some(13)],
+        ),
+        (
+            server("61296", "HKIX", "Hong Kong", "HK", "hkix.example.net"),
+            [Some(7), Some(9), Some(8)],
+        ),
+        (
+            server("65463", "HKBN", "Hong Kong", "HK", "hkbn.example.net"),
+            [Some(11), None, Some(10)],
+        ),
+        (
+            server("71541", "MTel", "Macao", "MO", "mtel.example.net"),
+            [Some(21), Some(24), Some(22)],
+        ),
+        (
+            server(
+                "8968",
+                "Homeplus",
+                "Kaohsiung",
+                "TW",
+                "homeplus.example.net",
+            ),
+            [Some(38), Some(35), Some(41)],
+        ),
+        (
+            server(
+                "18456",
+                "Chunghwa Mobile",
+                "Taichung",
+                "TW",
+                "chtm.example.net",
+            ),
+            [None, None, None],
+        ),
+        (
+            server("65464", "HKBN", "Hong Kong", "HK", "hkbn2.example.net"),
+            [Some(12), Some(11), Some(13)],
+        ),
+    ]
+}
+
+pub(crate) fn client() -> Client {
+    Client {
+        ip: "203.0.113.45".to_string(),
+        lat: 22.2796,
+        lon: 114.1887,
+        isp: "Example Broadband".to_string(),
+        isprating: 3.7,
+        rating: 0.0,
+        ispdlavg: 0.0,
+        ispulavg: 0.0,
+        loggedin: 0,
+        country: "HK".to_string(),
+    }
+}
+
+fn latency(ms: &[u64]) -> Option<Latency> {
+    let samples: Vec<Duration> = ms.iter().copied().map(Duration::from_millis).collect();
+    Latency::from_samples(&samples, 0)
+}
+
+/// Plays the script, sending the same events as a real run.
+pub async fn run(
+    sender: mpsc::UnboundedSender<Event>,
+    downloaded: Arc<AtomicU64>,
+    uploaded: Arc<AtomicU64>,
+) {
+    let timestamp = Utc::now();
+    let send = |event: Event| _ = sender.send(event);
+    let pause = |ms| tokio::time::sleep(Duration::from_millis(ms));
+
+    let client = client();
+    send(State::FetchConfig(Status::Start).into());
+    send(
+        StageEvent::Started {
+            stage: Stage::Discovery,
+        }
+        .into(),
+    );
+    pause(400).await;
+    send(
+        State::FetchConfig(Status::Ok(SimpleConfig {
+            ip: client.ip.clone(),
+            latitude: client.lat.to_string(),
+            longitude: client.lon.to_string(),
+            isp: client.isp.clone(),
+            country: client.country.clone(),
+            download_duration: TRANSFER_DURATION,
+            upload_duration: TRANSFER_DURATION,
+        }))
+        .into(),
+    );
+
+    let servers = servers();
+    send(State::FetchServers(Status::Start).into());
+    pause(300).await;
+    send(
+        StageEvent::Finished {
+            stage: Stage::Discovery,
+        }
+        .into(),
+    );
+    send(
+        State::FetchServers(Status::Ok(
+            servers.iter().map(|(server, _)| server.clone()).collect(),
+        ))
+        .into(),
+    );
+
+    send(State::RacingServers(Status::Start).into());
+    let race = async {
+        for attempt in 0..3 {
+            pause(400).await;
+            for (server, delays) in &servers {
+                send(
+                    AppEvent::RaceProbe(RaceProbe::new(
+                        server.id.clone(),
+                        attempt + 1,
+                        delays[attempt].map(Duration::from_millis),
+                    ))
+                    .into(),
+                );
+            }
+        }
+    };
+    stage::report(Stage::Racing, None, &sender, race).await;
+    let server = servers[1].0.clone();
+    send(State::RacingServers(Status::Ok(Box::new(server.clone()))).into());
+
+    send(State::IdleLatency(Status::Start).into());
+    stage::report(Stage::Latency, None, &sender, pause(1500)).await;
+    let idle_latency = latency(&[8, 7, 9, 8, 8, 10, 7]);
+    send(State::IdleLatency(Status::Ok(idle_latency)).into());
+
+    let sampler = |phase, counter| {
+        Sampler::new(
+            phase,
+            counter,
+            stage::sample_interval(TRANSFER_DURATION).max(TICK_INTERVAL),
+        )
+    };
+
+    send(State::Download(Status::Start).into());
+    let mut download_sampler = sampler(Stage::Download, downloaded.clone());
+    let download = download_sampler.run(&sender, transfer(&downloaded, DOWNLOAD_BPS));
+    let download = stage::report(Stage::Download, Some(&downloaded), &sender, download).await;
+    let download_latency = latency(&[18, 24, 31, 27, 22, 35, 29]);
+    send(State::Download(Status::Ok(download_latency)).into());
+
+    send(State::Upload(Status::Start).into());
+    let mut upload_sampler = sampler(Stage::Upload, uploaded.clone());
+    let upload = upload_sampler.run(&sender, transfer(&uploaded, UPLOAD_BPS));
+    let upload = stage::report(Stage::Upload, Some(&uploaded), &sender, upload).await;
+    let upload_latency = latency(&[12, 15, 14, 19, 16]);
+    send(State::Upload(Status::Ok(upload_latency)).into());
+
+    let mut result = SpeedTestResult::new(timestamp, &client, &server)
+        .with_download(download)
+        .with_upload(upload);
+    result.idle_latency = idle_latency;
+    result.download_latency = download_latency;
+    result.upload_latency = upload_latency;
+    send(AppEvent::Finished(Box::new(result)).into());
+}
+
+/// Advances `counter` along a ramp-up with a gentle wobble around `bps`.
+async fn transfer(counter: &AtomicU64, bps: f64) -> TransferResult {
+    let steps = TRANSFER_DURATION.as_millis() / TRANSFER_STEP.as_millis();
+    let mut total = 0;
+    for step in 0..steps {
+        tokio::time::sleep(TRANSFER_STEP).await;
+
+        let t = step as f64 / steps as f64;
+        let ramp = (t * 6.0).min(1.0);
+        let wobble = 1.0 + 0.08 * (step as f64 * 0.7).sin();
+        let bytes = (bps * ramp * wobble / 8.0 * TRANSFER_STEP.as_secs_f64()) as u64;
+
+        total += bytes;
+        counter.fetch_add(bytes, Ordering::Relaxed);
+    }
+    TransferResult::new(total, TRANSFER_DURATION)
+}
@@ -1,27 +1,61 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     sync::{
         Arc,
         atomic::{AtomicU64, Ordering},
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use chrono::Utc;
+use color_eyre::eyre;
+use crossterm::{
+    clipboard::CopyToClipboard,
+    event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal::SetTitle,
+};
 use ratatui::DefaultTerminal;
-use speedtest_rs_core::{model::Server, speed_tester::SpeedTester};
-use tokio::sync::mpsc;
+use speedtest_rs_core::{
+    cpu::CpuTime,
+    ifstats::{CounterCheck, IdleTraffic, InterfaceCounters, detect_link},
+    latency::Latency,
+    model::{Server, group_by_sponsor},
+    nat::detect_nat,
+    redact::{Redact, redact_coordinate, redact_ip},
+    result::{Diagnostics, RejectedServer, SpeedTestResult},
+    sampler::Sampler,
+    size::ByteSize,
+    speed_tester::SpeedTester,
+    stage::{ProgressTracker, Stage, StageEvent},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
 
 use crate::{
-    app::progress::Progress,
-    event::{AppEvent, Event, EventHandler, State, Status},
+    app::{partial::PartialRun, progress::Progress, scale::AxisScale, session::SessionBundle},
+    data_cap::DataCap,
+    event::{AppEvent, Event, EventHandler, NetworkIssue, State, Status, TICK_INTERVAL},
+    hooks::Hooks,
+    log::LogBuffer,
+    race_cache::RaceCache,
+    sink::{Sinks, write_atomic},
+    theme::Theme,
 };
 
+pub mod demo;
+pub mod partial;
 pub mod progress;
+pub mod scale;
+pub mod session;
+
+pub(crate) const MAX_RECORDS_LEN: usize = 20;
+
+#[cfg(target_os = "linux")]
+const IDLE_CHECK_DURATION: Duration = Duration::from_secs(3);
 
-const MAX_RECORDS_LEN: usize = 20;
+const IDLE_LATENCY_DURATION: Duration = Duration::from_secs(2);
 
-const RECORD_INTERVAL_SECS: f32 = 0.5;
+/// Typical duration of the server racing, which has no fixed length.
+const RACING_DURATION: Duration = Duration::from_secs(3);
 
 #[derive(Debug)]
 pub struct App {
@@ -33,11 +67,13 @@ pub struct App {
 
     pub fetch_servers: Progress<Vec<Server>>,
 
-    pub racing_servers: Progress<Server>,
+    pub racing_servers: Progress<Box<Server>>,
 
-    pub download: Progress<()>,
+    pub idle_latency: Progress<Option<Latency>>,
 
-    pub upload: Progress<()>,
+    pub download: Progress<Option<Latency>>,
+
+    pub upload: Progress<Option<Latency>>,
 
     pub downloaded: Arc<AtomicU64>,
 
@@ -51,13 +87,81 @@ pub struct App {
 
     pub uploaded_data: VecDeque<u64>,
 
-    pub last_download_time: Option<Instant>,
+    pub download_scale: AxisScale,
+
+    pub upload_scale: AxisScale,
+
+    /// Narrow layout for phone terminals.
+    pub compact: bool,
+
+    /// Hide identifying client details.
+    pub redact: bool,
+
+    /// Data the run likely transfers, shown until it completes.
+    pub usage_estimate: Option<ByteSize>,
+
+    /// What the run measured so far, kept if the user quits before it completes.
+    pub partial: PartialRun,
+
+    pub result: Option<SpeedTestResult>,
+
+    pub sinks: Sinks,
+
+    pub hooks: Hooks,
+
+    /// Completion hooks still running, awaited before exiting.
+    pending_hooks: Vec<JoinHandle<()>>,
+
+    pub options: RunOptions,
+
+    /// Live probe statistics of the servers being raced, by server id.
+    pub racing: HashMap<String, RaceStats>,
+
+    /// Number of ticks since start, drives animations.
+    pub ticks: usize,
+
+    /// Background traffic measured by the idle pre-check.
+    pub idle_traffic: Option<IdleTraffic>,
+
+    /// Why the run was aborted by the network pre-checks.
+    pub network_issue: Option<NetworkIssue>,
+
+    /// Tracks the stage events of the core for the overall progress.
+    progress: ProgressTracker,
+
+    title_percent: Option<u8>,
+
+    /// The summary was copied to the clipboard.
+    pub copied: bool,
+
+    /// Show the debug log pane instead of the config and servers.
+    pub show_debug: bool,
+
+    /// Show the wall-clock start and finish time of every stage.
+    pub show_timings: bool,
+
+    /// Show how the result was measured instead of the config and servers.
+    pub show_methodology: bool,
+
+    /// List one row per sponsor in the server table instead of every server.
+    pub group_servers: bool,
 
-    pub last_download_count: Option<u64>,
+    pub logs: LogBuffer,
 
-    pub last_upload_time: Option<Instant>,
+    pub event_stats: EventStats,
 
-    pub last_upload_count: Option<u64>,
+    /// One-off message shown in the footer, e.g. where the session was saved.
+    pub notice: Option<String>,
+
+    /// Note being typed after the run finished, `None` unless the prompt is open.
+    pub note_input: Option<String>,
+
+    /// Row id of the result in the history, to attach a note later.
+    history_id: Option<i64>,
+
+    pub started: Instant,
+
+    pub theme: Theme,
 
     shutdown_tx: tokio::sync::watch::Sender<bool>,
 
@@ -75,6 +179,7 @@ impl Default for App {
             fetch_config: Progress::new("Fetch Config"),
             fetch_servers: Progress::new("Fetch Servers"),
             racing_servers: Progress::new("Racing Servers"),
+            idle_latency: Progress::new("Idle Latency"),
             download: Progress::new("Download"),
             upload: Progress::new("Upload"),
 
@@ -86,10 +191,37 @@ impl Default for App {
 
             downloaded_data: VecDeque::with_capacity(MAX_RECORDS_LEN),
             uploaded_data: VecDeque::with_capacity(MAX_RECORDS_LEN),
-            last_download_time: None,
-            last_upload_time: None,
-            last_download_count: None,
-            last_upload_count: None,
+            download_scale: AxisScale::default(),
+            upload_scale: AxisScale::default(),
+
+            compact: false,
+            redact: false,
+            usage_estimate: None,
+            partial: PartialRun::default(),
+
+            result: None,
+            sinks: Sinks::default(),
+            hooks: Hooks::default(),
+            pending_hooks: vec![],
+            options: RunOptions::default(),
+            racing: HashMap::new(),
+            ticks: 0,
+            idle_traffic: None,
+            network_issue: None,
+            progress: ProgressTracker::new().with_expected(Stage::Racing, RACING_DURATION),
+            title_percent: None,
+            copied: false,
+            show_debug: false,
+            show_timings: false,
+            show_methodology: false,
+            group_servers: true,
+            logs: LogBuffer::default(),
+            event_stats: EventStats::default(),
+            notice: None,
+            note_input: None,
+            history_id: None,
+            started: Instant::now(),
+            theme: Theme::default(),
 
             shutdown_tx,
             shutdown_rx,
@@ -103,26 +235,111 @@ impl App {
         Self::default()
     }
 
-    pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
+    pub fn with_speed_tester(mut self, speed_tester: SpeedTester) -> Self {
+        self.speed_tester = speed_tester;
+        self
+    }
+
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    pub fn with_usage_estimate(mut self, estimate: Option<ByteSize>) -> Self {
+        self.usage_estimate = estimate;
+        self
+    }
+
+    pub fn with_redact(mut self, redact: bool) -> Self {
+        self.redact = redact;
+        self
+    }
+
+    pub fn with_sinks(mut self, sinks: Sinks) -> Self {
+        self.sinks = sinks;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_logs(mut self, logs: LogBuffer) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn with_options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Runs the TUI until the user quits, returning the result if the run completed.
+    pub async fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+    ) -> color_eyre::Result<Option<SpeedTestResult>> {
         self.spawn_speed_test();
 
         while self.running {
             terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
-
-            match self.events.next().await? {
-                Event::Tick => self.tick(),
-                Event::Crossterm(event) => {
-                    if let crossterm::event::Event::Key(key_event) = event {
-                        self.handle_key_events(key_event)?;
+            self.update_title()?;
+
+            // Handle everything that queued up since the last frame before drawing again, a
+            // single tick per frame is enough.
+            let mut ticked = false;
+            let mut next = Some(self.events.next().await?);
+            while let Some(event) = next.take() {
+                match event {
+                    Event::Tick if ticked => self.event_stats.dropped_ticks += 1,
+                    Event::Tick => {
+                        ticked = true;
+                        self.tick();
+                    }
+                    Event::Crossterm(event) => {
+                        if let crossterm::event::Event::Key(key_event) = event {
+                            self.handle_key_events(key_event)?;
+                        }
                     }
+                    Event::App(app_event) => self.handle_app_events(app_event)?,
+                }
+
+                next = self.events.try_next();
+                if next.is_some() {
+                    self.event_stats.coalesced += 1;
                 }
-                Event::App(app_event) => self.handle_app_events(app_event)?,
             }
         }
-        Ok(())
+        tracing::debug!(
+            "coalesced {} events, dropped {} ticks",
+            self.event_stats.coalesced,
+            self.event_stats.dropped_ticks
+        );
+
+        for hook in self.pending_hooks.drain(..) {
+            _ = hook.await;
+        }
+        Ok(self.result)
     }
 
     pub fn handle_key_events(&mut self, key_event: KeyEvent) -> color_eyre::Result<()> {
+        // Windows consoles report both key presses and releases, handle each key only once.
+        if key_event.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+        self.notice = None;
+
+        if self.note_input.is_some() {
+            self.handle_note_key(key_event);
+            return Ok(());
+        }
+
         match key_event.code {
             KeyCode::Esc | KeyCode::Char('q') => self.events.send(AppEvent::Quit),
             KeyCode::Char('c' | 'C' | 'd' | 'D')
@@ -136,63 +353,115 @@ impl App {
             KeyCode::Char('k') | KeyCode::Up => {
                 self.scroll_up();
             }
+            KeyCode::Char('y') => self.copy_summary()?,
+            KeyCode::Char('d') => self.show_debug = !self.show_debug,
+            KeyCode::Char('t') => self.show_timings = !self.show_timings,
+            KeyCode::Char('m') => self.show_methodology = !self.show_methodology,
+            KeyCode::Char('g') => {
+                self.group_servers = !self.group_servers;
+                self.max_servers_scroll = self.server_rows();
+                self.servers_scroll = self.servers_scroll.min(self.max_servers_scroll);
+            }
+            KeyCode::Char('L') => self.save_session(),
+            KeyCode::Char('n') => {
+                if let Some(result) = &self.result {
+                    self.note_input = Some(result.note.clone().unwrap_or_default());
+                }
+            }
             _ => (),
         }
         Ok(())
     }
 
+    /// Edits the note prompt, saving the note on enter.
+    fn handle_note_key(&mut self, key_event: KeyEvent) {
+        let Some(input) = &mut self.note_input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Esc => self.note_input = None,
+            KeyCode::Backspace => _ = input.pop(),
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Enter => {
+                let note = self.note_input.take();
+                if let Some(result) = self.result.take() {
+                    let result = result.with_note(note);
+                    self.notice = Some(match self.sinks.update_note(self.history_id, &result) {
+                        Ok(()) => "Note saved".to_string(),
+                        Err(e) => format!("Failed to save note: {e}"),
+                    });
+                    self.result = Some(result);
+                }
+            }
+            _ => (),
+        }
+    }
+
     pub fn handle_app_events(&mut self, app_event: AppEvent) -> color_eyre::Result<()> {
         match app_event {
             AppEvent::Quit => self.quit(),
+            AppEvent::RaceProbe(probe) => {
+                let stats = self.racing.entry(probe.server_id).or_default();
+                stats.attempts = stats.attempts.max(probe.attempt);
+                match probe.delay {
+                    Some(delay) => stats.delays.push(delay),
+                    None => stats.failures += 1,
+                }
+            }
+            AppEvent::IdleTraffic(idle_traffic) => self.idle_traffic = Some(idle_traffic),
+            AppEvent::Throughput(sample) => match sample.phase {
+                Stage::Download => self.downloaded_data.push_back(sample.bytes_per_sec as u64),
+                Stage::Upload => self.uploaded_data.push_back(sample.bytes_per_sec as u64),
+                _ => {}
+            },
+            AppEvent::Stage(event) => _ = self.progress.update(&event),
+            AppEvent::NetworkIssue(issue) => self.network_issue = Some(issue),
+            AppEvent::Finished(result) => {
+                let result = if self.redact {
+                    result.redact()
+                } else {
+                    *result
+                };
+                let result = self.options.annotate(result);
+                match self.sinks.write(&result) {
+                    Ok(history_id) => self.history_id = history_id,
+                    Err(e) => tracing::warn!("failed to write result: {}", e),
+                }
+                self.pending_hooks = self.hooks.post_run(&result);
+                self.result = Some(result);
+            }
             AppEvent::SetState(state) => {
+                self.partial.apply(&state, Instant::now());
                 let should_cancel = if state.is_error() {
                     Some(state.cancel_after())
                 } else {
                     None
                 };
+                if let State::FetchConfig(Status::Ok(config)) = &state {
+                    let latency = self
+                        .speed_tester
+                        .idle_latency_duration(IDLE_LATENCY_DURATION);
+                    self.progress = std::mem::take(&mut self.progress)
+                        .with_expected(Stage::Latency, latency)
+                        .with_expected(Stage::Download, config.download_duration)
+                        .with_expected(Stage::Upload, config.upload_duration);
+                }
                 match state {
+                    State::FetchConfig(Status::Ok(config)) if self.redact => {
+                        self.fetch_config.apply_status(Status::Ok(config.redact()))
+                    }
                     State::FetchConfig(st) => self.fetch_config.apply_status(st),
                     State::FetchServers(st) => {
-                        if let Status::Ok(servers) = &st {
-                            self.max_servers_scroll = servers.len();
-                        }
                         self.fetch_servers.apply_status(st);
+                        self.max_servers_scroll = self.server_rows();
                     }
+                    State::RacingServers(Status::Ok(server)) if self.redact => self
+                        .racing_servers
+                        .apply_status(Status::Ok(Box::new(server.redact()))),
                     State::RacingServers(st) => self.racing_servers.apply_status(st),
-                    State::Download(st) => {
-                        match &st {
-                            Status::Start => self.last_download_time = Some(Instant::now()),
-                            Status::Ok(_) | Status::Err(_) => {
-                                let downloaded = self.downloaded.load(Ordering::SeqCst)
-                                    - self.last_download_count.unwrap_or(0);
-                                let elapsed =
-                                    self.last_download_time.unwrap().elapsed().as_secs_f32();
-
-                                self.downloaded_data
-                                    .push_back((downloaded as f32 / elapsed) as u64);
-                                self.last_download_count = Some(downloaded);
-                            }
-                            _ => {}
-                        }
-                        self.download.apply_status(st)
-                    }
-                    State::Upload(st) => {
-                        match &st {
-                            Status::Start => self.last_upload_time = Some(Instant::now()),
-                            Status::Ok(_) | Status::Err(_) => {
-                                let uploaded = self.uploaded.load(Ordering::SeqCst)
-                                    - self.last_upload_count.unwrap_or(0);
-                                let elapsed =
-                                    self.last_upload_time.unwrap().elapsed().as_secs_f32();
-
-                                self.uploaded_data
-                                    .push_back((uploaded as f32 / elapsed) as u64);
-                                self.last_upload_count = Some(uploaded);
-                            }
-                            _ => {}
-                        }
-                        self.upload.apply_status(st)
-                    }
+                    State::IdleLatency(st) => self.idle_latency.apply_status(st),
+                    State::Download(st) => self.download.apply_status(st),
+                    State::Upload(st) => self.upload.apply_status(st),
                 };
 
                 if let Some(cancel_list) = should_cancel {
@@ -216,73 +485,184 @@ impl App {
         self.servers_scroll = self.servers_scroll.saturating_sub(1);
     }
 
-    pub fn tick(&mut self) {
-        if let Some(start) = self.last_download_time
-            && let Status::Start = self.download.status()
-        {
-            let now = Instant::now();
-            let elapsed = now.duration_since(start).as_secs_f32();
-
-            if elapsed >= RECORD_INTERVAL_SECS {
-                let current_downloaded = self.downloaded.load(Ordering::SeqCst);
-
-                let speed =
-                    (current_downloaded - self.last_download_count.unwrap_or(0)) as f32 / elapsed;
-
-                self.downloaded_data.push_back(speed as u64);
-                self.last_download_count = Some(current_downloaded);
-                self.last_download_time = Some(now);
+    /// Rows of the server table: one per server, or one per sponsor while grouped.
+    pub fn server_rows(&self) -> usize {
+        match self.fetch_servers.status() {
+            Status::Ok(servers) if self.group_servers => {
+                group_by_sponsor(servers.iter().cloned()).len()
             }
+            Status::Ok(servers) => servers.len(),
+            _ => 0,
         }
+    }
 
-        if let Some(start) = self.last_upload_time
-            && let Status::Start = self.upload.status()
-        {
-            let now = Instant::now();
-            let elapsed = now.duration_since(start).as_secs_f32();
+    /// Intervals the download and upload throughput are sampled at, adapted to the transfer
+    /// durations of the remote config and never shorter than a tick.
+    pub fn sample_intervals(&self) -> (Duration, Duration) {
+        let (download, upload) = match self.fetch_config.status() {
+            Status::Ok(config) => (config.download_duration, config.upload_duration),
+            _ => Default::default(),
+        };
+        (
+            sample_interval(&self.speed_tester, download),
+            sample_interval(&self.speed_tester, upload),
+        )
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+        self.download_scale.update(
+            self.min_download_byte_ps() as f64 * 8.0,
+            self.max_download_byte_ps() as f64 * 8.0,
+        );
+        self.upload_scale.update(
+            self.min_upload_byte_ps() as f64 * 8.0,
+            self.max_upload_byte_ps() as f64 * 8.0,
+        );
+    }
 
-            if elapsed >= RECORD_INTERVAL_SECS {
-                let current_uploaded = self.uploaded.load(Ordering::SeqCst);
+    /// Copies the one-line summary to the clipboard with an OSC 52 sequence, which also works over
+    /// SSH and in Termux.
+    fn copy_summary(&mut self) -> color_eyre::Result<()> {
+        if let Some(result) = &self.result {
+            crossterm::execute!(
+                std::io::stdout(),
+                CopyToClipboard::to_clipboard_from(result.summary_line())
+            )?;
+            self.copied = true;
+        }
+        Ok(())
+    }
 
-                let speed =
-                    (current_uploaded - self.last_upload_count.unwrap_or(0)) as f32 / elapsed;
+    /// Writes the session bundle for bug reports, reporting the outcome in the footer.
+    fn save_session(&mut self) {
+        let bundle = SessionBundle::from_app(self);
+        let written = bundle
+            .default_path()
+            .ok_or_else(|| eyre::eyre!("unable to determine the data directory"))
+            .and_then(|path| {
+                write_atomic(&path, &serde_json::to_vec_pretty(&bundle)?)?;
+                Ok(path)
+            });
+
+        self.notice = Some(match written {
+            Ok(path) => format!("Session saved to {}", path.display()),
+            Err(e) => format!("Failed to save session: {e}"),
+        });
+    }
 
-                self.uploaded_data.push_back(speed as u64);
-                self.last_upload_count = Some(current_uploaded);
-                self.last_upload_time = Some(now);
-            }
+    /// Shows the overall progress in the terminal title, only writing when the percentage changed.
+    fn update_title(&mut self) -> color_eyre::Result<()> {
+        let percent = (self.overall_progress() * 100.0) as u8;
+        if self.title_percent != Some(percent) {
+            crossterm::execute!(
+                std::io::stdout(),
+                SetTitle(format!("speedtest-rs {percent}%"))
+            )?;
+            self.title_percent = Some(percent);
         }
+        Ok(())
     }
 
     pub fn quit(&mut self) {
         _ = self.shutdown_tx.send(true);
+        if self.result.is_none() {
+            self.keep_partial_result();
+        }
         self.running = false
     }
 
+    /// Records what a run quit midway measured, marked aborted. No hooks run for it, the run did
+    /// not complete.
+    fn keep_partial_result(&mut self) {
+        let Some(result) = self.partial.result(
+            self.downloaded.load(Ordering::SeqCst),
+            self.uploaded.load(Ordering::SeqCst),
+            Instant::now(),
+        ) else {
+            return;
+        };
+        let result = if self.redact { result.redact() } else { result };
+        let result = self.options.annotate(result);
+        match self.sinks.write(&result) {
+            Ok(history_id) => self.history_id = history_id,
+            Err(e) => tracing::warn!("failed to write aborted result: {}", e),
+        }
+        self.result = Some(result);
+    }
+
     fn spawn_speed_test(&self) {
         let speed_tester = self.speed_tester.clone();
         let sender = self.events.clone_sender();
         let downloaded = self.downloaded.clone();
         let uploaded = self.uploaded.clone();
+        let options = self.options.clone();
         let mut shutdown = self.shutdown_rx.clone();
 
         tokio::spawn(async move {
             tokio::select! {
                 biased;
                 _ = shutdown.changed() => {},
-                _ = App::speedtest(speed_tester, sender, downloaded, uploaded) => {}
+                _ = async {
+                    if options.demo {
+                        demo::run(sender, downloaded, uploaded).await;
+                    } else {
+                        App::speedtest(speed_tester, options, sender, downloaded, uploaded).await;
+                    }
+                } => {}
             };
         });
     }
 
     pub async fn speedtest(
         speed_tester: SpeedTester,
+        options: RunOptions,
         sender: mpsc::UnboundedSender<Event>,
         downloaded: Arc<AtomicU64>,
         uploaded: Arc<AtomicU64>,
     ) {
+        let timestamp = Utc::now();
+
+        // Sample before any test traffic, otherwise our own requests would be counted.
+        #[cfg(target_os = "linux")]
+        let idle_traffic = if options.idle_check {
+            speedtest_rs_core::ifstats::sample_idle_traffic(IDLE_CHECK_DURATION).await
+        } else {
+            None
+        };
+        // `run_options` refuses `--idle-check` on other platforms.
+        #[cfg(not(target_os = "linux"))]
+        let idle_traffic: Option<IdleTraffic> = None;
+        if let Some(idle_traffic) = idle_traffic {
+            _ = sender.send(AppEvent::IdleTraffic(idle_traffic).into());
+        }
+
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<StageEvent>();
+        let progress_sender = sender.clone();
+        tokio::spawn(async move {
+            while let Some(event) = progress_rx.recv().await {
+                _ = progress_sender.send(event.into());
+            }
+        });
+        let speed_tester = speed_tester.with_progress(Some(progress_tx));
+
         _ = sender.send(State::FetchConfig(Status::Start).into());
 
+        // Fail in seconds when offline, and do not race servers behind a login page which would
+        // only measure the portal.
+        let precheck = match speed_tester.check_connectivity().await {
+            Ok(()) => speed_tester
+                .check_captive_portal()
+                .await
+                .map_err(|e| (NetworkIssue::CaptivePortal, e)),
+            Err(e) => Err((NetworkIssue::NoConnectivity, e)),
+        };
+        if let Err((issue, e)) = precheck {
+            _ = sender.send(AppEvent::NetworkIssue(issue).into());
+            _ = sender.send(State::FetchConfig(Status::Err(e.to_string())).into());
+            return;
+        }
+
         let config = match speed_tester.fetch_config().await {
             Ok(config) => {
                 _ = sender.send(State::FetchConfig(Status::Ok((&config).into())).into());
@@ -296,40 +676,281 @@ impl App {
 
         _ = sender.send(State::FetchServers(Status::Start).into());
 
-        let servers = match speed_tester.fetch_servers(config.threads()).await {
-            Ok(servers) => {
-                _ = sender
-                    .send(State::FetchServers(Status::Ok(servers.servers.servers.clone())).into());
-                servers
-            }
-            Err(e) => {
-                _ = sender.send(State::FetchServers(Status::Err(e.to_string())).into());
-                return;
+        let (mut servers, ignored_servers) =
+            match speed_tester.fetch_servers(config.threads()).await {
+                Ok(mut servers) => {
+                    let ignored =
+                        speed_tester.filter_ignored_servers(&mut servers.servers.servers, &config);
+                    _ = sender.send(
+                        State::FetchServers(Status::Ok(servers.servers.servers.clone())).into(),
+                    );
+                    (servers, ignored)
+                }
+                Err(e) => {
+                    _ = sender.send(State::FetchServers(Status::Err(e.to_string())).into());
+                    return;
+                }
+            };
+
+        _ = sender.send(State::RacingServers(Status::Start).into());
+
+        let network = match &options.race_cache {
+            Some(cache) => Some((cache, cache.key(&config.client.ip).await)),
+            None => None,
+        };
+        let preferred = options.server_id.as_ref().and_then(|id| {
+            let server = servers
+                .servers
+                .servers
+                .iter()
+                .find(|s| s.matches(id))
+                .cloned();
+            if server.is_none() {
+                tracing::warn!("server {id} is not in the server list, racing instead");
             }
+            server
+        });
+        let cached = preferred.or_else(|| {
+            let (cache, key) = network.as_ref()?;
+            let id = cache.lookup(key, Utc::now())?;
+            servers.servers.servers.iter().find(|s| s.id == id).cloned()
+        });
+
+        let mut rejected_servers = vec![];
+        let cached = match cached {
+            Some(server) => match speed_tester.validate_server(&config, &server).await {
+                Ok(()) => Some(server),
+                Err(reason) => {
+                    tracing::warn!("cached server {} failed validation: {}", server.id, reason);
+                    servers.servers.servers.retain(|s| s.id != server.id);
+                    rejected_servers.push(RejectedServer::new(&server, reason));
+                    None
+                }
+            },
+            None => None,
         };
 
-        _ = sender.send(State::RacingServers(Status::Start).into());
-        let server = match speed_tester
-            .select_fastest_server(servers.servers.servers)
-            .await
-        {
-            Ok(server) => {
-                _ = sender.send(State::RacingServers(Status::Ok(server.clone())).into());
+        let server = match cached {
+            Some(server) => {
+                tracing::debug!("testing against server {} without a race", server.id);
                 server
             }
-            Err(e) => {
-                _ = sender.send(State::RacingServers(Status::Err(e.to_string())).into());
-                return;
+            None => {
+                let (probe_tx, mut probe_rx) = mpsc::unbounded_channel();
+                let probe_sender = sender.clone();
+                tokio::spawn(async move {
+                    while let Some(probe) = probe_rx.recv().await {
+                        _ = probe_sender.send(AppEvent::RaceProbe(probe).into());
+                    }
+                });
+                match speed_tester
+                    .select_valid_server_with_probes(
+                        &config,
+                        servers.servers.servers,
+                        Some(probe_tx),
+                    )
+                    .await
+                {
+                    Ok((server, rejected)) => {
+                        rejected_servers.extend(rejected);
+                        if let Some((cache, key)) = network
+                            && let Err(e) = cache.store(key, server.id.clone(), Utc::now())
+                        {
+                            tracing::warn!("failed to cache race result: {}", e);
+                        }
+                        server
+                    }
+                    Err(e) => {
+                        _ = sender.send(State::RacingServers(Status::Err(e.to_string())).into());
+                        return;
+                    }
+                }
             }
         };
+        _ = sender.send(State::RacingServers(Status::Ok(Box::new(server.clone()))).into());
+
+        let plan = speed_tester.test_plan();
+        let idle_latency = if plan.latency {
+            _ = sender.send(State::IdleLatency(Status::Start).into());
+            let idle_latency = speed_tester
+                .idle_latency(&server, IDLE_LATENCY_DURATION)
+                .await;
+            _ = sender.send(State::IdleLatency(Status::Ok(idle_latency)).into());
+            idle_latency
+        } else {
+            _ = sender.send(State::IdleLatency(Status::Canceled).into());
+            None
+        };
+
+        let mut diagnostics = Diagnostics::default();
+        let (clock_skew, nat) = tokio::join!(
+            speed_tester.clock_skew(&server),
+            detect_nat(&config.client.ip)
+        );
+        diagnostics.clock_skew_ms = clock_skew.map(|skew| skew.num_milliseconds());
+        diagnostics.nat = nat;
+        diagnostics.link = detect_link();
+        diagnostics.ignored_servers = ignored_servers.iter().map(Into::into).collect();
+        diagnostics.rejected_servers = rejected_servers;
+
+        if let Some(data_cap) = options.data_cap.filter(DataCap::is_near) {
+            _ = sender.send(State::Download(Status::Canceled).into());
+            _ = sender.send(State::Upload(Status::Canceled).into());
+
+            let mut result = SpeedTestResult::new(timestamp, &config.client, &server)
+                .with_background(speed_tester.is_background());
+            result.idle_latency = idle_latency;
+            result.diagnostics = diagnostics;
+            if let Some(idle_traffic) = idle_traffic {
+                result = result.with_idle_traffic(idle_traffic);
+            }
+            result.warnings = result.detect_warnings();
+            result.warnings.push(data_cap.warning());
+            _ = sender.send(AppEvent::Finished(Box::new(result)).into());
+            return;
+        }
+
+        let transfers_start = Instant::now();
+        let cpu_time = CpuTime::read();
+
+        let mut result = SpeedTestResult::new(timestamp, &config.client, &server)
+            .with_background(speed_tester.is_background());
+        if plan.download {
+            _ = sender.send(State::Download(Status::Start).into());
+            let counters = InterfaceCounters::read();
+            let mut sampler = Sampler::new(
+                Stage::Download,
+                downloaded.clone(),
+                sample_interval(&speed_tester, config.max_download_duration()),
+            );
+            let ((download_errors, download), download_latency) = speed_tester
+                .loaded_latency(
+                    &server,
+                    sampler.run(
+                        &sender,
+                        speed_tester.measure_transfer(
+                            &config,
+                            Stage::Download,
+                            &downloaded,
+                            speed_tester.download(&config, &server, downloaded.clone()),
+                        ),
+                    ),
+                )
+                .await;
+            if let Some(before) = counters
+                && let Some(after) = InterfaceCounters::read()
+            {
+                diagnostics.download_counters = Some(CounterCheck::new(
+                    download.bytes,
+                    after.delta(&before).rx_bytes,
+                ));
+            }
+            if download.bytes == 0 {
+                _ = sender
+                    .send(State::Download(Status::Err(download_errors.explain_zero())).into());
+            } else {
+                _ = sender.send(State::Download(Status::Ok(download_latency)).into());
+            }
+            diagnostics.download_errors = download_errors;
+            result = result.with_download(download);
+            result.download_latency = download_latency;
+        } else {
+            _ = sender.send(State::Download(Status::Canceled).into());
+        }
 
-        _ = sender.send(State::Download(Status::Start).into());
-        speed_tester.download(&config, &server, downloaded).await;
-        _ = sender.send(State::Download(Status::Ok(())).into());
+        if plan.upload {
+            _ = sender.send(State::Upload(Status::Start).into());
+            let counters = InterfaceCounters::read();
+            let mut sampler = Sampler::new(
+                Stage::Upload,
+                uploaded.clone(),
+                sample_interval(&speed_tester, config.max_upload_duration()),
+            );
+            let (((upload_errors, upload_size_cap), upload), upload_latency) = speed_tester
+                .loaded_latency(
+                    &server,
+                    sampler.run(
+                        &sender,
+                        speed_tester.measure_transfer(
+                            &config,
+                            Stage::Upload,
+                            &uploaded,
+                            speed_tester.upload(&config, &server, uploaded.clone()),
+                        ),
+                    ),
+                )
+                .await;
+            if let Some(before) = counters
+                && let Some(after) = InterfaceCounters::read()
+            {
+                diagnostics.upload_counters = Some(CounterCheck::new(
+                    upload.bytes,
+                    after.delta(&before).tx_bytes,
+                ));
+            }
+            if upload.bytes == 0 {
+                _ = sender.send(State::Upload(Status::Err(upload_errors.explain_zero())).into());
+            } else {
+                _ = sender.send(State::Upload(Status::Ok(upload_latency)).into());
+            }
+            diagnostics.upload_errors = upload_errors;
+            diagnostics.upload_size_cap = upload_size_cap;
+            diagnostics.upload_accounting = Some(speed_tester.upload_accounting());
+            result = result.with_upload(upload);
+            result.upload_latency = upload_latency;
+        } else {
+            _ = sender.send(State::Upload(Status::Canceled).into());
+        }
 
-        _ = sender.send(State::Upload(Status::Start).into());
-        speed_tester.upload(&config, &server, uploaded).await;
-        _ = sender.send(State::Upload(Status::Ok(())).into());
+        if let Some(before) = cpu_time
+            && let Some(after) = CpuTime::read()
+        {
+            diagnostics.cpu_usage = Some(after.usage_since(&before, transfers_start.elapsed()));
+        }
+
+        result.methodology = Some(speed_tester.methodology(&config, &server).await);
+        result.idle_latency = idle_latency;
+        result.diagnostics = diagnostics;
+        if let Some(idle_traffic) = idle_traffic {
+            result = result.with_idle_traffic(idle_traffic);
+        }
+        result.warnings = result.detect_warnings();
+        if options.share {
+            match speed_tester.share_result(&result).await {
+                Ok(url) => result.share_url = Some(url),
+                Err(e) => tracing::warn!("failed to share result: {}", e),
+            }
+        }
+        _ = sender.send(AppEvent::Finished(Box::new(result)).into());
+    }
+
+    /// Raced servers by id, fastest first and servers without a successful probe last.
+    pub fn race_leaderboard(&self) -> Vec<(&str, &RaceStats)> {
+        let mut leaderboard: Vec<(&str, &RaceStats)> = self
+            .racing
+            .iter()
+            .map(|(id, stats)| (id.as_str(), stats))
+            .collect();
+        leaderboard.sort_by(|a, b| {
+            let avg = |stats: &RaceStats| stats.avg_ms().unwrap_or(f64::INFINITY);
+            avg(a.1).total_cmp(&avg(b.1)).then(a.0.cmp(b.0))
+        });
+        leaderboard
+    }
+
+    pub fn speed_tester(&self) -> &SpeedTester {
+        &self.speed_tester
+    }
+
+    /// Overall progress of the run in `0.0..=1.0`, from the stage events of the core weighted by
+    /// the expected stage durations.
+    pub fn overall_progress(&self) -> f64 {
+        if self.result.is_some() {
+            return 1.0;
+        }
+        self.progress
+            .current()
+            .map_or(0.0, |(stage, fraction, _)| stage.overall(fraction))
     }
 
     pub fn max_download_byte_ps(&self) -> usize {
@@ -373,6 +994,78 @@ impl App {
     }
 }
 
+/// Interval the throughput of a transfer lasting `transfer` is sampled at, never shorter than a
+/// tick of the screen.
+pub(crate) fn sample_interval(speed_tester: &SpeedTester, transfer: Duration) -> Duration {
+    speed_tester.sample_interval(transfer).max(TICK_INTERVAL)
+}
+
+/// Counters of the event loop, shown in the debug pane.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventStats {
+    /// Events handled within an already pending frame.
+    pub coalesced: u64,
+
+    /// Ticks skipped because a tick was already handled in the same frame.
+    pub dropped_ticks: u64,
+}
+
+/// Probes of a single server while racing.
+#[derive(Debug, Clone, Default)]
+pub struct RaceStats {
+    pub attempts: usize,
+    pub failures: usize,
+    pub delays: Vec<Duration>,
+}
+
+impl RaceStats {
+    /// Mean of the successful probes so far.
+    pub fn avg_ms(&self) -> Option<f64> {
+        if self.delays.is_empty() {
+            return None;
+        }
+        let total: f64 = self.delays.iter().map(|d| d.as_secs_f64() * 1000.0).sum();
+        Some(total / self.delays.len() as f64)
+    }
+}
+
+/// Settings of a single run that are not part of the [`SpeedTester`].
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Sample the interface counters before the test to detect background traffic.
+    pub idle_check: bool,
+
+    /// Reuse the winner of a recent race on the same network.
+    pub race_cache: Option<RaceCache>,
+
+    /// Play a canned run instead of testing, see [`demo`].
+    pub demo: bool,
+
+    /// Labels added to the result.
+    pub tags: Vec<String>,
+
+    /// Note attached to the result.
+    pub note: Option<String>,
+
+    /// Usage of the monthly data cap, runs near the cap measure the latency only.
+    pub data_cap: Option<DataCap>,
+
+    /// Server to test against without a race, if it is in the server list.
+    pub server_id: Option<String>,
+
+    /// Register complete results with speedtest.net for a shareable link.
+    pub share: bool,
+}
+
+impl RunOptions {
+    /// Adds the note and tags of the run to its result.
+    pub fn annotate(&self, result: SpeedTestResult) -> SpeedTestResult {
+        let mut result = result.with_note(self.note.clone());
+        result.tags.extend(self.tags.iter().cloned());
+        result
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SimpleConfig {
     pub ip: String,
@@ -380,6 +1073,8 @@ pub struct SimpleConfig {
     pub longitude: String,
     pub isp: String,
     pub country: String,
+    pub download_duration: Duration,
+    pub upload_duration: Duration,
 }
 
 impl From<&speedtest_rs_core::model::Config> for SimpleConfig {
@@ -390,6 +1085,26 @@ impl From<&speedtest_rs_core::model::Config> for SimpleConfig {
             longitude: value.client.lon.to_string(),
             isp: value.client.isp.clone(),
             country: value.client.country.clone(),
+            download_duration: value.max_download_duration(),
+            upload_duration: value.max_upload_duration(),
+        }
+    }
+}
+
+impl Redact for SimpleConfig {
+    fn redact(&self) -> Self {
+        let coordinate = |value: &str| {
+            value
+                .parse::<f64>()
+                .map(|v| redact_coordinate(v).to_string())
+                .unwrap_or_default()
+        };
+
+        Self {
+            ip: redact_ip(&self.ip),
+            latitude: coordinate(&self.latitude),
+            longitude: coordinate(&self.longitude),
+            ..self.clone()
         }
     }
 }
@@ -0,0 +1,155 @@
+//! What a run measured so far, kept as a result marked aborted when the user quits mid-run.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use speedtest_rs_core::{
+    latency::Latency,
+    model::Server,
+    result::{ClientInfo, SpeedTestResult, TransferResult},
+};
+
+use crate::event::{State, Status};
+
+#[derive(Debug, Clone)]
+pub struct PartialRun {
+    timestamp: DateTime<Utc>,
+    client: Option<ClientInfo>,
+    server: Option<Server>,
+    idle_latency: Option<Latency>,
+    download: Option<Phase>,
+    upload: Option<Phase>,
+}
+
+/// A transfer that started, `end` is `None` while it runs.
+#[derive(Debug, Clone, Copy)]
+struct Phase {
+    start: Instant,
+    end: Option<Instant>,
+    latency: Option<Latency>,
+}
+
+impl Default for PartialRun {
+    fn default() -> Self {
+        Self {
+            timestamp: Utc::now(),
+            client: None,
+            server: None,
+            idle_latency: None,
+            download: None,
+            upload: None,
+        }
+    }
+}
+
+impl PartialRun {
+    /// Records a state change, with the config before it is redacted for display.
+    pub fn apply(&mut self, state: &State, now: Instant) {
+        match state {
+            State::FetchConfig(Status::Ok(config)) => {
+                self.client = Some(ClientInfo {
+                    ip: config.ip.clone(),
+                    lat: config.latitude.parse().unwrap_or_default(),
+                    lon: config.longitude.parse().unwrap_or_default(),
+                    isp: config.isp.clone(),
+                    country: config.country.clone(),
+                });
+            }
+            State::RacingServers(Status::Ok(server)) => self.server = Some(*server.clone()),
+            State::IdleLatency(Status::Ok(latency)) => self.idle_latency = *latency,
+            State::Download(status) => Phase::apply(&mut self.download, status, now),
+            State::Upload(status) => Phase::apply(&mut self.upload, status, now),
+            _ => {}
+        }
+    }
+
+    /// The figures measured until `now`, with the byte counters of the transfers. `None` before a
+    /// server was selected, nothing was measured then.
+    pub fn result(&self, downloaded: u64, uploaded: u64, now: Instant) -> Option<SpeedTestResult> {
+        let client = self.client.clone()?;
+        let server = self.server.as_ref()?;
+
+        let mut result = SpeedTestResult::new(self.timestamp, client, server).with_aborted(true);
+        result.idle_latency = self.idle_latency;
+        if let Some(download) = self.download.filter(|_| downloaded > 0) {
+            result = result.with_download(TransferResult::new(downloaded, download.elapsed(now)));
+            result.download_latency = download.latency;
+        }
+        if let Some(upload) = self.upload.filter(|_| uploaded > 0) {
+            result = result.with_upload(TransferResult::new(uploaded, upload.elapsed(now)));
+            result.upload_latency = upload.latency;
+        }
+        result.warnings = result.detect_warnings();
+        Some(result)
+    }
+}
+
+impl Phase {
+    fn apply(phase: &mut Option<Phase>, status: &Status<Option<Latency>>, now: Instant) {
+        match (status, phase.as_mut()) {
+            (Status::Start, _) => {
+                *phase = Some(Phase {
+                    start: now,
+                    end: None,
+                    latency: None,
+                })
+            }
+            (Status::Ok(latency), Some(phase)) => {
+                phase.end = Some(now);
+                phase.latency = *latency;
+            }
+            (Status::Err(_), Some(phase)) => phase.end = Some(now),
+            _ => {}
+        }
+    }
+
+    fn elapsed(&self, now: Instant) -> Duration {
+        self.end.unwrap_or(now).duration_since(self.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        app::{SimpleConfig, demo, partial::PartialRun},
+        event::{State, Status},
+    };
+
+    #[test]
+    fn test_partial_run() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut partial = PartialRun::default();
+
+        let client = demo::client();
+        let config = SimpleConfig {
+            ip: client.ip.clone(),
+            latitude: client.lat.to_string(),
+            longitude: client.lon.to_string(),
+            isp: client.isp.clone(),
+            country: client.country.clone(),
+            download_duration: Duration::from_secs(10),
+            upload_duration: Duration::from_secs(10),
+        };
+        partial.apply(&State::FetchConfig(Status::Ok(config)), at(0));
+        assert!(partial.result(0, 0, at(1)).is_none());
+
+        let server = demo::servers()[0].0.clone();
+        partial.apply(&State::RacingServers(Status::Ok(Box::new(server))), at(1));
+        partial.apply(&State::IdleLatency(Status::Ok(None)), at(2));
+        partial.apply(&State::Download(Status::Start), at(2));
+        partial.apply(&State::Download(Status::Ok(None)), at(12));
+        partial.apply(&State::Upload(Status::Start), at(12));
+
+        let result = partial.result(125_000_000, 25_000_000, at(17)).unwrap();
+        assert!(result.is_aborted());
+        assert_eq!(result.download.unwrap().bits_per_second, 100_000_000.0);
+        // The upload ran for five seconds when the run was quit.
+        assert_eq!(result.upload.unwrap().elapsed_ms, 5_000);
+
+        let before_upload = partial.result(125_000_000, 0, at(12)).unwrap();
+        assert!(before_upload.upload.is_none());
+    }
+}
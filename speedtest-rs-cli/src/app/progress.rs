@@ -1,5 +1,7 @@
 use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
+
 use crate::event::Status;
 
 #[derive(Debug)]
@@ -7,6 +9,9 @@ pub struct Progress<T> {
     name: &'static str,
     start: Option<Instant>,
     end: Option<Instant>,
+    /// Wall-clock counterparts of `start` and `end`, which measure the elapsed time.
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
     status: Status<T>,
 }
 
@@ -16,6 +21,8 @@ impl<T> Progress<T> {
             name,
             start: None,
             end: None,
+            started_at: None,
+            finished_at: None,
             status: Status::Pending,
         }
     }
@@ -27,13 +34,28 @@ impl<T> Progress<T> {
     pub fn apply_status(&mut self, status: Status<T>) {
         if matches!(status, Status::Start) {
             self.start = Some(Instant::now());
+            self.started_at = Some(Utc::now());
         }
         if matches!(status, Status::Ok(_)) || matches!(status, Status::Err(_)) {
             self.end = Some(Instant::now());
+            self.finished_at = Some(Utc::now());
         }
         self.status = status;
     }
 
+    pub fn start(&self) -> Option<Instant> {
+        self.start
+    }
+
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.started_at
+    }
+
+    /// `None` while running and for canceled stages.
+    pub fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.finished_at
+    }
+
     pub fn status(&self) -> &Status<T> {
         &self.status
     }
@@ -52,3 +74,21 @@ impl<T> Progress<T> {
         end.duration_since(start)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{app::progress::Progress, event::Status};
+
+    #[test]
+    fn test_progress_timestamps() {
+        let mut progress = Progress::<()>::new("Download");
+        assert_eq!(progress.started_at(), None);
+
+        progress.apply_status(Status::Start);
+        assert!(progress.started_at().is_some());
+        assert_eq!(progress.finished_at(), None);
+
+        progress.apply_status(Status::Ok(()));
+        assert!(progress.finished_at() >= progress.started_at());
+    }
+}
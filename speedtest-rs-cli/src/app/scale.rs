@@ -0,0 +1,116 @@
+//! Y-axis scaling of the throughput charts.
+
+use speedtest_rs_core::bitrate_unit;
+
+/// The range only shrinks once the data uses less than this share of it.
+const SHRINK_THRESHOLD: f64 = 0.75;
+
+/// Share of the gap to the data closed per tick while shrinking.
+const SHRINK_RATE: f64 = 0.05;
+
+/// Headroom kept around the data.
+const MARGIN: f64 = 0.1;
+
+/// Y-axis range in bits per second that grows at once to fit new samples but shrinks slowly,
+/// so a single spike or dip does not rescale the chart back and forth.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AxisScale {
+    low: f64,
+    high: f64,
+}
+
+impl AxisScale {
+    /// Moves the range towards the `min_bps..=max_bps` of the plotted samples, called every tick.
+    pub fn update(&mut self, min_bps: f64, max_bps: f64) {
+        let low = min_bps * (1.0 - MARGIN);
+        let high = max_bps * (1.0 + MARGIN);
+
+        if self.high == 0.0 {
+            *self = Self { low, high };
+            return;
+        }
+
+        if high >= self.high {
+            self.high = high;
+        } else if high < self.high * SHRINK_THRESHOLD {
+            self.high -= (self.high - high) * SHRINK_RATE;
+        }
+
+        if low <= self.low {
+            self.low = low;
+        } else if self.low < low * SHRINK_THRESHOLD {
+            self.low += (low - self.low) * SHRINK_RATE;
+        }
+    }
+
+    /// Round ticks enclosing the range and the unit they are in, as its divisor and suffix.
+    pub fn ticks(&self) -> (Vec<f64>, f64, &'static str) {
+        let (unit, suffix) = bitrate_unit(self.high);
+        (y_ticks(self.low / unit, self.high / unit), unit, suffix)
+    }
+}
+
+/// Evenly spaced ticks at round values (1, 2 or 5 times a power of ten) enclosing `min..=max`.
+pub fn y_ticks(min: f64, max: f64) -> Vec<f64> {
+    let max = if max > min { max } else { min + 1.0 };
+    let rough = (max - min) / 3.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+
+    let first = (min / step).floor();
+    let last = (max / step).ceil();
+    (first as i64..=last as i64)
+        // Multiplying the index keeps values such as 0.3 free of accumulated float errors.
+        .map(|i| round_to(i as f64 * step, magnitude))
+        .collect()
+}
+
+fn round_to(value: f64, magnitude: f64) -> f64 {
+    if magnitude >= 1.0 {
+        value.round()
+    } else {
+        let scale = (1.0 / magnitude).round();
+        (value * scale).round() / scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::scale::{AxisScale, y_ticks};
+
+    #[test]
+    fn test_y_ticks() {
+        assert_eq!(y_ticks(64.8, 110.0), [60.0, 80.0, 100.0, 120.0]);
+        assert_eq!(y_ticks(0.3, 0.85), [0.2, 0.4, 0.6, 0.8, 1.0]);
+        assert_eq!(y_ticks(0.0, 0.0), [0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_axis_scale_hysteresis() {
+        let mut scale = AxisScale::default();
+        scale.update(90e6, 100e6);
+        let (ticks, unit, suffix) = scale.ticks();
+        assert_eq!((unit, suffix), (1e6, "Mbps"));
+        assert_eq!(ticks, [80.0, 90.0, 100.0, 110.0, 120.0]);
+
+        // Grows at once.
+        scale.update(90e6, 200e6);
+        assert_eq!(scale.ticks().0.last(), Some(&250.0));
+
+        // Small drops are ignored, large ones shrink the range gradually.
+        let before = scale;
+        scale.update(90e6, 190e6);
+        assert_eq!(scale, before);
+
+        scale.update(90e6, 100e6);
+        assert!(scale.high < before.high && scale.high > 110e6);
+        for _ in 0..200 {
+            scale.update(90e6, 100e6);
+        }
+        assert_eq!(scale.ticks().0.last(), Some(&150.0));
+    }
+}
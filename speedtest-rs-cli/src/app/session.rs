@@ -0,0 +1,129 @@
+//! "Save diagnostics": a snapshot of the current session for attaching to bug reports.
+
+use std::{path::PathBuf, time::Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use speedtest_rs_core::result::SpeedTestResult;
+
+use crate::{
+    app::{App, progress::Progress},
+    event::Status,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionBundle {
+    pub created: DateTime<Utc>,
+
+    pub version: &'static str,
+
+    pub stages: Vec<StageTiming>,
+
+    /// Download throughput samples in bytes per second.
+    pub download_samples: Vec<u64>,
+
+    /// Upload throughput samples in bytes per second.
+    pub upload_samples: Vec<u64>,
+
+    /// Probes of the server race, fastest first.
+    pub race: Vec<RaceEntry>,
+
+    pub result: Option<SpeedTestResult>,
+
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub name: &'static str,
+
+    pub status: String,
+
+    /// Start relative to the session start, `None` if the stage never started.
+    pub start_ms: Option<u64>,
+
+    pub elapsed_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RaceEntry {
+    pub server_id: String,
+
+    pub attempts: usize,
+
+    pub failures: usize,
+
+    pub avg_ms: Option<f64>,
+}
+
+impl StageTiming {
+    fn new<T>(progress: &Progress<T>, session_start: Instant) -> Self {
+        let status = match progress.status() {
+            Status::Pending => "pending".to_string(),
+            Status::Start => "running".to_string(),
+            Status::Ok(_) => "ok".to_string(),
+            Status::Err(e) => format!("error: {e}"),
+            Status::Canceled => "canceled".to_string(),
+        };
+
+        Self {
+            name: progress.name(),
+            status,
+            start_ms: progress
+                .start()
+                .map(|start| start.duration_since(session_start).as_millis() as u64),
+            elapsed_ms: progress.elapsed().as_millis() as u64,
+            started_at: progress.started_at(),
+            finished_at: progress.finished_at(),
+        }
+    }
+}
+
+impl SessionBundle {
+    pub fn from_app(app: &App) -> Self {
+        let start = app.started;
+
+        Self {
+            created: Utc::now(),
+            version: env!("CARGO_PKG_VERSION"),
+            stages: vec![
+                StageTiming::new(&app.fetch_config, start),
+                StageTiming::new(&app.fetch_servers, start),
+                StageTiming::new(&app.racing_servers, start),
+                StageTiming::new(&app.idle_latency, start),
+                StageTiming::new(&app.download, start),
+                StageTiming::new(&app.upload, start),
+            ],
+            download_samples: app.downloaded_data.iter().copied().collect(),
+            upload_samples: app.uploaded_data.iter().copied().collect(),
+            race: app
+                .race_leaderboard()
+                .into_iter()
+                .map(|(id, stats)| RaceEntry {
+                    server_id: id.to_string(),
+                    attempts: stats.attempts,
+                    failures: stats.failures,
+                    avg_ms: stats.avg_ms(),
+                })
+                .collect(),
+            result: app.result.clone(),
+            logs: app.logs.tail(usize::MAX),
+        }
+    }
+
+    /// `$XDG_DATA_HOME/speedtest-rs/sessions/session-<timestamp>.json` or the platform equivalent.
+    pub fn default_path(&self) -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| {
+            dir.join("speedtest-rs").join("sessions").join(format!(
+                "session-{}.json",
+                self.created.format("%Y%m%dT%H%M%S")
+            ))
+        })
+    }
+}
@@ -0,0 +1,718 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::eyre;
+use serde::Deserialize;
+use speedtest_rs_core::{
+    http::HttpVersion,
+    model::{ConfigOverrides, PhaseOverrides, Server, WarmUp},
+    net::IpFamily,
+    retry::RetryPolicy,
+    size::ByteSize,
+    stage::TestPlan,
+    urls::SpeedTestUrl,
+};
+
+use crate::theme::{ChartMarker, Palette};
+
+/// Command line arguments.
+#[derive(Debug, Clone, Default, Parser)]
+#[command(name = "speedtest-rs", version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Bind all test traffic to a network interface, given by name or by IP address.
+    #[arg(short, long)]
+    pub interface: Option<String>,
+
+    /// List the available network interfaces and exit.
+    #[arg(long)]
+    pub list_interfaces: bool,
+
+    /// Test over IPv4 only.
+    #[arg(short = '4', long, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Test over IPv6 only.
+    #[arg(short = '6', long)]
+    pub ipv6: bool,
+
+    /// Allow HTTP/2 for servers offering it over https, which multiplexes the concurrent
+    /// transfers over one connection instead of opening one per transfer.
+    #[arg(long)]
+    pub http2: bool,
+
+    /// Device profile tuning concurrency and layout. Detected automatically when omitted.
+    #[arg(long, value_enum)]
+    pub profile: Option<Profile>,
+
+    /// Maximum number of concurrent download/upload connections.
+    #[arg(long)]
+    pub max_concurrency: Option<usize>,
+
+    /// Length of the download, e.g. 15s, instead of the value of the remote config.
+    #[arg(long, value_name = "DURATION", value_parser = crate::ping::parse_interval)]
+    pub download_duration: Option<Duration>,
+
+    /// Length of the upload, e.g. 5s, instead of the value of the remote config.
+    #[arg(long, value_name = "DURATION", value_parser = crate::ping::parse_interval)]
+    pub upload_duration: Option<Duration>,
+
+    /// Concurrent download connections instead of the value of the remote config.
+    #[arg(long, value_name = "N")]
+    pub download_threads: Option<usize>,
+
+    /// Concurrent upload connections instead of the value of the remote config.
+    #[arg(long, value_name = "N")]
+    pub upload_threads: Option<usize>,
+
+    /// Side lengths of the downloaded images in the order they are requested, e.g. 1000,4000.
+    #[arg(long, value_name = "SIZES", value_delimiter = ',')]
+    pub download_sizes: Vec<usize>,
+
+    /// Upload request sizes in the order they are sent, e.g. 256K,1M,4M.
+    #[arg(long, value_name = "SIZES", value_delimiter = ',')]
+    pub upload_sizes: Vec<ByteSize>,
+
+    /// Upload requests at most instead of the value of the remote config, the upload ends early
+    /// once they are sent.
+    #[arg(long, value_name = "N")]
+    pub max_chunk_count: Option<usize>,
+
+    /// Leave the start of each transfer out of its reported rate: the first seconds, e.g. 2s, or
+    /// the first bytes of every connection, e.g. 1M. The result keeps the raw rate as well.
+    #[arg(long, value_name = "DURATION|SIZE")]
+    pub warm_up: Option<WarmUp>,
+
+    /// Skip the download, e.g. to test the upload of an asymmetric link on its own.
+    #[arg(long, conflicts_with = "no_upload")]
+    pub no_download: bool,
+
+    /// Skip the upload, which also saves the data it would send on a metered connection.
+    #[arg(long)]
+    pub no_upload: bool,
+
+    /// Color palette of the TUI, including palettes for color-blind users.
+    #[arg(long, value_enum)]
+    pub palette: Option<Palette>,
+
+    /// Symbols the charts are drawn with, for fonts or terminals that render braille poorly.
+    #[arg(long, value_enum)]
+    pub chart_marker: Option<ChartMarker>,
+
+    /// Time between the throughput samples of the charts, e.g. 100ms. By default 50ms to 500ms
+    /// depending on the test duration, but never shorter than the 100ms screen refresh.
+    #[arg(long, value_name = "INTERVAL", value_parser = crate::ping::parse_interval)]
+    pub sample_interval: Option<Duration>,
+
+    /// Measure the idle latency with this many probes instead of probing for two seconds.
+    #[arg(long, value_name = "N")]
+    pub latency_probes: Option<usize>,
+
+    /// Mask the public IP address, precise coordinates and server host names in all outputs,
+    /// keeping country and ISP, so results can be shared publicly.
+    #[arg(long, global = true)]
+    pub redact: bool,
+
+    /// Watch the network interfaces for a few seconds before the test and warn if other
+    /// applications are using the connection. Linux only.
+    #[arg(long)]
+    pub idle_check: bool,
+
+    /// Low-priority mode for scheduled runs on shared connections: fewer connections with pauses
+    /// in between. Results are labeled as background mode since they under-report the capacity.
+    #[arg(long)]
+    pub background: bool,
+
+    /// Pick the server among the results of a search by city, sponsor or host name, e.g. "Tokyo",
+    /// instead of the servers near you.
+    #[arg(long, value_name = "KEYWORD")]
+    pub search: Option<String>,
+
+    /// Only consider servers in this country, given by its ISO code, e.g. JP.
+    #[arg(long, value_name = "CODE")]
+    pub cc: Option<String>,
+
+    /// Only consider servers whose sponsor contains this text, ignoring case, e.g. "hkbn".
+    #[arg(long, value_name = "TEXT")]
+    pub sponsor: Option<String>,
+
+    /// Only consider servers whose name, usually the city, contains this text, ignoring case.
+    #[arg(long, value_name = "TEXT")]
+    pub server_name: Option<String>,
+
+    /// Test against this server instead of racing, e.g. an id listed by `servers --ping` or a host
+    /// like speed.example.com. Falls back to the race when the server is not in the list for your
+    /// location.
+    #[arg(long, value_name = "ID_OR_HOST")]
+    pub server_id: Option<String>,
+
+    /// Select the server by latency and distance instead of the lowest latency alone, optionally
+    /// with custom weights, e.g. "latency=1,distance=0.5" (the default).
+    #[arg(long, value_name = "WEIGHTS", num_args = 0..=1, default_missing_value = "")]
+    pub weighted: Option<speedtest_rs_core::scoring::Scoring>,
+
+    /// Download from the three best servers for two seconds each and test against the fastest,
+    /// since the lowest latency does not imply the most capacity.
+    #[arg(long)]
+    pub probe_capacity: bool,
+
+    /// Keep only the best server of every sponsor, so --probe-capacity compares different
+    /// operators and a server failing validation is replaced by another operator's.
+    #[arg(long)]
+    pub per_sponsor: bool,
+
+    /// Race servers whose hosts resolve to the same IP address only once. Servers listed with the
+    /// same host are always raced once.
+    #[arg(long)]
+    pub dedup_by_ip: bool,
+
+    /// Race only the N servers nearest to you by their coordinates.
+    #[arg(long, value_name = "N")]
+    pub closest: Option<usize>,
+
+    /// Check with HEAD requests which download images the server hosts and skip the missing ones,
+    /// for nonstandard servers. The result is cached per server for a week.
+    #[arg(long)]
+    pub probe_assets: bool,
+
+    /// Skip the HEAD request that checks the selected server serves real download images before
+    /// the test, which catches captive portals and falls back to the next best server.
+    #[arg(long)]
+    pub no_validate: bool,
+
+    /// Skip the DNS lookup and request that fail fast when offline, e.g. behind a proxy doing
+    /// the DNS resolution.
+    #[arg(long)]
+    pub no_connectivity_check: bool,
+
+    /// Skip the request to a known empty page that detects captive portals before the discovery.
+    #[arg(long)]
+    pub no_portal_check: bool,
+
+    /// Keep the servers the speedtest.net config asks to ignore, which are dropped by default.
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Check the size the server reports to have received and count incomplete uploads as
+    /// failed, which takes their bytes out of the upload speed.
+    #[arg(long)]
+    pub verify_upload: bool,
+
+    /// Register the result with speedtest.net and show the link to its result image.
+    #[arg(long)]
+    pub share: bool,
+
+    /// Download path of self-hosted servers with a different layout, e.g.
+    /// "{base}/download?size={size}". Placeholders: {base}, {url}, {host} and {size}.
+    #[arg(long, value_name = "TEMPLATE")]
+    pub download_url_template: Option<String>,
+
+    /// Download templates of individual servers by server id, only settable in the config file.
+    #[arg(skip)]
+    pub server_download_url_templates: HashMap<String, String>,
+
+    /// Fetch the config and server lists from this host instead of speedtest.net, e.g. a corporate
+    /// mirror like speedtest.example.com:8080. Repeatable, tried in order.
+    #[arg(long = "config-host", value_name = "HOST")]
+    pub config_hosts: Vec<String>,
+
+    /// Retry the config and server list fetches this many times, with a growing pause in between.
+    /// Defaults to 2, 0 gives up after the first failure.
+    #[arg(long, value_name = "N")]
+    pub fetch_retries: Option<u32>,
+
+    /// Give up an attempt to fetch the config or server lists after this long, e.g. 5s, even if
+    /// its requests did not time out yet.
+    #[arg(long, value_name = "DURATION", value_parser = crate::ping::parse_interval)]
+    pub fetch_timeout: Option<Duration>,
+
+    /// Test against this self-hosted server instead of the server lists, given by the URL of its
+    /// upload.php, e.g. a speedtest-mini install. Repeatable. Works without access to
+    /// speedtest.net, with the stock config standing in.
+    #[arg(long = "server-url", value_name = "URL", value_parser = parse_server_url)]
+    pub server_urls: Vec<Server>,
+
+    /// Resolve the server names with this DNS server instead of the system resolver: an address
+    /// such as 1.1.1.1 for plain DNS, or an https URL such as https://dns.google/dns-query for DNS
+    /// over HTTPS.
+    #[arg(long, value_name = "SERVER", value_parser = parse_dns_server)]
+    pub dns: Option<DnsServer>,
+
+    /// Label the result, e.g. "wifi" or "office", to filter or compare runs later. Repeatable.
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Attach a free-text note to the result, e.g. "after router firmware update". Stored in the
+    /// history and included in the result JSON.
+    #[arg(long, value_name = "TEXT")]
+    pub note: Option<String>,
+
+    /// SQLite history database, e.g. on a share Grafana reads from. Defaults to history.db in the
+    /// data directory.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub db_path: Option<PathBuf>,
+
+    /// Monthly data allowance of a metered connection, e.g. 50G. Once the runs of this month
+    /// used 90% of it, runs measure the latency only.
+    #[arg(long, value_name = "SIZE")]
+    pub monthly_cap: Option<ByteSize>,
+
+    /// Do not record the result in the local history.
+    #[arg(long)]
+    pub no_history: bool,
+
+    /// Do not compare the result with the baseline set by `baseline set`.
+    #[arg(long)]
+    pub no_baseline: bool,
+
+    /// Regression against the baseline in percent that still exits successfully.
+    #[arg(long, value_name = "PERCENT", default_value_t = 10.0)]
+    pub baseline_threshold: f64,
+
+    /// Always overwrite this file with the most recent result as JSON, e.g. for dashboards.
+    #[arg(long, value_name = "PATH")]
+    pub latest_json: Option<PathBuf>,
+
+    /// Ring the terminal bell when the run finished.
+    #[arg(long)]
+    pub bell: bool,
+
+    /// Shell command run when the run finished, e.g. "notify-send done". The one-line summary is
+    /// passed in the SPEEDTEST_SUMMARY environment variable.
+    #[arg(long, value_name = "COMMAND")]
+    pub on_complete: Option<String>,
+
+    /// Shell command run before the test, e.g. to pause torrents. The test is aborted if it fails.
+    #[arg(long, value_name = "COMMAND")]
+    pub pre_run: Option<String>,
+
+    /// Shell command run after the test with the result JSON on stdin and the key figures in
+    /// SPEEDTEST_* environment variables.
+    #[arg(long, value_name = "COMMAND")]
+    pub post_run: Option<String>,
+
+    /// Run even if another test is in progress.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Write a timeline of the stages and of every request as Chrome trace JSON, for
+    /// chrome://tracing or ui.perfetto.dev.
+    #[arg(long, value_name = "PATH")]
+    pub trace_out: Option<PathBuf>,
+
+    /// Simulate a bad network, e.g. "latency=200ms,bandwidth=10M,failures=0.2,seed=7".
+    #[cfg(feature = "impairment")]
+    #[arg(long, value_name = "SPEC")]
+    pub impair: Option<speedtest_rs_core::impair::Impairment>,
+
+    /// Test against the nearest M-Lab server with the ndt7 protocol instead of speedtest.net,
+    /// printing the result when done. WebSockets get through most middleboxes.
+    #[cfg(feature = "ndt7")]
+    #[arg(long)]
+    pub ndt7: bool,
+
+    /// Print the progress as plain lines instead of the TUI, with the live rate of the transfers
+    /// on a status line updated in place, or a line per second when the output is not a terminal.
+    #[arg(long)]
+    pub simple: bool,
+
+    /// Unix only: run like `--simple`, then stay up and run again on every SIGUSR1. SIGHUP reads
+    /// the config file again for the next runs, SIGTERM or Ctrl+C exits.
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Start without asking to confirm the estimated data usage in `--simple` mode.
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Play a canned run without network access, for screenshots and trying out the layout.
+    /// Nothing is recorded and no hooks run.
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Always race the servers instead of reusing the winner of a race on the same network
+    /// within the last 10 minutes.
+    #[arg(long)]
+    pub no_race_cache: bool,
+
+    /// Read settings from this file instead of the default config.toml.
+    #[arg(short, long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Import the preferred server from the settings of the official Ookla CLI, by default
+    /// ~/.config/ookla/speedtest-cli.json. Other settings take precedence.
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "")]
+    pub ookla_config: Option<PathBuf>,
+
+    /// Read settings from an OpenWrt UCI config file. Defaults to /etc/config/speedtest-rs when
+    /// that file exists.
+    #[arg(long, value_name = "PATH")]
+    pub uci_config: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Inspect the results of previous runs.
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+
+    /// Inspect the pool of test servers near you. `--group` alone lists them by sponsor.
+    #[command(arg_required_else_help = true)]
+    Servers {
+        /// Race all servers and print their latencies, without transferring bulk data.
+        #[arg(long)]
+        ping: bool,
+
+        /// Group the listed servers by sponsor.
+        #[arg(long, global = true)]
+        group: bool,
+
+        #[command(subcommand)]
+        command: Option<ServersCommand>,
+    },
+
+    /// Write a self-contained HTML report of the recent runs, e.g. to attach to an ISP support
+    /// ticket.
+    Report {
+        /// Period covered by the report, e.g. 7d or 4w.
+        #[arg(long, value_name = "AGE", default_value = "30d", value_parser = crate::history::query::parse_age)]
+        last: Duration,
+
+        #[arg(short, long, value_name = "PATH", default_value = "report.html")]
+        out: PathBuf,
+    },
+
+    /// Compare two tagged groups of runs, e.g. before and after a router change, and tell
+    /// whether the difference is significant.
+    Ab {
+        /// Tag of the first group.
+        #[arg(long, value_name = "TAG")]
+        tag_a: String,
+
+        /// Tag of the second group.
+        #[arg(long, value_name = "TAG")]
+        tag_b: String,
+
+        /// Only runs within this age, e.g. 7d or 4w.
+        #[arg(long, value_name = "AGE", value_parser = crate::history::query::parse_age)]
+        since: Option<Duration>,
+    },
+
+    /// Print the version, enabled features and platform capabilities, e.g. for bug reports.
+    Info {
+        /// Print JSON for scripted feature detection.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare results against a stored reference result.
+    Baseline {
+        #[command(subcommand)]
+        command: BaselineCommand,
+    },
+
+    /// Run the full test against several servers one after the other and compare the results,
+    /// e.g. to tell routing differences between points of presence.
+    Compare {
+        /// Number of servers of the lowest latency to test.
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        top: usize,
+
+        /// Print the results as a JSON array.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Continuously measure the latency to a single server, without transferring bulk data.
+    Ping {
+        /// Server to probe, see `servers --ping`. Defaults to the winner of a server race.
+        #[arg(long)]
+        server_id: Option<String>,
+
+        /// Time between probes, e.g. 500ms or 2s.
+        #[arg(long, default_value = "1s", value_parser = crate::ping::parse_interval)]
+        interval: Duration,
+
+        /// Stop after this many probes instead of running until interrupted.
+        #[arg(short, long)]
+        count: Option<u64>,
+
+        /// Output of the individual probes. The summary is always printed to stderr.
+        #[arg(long, value_enum, default_value_t)]
+        format: PingFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PingFormat {
+    /// One line per probe with a sparkline of the recent latencies.
+    #[default]
+    Text,
+
+    /// Streamed CSV rows with a header.
+    Csv,
+
+    /// Streamed JSON objects, one per line.
+    Json,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum HistoryCommand {
+    /// Show the median download speed by hour of day and day of week.
+    Heatmap {
+        #[command(flatten)]
+        filter: HistoryFilter,
+
+        /// Also write the heatmap as an SVG image.
+        #[arg(long, value_name = "PATH")]
+        svg: Option<PathBuf>,
+    },
+
+    /// Print the recorded runs, e.g. for spreadsheets.
+    Export {
+        #[command(flatten)]
+        filter: HistoryFilter,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "PATH")]
+        out: Option<PathBuf>,
+    },
+}
+
+/// Selects the runs a history subcommand works on, all conditions must match.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct HistoryFilter {
+    /// Only runs within this age, e.g. 12h, 7d or 2w.
+    #[arg(long, value_name = "AGE", value_parser = crate::history::query::parse_age)]
+    pub since: Option<Duration>,
+
+    /// Only runs against this server.
+    #[arg(long, value_name = "ID")]
+    pub server_id: Option<String>,
+
+    /// Only runs with this tag, e.g. background or contended.
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Only runs with at least this download speed, e.g. 100Mbps.
+    #[arg(long, value_name = "BITRATE", value_parser = crate::history::query::parse_bitrate)]
+    pub min_download: Option<f64>,
+
+    /// Only runs with at least this upload speed, e.g. 20Mbps.
+    #[arg(long, value_name = "BITRATE", value_parser = crate::history::query::parse_bitrate)]
+    pub min_upload: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One row per run with the key figures and a header.
+    #[default]
+    Csv,
+    /// An array of the full results.
+    Json,
+    /// Columnar file with the same columns as the CSV, e.g. for pandas or DuckDB.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ServersCommand {
+    /// Compare the current server list with the snapshot of the previous call and report added,
+    /// removed and changed servers.
+    Diff {
+        /// Snapshot to compare against. Defaults to servers.json in the data directory.
+        #[arg(long, value_name = "PATH")]
+        snapshot: Option<PathBuf>,
+
+        /// Keep the snapshot as it is instead of replacing it with the current list.
+        #[arg(long)]
+        no_update: bool,
+    },
+
+    /// Search servers anywhere in the world by city, sponsor or host name, e.g. to find the id of
+    /// a server to test against.
+    Search { keyword: String },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum BaselineCommand {
+    /// Store a result as the reference for later comparisons.
+    Set {
+        /// Result JSON file, e.g. written by --latest-json. Defaults to the latest history entry.
+        #[arg(value_name = "PATH")]
+        result: Option<PathBuf>,
+    },
+
+    /// Print the deltas of a result against the baseline and exit with an error if it regressed.
+    Compare {
+        /// Result JSON file, e.g. written by --latest-json. Defaults to the latest history entry.
+        #[arg(value_name = "PATH")]
+        result: Option<PathBuf>,
+
+        /// Regression in percent that is still tolerated.
+        #[arg(long, default_value_t = 10.0)]
+        threshold: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Regular desktop terminal.
+    Desktop,
+
+    /// Android phones running Termux: fewer connections and a compact layout.
+    Termux,
+}
+
+impl Args {
+    pub fn profile(&self) -> Profile {
+        self.profile.unwrap_or_else(Profile::detect)
+    }
+
+    pub fn speed_test_url(&self) -> SpeedTestUrl {
+        let mut urls = SpeedTestUrl::new();
+        if let Some(template) = &self.download_url_template {
+            urls = urls.download_template(template);
+        }
+        for (server_id, template) in &self.server_download_url_templates {
+            urls = urls.server_download_template(server_id, template);
+        }
+        urls.hosts(self.config_hosts.clone())
+            .servers(self.server_urls.clone())
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency
+            .or_else(|| self.profile().max_concurrency())
+    }
+
+    /// The settings of the remote config given locally.
+    pub fn config_overrides(&self) -> ConfigOverrides {
+        ConfigOverrides {
+            download: PhaseOverrides {
+                duration: self.download_duration,
+                threads: self.download_threads,
+            },
+            upload: PhaseOverrides {
+                duration: self.upload_duration,
+                threads: self.upload_threads,
+            },
+            download_sizes: Some(self.download_sizes.clone()),
+            upload_sizes: Some(self.upload_sizes.clone()),
+            max_chunk_count: self.max_chunk_count,
+        }
+    }
+
+    /// Retries of the discovery fetches, the defaults of the core for unset options.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            attempts: self
+                .fetch_retries
+                .map_or(default.attempts, |retries| retries.saturating_add(1)),
+            attempt_timeout: self.fetch_timeout,
+            ..default
+        }
+    }
+
+    /// The phases to run after the server selection. Clap rejects both flags at once, but the
+    /// config file can still turn off the other transfer.
+    pub fn test_plan(&self) -> eyre::Result<TestPlan> {
+        if self.no_download && self.no_upload {
+            eyre::bail!("both the download and the upload are disabled, nothing left to measure");
+        }
+        Ok(TestPlan {
+            download: !self.no_download,
+            upload: !self.no_upload,
+            ..TestPlan::default()
+        })
+    }
+
+    pub fn http_version(&self) -> HttpVersion {
+        if self.http2 {
+            HttpVersion::Http2
+        } else {
+            HttpVersion::Http1
+        }
+    }
+
+    pub fn ip_family(&self) -> Option<IpFamily> {
+        match (self.ipv4, self.ipv6) {
+            (true, _) => Some(IpFamily::V4),
+            (_, true) => Some(IpFamily::V6),
+            _ => None,
+        }
+    }
+}
+
+impl Profile {
+    pub fn detect() -> Self {
+        let is_termux = std::env::var_os("TERMUX_VERSION").is_some()
+            || std::env::var("PREFIX").is_ok_and(|prefix| prefix.contains("com.termux"));
+
+        if is_termux {
+            Profile::Termux
+        } else {
+            Profile::Desktop
+        }
+    }
+
+    /// Upper bound of concurrent transfers, `None` keeps the values of the remote config.
+    pub fn max_concurrency(&self) -> Option<usize> {
+        match self {
+            Profile::Desktop => None,
+            Profile::Termux => Some(4),
+        }
+    }
+
+    /// Download and upload rates assumed before a run was recorded, in bits per second.
+    pub fn typical_bitrates(&self) -> (f64, f64) {
+        match self {
+            Profile::Desktop => (300e6, 50e6),
+            Profile::Termux => (50e6, 10e6),
+        }
+    }
+
+    pub fn compact_layout(&self) -> bool {
+        matches!(self, Profile::Termux)
+    }
+}
+
+/// A self-hosted server from the URL of its `upload.php`.
+fn parse_server_url(value: &str) -> Result<Server, String> {
+    Server::from_url(value).map_err(|e| e.to_string())
+}
+
+/// The DNS server of `--dns`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsServer {
+    /// Plain DNS over UDP.
+    Udp(SocketAddr),
+    /// The URL of a DNS over HTTPS server.
+    Https(String),
+}
+
+/// An https URL, or an address with an optional port, 53 by default.
+pub fn parse_dns_server(value: &str) -> Result<DnsServer, String> {
+    if value.starts_with("https://") {
+        return Ok(DnsServer::Https(value.to_string()));
+    }
+    value
+        .parse::<SocketAddr>()
+        .or_else(|_| value.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map(DnsServer::Udp)
+        .map_err(|_| format!("invalid DNS server '{value}', expected an address or an https URL"))
+}
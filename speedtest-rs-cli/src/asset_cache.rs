@@ -0,0 +1,119 @@
+//! Remembers the download images each server hosts, so `--probe-assets` only probes a server
+//! once in a while.
+
+use std::{path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize};
+use speedtest_rs_core::speed_tester::AssetSizes;
+
+use crate::sink::write_atomic;
+
+/// How long the probed sizes of a server are reused.
+pub const ASSET_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    server_id: String,
+    sizes: Vec<usize>,
+    probed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetCache {
+    path: PathBuf,
+}
+
+impl AssetCache {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// `$XDG_CACHE_HOME/speedtest-rs/assets.json` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("speedtest-rs").join("assets.json"))
+    }
+
+    /// The sizes probed recently enough, by server id.
+    pub fn lookup(&self, now: DateTime<Utc>) -> AssetSizes {
+        self.load()
+            .into_iter()
+            .filter(|entry| is_fresh(entry, now))
+            .map(|entry| (entry.server_id, entry.sizes))
+            .collect()
+    }
+
+    /// Records newly probed servers, keeping the probe time of the ones already cached.
+    pub fn store(&self, sizes: AssetSizes, now: DateTime<Utc>) -> eyre::Result<()> {
+        let cached: Vec<Entry> = self
+            .load()
+            .into_iter()
+            .filter(|entry| is_fresh(entry, now))
+            .collect();
+
+        let mut entries: Vec<Entry> = cached
+            .iter()
+            .filter(|entry| !sizes.contains_key(&entry.server_id))
+            .cloned()
+            .collect();
+        for (server_id, sizes) in sizes {
+            let probed_at = cached
+                .iter()
+                .find(|entry| entry.server_id == server_id && entry.sizes == sizes)
+                .map_or(now, |entry| entry.probed_at);
+            entries.push(Entry {
+                server_id,
+                sizes,
+                probed_at,
+            });
+        }
+        entries.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+        write_atomic(&self.path, &serde_json::to_vec_pretty(&entries)?)
+    }
+
+    /// A missing or corrupt cache is treated as empty.
+    fn load(&self) -> Vec<Entry> {
+        std::fs::read(&self.path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default()
+    }
+}
+
+fn is_fresh(entry: &Entry, now: DateTime<Utc>) -> bool {
+    (now - entry.probed_at)
+        .to_std()
+        .is_ok_and(|age| age < ASSET_CACHE_TTL)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use speedtest_rs_core::speed_tester::AssetSizes;
+
+    use crate::asset_cache::{ASSET_CACHE_TTL, AssetCache};
+
+    #[test]
+    fn test_asset_cache() {
+        let path = std::env::temp_dir().join(format!(
+            "speedtest-rs-test-assets-{}.json",
+            std::process::id()
+        ));
+        let cache = AssetCache::new(path.clone());
+        let now = Utc::now();
+        assert!(cache.lookup(now).is_empty());
+
+        let sizes = AssetSizes::from([("35791".to_string(), vec![350, 500])]);
+        cache.store(sizes.clone(), now).unwrap();
+        assert_eq!(cache.lookup(now), sizes);
+
+        // Storing the same sizes later keeps the original probe time.
+        let later = now + TimeDelta::days(1);
+        cache.store(sizes.clone(), later).unwrap();
+        let expired = now + TimeDelta::from_std(ASSET_CACHE_TTL).unwrap();
+        assert!(cache.lookup(expired).is_empty());
+
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,214 @@
+//! Reference result for SLA-style checks of subsequent runs.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre;
+use speedtest_rs_core::result::SpeedTestResult;
+
+use crate::{args::BaselineCommand, history::History, sink::write_atomic};
+
+/// Relative change of a single metric against the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delta {
+    pub name: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    pub unit: &'static str,
+    /// Whether larger values are better, e.g. throughput but not latency.
+    pub higher_is_better: bool,
+}
+
+impl Delta {
+    /// Change in percent, positive when the value grew.
+    pub fn percent(&self) -> f64 {
+        if self.baseline == 0.0 {
+            return 0.0;
+        }
+        (self.current - self.baseline) / self.baseline * 100.0
+    }
+
+    /// How much worse the current value is in percent, zero or negative when it improved.
+    pub fn regression(&self) -> f64 {
+        if self.higher_is_better {
+            -self.percent()
+        } else {
+            self.percent()
+        }
+    }
+}
+
+/// Metrics present in both results.
+pub fn compare(baseline: &SpeedTestResult, current: &SpeedTestResult) -> Vec<Delta> {
+    let mbps = |bps: f64| bps / 1_000_000.0;
+    let mut deltas = vec![];
+
+    if let (Some(b), Some(c)) = (baseline.download, current.download) {
+        deltas.push(Delta {
+            name: "Download",
+            baseline: mbps(b.bits_per_second),
+            current: mbps(c.bits_per_second),
+            unit: "Mbps",
+            higher_is_better: true,
+        });
+    }
+    if let (Some(b), Some(c)) = (baseline.upload, current.upload) {
+        deltas.push(Delta {
+            name: "Upload",
+            baseline: mbps(b.bits_per_second),
+            current: mbps(c.bits_per_second),
+            unit: "Mbps",
+            higher_is_better: true,
+        });
+    }
+    if let (Some(b), Some(c)) = (baseline.idle_latency, current.idle_latency) {
+        deltas.push(Delta {
+            name: "Latency",
+            baseline: b.avg_ms,
+            current: c.avg_ms,
+            unit: "ms",
+            higher_is_better: false,
+        });
+    }
+    deltas
+}
+
+/// `$XDG_DATA_HOME/speedtest-rs/baseline.json` or the platform equivalent.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("speedtest-rs").join("baseline.json"))
+}
+
+fn read_result(path: &Path) -> eyre::Result<SpeedTestResult> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Reads the given result file or falls back to the most recent run in the history.
+fn load_result(path: Option<PathBuf>, history: Option<&Path>) -> eyre::Result<SpeedTestResult> {
+    match path {
+        Some(path) => read_result(&path),
+        None => History::open_or_default(history)?
+            .latest()?
+            .ok_or_else(|| eyre::eyre!("no result recorded yet, run a test first")),
+    }
+}
+
+pub fn run(command: BaselineCommand, history: Option<&Path>) -> eyre::Result<()> {
+    let path =
+        default_path().ok_or_else(|| eyre::eyre!("unable to determine the data directory"))?;
+
+    match command {
+        BaselineCommand::Set { result } => {
+            let result = load_result(result, history)?;
+            write_atomic(&path, &serde_json::to_vec_pretty(&result)?)?;
+            println!(
+                "Baseline set to the result of {} ({}).",
+                result.timestamp.to_rfc3339(),
+                result.server.sponsor
+            );
+        }
+        BaselineCommand::Compare { result, threshold } => {
+            let baseline = read_result(&path)
+                .map_err(|e| eyre::eyre!("failed to read baseline {}: {e}", path.display()))?;
+            let current = load_result(result, history)?;
+            report(&baseline, &current, threshold)?;
+        }
+    }
+    Ok(())
+}
+
+/// Compares a finished run with the baseline, if one was set, and fails like `baseline compare`
+/// when it regressed by more than `threshold` percent.
+pub fn check(current: &SpeedTestResult, threshold: f64) -> eyre::Result<()> {
+    let Some(path) = default_path().filter(|path| path.exists()) else {
+        return Ok(());
+    };
+    let baseline = read_result(&path)
+        .map_err(|e| eyre::eyre!("failed to read baseline {}: {e}", path.display()))?;
+    report(&baseline, current, threshold)
+}
+
+/// Prints the deltas and fails when any metric regressed by more than `threshold` percent.
+fn report(
+    baseline: &SpeedTestResult,
+    current: &SpeedTestResult,
+    threshold: f64,
+) -> eyre::Result<()> {
+    let deltas = compare(baseline, current);
+    for delta in &deltas {
+        println!(
+            "{:<8} {:>9.2} -> {:>9.2} {:<4} ({:+.1}%)",
+            delta.name,
+            delta.baseline,
+            delta.current,
+            delta.unit,
+            delta.percent()
+        );
+    }
+
+    let regressed = deltas
+        .iter()
+        .filter(|d| d.regression() > threshold)
+        .map(|d| d.name)
+        .collect::<Vec<_>>();
+    if !regressed.is_empty() {
+        eyre::bail!(
+            "{} regressed by more than {threshold}%",
+            regressed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use speedtest_rs_core::result::{SpeedTestResult, TransferResult};
+
+    use crate::{
+        app::demo,
+        baseline::{Delta, report},
+    };
+
+    #[test]
+    fn test_report() {
+        let servers = demo::servers();
+        let result = |bytes| {
+            SpeedTestResult::new(Utc::now(), &demo::client(), &servers[0].0)
+                .with_download(TransferResult::new(bytes, Duration::from_secs(10)))
+        };
+        let baseline = result(125_000_000);
+
+        assert!(report(&baseline, &result(118_750_000), 10.0).is_ok());
+        let e = report(&baseline, &result(100_000_000), 10.0).unwrap_err();
+        assert_eq!(e.to_string(), "Download regressed by more than 10%");
+    }
+
+    #[test]
+    fn test_delta_regression() {
+        let download = Delta {
+            name: "Download",
+            baseline: 100.0,
+            current: 80.0,
+            unit: "Mbps",
+            higher_is_better: true,
+        };
+        assert_eq!(download.percent(), -20.0);
+        assert_eq!(download.regression(), 20.0);
+
+        let latency = Delta {
+            name: "Latency",
+            baseline: 10.0,
+            current: 8.0,
+            unit: "ms",
+            higher_is_better: false,
+        };
+        assert_eq!(latency.regression(), -20.0);
+
+        let empty = Delta {
+            baseline: 0.0,
+            ..download
+        };
+        assert_eq!(empty.percent(), 0.0);
+    }
+}
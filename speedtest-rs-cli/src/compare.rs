@@ -0,0 +1,96 @@
+//! `speedtest-rs compare`: the full test against several servers one after the other, e.g. for ISPs
+//! verifying the routes to different points of presence.
+
+use color_eyre::eyre;
+use speedtest_rs_core::{
+    redact::Redact,
+    result::{SpeedTestResult, TransferResult},
+    speed_tester::SpeedTester,
+};
+
+use crate::simple;
+
+/// Tests the `top` servers of the lowest latency and prints a row per server, or the results as a
+/// JSON array, masked with `redact`. Ctrl+C or SIGTERM skips the remaining servers.
+pub async fn run(
+    speed_tester: &SpeedTester,
+    top: usize,
+    json: bool,
+    redact: bool,
+) -> eyre::Result<()> {
+    let mut results = speed_tester
+        .clone()
+        .with_cancellation(simple::shutdown_token())
+        .run_multi_server_test(top)
+        .await
+        .map_err(|e| eyre::eyre!("{e}"))?;
+    if redact {
+        results = results.iter().map(Redact::redact).collect();
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        for line in table(&results) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+/// A header and a row per result, in the order the servers were tested.
+fn table(results: &[SpeedTestResult]) -> Vec<String> {
+    let rate = |transfer: Option<TransferResult>| match transfer {
+        Some(transfer) => format!("{:.1} Mbps", transfer.bits_per_second / 1_000_000.0),
+        None => "-".to_string(),
+    };
+
+    let mut lines = vec![format!(
+        "{:>8}  {:>11}  {:>11}  Server",
+        "Latency", "Download", "Upload"
+    )];
+    lines.extend(results.iter().map(|result| {
+        let latency = match result.idle_latency {
+            Some(latency) => format!("{:.1} ms", latency.avg_ms),
+            None => "-".to_string(),
+        };
+        format!(
+            "{latency:>8}  {:>11}  {:>11}  {:>6}  {} ({}, {})",
+            rate(result.download),
+            rate(result.upload),
+            result.server.id,
+            result.server.sponsor,
+            result.server.name,
+            result.server.cc
+        )
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use speedtest_rs_core::result::{SpeedTestResult, TransferResult};
+
+    use crate::{app::demo, compare::table};
+
+    #[test]
+    fn test_table() {
+        let servers = demo::servers();
+        let tested = SpeedTestResult::new(Utc::now(), &demo::client(), &servers[0].0)
+            .with_download(TransferResult::new(125_000_000, Duration::from_secs(10)));
+        let untested = SpeedTestResult::new(Utc::now(), &demo::client(), &servers[1].0);
+
+        let lines = table(&[tested, untested]);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(" Latency     Download       Upload  Server"));
+        assert!(lines[1].contains("  100.0 Mbps            -  "));
+        assert!(lines[1].ends_with(&format!(
+            "{}  {} ({}, {})",
+            servers[0].0.id, servers[0].0.sponsor, servers[0].0.name, servers[0].0.cc
+        )));
+        assert!(lines[2].starts_with("       -            -            -  "));
+    }
+}
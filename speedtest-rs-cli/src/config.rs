@@ -0,0 +1,490 @@
+//! Settings file support. Values given on the command line always take precedence.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use serde::Deserialize;
+use speedtest_rs_core::{
+    model::{Server, WarmUp},
+    net::IpFamily,
+    size::ByteSize,
+};
+
+use crate::{
+    args::{Args, DnsServer, Profile, parse_dns_server},
+    ookla::OoklaSettings,
+    theme::{ChartMarker, Palette},
+    uci::{self, UciConfig, UciSection},
+};
+
+/// Settings read from `config.toml` or the UCI config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FileConfig {
+    pub interface: Option<String>,
+
+    /// `"ipv4"` or `"ipv6"` to test over one family only.
+    pub ip_family: Option<IpFamily>,
+
+    /// Allow HTTP/2 multiplexing, disabled by default.
+    pub http2: Option<bool>,
+
+    pub profile: Option<Profile>,
+
+    pub palette: Option<Palette>,
+
+    pub chart_marker: Option<ChartMarker>,
+
+    /// Time between the chart samples, e.g. `"100ms"`.
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub sample_interval: Option<Duration>,
+
+    pub max_concurrency: Option<usize>,
+
+    pub latency_probes: Option<usize>,
+
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub download_duration: Option<Duration>,
+
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub upload_duration: Option<Duration>,
+
+    pub download_threads: Option<usize>,
+
+    pub upload_threads: Option<usize>,
+
+    pub download_sizes: Vec<usize>,
+
+    /// Upload request sizes, e.g. `["256K", "1M"]`.
+    #[serde(deserialize_with = "deserialize_sizes")]
+    pub upload_sizes: Vec<ByteSize>,
+
+    pub max_chunk_count: Option<usize>,
+
+    /// Start of each transfer left out of its rate, e.g. `"2s"` or `"1M"`.
+    #[serde(deserialize_with = "deserialize_warm_up")]
+    pub warm_up: Option<WarmUp>,
+
+    pub redact: Option<bool>,
+
+    pub idle_check: Option<bool>,
+
+    pub background: Option<bool>,
+
+    pub verify_upload: Option<bool>,
+
+    pub share: Option<bool>,
+
+    /// Search keyword for the candidate servers, e.g. a city.
+    pub search: Option<String>,
+
+    /// ISO code of the country the server has to be in.
+    pub cc: Option<String>,
+
+    /// Text the sponsor of the server has to contain.
+    pub sponsor: Option<String>,
+
+    /// Text the name of the server has to contain.
+    pub server_name: Option<String>,
+
+    /// Server to test against instead of racing.
+    pub server_id: Option<String>,
+
+    /// Drop the servers the remote config asks to ignore, enabled by default.
+    pub ignore_ids: Option<bool>,
+
+    pub probe_capacity: Option<bool>,
+
+    pub per_sponsor: Option<bool>,
+
+    pub dedup_by_ip: Option<bool>,
+
+    pub closest: Option<usize>,
+
+    pub probe_assets: Option<bool>,
+
+    /// Run the download, enabled by default.
+    pub download: Option<bool>,
+
+    /// Run the upload, enabled by default.
+    pub upload: Option<bool>,
+
+    /// Check the selected server before the test, enabled by default.
+    pub validate_server: Option<bool>,
+
+    /// Check the internet is reachable before the discovery, enabled by default.
+    pub connectivity_check: Option<bool>,
+
+    /// Check for a captive portal before the discovery, enabled by default.
+    pub portal_check: Option<bool>,
+
+    /// Monthly data allowance, e.g. `"50G"`.
+    #[serde(deserialize_with = "deserialize_size")]
+    pub monthly_cap: Option<ByteSize>,
+
+    /// Labels added to every result.
+    pub tags: Vec<String>,
+
+    pub download_url_template: Option<String>,
+
+    /// Download templates of individual servers by server id.
+    pub download_url_templates: HashMap<String, String>,
+
+    /// Hosts of the config and server lists instead of speedtest.net.
+    pub config_hosts: Vec<String>,
+
+    pub fetch_retries: Option<u32>,
+
+    /// Time limit of an attempt to fetch the config or server lists, e.g. `"5s"`.
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub fetch_timeout: Option<Duration>,
+
+    /// Self-hosted servers by the URL of their `upload.php`, tested instead of the server lists.
+    #[serde(deserialize_with = "deserialize_server_urls")]
+    pub server_urls: Vec<Server>,
+
+    /// DNS server of the lookups, e.g. `"1.1.1.1"` or `"https://dns.google/dns-query"`.
+    #[serde(deserialize_with = "deserialize_dns_server")]
+    pub dns: Option<DnsServer>,
+
+    /// Record results in the local history, enabled by default.
+    pub history: Option<bool>,
+
+    /// History database instead of the default location.
+    pub db_path: Option<PathBuf>,
+
+    /// Always overwrite this file with the most recent result.
+    pub latest_json: Option<PathBuf>,
+
+    /// Reuse recent race results on the same network, enabled by default.
+    pub race_cache: Option<bool>,
+
+    pub bell: Option<bool>,
+
+    pub on_complete: Option<String>,
+
+    pub pre_run: Option<String>,
+
+    /// Receives the result JSON on stdin.
+    pub post_run: Option<String>,
+
+    /// Settings of the Ookla CLI to import, empty for its default location.
+    pub ookla_config: Option<PathBuf>,
+}
+
+impl FileConfig {
+    /// `$XDG_CONFIG_HOME/speedtest-rs/config.toml` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("speedtest-rs").join("config.toml"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let raw = std::fs::read_to_string(path.as_ref())?;
+        Ok(toml::from_str(&raw)?)
+    }
+
+    pub fn from_uci(section: &UciSection) -> eyre::Result<Self> {
+        let parse_bool = |value: &str| matches!(value, "1" | "on" | "yes" | "true" | "enabled");
+
+        Ok(Self {
+            interface: section.option("interface").map(str::to_string),
+            ip_family: section
+                .option("ip_family")
+                .map(|v| v.parse().map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            http2: section.option("http2").map(parse_bool),
+            profile: section
+                .option("profile")
+                .map(|v| Profile::from_str(v, true).map_err(eyre::Error::msg))
+                .transpose()?,
+            palette: section
+                .option("palette")
+                .map(|v| Palette::from_str(v, true).map_err(eyre::Error::msg))
+                .transpose()?,
+            chart_marker: section
+                .option("chart_marker")
+                .map(|v| ChartMarker::from_str(v, true).map_err(eyre::Error::msg))
+                .transpose()?,
+            max_concurrency: section
+                .option("max_concurrency")
+                .map(str::parse)
+                .transpose()?,
+            latency_probes: section
+                .option("latency_probes")
+                .map(str::parse)
+                .transpose()?,
+            download_duration: section
+                .option("download_duration")
+                .map(|v| crate::ping::parse_interval(v).map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            upload_duration: section
+                .option("upload_duration")
+                .map(|v| crate::ping::parse_interval(v).map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            download_threads: section
+                .option("download_threads")
+                .map(str::parse)
+                .transpose()?,
+            upload_threads: section
+                .option("upload_threads")
+                .map(str::parse)
+                .transpose()?,
+            download_sizes: section
+                .list("download_size")
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            upload_sizes: section
+                .list("upload_size")
+                .map(|v| v.parse().map_err(|e| eyre::eyre!("{e}")))
+                .collect::<Result<_, _>>()?,
+            max_chunk_count: section
+                .option("max_chunk_count")
+                .map(str::parse)
+                .transpose()?,
+            redact: section.option("redact").map(parse_bool),
+            idle_check: section.option("idle_check").map(parse_bool),
+            background: section.option("background").map(parse_bool),
+            verify_upload: section.option("verify_upload").map(parse_bool),
+            share: section.option("share").map(parse_bool),
+            search: section.option("search").map(str::to_string),
+            cc: section.option("cc").map(str::to_string),
+            sponsor: section.option("sponsor").map(str::to_string),
+            server_name: section.option("server_name").map(str::to_string),
+            server_id: section.option("server_id").map(str::to_string),
+            ignore_ids: section.option("ignore_ids").map(parse_bool),
+            probe_capacity: section.option("probe_capacity").map(parse_bool),
+            per_sponsor: section.option("per_sponsor").map(parse_bool),
+            dedup_by_ip: section.option("dedup_by_ip").map(parse_bool),
+            closest: section.option("closest").map(str::parse).transpose()?,
+            probe_assets: section.option("probe_assets").map(parse_bool),
+            download: section.option("download").map(parse_bool),
+            upload: section.option("upload").map(parse_bool),
+            validate_server: section.option("validate_server").map(parse_bool),
+            connectivity_check: section.option("connectivity_check").map(parse_bool),
+            portal_check: section.option("portal_check").map(parse_bool),
+            warm_up: section
+                .option("warm_up")
+                .map(|v| v.parse().map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            sample_interval: section
+                .option("sample_interval")
+                .map(|v| crate::ping::parse_interval(v).map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            monthly_cap: section
+                .option("monthly_cap")
+                .map(|v| v.parse().map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            tags: section.list("tag").map(str::to_string).collect(),
+            download_url_template: section.option("download_url_template").map(str::to_string),
+            download_url_templates: HashMap::new(),
+            config_hosts: section.list("config_host").map(str::to_string).collect(),
+            fetch_retries: section
+                .option("fetch_retries")
+                .map(str::parse)
+                .transpose()?,
+            fetch_timeout: section
+                .option("fetch_timeout")
+                .map(|v| crate::ping::parse_interval(v).map_err(|e| eyre::eyre!("{e}")))
+                .transpose()?,
+            server_urls: section
+                .list("server_url")
+                .map(|v| Server::from_url(v).map_err(|e| eyre::eyre!("{e}")))
+                .collect::<Result<_, _>>()?,
+            dns: section
+                .option("dns")
+                .map(|v| parse_dns_server(v).map_err(eyre::Error::msg))
+                .transpose()?,
+            history: section.option("history").map(parse_bool),
+            db_path: section.option("db_path").map(PathBuf::from),
+            latest_json: section.option("latest_json").map(PathBuf::from),
+            race_cache: section.option("race_cache").map(parse_bool),
+            bell: section.option("bell").map(parse_bool),
+            on_complete: section.option("on_complete").map(str::to_string),
+            pre_run: section.option("pre_run").map(str::to_string),
+            post_run: section.option("post_run").map(str::to_string),
+            ookla_config: section.option("ookla_config").map(PathBuf::from),
+        })
+    }
+}
+
+/// Intervals are written with a unit, e.g. `"100ms"`.
+fn deserialize_interval<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|interval| crate::ping::parse_interval(&interval).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Sizes are written with a unit in the config, e.g. `"50G"`.
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<ByteSize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|size| size.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_warm_up<'de, D>(deserializer: D) -> Result<Option<WarmUp>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|warm_up| warm_up.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_server_urls<'de, D>(deserializer: D) -> Result<Vec<Server>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|url| Server::from_url(url).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn deserialize_dns_server<'de, D>(deserializer: D) -> Result<Option<DnsServer>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| parse_dns_server(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_sizes<'de, D>(deserializer: D) -> Result<Vec<ByteSize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|size| size.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+impl Args {
+    /// Fills the settings not given on the command line from `config.toml` and the UCI config.
+    pub fn load_config(&mut self) -> eyre::Result<()> {
+        let file = match &self.config {
+            Some(path) => Some(FileConfig::load(path)?),
+            None => match FileConfig::default_path() {
+                Some(path) if path.exists() => Some(FileConfig::load(path)?),
+                _ => None,
+            },
+        };
+        if let Some(file) = file {
+            self.merge(file);
+        }
+
+        let uci = match &self.uci_config {
+            Some(path) => Some(UciConfig::load(path)?),
+            None if Path::new(uci::DEFAULT_UCI_PATH).exists() => {
+                Some(UciConfig::load(uci::DEFAULT_UCI_PATH)?)
+            }
+            None => None,
+        };
+        if let Some(section) = uci.as_ref().and_then(|uci| uci.section("speedtest")) {
+            self.merge(FileConfig::from_uci(section)?);
+        }
+
+        // Imported last, so the settings of speedtest-rs itself take precedence.
+        if let Some(path) = &self.ookla_config {
+            let path = if path.as_os_str().is_empty() {
+                OoklaSettings::default_path()
+                    .ok_or_else(|| eyre::eyre!("unable to determine the config directory"))?
+            } else {
+                path.clone()
+            };
+            self.merge(OoklaSettings::load(path)?.to_file_config());
+        }
+
+        Ok(())
+    }
+
+    fn merge(&mut self, file: FileConfig) {
+        self.interface = self.interface.take().or(file.interface);
+        if !self.ipv4 && !self.ipv6 {
+            self.ipv4 = file.ip_family == Some(IpFamily::V4);
+            self.ipv6 = file.ip_family == Some(IpFamily::V6);
+        }
+        self.http2 |= file.http2.unwrap_or_default();
+        self.profile = self.profile.or(file.profile);
+        self.palette = self.palette.or(file.palette);
+        self.chart_marker = self.chart_marker.or(file.chart_marker);
+        self.sample_interval = self.sample_interval.or(file.sample_interval);
+        self.max_concurrency = self.max_concurrency.or(file.max_concurrency);
+        self.latency_probes = self.latency_probes.or(file.latency_probes);
+        self.download_duration = self.download_duration.or(file.download_duration);
+        self.upload_duration = self.upload_duration.or(file.upload_duration);
+        self.download_threads = self.download_threads.or(file.download_threads);
+        self.upload_threads = self.upload_threads.or(file.upload_threads);
+        if self.download_sizes.is_empty() {
+            self.download_sizes = file.download_sizes;
+        }
+        if self.upload_sizes.is_empty() {
+            self.upload_sizes = file.upload_sizes;
+        }
+        self.max_chunk_count = self.max_chunk_count.or(file.max_chunk_count);
+        self.warm_up = self.warm_up.or(file.warm_up);
+        self.redact |= file.redact.unwrap_or_default();
+        self.idle_check |= file.idle_check.unwrap_or_default();
+        self.background |= file.background.unwrap_or_default();
+        self.verify_upload |= file.verify_upload.unwrap_or_default();
+        self.share |= file.share.unwrap_or_default();
+        self.search = self.search.take().or(file.search);
+        self.cc = self.cc.take().or(file.cc);
+        self.sponsor = self.sponsor.take().or(file.sponsor);
+        self.server_name = self.server_name.take().or(file.server_name);
+        self.server_id = self.server_id.take().or(file.server_id);
+        self.no_ignore |= file.ignore_ids == Some(false);
+        self.probe_capacity |= file.probe_capacity.unwrap_or_default();
+        self.per_sponsor |= file.per_sponsor.unwrap_or_default();
+        self.dedup_by_ip |= file.dedup_by_ip.unwrap_or_default();
+        self.closest = self.closest.or(file.closest);
+        self.probe_assets |= file.probe_assets.unwrap_or_default();
+        self.no_download |= file.download == Some(false);
+        self.no_upload |= file.upload == Some(false);
+        self.no_validate |= file.validate_server == Some(false);
+        self.no_connectivity_check |= file.connectivity_check == Some(false);
+        self.no_portal_check |= file.portal_check == Some(false);
+        self.monthly_cap = self.monthly_cap.or(file.monthly_cap);
+        if self.tags.is_empty() {
+            self.tags = file.tags;
+        }
+        self.download_url_template = self
+            .download_url_template
+            .take()
+            .or(file.download_url_template);
+        for (server_id, template) in file.download_url_templates {
+            self.server_download_url_templates
+                .entry(server_id)
+                .or_insert(template);
+        }
+        if self.config_hosts.is_empty() {
+            self.config_hosts = file.config_hosts;
+        }
+        self.fetch_retries = self.fetch_retries.or(file.fetch_retries);
+        self.fetch_timeout = self.fetch_timeout.or(file.fetch_timeout);
+        if self.server_urls.is_empty() {
+            self.server_urls = file.server_urls;
+        }
+        self.dns = self.dns.take().or(file.dns);
+        self.no_history |= file.history == Some(false);
+        self.db_path = self.db_path.take().or(file.db_path);
+        self.latest_json = self.latest_json.take().or(file.latest_json);
+        self.no_race_cache |= file.race_cache == Some(false);
+        self.bell |= file.bell.unwrap_or_default();
+        self.on_complete = self.on_complete.take().or(file.on_complete);
+        self.pre_run = self.pre_run.take().or(file.pre_run);
+        self.post_run = self.post_run.take().or(file.post_run);
+        self.ookla_config = self.ookla_config.take().or(file.ookla_config);
+    }
+}
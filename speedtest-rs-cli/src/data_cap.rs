@@ -0,0 +1,153 @@
+//! Guard rails for metered connections: once the data of this month's runs approaches
+//! `--monthly-cap`, runs measure the latency only, and the data of a run is estimated up front.
+
+use std::{
+    io::{BufRead, Write},
+    time::Duration,
+};
+
+use speedtest_rs_core::{
+    Humanize,
+    result::{SpeedTestResult, Warning, WarningCode},
+    size::ByteSize,
+};
+
+use crate::args::Profile;
+
+/// Share of the cap from which runs stop transferring bulk data.
+pub const DATA_CAP_MARGIN: f64 = 0.9;
+
+/// Data the runs of the current month transferred, against the configured cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataCap {
+    pub used: ByteSize,
+    pub cap: ByteSize,
+}
+
+impl DataCap {
+    pub fn is_near(&self) -> bool {
+        self.used.bytes() as f64 >= self.cap.bytes() as f64 * DATA_CAP_MARGIN
+    }
+
+    pub fn warning(&self) -> Warning {
+        Warning::new(
+            WarningCode::DataCap,
+            format!(
+                "Monthly data cap nearly reached ({} of {}), measured latency only",
+                self.used.humanize_bytes(),
+                self.cap.humanize_bytes()
+            ),
+        )
+    }
+}
+
+/// Transfer time of the usual remote config, per direction.
+const TYPICAL_TRANSFER: Duration = Duration::from_secs(10);
+
+/// Likely data of a run: what the latest recorded run transferred, or the typical rates of the
+/// profile over the usual transfer time.
+pub fn estimate_usage(profile: Profile, latest: Option<&SpeedTestResult>) -> ByteSize {
+    let recorded = latest
+        .map(|result| result.download.map_or(0, |t| t.bytes) + result.upload.map_or(0, |t| t.bytes))
+        .filter(|&bytes| bytes > 0);
+    if let Some(bytes) = recorded {
+        return ByteSize(bytes);
+    }
+
+    let (download, upload) = profile.typical_bitrates();
+    ByteSize(((download + upload) / 8.0 * TYPICAL_TRANSFER.as_secs_f64()) as u64)
+}
+
+/// Asks whether to start a run transferring about `estimate`, anything but yes declines.
+pub fn confirm_usage(
+    estimate: ByteSize,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> std::io::Result<bool> {
+    write!(
+        output,
+        "This run will transfer about {}. Continue? [y/N] ",
+        estimate.humanize_bytes()
+    )?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use speedtest_rs_core::{
+        result::{SpeedTestResult, TransferResult},
+        size::ByteSize,
+    };
+
+    use crate::{
+        app::demo,
+        args::Profile,
+        data_cap::{DataCap, confirm_usage, estimate_usage},
+    };
+
+    #[test]
+    fn test_data_cap() {
+        let cap = ByteSize::mib(1000);
+        assert!(
+            !DataCap {
+                used: ByteSize::mib(899),
+                cap
+            }
+            .is_near()
+        );
+
+        let near = DataCap {
+            used: ByteSize::mib(900),
+            cap,
+        };
+        assert!(near.is_near());
+        assert_eq!(
+            near.warning().message,
+            "Monthly data cap nearly reached (900.00 MBytes of 1000.00 MBytes), measured latency only"
+        );
+    }
+
+    #[test]
+    fn test_estimate_usage() {
+        assert_eq!(estimate_usage(Profile::Termux, None), ByteSize(75_000_000));
+        assert!(estimate_usage(Profile::Desktop, None) > estimate_usage(Profile::Termux, None));
+
+        let mut latest = SpeedTestResult::new(Utc::now(), &demo::client(), &demo::servers()[0].0)
+            .with_download(TransferResult::new(400_000_000, Duration::from_secs(10)))
+            .with_upload(TransferResult::new(50_000_000, Duration::from_secs(10)));
+        assert_eq!(
+            estimate_usage(Profile::Termux, Some(&latest)),
+            ByteSize(450_000_000)
+        );
+
+        latest.download = None;
+        latest.upload = None;
+        assert_eq!(
+            estimate_usage(Profile::Termux, Some(&latest)),
+            ByteSize(75_000_000)
+        );
+    }
+
+    #[test]
+    fn test_confirm_usage() {
+        let mut prompt = vec![];
+        assert!(confirm_usage(ByteSize::mib(100), &b"y\n"[..], &mut prompt).unwrap());
+        assert_eq!(
+            String::from_utf8(prompt).unwrap(),
+            "This run will transfer about 100.00 MBytes. Continue? [y/N] "
+        );
+        assert!(confirm_usage(ByteSize::mib(100), &b"Yes\n"[..], vec![]).unwrap());
+        assert!(!confirm_usage(ByteSize::mib(100), &b"\n"[..], vec![]).unwrap());
+        assert!(!confirm_usage(ByteSize::mib(100), &b""[..], vec![]).unwrap());
+    }
+}
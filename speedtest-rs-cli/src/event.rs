@@ -1,14 +1,21 @@
 use color_eyre::eyre::OptionExt;
 use crossterm::event::Event as CrosstermEvent;
 use futures::{FutureExt, StreamExt};
-use speedtest_rs_core::model::Server;
+use speedtest_rs_core::{
+    ifstats::IdleTraffic,
+    latency::Latency,
+    model::Server,
+    result::SpeedTestResult,
+    sampler::ThroughputSample,
+    stage::{RaceProbe, StageEvent},
+};
 use std::time::Duration;
 use tokio::sync::mpsc;
 
 use crate::app::SimpleConfig;
 
-/// The frequency at which tick events are emitted.
-const TICK_FPS: f64 = 10.0;
+/// The interval at which tick events are emitted, which bounds how often the transfers are sampled.
+pub(crate) const TICK_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Representation of all possible events.
 #[derive(Clone, Debug)]
@@ -38,6 +45,34 @@ pub enum AppEvent {
     Quit,
 
     SetState(State),
+
+    /// A server probe completed while racing.
+    RaceProbe(RaceProbe),
+
+    /// The pre-checks found the network unusable, the run is aborted.
+    NetworkIssue(NetworkIssue),
+
+    /// Result of the idle traffic pre-check.
+    IdleTraffic(IdleTraffic),
+
+    /// Rate of the running transfer over the last sample interval.
+    Throughput(ThroughputSample),
+
+    /// Progress of the running stage as reported by the core.
+    Stage(StageEvent),
+
+    /// The whole run completed.
+    Finished(Box<SpeedTestResult>),
+}
+
+/// Why the network cannot be tested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkIssue {
+    /// Nothing answers, e.g. the cable is unplugged or DNS is down.
+    NoConnectivity,
+
+    /// Requests are redirected to a login page.
+    CaptivePortal,
 }
 
 /// Application state.
@@ -50,13 +85,16 @@ pub enum State {
     FetchServers(Status<Vec<Server>>),
 
     /// Step3. Racing fastest server
-    RacingServers(Status<Server>),
+    RacingServers(Status<Box<Server>>),
 
-    /// Step4. Download
-    Download(Status<()>),
+    /// Step4. Idle latency baseline
+    IdleLatency(Status<Option<Latency>>),
 
-    /// Step5. Upload
-    Upload(Status<()>),
+    /// Step5. Download, with the latency measured under load
+    Download(Status<Option<Latency>>),
+
+    /// Step6. Upload, with the latency measured under load
+    Upload(Status<Option<Latency>>),
 }
 
 #[derive(Debug, Clone)]
@@ -108,6 +146,11 @@ impl EventHandler {
             .ok_or_eyre("Failed to receive event")
     }
 
+    /// Returns an already queued event without waiting.
+    pub fn try_next(&mut self) -> Option<Event> {
+        self.receiver.try_recv().ok()
+    }
+
     /// Queue an app event to be sent to the event receiver.
     ///
     /// This is useful for sending events to the event handler which will be processed by the next
@@ -139,9 +182,8 @@ impl EventTask {
     ///
     /// This function emits tick events at a fixed rate and polls for crossterm events in between.
     async fn run(self) -> color_eyre::Result<()> {
-        let tick_rate = Duration::from_secs_f64(1.0 / TICK_FPS);
         let mut reader = crossterm::event::EventStream::new();
-        let mut tick = tokio::time::interval(tick_rate);
+        let mut tick = tokio::time::interval(TICK_INTERVAL);
 
         loop {
             let tick_delay = tick.tick();
@@ -176,6 +218,7 @@ impl State {
             State::FetchConfig(Status::Err(_))
                 | State::FetchServers(Status::Err(_))
                 | State::RacingServers(Status::Err(_))
+                | State::IdleLatency(Status::Err(_))
                 | State::Download(Status::Err(_))
                 | State::Upload(Status::Err(_))
         )
@@ -186,19 +229,26 @@ impl State {
             Self::FetchConfig(_) => vec![
                 State::FetchServers(Status::Canceled),
                 State::RacingServers(Status::Canceled),
+                State::IdleLatency(Status::Canceled),
                 State::Download(Status::Canceled),
                 State::Upload(Status::Canceled),
             ],
             Self::FetchServers(_) => vec![
                 State::RacingServers(Status::Canceled),
+                State::IdleLatency(Status::Canceled),
                 State::Download(Status::Canceled),
                 State::Upload(Status::Canceled),
             ],
             Self::RacingServers(_) => vec![
+                State::IdleLatency(Status::Canceled),
                 State::Download(Status::Canceled),
                 State::Upload(Status::Canceled),
             ],
-            Self::Download(_) => vec![State::Upload(Status::Canceled)],
+            Self::IdleLatency(_) => vec![
+                State::Download(Status::Canceled),
+                State::Upload(Status::Canceled),
+            ],
+            // A download that transferred nothing does not prevent the upload from working.
             _ => vec![],
         }
     }
@@ -210,6 +260,18 @@ impl From<AppEvent> for Event {
     }
 }
 
+impl From<ThroughputSample> for Event {
+    fn from(sample: ThroughputSample) -> Self {
+        Event::App(AppEvent::Throughput(sample))
+    }
+}
+
+impl From<StageEvent> for Event {
+    fn from(event: StageEvent) -> Self {
+        Event::App(AppEvent::Stage(event))
+    }
+}
+
 impl From<State> for Event {
     fn from(value: State) -> Self {
         Event::App(AppEvent::SetState(value))
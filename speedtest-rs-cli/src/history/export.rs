@@ -0,0 +1,196 @@
+//! Tabular export of stored runs.
+
+use speedtest_rs_core::result::SpeedTestResult;
+
+/// Columns of the Parquet export, one row per run.
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "
+message run {
+    REQUIRED INT64 timestamp (TIMESTAMP(MICROS, true));
+    OPTIONAL BYTE_ARRAY server_id (UTF8);
+    OPTIONAL BYTE_ARRAY server_sponsor (UTF8);
+    OPTIONAL BYTE_ARRAY server_name (UTF8);
+    OPTIONAL DOUBLE download_bps;
+    OPTIONAL DOUBLE upload_bps;
+    OPTIONAL DOUBLE idle_latency_ms;
+    OPTIONAL DOUBLE jitter_ms;
+    OPTIONAL BYTE_ARRAY tags (UTF8);
+    OPTIONAL BYTE_ARRAY note (UTF8);
+}
+";
+
+const CSV_HEADER: &str = "timestamp,server_id,server_sponsor,server_name,download_bps,upload_bps,\
+                          idle_latency_ms,tags,note";
+
+/// One row per run, empty cells for missing figures and tags joined by `;`.
+pub fn to_csv(results: &[SpeedTestResult]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+    for result in results {
+        let row = [
+            result.timestamp.to_rfc3339(),
+            result.server.id.clone(),
+            result.server.sponsor.clone(),
+            result.server.name.clone(),
+            result
+                .download
+                .map_or(String::new(), |d| format!("{:.0}", d.bits_per_second)),
+            result
+                .upload
+                .map_or(String::new(), |u| format!("{:.0}", u.bits_per_second)),
+            result
+                .idle_latency
+                .map_or(String::new(), |l| format!("{:.1}", l.avg_ms)),
+            result.tags.join(";"),
+            result.note.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// A Parquet file with a single row group, e.g. for pandas or DuckDB. Tags are joined by `;` as
+/// in the CSV export.
+#[cfg(feature = "parquet")]
+pub fn to_parquet(results: &[SpeedTestResult]) -> color_eyre::eyre::Result<Vec<u8>> {
+    use std::sync::Arc;
+
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+
+    enum Column {
+        Timestamp(Vec<i64>),
+        Double(Vec<Option<f64>>),
+        Text(Vec<Option<String>>),
+    }
+
+    let text =
+        |f: fn(&SpeedTestResult) -> Option<String>| Column::Text(results.iter().map(f).collect());
+    let double =
+        |f: fn(&SpeedTestResult) -> Option<f64>| Column::Double(results.iter().map(f).collect());
+    let columns = [
+        Column::Timestamp(
+            results
+                .iter()
+                .map(|r| r.timestamp.timestamp_micros())
+                .collect(),
+        ),
+        text(|r| Some(r.server.id.clone())),
+        text(|r| Some(r.server.sponsor.clone())),
+        text(|r| Some(r.server.name.clone())),
+        double(|r| r.download.map(|d| d.bits_per_second)),
+        double(|r| r.upload.map(|u| u.bits_per_second)),
+        double(|r| r.idle_latency.map(|l| l.avg_ms)),
+        double(|r| r.idle_latency.map(|l| l.jitter_ms)),
+        text(|r| Some(r.tags.join(";")).filter(|tags| !tags.is_empty())),
+        text(|r| r.note.clone()),
+    ];
+
+    // Optional columns take the values that are present and a definition level per row.
+    fn levels<T: Clone>(values: &[Option<T>]) -> (Vec<T>, Vec<i16>) {
+        let present = values.iter().flatten().cloned().collect();
+        let levels = values.iter().map(|v| i16::from(v.is_some())).collect();
+        (present, levels)
+    }
+
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let mut buf = vec![];
+    let mut writer =
+        SerializedFileWriter::new(&mut buf, schema, Arc::new(WriterProperties::default()))?;
+    let mut row_group = writer.next_row_group()?;
+    for column in &columns {
+        let mut writer = row_group
+            .next_column()?
+            .ok_or_else(|| color_eyre::eyre::eyre!("parquet schema has too few columns"))?;
+        match column {
+            Column::Timestamp(values) => {
+                writer
+                    .typed::<Int64Type>()
+                    .write_batch(values, None, None)?;
+            }
+            Column::Double(values) => {
+                let (present, levels) = levels(values);
+                writer
+                    .typed::<DoubleType>()
+                    .write_batch(&present, Some(&levels), None)?;
+            }
+            Column::Text(values) => {
+                let (present, levels) = levels(values);
+                let present: Vec<ByteArray> = present
+                    .into_iter()
+                    .map(|s| ByteArray::from(s.into_bytes()))
+                    .collect();
+                writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&present, Some(&levels), None)?;
+            }
+        }
+        writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(buf)
+}
+
+/// Quotes fields containing separators, quotes or line breaks.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::export::csv_field;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Hong Kong"), "Hong Kong");
+        assert_eq!(
+            csv_field("after update, reboot"),
+            "\"after update, reboot\""
+        );
+        assert_eq!(
+            csv_field("the \"new\" router"),
+            "\"the \"\"new\"\" router\""
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_to_parquet() {
+        use std::time::Duration;
+
+        use chrono::Utc;
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use speedtest_rs_core::result::{SpeedTestResult, TransferResult};
+
+        use crate::{app::demo, history::export::to_parquet};
+
+        let servers = demo::servers();
+        let results = [
+            SpeedTestResult::new(Utc::now(), &demo::client(), &servers[0].0)
+                .with_download(TransferResult::new(1_000_000, Duration::from_secs(1)))
+                .with_note(Some("after firmware update".to_string())),
+            SpeedTestResult::new(Utc::now(), &demo::client(), &servers[1].0),
+        ];
+
+        let path = std::env::temp_dir().join(format!(
+            "speedtest-rs-test-export-{}.parquet",
+            std::process::id()
+        ));
+        std::fs::write(&path, to_parquet(&results).unwrap()).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 2);
+        assert_eq!(metadata.schema_descr().num_columns(), 10);
+
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::Write;
+
+use chrono::{Datelike, Local, Timelike};
+use speedtest_rs_core::{Humanize, result::SpeedTestResult};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+const SVG_CELL: usize = 22;
+
+/// Median download speed per (day of week, hour of day) in local time.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    /// Bits per second, indexed by `[weekday from monday][hour]`.
+    cells: [[Option<f64>; 24]; 7],
+}
+
+impl Heatmap {
+    pub fn from_results(results: &[SpeedTestResult]) -> Self {
+        let mut samples: Vec<Vec<Vec<f64>>> = vec![vec![vec![]; 24]; 7];
+
+        for result in results {
+            let Some(download) = result.download else {
+                continue;
+            };
+            let local = result.timestamp.with_timezone(&Local);
+            samples[local.weekday().num_days_from_monday() as usize][local.hour() as usize]
+                .push(download.bits_per_second);
+        }
+
+        let mut cells = [[None; 24]; 7];
+        for (day, hours) in samples.iter_mut().enumerate() {
+            for (hour, values) in hours.iter_mut().enumerate() {
+                cells[day][hour] = median(values);
+            }
+        }
+        Self { cells }
+    }
+
+    fn range(&self) -> Option<(f64, f64)> {
+        self.cells
+            .iter()
+            .flatten()
+            .flatten()
+            .fold(None, |range, &v| match range {
+                None => Some((v, v)),
+                Some((min, max)) => Some((min.min(v), max.max(v))),
+            })
+    }
+
+    /// Position of a value within the observed range, in `0.0..=1.0`.
+    fn level(&self, value: f64) -> f64 {
+        match self.range() {
+            Some((min, max)) if max > min => (value - min) / (max - min),
+            _ => 1.0,
+        }
+    }
+
+    pub fn render_terminal(&self) -> String {
+        let mut out = String::new();
+
+        let Some((min, max)) = self.range() else {
+            return "No downloads in history yet.\n".to_string();
+        };
+
+        _ = writeln!(out, "Median download speed by hour of day (local time)\n");
+        _ = write!(out, "    ");
+        for hour in 0..24 {
+            _ = write!(out, " {hour:02}");
+        }
+        _ = writeln!(out);
+
+        for (day, hours) in self.cells.iter().enumerate() {
+            _ = write!(out, "{}", WEEKDAYS[day]);
+            _ = write!(out, " ");
+            for cell in hours {
+                let shade = match cell {
+                    Some(v) => {
+                        let idx = (self.level(*v) * (SHADES.len() - 1) as f64).round() as usize;
+                        SHADES[idx].to_string().repeat(2)
+                    }
+                    None => "  ".to_string(),
+                };
+                _ = write!(out, " {shade}");
+            }
+            _ = writeln!(out);
+        }
+
+        _ = writeln!(
+            out,
+            "\n{} {}  ..  {} {}",
+            SHADES[0],
+            format_bps(min),
+            SHADES[SHADES.len() - 1],
+            format_bps(max)
+        );
+        out
+    }
+
+    pub fn render_svg(&self) -> String {
+        let label_width = 40;
+        let header_height = 20;
+        let width = label_width + SVG_CELL * 24;
+        let height = header_height + SVG_CELL * 7;
+
+        let mut svg = String::new();
+        _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="11">"#
+        );
+
+        for hour in 0..24 {
+            _ = writeln!(
+                svg,
+                r#"<text x="{}" y="14" text-anchor="middle">{hour:02}</text>"#,
+                label_width + hour * SVG_CELL + SVG_CELL / 2
+            );
+        }
+
+        for (day, hours) in self.cells.iter().enumerate() {
+            let y = header_height + day * SVG_CELL;
+            _ = writeln!(
+                svg,
+                r#"<text x="4" y="{}">{}</text>"#,
+                y + SVG_CELL / 2 + 4,
+                WEEKDAYS[day]
+            );
+
+            for (hour, cell) in hours.iter().enumerate() {
+                let x = label_width + hour * SVG_CELL;
+                match cell {
+                    Some(v) => {
+                        // Red for the slowest hours, green for the fastest.
+                        let level = self.level(*v);
+                        let red = (220.0 * (1.0 - level)) as u8;
+                        let green = (60.0 + 140.0 * level) as u8;
+                        _ = writeln!(
+                            svg,
+                            r##"<rect x="{x}" y="{y}" width="{w}" height="{w}" fill="#{red:02x}{green:02x}50"><title>{} {hour:02}:00 {}</title></rect>"##,
+                            WEEKDAYS[day],
+                            format_bps(*v),
+                            w = SVG_CELL - 2,
+                        );
+                    }
+                    None => {
+                        _ = writeln!(
+                            svg,
+                            r##"<rect x="{x}" y="{y}" width="{w}" height="{w}" fill="#eeeeee"/>"##,
+                            w = SVG_CELL - 2,
+                        );
+                    }
+                }
+            }
+        }
+
+        _ = writeln!(svg, "</svg>");
+        svg
+    }
+}
+
+fn format_bps(bps: f64) -> String {
+    ((bps / 8.0) as usize).humanize_bitrate(1000)
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::heatmap::median;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+}
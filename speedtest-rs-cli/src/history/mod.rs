@@ -0,0 +1,456 @@
+//! Local store of previous results, kept in a SQLite database.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use speedtest_rs_core::{redact::Redact, result::SpeedTestResult};
+
+use crate::{
+    args::{ExportFormat, HistoryCommand, HistoryFilter},
+    sink::write_atomic,
+};
+
+pub mod export;
+pub mod heatmap;
+pub mod query;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS runs (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp       TEXT    NOT NULL,
+    client_ip       TEXT    NOT NULL,
+    client_isp      TEXT    NOT NULL,
+    client_country  TEXT    NOT NULL,
+    server_id       TEXT    NOT NULL,
+    server_name     TEXT    NOT NULL,
+    server_sponsor  TEXT    NOT NULL,
+    server_country  TEXT    NOT NULL,
+    download_bps    REAL,
+    download_bytes  INTEGER,
+    upload_bps      REAL,
+    upload_bytes    INTEGER,
+    note            TEXT,
+    aborted         INTEGER NOT NULL DEFAULT 0,
+    result_json     TEXT    NOT NULL
+);
+CREATE INDEX IF NOT EXISTS runs_timestamp ON runs (timestamp);
+"#;
+
+/// Read-only views for dashboards such as Grafana's SQLite datasource, recreated on open so they
+/// follow schema changes. Column names are kept stable across versions. The rates leave out runs
+/// quit midway, the usage counts their bytes.
+const VIEWS: &str = r#"
+DROP VIEW IF EXISTS run_metrics;
+CREATE VIEW run_metrics AS
+SELECT
+    id,
+    CAST(strftime('%s', timestamp) AS INTEGER) AS time,
+    timestamp,
+    server_id,
+    server_sponsor,
+    server_name,
+    download_bps / 1e6 AS download_mbps,
+    upload_bps / 1e6 AS upload_mbps,
+    json_extract(result_json, '$.idle_latency.avg_ms') AS latency_ms,
+    json_extract(result_json, '$.idle_latency.jitter_ms') AS jitter_ms,
+    note
+FROM runs
+WHERE aborted = 0;
+
+DROP VIEW IF EXISTS daily_stats;
+CREATE VIEW daily_stats AS
+SELECT
+    date(timestamp) AS day,
+    CAST(strftime('%s', date(timestamp)) AS INTEGER) AS time,
+    COUNT(*) AS runs,
+    AVG(download_mbps) AS avg_download_mbps,
+    MIN(download_mbps) AS min_download_mbps,
+    MAX(download_mbps) AS max_download_mbps,
+    AVG(upload_mbps) AS avg_upload_mbps,
+    MIN(upload_mbps) AS min_upload_mbps,
+    MAX(upload_mbps) AS max_upload_mbps,
+    AVG(latency_ms) AS avg_latency_ms
+FROM run_metrics
+GROUP BY day;
+
+DROP VIEW IF EXISTS daily_usage;
+CREATE VIEW daily_usage AS
+SELECT
+    date(timestamp) AS day,
+    CAST(strftime('%s', date(timestamp)) AS INTEGER) AS time,
+    COUNT(*) AS runs,
+    COALESCE(SUM(download_bytes), 0) AS download_bytes,
+    COALESCE(SUM(upload_bytes), 0) AS upload_bytes,
+    COALESCE(SUM(download_bytes), 0) + COALESCE(SUM(upload_bytes), 0) AS total_bytes
+FROM runs
+GROUP BY day;
+
+DROP VIEW IF EXISTS server_stats;
+CREATE VIEW server_stats AS
+SELECT
+    server_id,
+    server_sponsor,
+    server_name,
+    COUNT(*) AS runs,
+    MAX(time) AS last_time,
+    AVG(download_mbps) AS avg_download_mbps,
+    MAX(download_mbps) AS max_download_mbps,
+    AVG(upload_mbps) AS avg_upload_mbps,
+    MAX(upload_mbps) AS max_upload_mbps,
+    AVG(latency_ms) AS avg_latency_ms
+FROM run_metrics
+GROUP BY server_id;
+"#;
+
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// `$XDG_DATA_HOME/speedtest-rs/history.db` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("speedtest-rs").join("history.db"))
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        if let Some(dir) = path.as_ref().parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        migrate(&conn)?;
+        conn.execute_batch(VIEWS)?;
+        Ok(Self { conn })
+    }
+
+    /// Opens the database at `path`, or at the default path if `None`.
+    pub fn open_or_default(path: Option<&Path>) -> eyre::Result<Self> {
+        match path {
+            Some(path) => Self::open(path),
+            None => Self::open(
+                Self::default_path()
+                    .ok_or_else(|| eyre::eyre!("unable to determine the data directory"))?,
+            ),
+        }
+    }
+
+    pub fn insert(&self, result: &SpeedTestResult) -> eyre::Result<i64> {
+        self.conn.execute(
+            "INSERT INTO runs (
+                timestamp, client_ip, client_isp, client_country,
+                server_id, server_name, server_sponsor, server_country,
+                download_bps, download_bytes, upload_bps, upload_bytes, note, aborted, result_json
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                result.timestamp.to_rfc3339(),
+                result.client.ip,
+                result.client.isp,
+                result.client.country,
+                result.server.id,
+                result.server.name,
+                result.server.sponsor,
+                result.server.country,
+                result.download.map(|d| d.bits_per_second),
+                result.download.map(|d| d.bytes as i64),
+                result.upload.map(|u| u.bits_per_second),
+                result.upload.map(|u| u.bytes as i64),
+                result.note,
+                result.is_aborted(),
+                serde_json::to_string(result)?,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Replaces the note of the run with row id `id`, e.g. one added after the run finished.
+    pub fn set_note(&self, id: i64, result: &SpeedTestResult) -> eyre::Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE runs SET note = ?1, result_json = ?2 WHERE id = ?3",
+            params![result.note, serde_json::to_string(result)?, id],
+        )?;
+        if updated == 0 {
+            eyre::bail!("run {id} is not in the history");
+        }
+        Ok(())
+    }
+
+    /// All stored complete results, oldest first.
+    pub fn results(&self) -> eyre::Result<Vec<SpeedTestResult>> {
+        self.query(&HistoryFilter::default())
+    }
+
+    /// The stored complete results matching `filter`, oldest first.
+    pub fn query(&self, filter: &HistoryFilter) -> eyre::Result<Vec<SpeedTestResult>> {
+        let (clause, params) = filter.to_sql(Utc::now());
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT result_json FROM runs {clause} ORDER BY timestamp"
+        ))?;
+
+        let rows = stmt.query_map(params_from_iter(params), |row| row.get::<_, String>(0))?;
+
+        let mut results = vec![];
+        for json in rows {
+            results.push(serde_json::from_str(&json?)?);
+        }
+        Ok(results)
+    }
+
+    /// Bytes transferred by the runs of the calendar month of `now`, in UTC, including the runs
+    /// quit midway.
+    pub fn month_usage(&self, now: DateTime<Utc>) -> eyre::Result<u64> {
+        let bytes: i64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(total_bytes), 0) FROM daily_usage
+             WHERE day >= date(?1, 'start of month')",
+            [now.to_rfc3339()],
+            |row| row.get(0),
+        )?;
+        Ok(bytes as u64)
+    }
+
+    /// The most recent complete result, if any run was recorded.
+    pub fn latest(&self) -> eyre::Result<Option<SpeedTestResult>> {
+        let json = self
+            .conn
+            .query_row(
+                "SELECT result_json FROM runs WHERE aborted = 0 ORDER BY timestamp DESC LIMIT 1",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+
+        Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+}
+
+/// Adds the columns of newer versions to databases created by older ones.
+fn migrate(conn: &Connection) -> eyre::Result<()> {
+    let has_note = conn
+        .prepare("SELECT 1 FROM pragma_table_info('runs') WHERE name = 'note'")?
+        .exists([])?;
+    if !has_note {
+        conn.execute_batch("ALTER TABLE runs ADD COLUMN note TEXT")?;
+    }
+
+    let has_aborted = conn
+        .prepare("SELECT 1 FROM pragma_table_info('runs') WHERE name = 'aborted'")?
+        .exists([])?;
+    if !has_aborted {
+        conn.execute_batch(
+            "ALTER TABLE runs ADD COLUMN aborted INTEGER NOT NULL DEFAULT 0;
+             UPDATE runs SET aborted = 1 WHERE EXISTS (
+                 SELECT 1 FROM json_each(result_json, '$.tags') WHERE value = 'aborted'
+             );",
+        )?;
+    }
+    Ok(())
+}
+
+/// Runs a `history` subcommand, exports are masked with `redact`.
+pub fn run(command: HistoryCommand, path: Option<&Path>, redact: bool) -> eyre::Result<()> {
+    let history = History::open_or_default(path)?;
+
+    match command {
+        HistoryCommand::Heatmap { filter, svg } => {
+            let heatmap = heatmap::Heatmap::from_results(&history.query(&filter)?);
+            print!("{}", heatmap.render_terminal());
+
+            if let Some(path) = svg {
+                std::fs::write(&path, heatmap.render_svg())?;
+                println!("SVG written to {}", path.display());
+            }
+        }
+        HistoryCommand::Export {
+            filter,
+            format,
+            out,
+        } => {
+            let mut results = history.query(&filter)?;
+            if redact {
+                results = results.iter().map(Redact::redact).collect();
+            }
+            let exported = match format {
+                ExportFormat::Csv => export::to_csv(&results).into_bytes(),
+                ExportFormat::Json => {
+                    let mut json = serde_json::to_vec_pretty(&results)?;
+                    json.push(b'\n');
+                    json
+                }
+                #[cfg(feature = "parquet")]
+                ExportFormat::Parquet => export::to_parquet(&results)?,
+            };
+            match out {
+                Some(path) => {
+                    write_atomic(&path, &exported)?;
+                    eprintln!("{} runs written to {}", results.len(), path.display());
+                }
+                None => std::io::stdout().write_all(&exported)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{Datelike, TimeDelta, Utc};
+    use rusqlite::Connection;
+    use speedtest_rs_core::result::{SpeedTestResult, TransferResult};
+
+    use crate::{app::demo, args::HistoryFilter, history::History};
+
+    #[test]
+    fn test_note() {
+        let path = std::env::temp_dir().join(format!(
+            "speedtest-rs-test-history-{}.db",
+            std::process::id()
+        ));
+        _ = std::fs::remove_file(&path);
+
+        // A database created before notes existed.
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE runs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT, timestamp TEXT NOT NULL,
+                    client_ip TEXT NOT NULL, client_isp TEXT NOT NULL,
+                    client_country TEXT NOT NULL, server_id TEXT NOT NULL,
+                    server_name TEXT NOT NULL, server_sponsor TEXT NOT NULL,
+                    server_country TEXT NOT NULL, download_bps REAL, download_bytes INTEGER,
+                    upload_bps REAL, upload_bytes INTEGER, result_json TEXT NOT NULL
+                )",
+            )
+            .unwrap();
+
+        let history = History::open(&path).unwrap();
+        let result = SpeedTestResult::new(Utc::now(), &demo::client(), &demo::servers()[0].0)
+            .with_note(Some("  before firmware update ".to_string()));
+        let id = history.insert(&result).unwrap();
+        assert_eq!(
+            history.latest().unwrap().unwrap().note.as_deref(),
+            Some("before firmware update")
+        );
+
+        let result = result.with_note(Some("after firmware update".to_string()));
+        history.set_note(id, &result).unwrap();
+        let note: String = history
+            .conn
+            .query_row("SELECT note FROM runs WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(note, "after firmware update");
+        assert!(history.set_note(id + 1, &result).is_err());
+
+        _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_query() {
+        let path =
+            std::env::temp_dir().join(format!("speedtest-rs-test-query-{}.db", std::process::id()));
+        _ = std::fs::remove_file(&path);
+        let history = History::open(&path).unwrap();
+
+        let client = demo::client();
+        let servers = demo::servers();
+        let now = Utc::now();
+        let run = |days_ago, server: usize, mbps: u64, background| {
+            let result =
+                SpeedTestResult::new(now - TimeDelta::days(days_ago), &client, &servers[server].0)
+                    .with_download(TransferResult::new(
+                        mbps * 1_000_000 / 8,
+                        Duration::from_secs(1),
+                    ))
+                    .with_background(background);
+            history.insert(&result).unwrap();
+        };
+        run(30, 0, 300, false);
+        run(3, 0, 80, true);
+        run(1, 1, 250, false);
+        // Quit midway: kept for the data cap, left out everywhere else.
+        let aborted = SpeedTestResult::new(now, &client, &servers[2].0)
+            .with_download(TransferResult::new(
+                1_000_000_000 / 8,
+                Duration::from_secs(1),
+            ))
+            .with_aborted(true);
+        history.insert(&aborted).unwrap();
+        assert_eq!(history.latest().unwrap().unwrap().server.id, "61296");
+
+        let ids = |filter: HistoryFilter| -> Vec<String> {
+            let results = history.query(&filter).unwrap();
+            results.into_iter().map(|r| r.server.id).collect()
+        };
+        assert_eq!(ids(HistoryFilter::default()), ["35791", "35791", "61296"]);
+        assert_eq!(
+            ids(HistoryFilter {
+                since: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+                ..Default::default()
+            }),
+            ["35791", "61296"]
+        );
+        assert_eq!(
+            ids(HistoryFilter {
+                server_id: Some("35791".to_string()),
+                min_download: Some(100_000_000.0),
+                ..Default::default()
+            }),
+            ["35791"]
+        );
+        assert_eq!(
+            ids(HistoryFilter {
+                tag: Some("background".to_string()),
+                ..Default::default()
+            }),
+            ["35791"]
+        );
+
+        let (days, runs, max_download): (i64, i64, f64) = history
+            .conn
+            .query_row(
+                "SELECT COUNT(*), SUM(runs), MAX(max_download_mbps) FROM daily_stats",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((days, runs), (3, 3));
+        assert!((max_download - 300.0).abs() < 1e-6, "{max_download}");
+
+        let (time, server_runs): (i64, i64) = history
+            .conn
+            .query_row(
+                "SELECT last_time, runs FROM server_stats WHERE server_id = '35791'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(time, (now - TimeDelta::days(3)).timestamp());
+        assert_eq!(server_runs, 2);
+
+        // Only the runs of the current calendar month count.
+        let usage = history.month_usage(now + TimeDelta::days(40)).unwrap();
+        assert_eq!(usage, 0);
+        let recent = [
+            (30, 37_500_000),
+            (3, 10_000_000),
+            (1, 31_250_000),
+            (0, 125_000_000),
+        ]
+        .into_iter()
+        .filter(|(days_ago, _)| (now - TimeDelta::days(*days_ago)).month() == now.month())
+        .map(|(_, bytes)| bytes)
+        .sum::<u64>();
+        assert_eq!(history.month_usage(now).unwrap(), recent);
+
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,128 @@
+//! Filters over the stored runs, translated into SQL so only matching rows are decoded.
+
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rusqlite::types::Value;
+use speedtest_rs_core::{G_BITS_PER_SEC, K_BITS_PER_SEC, M_BITS_PER_SEC};
+
+use crate::args::HistoryFilter;
+
+/// Parses ages like `90m`, `12h`, `7d` or `2w`.
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let invalid = || format!("invalid age '{value}', expected e.g. 12h, 7d or 2w");
+
+    let unit_secs = match value.chars().last() {
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        Some('w') => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = value[..value.len() - 1].parse().map_err(|_| invalid())?;
+    let secs = count.checked_mul(unit_secs).ok_or_else(invalid)?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Parses bitrates like `100Mbps`, `1.5G` or `500kbps` into bits per second.
+pub fn parse_bitrate(value: &str) -> Result<f64, String> {
+    let trimmed = value.trim();
+    let number = trimmed
+        .strip_suffix("bps")
+        .or_else(|| trimmed.strip_suffix("bit/s"))
+        .unwrap_or(trimmed);
+    let (number, multiplier) = match number.chars().last() {
+        Some('K' | 'k') => (&number[..number.len() - 1], K_BITS_PER_SEC),
+        Some('M' | 'm') => (&number[..number.len() - 1], M_BITS_PER_SEC),
+        Some('G' | 'g') => (&number[..number.len() - 1], G_BITS_PER_SEC),
+        _ => (number, 1),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(bps) if bps.is_finite() && bps >= 0.0 => Ok(bps * multiplier as f64),
+        _ => Err(format!(
+            "invalid bitrate '{value}', expected e.g. 100Mbps or 1.5Gbps"
+        )),
+    }
+}
+
+impl HistoryFilter {
+    /// The `WHERE` clause and its parameters, runs are matched against `now` for `--since`. Runs
+    /// quit midway never match, their partial rates would skew the statistics.
+    pub fn to_sql(&self, now: DateTime<Utc>) -> (String, Vec<Value>) {
+        let mut conditions = vec!["aborted = 0"];
+        let mut params = vec![];
+
+        // An age reaching past the earliest representable date matches every run.
+        if let Some(cutoff) = self
+            .since
+            .and_then(|since| TimeDelta::from_std(since).ok())
+            .and_then(|since| now.checked_sub_signed(since))
+        {
+            conditions.push("timestamp >= ?");
+            params.push(Value::Text(cutoff.to_rfc3339()));
+        }
+        if let Some(server_id) = &self.server_id {
+            conditions.push("server_id = ?");
+            params.push(Value::Text(server_id.clone()));
+        }
+        if let Some(tag) = &self.tag {
+            conditions
+                .push("EXISTS (SELECT 1 FROM json_each(result_json, '$.tags') WHERE value = ?)");
+            params.push(Value::Text(tag.clone()));
+        }
+        if let Some(bps) = self.min_download {
+            conditions.push("download_bps >= ?");
+            params.push(Value::Real(bps));
+        }
+        if let Some(bps) = self.min_upload {
+            conditions.push("upload_bps >= ?");
+            params.push(Value::Real(bps));
+        }
+
+        (format!("WHERE {}", conditions.join(" AND ")), params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::{
+        args::HistoryFilter,
+        history::query::{parse_age, parse_bitrate},
+    };
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!(parse_age("7d"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(parse_age("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("-1d").is_err());
+        assert!(parse_age("99999999999999999w").is_err());
+
+        assert_eq!(parse_bitrate("100Mbps"), Ok(100_000_000.0));
+        assert_eq!(parse_bitrate("1.5G"), Ok(1_500_000_000.0));
+        assert_eq!(parse_bitrate("500kbps"), Ok(500_000.0));
+        assert_eq!(parse_bitrate("64"), Ok(64.0));
+        assert!(parse_bitrate("fast").is_err());
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let filter = |since| HistoryFilter {
+            since: Some(since),
+            ..Default::default()
+        };
+        let (clause, params) = filter(parse_age("7d").unwrap()).to_sql(Utc::now());
+        assert_eq!(clause, "WHERE aborted = 0 AND timestamp >= ?");
+        assert_eq!(params.len(), 1);
+
+        let (clause, params) = filter(parse_age("100000000w").unwrap()).to_sql(Utc::now());
+        assert_eq!(clause, "WHERE aborted = 0");
+        assert!(params.is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! Notifications and user commands run around a test.
+
+use std::{
+    io::Write,
+    process::{Output, Stdio},
+    time::Duration,
+};
+
+use color_eyre::eyre;
+use speedtest_rs_core::result::SpeedTestResult;
+use tokio::{io::AsyncWriteExt, task::JoinHandle};
+
+/// Completion hooks still running after this long are killed, so a hung command cannot keep the
+/// TUI from exiting.
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Actions triggered before and after a run.
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    /// Ring the terminal bell when the run finished.
+    pub bell: bool,
+
+    /// Shell command run when the run finished, e.g. `notify-send done`.
+    pub on_complete: Option<String>,
+
+    /// Shell command run before the test, e.g. to pause torrents. The test is aborted if it
+    /// fails.
+    pub pre_run: Option<String>,
+
+    /// Shell command run after the test with the result JSON on stdin.
+    pub post_run: Option<String>,
+}
+
+impl Hooks {
+    /// Runs the pre-run command to completion, its output goes to the terminal.
+    pub async fn pre_run(&self) -> eyre::Result<()> {
+        let Some(command) = &self.pre_run else {
+            return Ok(());
+        };
+
+        let status = shell(command).status().await?;
+        if !status.success() {
+            eyre::bail!("pre-run hook '{command}' exited with {status}");
+        }
+        Ok(())
+    }
+
+    /// Starts the completion hooks without blocking. They may run while the TUI owns the
+    /// terminal in raw mode, so their output is captured into the log instead, and they are
+    /// killed after [`HOOK_TIMEOUT`]. Await the returned handles before exiting.
+    pub fn post_run(&self, result: &SpeedTestResult) -> Vec<JoinHandle<()>> {
+        if self.bell {
+            print!("\x07");
+            // Stdout is line buffered, the bell would otherwise wait for the next newline.
+            _ = std::io::stdout().flush();
+        }
+
+        let mut handles = vec![];
+        if let Some(command) = &self.on_complete {
+            handles.push(spawn_hook(command.clone(), result, false));
+        }
+        if let Some(command) = &self.post_run {
+            handles.push(spawn_hook(command.clone(), result, true));
+        }
+        handles
+    }
+}
+
+/// Key figures of the result for hooks that do not want to parse JSON.
+pub fn result_env(result: &SpeedTestResult) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("SPEEDTEST_SUMMARY", result.summary_line()),
+        ("SPEEDTEST_TIMESTAMP", result.timestamp.to_rfc3339()),
+        ("SPEEDTEST_SERVER_ID", result.server.id.clone()),
+        ("SPEEDTEST_SERVER_NAME", result.server.sponsor.clone()),
+    ];
+    if let Some(download) = result.download {
+        env.push((
+            "SPEEDTEST_DOWNLOAD_BPS",
+            format!("{:.0}", download.bits_per_second),
+        ));
+    }
+    if let Some(upload) = result.upload {
+        env.push((
+            "SPEEDTEST_UPLOAD_BPS",
+            format!("{:.0}", upload.bits_per_second),
+        ));
+    }
+    if let Some(latency) = result.idle_latency {
+        env.push(("SPEEDTEST_LATENCY_MS", format!("{:.1}", latency.avg_ms)));
+    }
+    if let Some(note) = &result.note {
+        env.push(("SPEEDTEST_NOTE", note.clone()));
+    }
+    env
+}
+
+fn spawn_hook(command: String, result: &SpeedTestResult, with_json: bool) -> JoinHandle<()> {
+    let env = result_env(result);
+    let json = if with_json {
+        serde_json::to_vec(result).ok()
+    } else {
+        None
+    };
+
+    tokio::spawn(async move {
+        match run_hook(&command, env, json, HOOK_TIMEOUT).await {
+            Ok(output) => {
+                let lines = |raw: &[u8]| String::from_utf8_lossy(raw).trim_end().to_string();
+                let (stdout, stderr) = (lines(&output.stdout), lines(&output.stderr));
+                if !stdout.is_empty() {
+                    tracing::debug!("hook '{}' printed: {}", command, stdout);
+                }
+                if !output.status.success() {
+                    tracing::warn!(
+                        "hook '{}' exited with {}: {}",
+                        command,
+                        output.status,
+                        stderr
+                    );
+                } else if !stderr.is_empty() {
+                    tracing::debug!("hook '{}' printed to stderr: {}", command, stderr);
+                }
+            }
+            Err(e) => tracing::warn!("failed to run hook '{}': {}", command, e),
+        }
+    })
+}
+
+/// Runs `command` detached from the terminal: `json` on stdin, the output captured, and killed
+/// once `timeout` passed.
+async fn run_hook(
+    command: &str,
+    env: Vec<(&'static str, String)>,
+    json: Option<Vec<u8>>,
+    timeout: Duration,
+) -> std::io::Result<Output> {
+    let mut child = shell(command)
+        .envs(env)
+        .stdin(if json.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let finished = async {
+        if let (Some(json), Some(mut stdin)) = (json, child.stdin.take()) {
+            // The command may not read its input at all, a broken pipe is fine.
+            _ = stdin.write_all(&json).await;
+        }
+        child.wait_with_output().await
+    };
+    // Dropping the child on a timeout kills it.
+    tokio::time::timeout(timeout, finished)
+        .await
+        .unwrap_or_else(|_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {timeout:?}"),
+            ))
+        })
+}
+
+/// Runs `command` with the platform shell.
+pub fn shell(command: &str) -> tokio::process::Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::hooks::{run_hook, shell};
+
+    #[tokio::test]
+    async fn test_shell() {
+        if cfg!(windows) {
+            return;
+        }
+        let output = shell("echo $HOOK_VALUE")
+            .env("HOOK_VALUE", "done")
+            .output()
+            .await
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"done\n");
+    }
+
+    #[tokio::test]
+    async fn test_run_hook() {
+        if cfg!(windows) {
+            return;
+        }
+        let env = vec![("SPEEDTEST_SUMMARY", "done".to_string())];
+        let json = Some(b"{}".to_vec());
+        let output = run_hook(
+            "echo $SPEEDTEST_SUMMARY; cat",
+            env,
+            json,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, b"done\n{}");
+
+        let started = Instant::now();
+        let e = run_hook("sleep 10", vec![], None, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,150 @@
+//! `speedtest-rs info`: build and platform details, for bug reports and scripted feature
+//! detection.
+
+use color_eyre::eyre;
+use serde::Serialize;
+use speedtest_rs_core::{
+    cpu::CpuTime,
+    ifstats::{InterfaceCounters, detect_link},
+};
+
+#[derive(Debug, Serialize)]
+pub struct Info {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// Cargo features the binary was built with.
+    pub features: Vec<&'static str>,
+    pub providers: Vec<&'static str>,
+    pub protocols: Vec<&'static str>,
+    pub capabilities: Capabilities,
+}
+
+/// What the platform allows this process to measure.
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    /// Unprivileged ICMP echo sockets are permitted.
+    pub icmp: bool,
+    /// The kernel reports TCP statistics such as the RTT of a connection.
+    pub tcp_info: bool,
+    /// Interface byte counters for the idle check and the counter diagnostics.
+    pub interface_counters: bool,
+    /// Negotiated speed of the default route interface.
+    pub link_speed: bool,
+    /// CPU time of the process for the CPU-bound warning.
+    pub cpu_time: bool,
+}
+
+impl Info {
+    pub fn detect() -> Self {
+        let features = [
+            ("impairment", cfg!(feature = "impairment")),
+            ("ndt7", cfg!(feature = "ndt7")),
+            ("parquet", cfg!(feature = "parquet")),
+        ]
+        .into_iter()
+        .filter_map(|(feature, enabled)| enabled.then_some(feature))
+        .collect();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            features,
+            providers: if cfg!(feature = "ndt7") {
+                vec!["ookla", "mlab"]
+            } else {
+                vec!["ookla"]
+            },
+            protocols: if cfg!(feature = "ndt7") {
+                vec!["http", "https", "ws", "wss"]
+            } else {
+                vec!["http", "https"]
+            },
+            capabilities: Capabilities {
+                icmp: icmp_allowed(),
+                tcp_info: cfg!(any(target_os = "linux", target_os = "android")),
+                interface_counters: InterfaceCounters::read().is_some(),
+                link_speed: detect_link().is_some(),
+                cpu_time: CpuTime::read().is_some(),
+            },
+        }
+    }
+}
+
+fn icmp_allowed() -> bool {
+    let read = |path| std::fs::read_to_string(path).unwrap_or_default();
+    can_ping(
+        &read("/proc/sys/net/ipv4/ping_group_range"),
+        &read("/proc/self/status"),
+    )
+}
+
+/// Root, or an effective group within `net.ipv4.ping_group_range`, may open ICMP sockets.
+fn can_ping(ping_group_range: &str, status: &str) -> bool {
+    let effective = |key: &str| -> Option<u64> {
+        let line = status.lines().find(|line| line.starts_with(key))?;
+        // `Uid:` and `Gid:` list the real, effective, saved and filesystem ids.
+        line.split_whitespace().nth(2)?.parse().ok()
+    };
+    if effective("Uid:") == Some(0) {
+        return true;
+    }
+
+    let range: Vec<u64> = ping_group_range
+        .split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    match (range.as_slice(), effective("Gid:")) {
+        ([low, high], Some(gid)) => (*low..=*high).contains(&gid),
+        _ => false,
+    }
+}
+
+pub fn run(json: bool) -> eyre::Result<()> {
+    let info = Info::detect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let list = |values: &[&str]| {
+        if values.is_empty() {
+            "none".to_string()
+        } else {
+            values.join(", ")
+        }
+    };
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let capabilities = &info.capabilities;
+
+    println!("speedtest-rs {} ({} {})", info.version, info.os, info.arch);
+    println!("Features:            {}", list(&info.features));
+    println!("Providers:           {}", list(&info.providers));
+    println!("Protocols:           {}", list(&info.protocols));
+    println!("ICMP sockets:        {}", yes_no(capabilities.icmp));
+    println!("TCP_INFO:            {}", yes_no(capabilities.tcp_info));
+    println!(
+        "Interface counters:  {}",
+        yes_no(capabilities.interface_counters)
+    );
+    println!("Link speed:          {}", yes_no(capabilities.link_speed));
+    println!("CPU time:            {}", yes_no(capabilities.cpu_time));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::info::can_ping;
+
+    #[test]
+    fn test_can_ping() {
+        let user = "Name:\tspeedtest-rs\nUid:\t1000\t1000\t1000\t1000\nGid:\t100\t100\t100\t100\n";
+        assert!(can_ping("0\t2147483647", user));
+        assert!(!can_ping("1\t0", user));
+        assert!(!can_ping("", user));
+
+        let root = "Uid:\t0\t0\t0\t0\nGid:\t0\t0\t0\t0\n";
+        assert!(can_ping("1\t0", root));
+    }
+}
@@ -0,0 +1,73 @@
+use std::net::IpAddr;
+
+use color_eyre::eyre::{self, OptionExt};
+
+/// A local network interface address usable with `--interface`.
+#[derive(Debug, Clone)]
+pub struct LocalInterface {
+    /// Interface name. On Windows this is the adapter friendly name, e.g. "Ethernet" or "Wi-Fi".
+    pub name: String,
+
+    /// Permanent adapter identifier (a GUID on Windows, the interface name elsewhere).
+    pub id: String,
+
+    pub ip: IpAddr,
+
+    pub is_up: bool,
+}
+
+/// Enumerates all non-loopback interface addresses of this host.
+pub fn list_interfaces() -> eyre::Result<Vec<LocalInterface>> {
+    let interfaces = if_addrs::get_if_addrs()?
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| LocalInterface {
+            #[cfg(windows)]
+            id: iface.adapter_name.clone(),
+            #[cfg(not(windows))]
+            id: iface.name.clone(),
+            ip: iface.ip(),
+            is_up: iface.is_oper_up(),
+            name: iface.name,
+        })
+        .collect();
+
+    Ok(interfaces)
+}
+
+/// Resolves the value of `--interface` to a local address.
+///
+/// Accepts a literal IP address, an interface name (matched case-insensitively, since Windows
+/// friendly names are commonly typed with different casing) or a Windows adapter GUID. IPv4
+/// addresses are preferred when an interface has both families.
+pub fn resolve_interface(value: &str) -> eyre::Result<IpAddr> {
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Ok(ip);
+    }
+
+    let mut candidates: Vec<_> = list_interfaces()?
+        .into_iter()
+        .filter(|iface| {
+            iface.name.eq_ignore_ascii_case(value) || iface.id.eq_ignore_ascii_case(value)
+        })
+        .collect();
+
+    candidates.sort_by_key(|iface| (!iface.is_up, iface.ip.is_ipv6()));
+
+    candidates.first().map(|iface| iface.ip).ok_or_eyre(format!(
+        "no interface named '{value}'. use --list-interfaces to show the available ones"
+    ))
+}
+
+/// Prints the available interfaces, one address per line.
+pub fn print_interfaces() -> eyre::Result<()> {
+    for iface in list_interfaces()? {
+        let state = if iface.is_up { "up" } else { "down" };
+        if iface.id == iface.name {
+            println!("{:<20} {:<40} {}", iface.name, iface.ip, state);
+        } else {
+            println!("{:<20} {:<40} {} {}", iface.name, iface.ip, state, iface.id);
+        }
+    }
+    Ok(())
+}
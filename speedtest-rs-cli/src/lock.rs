@@ -0,0 +1,94 @@
+//! Advisory lock preventing concurrent runs from saturating the link at the same time.
+
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre;
+
+/// Returned when another process holds the lock.
+#[derive(Debug)]
+pub struct AlreadyRunning {
+    pub pid: Option<u32>,
+}
+
+impl std::fmt::Display for AlreadyRunning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pid {
+            Some(pid) => write!(
+                f,
+                "another test is running (pid {pid}), use --force to override"
+            ),
+            None => write!(f, "another test is running, use --force to override"),
+        }
+    }
+}
+
+impl std::error::Error for AlreadyRunning {}
+
+/// Held for the duration of a run, the OS releases it when the process exits.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// `$XDG_RUNTIME_DIR/speedtest-rs.lock`, falling back to the temporary directory.
+    pub fn default_path() -> PathBuf {
+        dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("speedtest-rs.lock")
+    }
+
+    /// Fails with [`AlreadyRunning`] if the lock is held by another process.
+    pub fn acquire(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref())?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                _ = file.read_to_string(&mut pid);
+                return Err(AlreadyRunning {
+                    pid: pid.trim().parse().ok(),
+                }
+                .into());
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lock::{AlreadyRunning, RunLock};
+
+    #[test]
+    fn test_run_lock() {
+        let path =
+            std::env::temp_dir().join(format!("speedtest-rs-test-{}.lock", std::process::id()));
+
+        let lock = RunLock::acquire(&path).unwrap();
+        let err = RunLock::acquire(&path).unwrap_err();
+        let busy = err.downcast_ref::<AlreadyRunning>().unwrap();
+        assert_eq!(busy.pid, Some(std::process::id()));
+
+        drop(lock);
+        assert!(RunLock::acquire(&path).is_ok());
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,136 @@
+//! In-memory log capture for the debug pane, the TUI owns the terminal so logs cannot go to
+//! stderr.
+
+use std::{
+    collections::VecDeque,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use tracing::Level;
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard, TraceStyle};
+use tracing_subscriber::{
+    filter::Targets, fmt::MakeWriter, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+/// Number of log lines kept.
+const MAX_LOG_LINES: usize = 500;
+
+/// Shared ring buffer of formatted log lines.
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogBuffer {
+    /// Installs a global subscriber writing into this buffer. With `trace_out` the spans are also
+    /// recorded as a Chrome trace, which is complete once the returned guard is dropped.
+    pub fn install(&self, trace_out: Option<&Path>) -> std::io::Result<Option<FlushGuard>> {
+        let filter = Targets::new()
+            .with_target("speedtest_rs_core", Level::DEBUG)
+            .with_target("speedtest_rs_cli", Level::DEBUG)
+            .with_default(Level::WARN);
+
+        let fmt = tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_target(false)
+            .with_writer(self.clone());
+
+        // Requests overlap and move between threads, hence async spans rather than per thread.
+        let (chrome, guard) = match trace_out {
+            Some(path) => {
+                let (layer, guard) = ChromeLayerBuilder::new()
+                    .writer(std::fs::File::create(path)?)
+                    .trace_style(TraceStyle::Async)
+                    .include_args(true)
+                    .build();
+                (Some(layer), Some(guard))
+            }
+            None => (None, None),
+        };
+
+        _ = tracing_subscriber::registry()
+            .with(fmt)
+            .with(chrome)
+            .with(filter)
+            .try_init();
+        Ok(guard)
+    }
+
+    pub fn push(&self, line: impl Into<String>) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.into());
+    }
+
+    /// The most recent `n` lines, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Collects the bytes of one log event and pushes them as lines when dropped.
+pub struct LogWriter {
+    buffer: LogBuffer,
+    bytes: Vec<u8>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        for line in String::from_utf8_lossy(&self.bytes).lines() {
+            self.buffer.push(line);
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter {
+            buffer: self.clone(),
+            bytes: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use crate::log::LogBuffer;
+
+    #[test]
+    fn test_log_buffer() {
+        let buffer = LogBuffer::default();
+        {
+            let mut writer = buffer.make_writer();
+            writer.write_all(b"first\nsecond\n").unwrap();
+        }
+        buffer.push("third");
+
+        assert_eq!(buffer.tail(2), ["second", "third"]);
+        assert_eq!(buffer.tail(10).len(), 3);
+    }
+}
@@ -1,18 +1,425 @@
-use crate::app::App;
+use std::io::{IsTerminal, Write};
 
+use chrono::Utc;
+use clap::Parser;
+use speedtest_rs_core::{
+    dns::{DohResolver, UdpResolver},
+    model::ServerFilter,
+    result::SpeedTestResult,
+    size::ByteSize,
+    speed_tester::SpeedTester,
+};
+
+use crate::{
+    app::{App, RunOptions},
+    args::{Args, Command, DnsServer},
+    asset_cache::AssetCache,
+    data_cap::{DataCap, confirm_usage, estimate_usage},
+    history::History,
+    hooks::Hooks,
+    lock::RunLock,
+    log::LogBuffer,
+    race_cache::RaceCache,
+    sink::Sinks,
+    theme::Theme,
+};
+
+pub mod ab;
 pub mod app;
+pub mod args;
+pub mod asset_cache;
+pub mod baseline;
+pub mod compare;
+pub mod config;
+pub mod data_cap;
 pub mod event;
+pub mod history;
+pub mod hooks;
+pub mod info;
+pub mod interface;
+pub mod lock;
+pub mod log;
+#[cfg(feature = "ndt7")]
+pub mod ndt7;
+pub mod ookla;
+pub mod ping;
+pub mod race_cache;
+pub mod report;
+pub mod servers;
+pub mod simple;
+pub mod sink;
+pub mod theme;
+pub mod uci;
 pub mod ui;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
 
-    let terminal = ratatui::init();
+    let mut args = Args::parse();
+    args.load_config()?;
+
+    if args.list_interfaces {
+        return interface::print_interfaces();
+    }
+
+    let profile = args.profile();
+    let speed_tester = build_speed_tester(&args)?;
+
+    let asset_cache = if args.probe_assets && !args.demo {
+        AssetCache::default_path().map(AssetCache::new)
+    } else {
+        None
+    };
+    let speed_tester = match &asset_cache {
+        Some(cache) => speed_tester.with_asset_sizes(cache.lookup(Utc::now())),
+        None => speed_tester,
+    };
+
+    if let Some(command) = args.command.clone() {
+        return match command {
+            Command::History { command } => {
+                history::run(command, args.db_path.as_deref(), args.redact)
+            }
+            Command::Report { last, out } => {
+                report::run(last, &out, args.db_path.as_deref(), args.redact)
+            }
+            Command::Ab {
+                tag_a,
+                tag_b,
+                since,
+            } => ab::run(&tag_a, &tag_b, since, args.db_path.as_deref()),
+            Command::Info { json } => info::run(json),
+            Command::Baseline { command } => baseline::run(command, args.db_path.as_deref()),
+            Command::Ping {
+                server_id,
+                interval,
+                count,
+                format,
+            } => ping::run(&speed_tester, server_id, interval, count, format).await,
+            Command::Compare { top, json } => {
+                compare::run(&speed_tester, top, json, args.redact).await
+            }
+            Command::Servers {
+                ping: true, group, ..
+            } => servers::ping(&speed_tester, group).await,
+            Command::Servers {
+                command: Some(command),
+                group,
+                ..
+            } => servers::run(command, &speed_tester, group).await,
+            Command::Servers {
+                command: None,
+                group,
+                ..
+            } => servers::list(&speed_tester, group).await,
+        };
+    }
+
+    // Only read an existing history, a first run has nothing to base the estimate on.
+    let usage_estimate = (!args.demo).then(|| {
+        let latest = args
+            .db_path
+            .clone()
+            .or_else(History::default_path)
+            .filter(|path| !args.no_history && path.exists())
+            .and_then(|path| {
+                History::open(path)
+                    .and_then(|history| history.latest())
+                    .ok()
+            })
+            .flatten();
+        estimate_usage(profile, latest.as_ref())
+    });
+    if args.simple
+        && !args.daemon
+        && !args.yes
+        && std::io::stdin().is_terminal()
+        && let Some(estimate) = usage_estimate
+        && !confirm_usage(estimate, std::io::stdin().lock(), std::io::stdout())?
+    {
+        return Ok(());
+    }
+
+    let _lock = if args.force || args.demo {
+        None
+    } else {
+        Some(RunLock::acquire(RunLock::default_path())?)
+    };
+
+    let logs = LogBuffer::default();
+    // Held until the end of main, dropping it writes the rest of the trace.
+    let _trace = logs.install(args.trace_out.as_deref())?;
 
-    let result = App::new().run(terminal).await;
+    if args.daemon {
+        #[cfg(unix)]
+        return daemon(args, speed_tester, asset_cache).await;
+        #[cfg(not(unix))]
+        color_eyre::eyre::bail!("--daemon needs Unix signals");
+    }
 
+    let hooks = hooks(&args);
+    hooks.pre_run().await?;
+    let options = run_options(&args)?;
+    let sinks = sinks(&args);
+
+    #[cfg(feature = "ndt7")]
+    if args.ndt7 {
+        let result = ndt7::run(speed_tester, options, sinks, hooks, args.redact).await?;
+        println!("{}", result.summary_line());
+        return check_baseline(&args, &result);
+    }
+
+    // Shares the asset cache with the tester moved into the app.
+    let probed_assets = speed_tester.clone();
+    let result = if args.simple {
+        simple::run(speed_tester, options, sinks, hooks, args.redact).await
+    } else {
+        let terminal = init_terminal();
+        let result = App::new()
+            .with_speed_tester(speed_tester)
+            .with_compact(profile.compact_layout())
+            .with_redact(args.redact)
+            .with_usage_estimate(usage_estimate)
+            .with_options(options)
+            .with_sinks(sinks)
+            .with_hooks(hooks)
+            .with_logs(logs)
+            .with_theme(Theme::new(args.palette.unwrap_or_default()).with_marker(args.chart_marker))
+            .run(terminal)
+            .await;
+        restore_terminal();
+        result
+    };
+
+    if let Some(cache) = asset_cache
+        && let Err(e) = cache.store(probed_assets.asset_sizes(), Utc::now())
+    {
+        eprintln!("Failed to update the asset cache: {e}");
+    }
+
+    if let Some(result) = result? {
+        println!("{}", result.summary_line());
+        check_baseline(&args, &result)?;
+    }
+    Ok(())
+}
+
+/// Prints the deltas against the baseline after a complete run, unless `--no-baseline` or
+/// `--demo`.
+fn check_baseline(args: &Args, result: &SpeedTestResult) -> color_eyre::Result<()> {
+    if args.no_baseline || args.demo || result.is_aborted() {
+        return Ok(());
+    }
+    baseline::check(result, args.baseline_threshold)
+}
+
+/// Saves the terminal title before the app puts its progress there, and brings it back on a panic.
+fn init_terminal() -> ratatui::DefaultTerminal {
+    write_title_stack("\x1b[22;0t");
+    let terminal = ratatui::init();
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        write_title_stack("\x1b[23;0t");
+        hook(info);
+    }));
+    terminal
+}
+
+/// Restores the terminal and the title saved by [`init_terminal`].
+fn restore_terminal() {
     ratatui::restore();
+    write_title_stack("\x1b[23;0t");
+}
+
+/// Pushes (`22`) or pops (`23`) the title stack of xterm compatible terminals, ignored elsewhere.
+fn write_title_stack(sequence: &str) {
+    let mut stdout = std::io::stdout();
+    _ = stdout.write_all(sequence.as_bytes());
+    _ = stdout.flush();
+}
+
+/// `--daemon`: a `--simple` run, then another one for every SIGUSR1 until SIGTERM or Ctrl+C.
+/// SIGHUP reads the command line and the config file again for the runs after it.
+#[cfg(unix)]
+async fn daemon(
+    mut args: Args,
+    mut speed_tester: SpeedTester,
+    asset_cache: Option<AssetCache>,
+) -> color_eyre::Result<()> {
+    // Registered before the first run, so signals during a run wait for its end.
+    let mut signals = simple::DaemonSignals::new()?;
+    let shutdown = simple::shutdown_token();
+    loop {
+        let hooks = hooks(&args);
+        hooks.pre_run().await?;
+        let result = simple::run(
+            speed_tester.clone(),
+            run_options(&args)?,
+            sinks(&args),
+            hooks,
+            args.redact,
+        )
+        .await?;
+        if let Some(cache) = &asset_cache
+            && let Err(e) = cache.store(speed_tester.asset_sizes(), Utc::now())
+        {
+            eprintln!("Failed to update the asset cache: {e}");
+        }
+        if let Some(result) = result {
+            println!("{}", result.summary_line());
+            // A regression is reported, but the daemon keeps serving runs.
+            if let Err(e) = check_baseline(&args, &result) {
+                eprintln!("{e}");
+            }
+        }
+
+        loop {
+            let signal = tokio::select! {
+                _ = shutdown.cancelled() => return Ok(()),
+                signal = signals.next() => signal,
+            };
+            match signal {
+                simple::DaemonSignal::Run => break,
+                simple::DaemonSignal::Reload => match reload(asset_cache.as_ref()) {
+                    Ok(reloaded) => {
+                        (args, speed_tester) = reloaded;
+                        eprintln!("Reloaded the configuration");
+                    }
+                    Err(e) => eprintln!("Failed to reload the configuration: {e}"),
+                },
+            }
+        }
+    }
+}
+
+/// The arguments of the command line with the config file as it is now, and their tester.
+#[cfg(unix)]
+fn reload(asset_cache: Option<&AssetCache>) -> color_eyre::Result<(Args, SpeedTester)> {
+    let mut args = Args::try_parse()?;
+    args.load_config()?;
+    let speed_tester = build_speed_tester(&args)?;
+    let speed_tester = match asset_cache {
+        Some(cache) => speed_tester.with_asset_sizes(cache.lookup(Utc::now())),
+        None => speed_tester,
+    };
+    Ok((args, speed_tester))
+}
+
+/// The tester configured by the arguments.
+fn build_speed_tester(args: &Args) -> color_eyre::Result<SpeedTester> {
+    let speed_tester = match &args.interface {
+        Some(value) => SpeedTester::new_with_local_addr(interface::resolve_interface(value)?),
+        None => SpeedTester::default(),
+    }
+    .with_ip_family(args.ip_family())
+    .with_http_version(args.http_version())
+    .with_url(args.speed_test_url())
+    .with_retry_policy(args.retry_policy())
+    .with_max_concurrency(args.max_concurrency())
+    .with_config_overrides(args.config_overrides())
+    .with_background(args.background)
+    .with_upload_verification(args.verify_upload)
+    .with_search(args.search.clone())
+    .with_country_code(args.cc.clone())
+    .with_server_filter(Some(ServerFilter {
+        sponsor: args.sponsor.clone(),
+        name: args.server_name.clone(),
+        ..ServerFilter::default()
+    }))
+    .with_ignore_ids(!args.no_ignore)
+    .with_scoring(args.weighted)
+    .with_capacity_probe(args.probe_capacity)
+    .with_per_sponsor(args.per_sponsor)
+    .with_ip_dedup(args.dedup_by_ip)
+    .with_closest_servers(args.closest)
+    .with_asset_probe(args.probe_assets)
+    .with_server_validation(!args.no_validate)
+    .with_sample_interval(args.sample_interval)
+    .with_warm_up(args.warm_up)
+    .with_test_plan(args.test_plan()?)
+    .with_latency_probes(args.latency_probes);
+    let speed_tester = match args.dns.clone() {
+        Some(DnsServer::Udp(server)) => speed_tester.with_resolver(UdpResolver::new(server)),
+        Some(DnsServer::Https(url)) => speed_tester.with_resolver(DohResolver::new(url)),
+        None => speed_tester,
+    };
+    let speed_tester = if args.no_connectivity_check {
+        speed_tester.with_connectivity_probe(None)
+    } else {
+        speed_tester
+    };
+    let speed_tester = if args.no_portal_check {
+        speed_tester.with_captive_portal_probe(None)
+    } else {
+        speed_tester
+    };
+    #[cfg(feature = "impairment")]
+    let speed_tester = match args.impair {
+        Some(impairment) => speed_tester.with_impairment(impairment),
+        None => speed_tester,
+    };
+
+    Ok(speed_tester)
+}
+
+fn hooks(args: &Args) -> Hooks {
+    if args.demo {
+        Hooks::default()
+    } else {
+        Hooks {
+            bell: args.bell,
+            on_complete: args.on_complete.clone(),
+            pre_run: args.pre_run.clone(),
+            post_run: args.post_run.clone(),
+        }
+    }
+}
+
+/// The options of one run, the data cap counts the usage of the month so far.
+fn run_options(args: &Args) -> color_eyre::Result<RunOptions> {
+    let data_cap = match args.monthly_cap {
+        Some(cap) if !args.demo => {
+            let history = History::open_or_default(args.db_path.as_deref())?;
+            Some(DataCap {
+                used: ByteSize(history.month_usage(Utc::now())?),
+                cap,
+            })
+        }
+        _ => None,
+    };
+    #[cfg(not(target_os = "linux"))]
+    if args.idle_check {
+        color_eyre::eyre::bail!("--idle-check needs the interface counters of Linux");
+    }
+
+    Ok(RunOptions {
+        idle_check: args.idle_check,
+        race_cache: if args.no_race_cache {
+            None
+        } else {
+            RaceCache::default_path().map(|path| RaceCache::new(path, args.interface.clone()))
+        },
+        demo: args.demo,
+        tags: args.tags.clone(),
+        note: args.note.clone(),
+        data_cap,
+        server_id: args.server_id.clone(),
+        share: args.share,
+    })
+}
 
-    result
+fn sinks(args: &Args) -> Sinks {
+    if args.demo {
+        Sinks::default()
+    } else {
+        Sinks {
+            latest_json: args.latest_json.clone(),
+            history: if args.no_history {
+                None
+            } else {
+                args.db_path.clone().or_else(History::default_path)
+            },
+        }
+    }
 }
@@ -0,0 +1,33 @@
+//! `--ndt7`: the test against the nearest M-Lab server with the ndt7 protocol, without the TUI.
+
+use color_eyre::eyre;
+use speedtest_rs_core::{redact::Redact, result::SpeedTestResult, speed_tester::SpeedTester};
+
+use crate::{app::RunOptions, hooks::Hooks, simple, sink::Sinks};
+
+/// Runs the test, stored and passed to the hooks like the results of the other modes. Ctrl+C or
+/// SIGTERM ends the running phase early.
+pub async fn run(
+    speed_tester: SpeedTester,
+    options: RunOptions,
+    sinks: Sinks,
+    hooks: Hooks,
+    redact: bool,
+) -> eyre::Result<SpeedTestResult> {
+    eprintln!("Testing against the nearest M-Lab server with ndt7...");
+    let result = speed_tester
+        .with_cancellation(simple::shutdown_token())
+        .run_ndt7_test()
+        .await
+        .map_err(|e| eyre::eyre!("{e}"))?;
+
+    let result = if redact { result.redact() } else { result };
+    let result = options.annotate(result);
+    if let Err(e) = sinks.write(&result) {
+        tracing::warn!("failed to write result: {}", e);
+    }
+    for hook in hooks.post_run(&result) {
+        _ = hook.await;
+    }
+    Ok(result)
+}
@@ -0,0 +1,111 @@
+//! Import of the settings of the official Ookla speedtest CLI, so users switching over keep their
+//! server preference.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre;
+use serde::Deserialize;
+
+use crate::config::FileConfig;
+
+/// `speedtest-cli.json` of the Ookla CLI.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OoklaConfig {
+    #[serde(default)]
+    settings: OoklaSettings,
+}
+
+/// The `Settings` object, with the key names the Ookla CLI writes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct OoklaSettings {
+    /// Hash of the accepted license, speedtest-rs has no license to accept.
+    pub license_accepted: Option<String>,
+
+    /// When the GDPR notice was accepted, in Unix seconds.
+    #[serde(rename = "GDPRTimeStamp")]
+    pub gdpr_time_stamp: Option<i64>,
+
+    /// Server the tests are pinned to, written as a number or a string.
+    #[serde(deserialize_with = "deserialize_id")]
+    pub server_id: Option<String>,
+}
+
+impl OoklaSettings {
+    /// `$XDG_CONFIG_HOME/ookla/speedtest-cli.json` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("ookla").join("speedtest-cli.json"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| eyre::eyre!("failed to read {}: {e}", path.display()))?;
+        Self::parse(&raw)
+    }
+
+    pub fn parse(raw: &str) -> eyre::Result<Self> {
+        Ok(serde_json::from_str::<OoklaConfig>(raw)?.settings)
+    }
+
+    /// The settings speedtest-rs has an equivalent for.
+    pub fn to_file_config(&self) -> FileConfig {
+        if self.license_accepted.is_some() || self.gdpr_time_stamp.is_some() {
+            tracing::debug!("ignoring the Ookla license and GDPR consent, not needed here");
+        }
+        FileConfig {
+            server_id: self.server_id.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+fn deserialize_id<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u64),
+        Text(String),
+    }
+
+    Ok(match Option::<Id>::deserialize(deserializer)? {
+        Some(Id::Number(id)) => Some(id.to_string()),
+        Some(Id::Text(id)) if !id.trim().is_empty() => Some(id.trim().to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ookla::OoklaSettings;
+
+    #[test]
+    fn test_parse_ookla_settings() {
+        let raw = r#"{
+            "Settings": {
+                "LicenseAccepted": "604ec27f828456331ebf441826292c49276bd3c1bee1a2f65a6452f505c4061c",
+                "GDPRTimeStamp": 1700000000,
+                "ServerId": 35791
+            }
+        }"#;
+        let settings = OoklaSettings::parse(raw).unwrap();
+        assert_eq!(settings.gdpr_time_stamp, Some(1_700_000_000));
+        assert_eq!(settings.server_id.as_deref(), Some("35791"));
+        assert_eq!(
+            settings.to_file_config().server_id.as_deref(),
+            Some("35791")
+        );
+
+        let license_only = r#"{"Settings": {"LicenseAccepted": "604ec27f", "ServerId": ""}}"#;
+        assert_eq!(OoklaSettings::parse(license_only).unwrap().server_id, None);
+        assert_eq!(
+            OoklaSettings::parse("{}").unwrap(),
+            OoklaSettings::default()
+        );
+        assert!(OoklaSettings::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! `speedtest-rs ping`: continuous latency monitor against a single server, for diagnosing
+//! flapping connections without repeated bulk transfers.
+
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{SecondsFormat, Utc};
+use color_eyre::eyre;
+use serde::Serialize;
+use speedtest_rs_core::{latency::Latency, model::Server, speed_tester::SpeedTester};
+use tokio::time::MissedTickBehavior;
+
+use crate::args::PingFormat;
+
+/// Number of recent probes shown in the sparkline.
+const SPARKLINE_WIDTH: usize = 40;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A single probe as streamed in the CSV and JSON formats.
+#[derive(Debug, Clone, Serialize)]
+struct Probe {
+    timestamp: String,
+    seq: u64,
+    /// `None` if the probe failed or timed out.
+    rtt_ms: Option<f64>,
+}
+
+/// Parses intervals like `500ms`, `1s` or `2.5s`.
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let duration = if let Some(ms) = value.strip_suffix("ms") {
+        ms.parse().map(Duration::from_millis).map_err(|_| ())
+    } else if let Some(secs) = value.strip_suffix('s') {
+        secs.parse::<f64>()
+            .map_err(|_| ())
+            .and_then(|secs| Duration::try_from_secs_f64(secs).map_err(|_| ()))
+    } else {
+        Err(())
+    }
+    .map_err(|_| format!("invalid interval '{value}', expected e.g. 500ms or 1s"))?;
+
+    if duration.is_zero() {
+        return Err("interval must be positive".to_string());
+    }
+    Ok(duration)
+}
+
+/// One character per probe scaled between the fastest and slowest of `window`, a space for a lost
+/// probe.
+pub fn sparkline(window: &[Option<f64>]) -> String {
+    let rtts = window.iter().flatten().copied();
+    let min = rtts.clone().fold(f64::INFINITY, f64::min);
+    let max = rtts.fold(0.0, f64::max);
+
+    window
+        .iter()
+        .map(|rtt| match rtt {
+            None => ' ',
+            Some(_) if max <= min => SPARKS[0],
+            Some(rtt) => {
+                let level = (rtt - min) / (max - min) * (SPARKS.len() - 1) as f64;
+                SPARKS[level.round() as usize]
+            }
+        })
+        .collect()
+}
+
+async fn resolve_server(
+    speed_tester: &SpeedTester,
+    server_id: Option<&str>,
+) -> eyre::Result<Server> {
+    let discovery = speed_tester
+        .discover()
+        .await
+        .map_err(|e| eyre::eyre!("{e}"))?;
+
+    match server_id {
+        Some(id) => discovery
+            .servers
+            .into_iter()
+            .find(|server| server.id == id)
+            .ok_or_else(|| eyre::eyre!("server {id} is not in the server list for your location")),
+        None => Ok(speed_tester
+            .pick_server(&discovery)
+            .await
+            .map_err(|e| eyre::eyre!("{e}"))?
+            .server),
+    }
+}
+
+/// Probes the server every `interval` until interrupted or `count` probes were sent.
+pub async fn run(
+    speed_tester: &SpeedTester,
+    server_id: Option<String>,
+    interval: Duration,
+    count: Option<u64>,
+    format: PingFormat,
+) -> eyre::Result<()> {
+    let server = resolve_server(speed_tester, server_id.as_deref()).await?;
+    eprintln!(
+        "Pinging {} {} ({}, {}) every {:?}, press Ctrl-C to stop.",
+        server.id, server.sponsor, server.name, server.cc, interval
+    );
+    if format == PingFormat::Csv {
+        println!("timestamp,seq,rtt_ms");
+    }
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut samples = vec![];
+    let mut lost = 0;
+    let mut window = VecDeque::with_capacity(SPARKLINE_WIDTH);
+
+    let probes = async {
+        for seq in 1.. {
+            if count.is_some_and(|count| seq > count) {
+                break;
+            }
+            ticker.tick().await;
+
+            let rtt = speed_tester.ping(&server).await;
+            match rtt {
+                Some(rtt) => samples.push(rtt),
+                None => lost += 1,
+            }
+            let probe = Probe {
+                timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                seq,
+                rtt_ms: rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+            };
+
+            if window.len() == SPARKLINE_WIDTH {
+                window.pop_front();
+            }
+            window.push_back(probe.rtt_ms);
+
+            match format {
+                PingFormat::Text => println!(
+                    "{:>5}  {:>9}  {}",
+                    probe.seq,
+                    probe
+                        .rtt_ms
+                        .map_or("lost".to_string(), |ms| format!("{ms:.1} ms")),
+                    sparkline(window.make_contiguous())
+                ),
+                PingFormat::Csv => println!(
+                    "{},{},{}",
+                    probe.timestamp,
+                    probe.seq,
+                    probe.rtt_ms.map_or(String::new(), |ms| format!("{ms:.3}"))
+                ),
+                PingFormat::Json => println!("{}", serde_json::to_string(&probe)?),
+            }
+        }
+        eyre::Ok(())
+    };
+
+    tokio::select! {
+        result = probes => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    // The summary goes to stderr so CSV and JSON output stay machine-readable.
+    match Latency::from_samples(&samples, lost) {
+        Some(latency) => eprintln!(
+            "{} probes, {} lost, latency min/avg/max {:.1}/{:.1}/{:.1} ms, jitter {:.1} ms",
+            latency.samples + latency.lost,
+            latency.lost,
+            latency.min_ms,
+            latency.avg_ms,
+            latency.max_ms,
+            latency.jitter_ms
+        ),
+        None => eprintln!("{lost} probes, all lost"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::ping::{parse_interval, sparkline};
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_interval("2.5s"), Ok(Duration::from_millis(2500)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("1").is_err());
+        assert!(parse_interval("-1s").is_err());
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[Some(10.0), Some(20.0), None, Some(15.0)]),
+            "▁█ ▅"
+        );
+        assert_eq!(sparkline(&[Some(7.0), Some(7.0)]), "▁▁");
+        assert_eq!(sparkline(&[None]), " ");
+    }
+}
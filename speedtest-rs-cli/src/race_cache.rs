@@ -0,0 +1,178 @@
+//! Remembers the winner of the server race, so back-to-back runs on the same network skip it.
+
+use std::{path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize};
+use speedtest_rs_core::nat::{default_gateway, source_address};
+
+use crate::sink::write_atomic;
+
+/// How long a race result is reused.
+pub const RACE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Network identity a race result is valid for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkKey {
+    /// Public IP address reported by the remote config.
+    pub ip: String,
+
+    /// Interface the traffic is bound to, `None` for the default route.
+    pub interface: Option<String>,
+
+    /// Local address the traffic leaves from. Another LAN behind the same public IP, e.g. the
+    /// guest network of a router, hands out other addresses.
+    #[serde(default)]
+    pub local_ip: Option<String>,
+
+    /// Gateway of the default route, which changes with the access point or router.
+    #[serde(default)]
+    pub gateway: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    network: NetworkKey,
+    server_id: String,
+    raced_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaceCache {
+    path: PathBuf,
+    ttl: Duration,
+    interface: Option<String>,
+}
+
+impl RaceCache {
+    pub fn new(path: PathBuf, interface: Option<String>) -> Self {
+        Self {
+            path,
+            ttl: RACE_CACHE_TTL,
+            interface,
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/speedtest-rs/race.json` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("speedtest-rs").join("race.json"))
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The network reaching the servers from `ip`, the public address of the remote config.
+    pub async fn key(&self, ip: &str) -> NetworkKey {
+        let local_ip = match ip.parse() {
+            Ok(public) => source_address(public).await,
+            Err(_) => None,
+        };
+        NetworkKey {
+            ip: ip.to_string(),
+            interface: self.interface.clone(),
+            local_ip: local_ip.map(|ip| ip.to_string()),
+            gateway: default_gateway().map(|gateway| gateway.to_string()),
+        }
+    }
+
+    /// Id of the server that won the last race on `network`, if it is recent enough.
+    pub fn lookup(&self, network: &NetworkKey, now: DateTime<Utc>) -> Option<String> {
+        self.load()
+            .into_iter()
+            .find(|entry| entry.network == *network && self.is_fresh(entry, now))
+            .map(|entry| entry.server_id)
+    }
+
+    pub fn store(
+        &self,
+        network: NetworkKey,
+        server_id: String,
+        now: DateTime<Utc>,
+    ) -> eyre::Result<()> {
+        let mut entries: Vec<Entry> = self
+            .load()
+            .into_iter()
+            .filter(|entry| entry.network != network && self.is_fresh(entry, now))
+            .collect();
+        entries.push(Entry {
+            network,
+            server_id,
+            raced_at: now,
+        });
+        write_atomic(&self.path, &serde_json::to_vec_pretty(&entries)?)
+    }
+
+    /// Forgets all race results, e.g. when the network changed.
+    pub fn invalidate(&self) -> eyre::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_fresh(&self, entry: &Entry, now: DateTime<Utc>) -> bool {
+        (now - entry.raced_at)
+            .to_std()
+            .is_ok_and(|age| age < self.ttl)
+    }
+
+    /// A missing or corrupt cache is treated as empty.
+    fn load(&self) -> Vec<Entry> {
+        std::fs::read(&self.path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::race_cache::{NetworkKey, RACE_CACHE_TTL, RaceCache};
+
+    #[tokio::test]
+    async fn test_race_cache() {
+        let path = std::env::temp_dir().join(format!(
+            "speedtest-rs-test-race-{}.json",
+            std::process::id()
+        ));
+        let cache = RaceCache::new(path.clone(), Some("eth0".to_string()));
+        let other = RaceCache::new(path.clone(), None);
+        let now = Utc::now();
+
+        let home = NetworkKey {
+            local_ip: Some("192.168.1.23".to_string()),
+            gateway: Some("192.168.1.1".to_string()),
+            ..cache.key("203.0.113.45").await
+        };
+        assert_eq!(cache.lookup(&home, now), None);
+
+        cache.store(home.clone(), "35791".to_string(), now).unwrap();
+        assert_eq!(cache.lookup(&home, now).as_deref(), Some("35791"));
+
+        // Another interface, public IP, local address or gateway is another network.
+        assert_eq!(other.lookup(&other.key("203.0.113.45").await, now), None);
+        assert_eq!(cache.lookup(&cache.key("198.51.100.7").await, now), None);
+        let guest = NetworkKey {
+            local_ip: Some("192.168.2.23".to_string()),
+            ..home.clone()
+        };
+        assert_eq!(cache.lookup(&guest, now), None);
+        let roamed = NetworkKey {
+            gateway: Some("192.168.1.254".to_string()),
+            ..home.clone()
+        };
+        assert_eq!(cache.lookup(&roamed, now), None);
+
+        let later = now + TimeDelta::from_std(RACE_CACHE_TTL).unwrap();
+        assert_eq!(cache.lookup(&home, later), None);
+
+        cache.invalidate().unwrap();
+        assert_eq!(cache.lookup(&home, now), None);
+        cache.invalidate().unwrap();
+    }
+}
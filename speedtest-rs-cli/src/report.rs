@@ -0,0 +1,352 @@
+//! `speedtest-rs report`: a self-contained HTML report of the recent history, meant to be
+//! attached to ISP support tickets.
+
+use std::{fmt::Write, path::Path, time::Duration};
+
+use chrono::{DateTime, Local, Utc};
+use color_eyre::eyre;
+use speedtest_rs_core::{redact::Redact, result::SpeedTestResult};
+
+use crate::{
+    args::HistoryFilter,
+    history::{History, heatmap::Heatmap},
+    sink::write_atomic,
+};
+
+const CHART_WIDTH: f64 = 720.0;
+
+const CHART_HEIGHT: f64 = 240.0;
+
+/// Room for the axis labels around the plot area.
+const CHART_MARGIN: f64 = 40.0;
+
+const DOWNLOAD_COLOR: &str = "#1f77b4";
+
+const UPLOAD_COLOR: &str = "#ff7f0e";
+
+const STYLE: &str = "
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }
+h1 { margin-bottom: 0; }
+.meta { color: #666; margin-top: 0.2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+th { background: #f4f4f4; }
+.download { color: #1f77b4; }
+.upload { color: #ff7f0e; }
+";
+
+/// Writes the report of the runs within `last` to `out`, masked with `redact`.
+pub fn run(last: Duration, out: &Path, history: Option<&Path>, redact: bool) -> eyre::Result<()> {
+    let filter = HistoryFilter {
+        since: Some(last),
+        ..Default::default()
+    };
+    let mut results = History::open_or_default(history)?.query(&filter)?;
+    if redact {
+        results = results.iter().map(Redact::redact).collect();
+    }
+    if results.is_empty() {
+        eyre::bail!("no runs recorded in the selected period");
+    }
+
+    write_atomic(out, render(&results, last, Utc::now()).as_bytes())?;
+    println!(
+        "Report of {} runs written to {}",
+        results.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// The complete HTML document, `results` must be ordered oldest first.
+pub fn render(results: &[SpeedTestResult], last: Duration, now: DateTime<Utc>) -> String {
+    let mut html = String::new();
+    _ = writeln!(html, "<!DOCTYPE html>");
+    _ = writeln!(html, r#"<html lang="en"><head><meta charset="utf-8">"#);
+    _ = writeln!(html, "<title>Internet speed report</title>");
+    _ = writeln!(html, "<style>{STYLE}</style></head><body>");
+
+    let first = results.first().map(|r| r.timestamp);
+    let latest = results.last();
+    _ = writeln!(html, "<h1>Internet speed report</h1>");
+    _ = writeln!(
+        html,
+        r#"<p class="meta">{} runs from {} to {}, last {} days</p>"#,
+        results.len(),
+        first.map_or("-".to_string(), local_time),
+        latest.map_or("-".to_string(), |r| local_time(r.timestamp)),
+        last.as_secs() / (24 * 60 * 60)
+    );
+
+    if let Some(latest) = latest {
+        _ = writeln!(html, "<h2>Connection</h2><table>");
+        for (label, value) in [
+            ("Provider", latest.client.isp.as_str()),
+            ("Public IP address", latest.client.ip.as_str()),
+            ("Country", latest.client.country.as_str()),
+        ] {
+            _ = writeln!(html, "<tr><th>{label}</th><td>{}</td></tr>", escape(value));
+        }
+        _ = writeln!(html, "</table>");
+    }
+
+    render_summary(&mut html, results);
+
+    _ = writeln!(
+        html,
+        r#"<h2>Speed over time</h2><p><span class="download">■ Download</span> <span class="upload">■ Upload</span></p>"#
+    );
+    html.push_str(&render_chart(results));
+
+    _ = writeln!(
+        html,
+        "<h2>Median download speed by hour of day (local time)</h2>"
+    );
+    html.push_str(&Heatmap::from_results(results).render_svg());
+
+    render_runs(&mut html, results);
+
+    _ = writeln!(
+        html,
+        "<h2>Methodology</h2>
+<ul>
+<li>Each run downloads from and uploads to a speedtest.net server over several parallel HTTP
+connections for a fixed duration. Speeds are the transferred bytes divided by the transfer time,
+in decimal megabits per second (1 Mbps = 1,000,000 bits per second).</li>
+<li>The server is chosen by the lowest latency among the servers near the client unless configured
+otherwise. Latency is the average round trip of HTTP requests to the server before the transfers
+start, jitter the mean difference between consecutive probes.</li>
+<li>Runs tagged <em>contended</em> saw traffic of other applications on the network interface
+before the test, runs tagged <em>background</em> used fewer connections on purpose. Both may
+under-report the capacity of the line.</li>
+</ul>"
+    );
+
+    _ = writeln!(
+        html,
+        r#"<p class="meta">Generated {} by speedtest-rs {} on {}.</p>"#,
+        local_time(now),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS
+    );
+    _ = writeln!(html, "</body></html>");
+    html
+}
+
+fn render_summary(html: &mut String, results: &[SpeedTestResult]) {
+    let download: Vec<f64> = results
+        .iter()
+        .filter_map(|r| r.download.map(|d| d.bits_per_second / 1e6))
+        .collect();
+    let upload: Vec<f64> = results
+        .iter()
+        .filter_map(|r| r.upload.map(|u| u.bits_per_second / 1e6))
+        .collect();
+    let latency: Vec<f64> = results
+        .iter()
+        .filter_map(|r| r.idle_latency.map(|l| l.avg_ms))
+        .collect();
+
+    _ = writeln!(
+        html,
+        "<h2>Summary</h2><table><tr><th></th><th>Median</th><th>Slowest 10%</th><th>Minimum</th><th>Maximum</th></tr>"
+    );
+    for (label, values, unit) in [
+        ("Download", download, "Mbps"),
+        ("Upload", upload, "Mbps"),
+        ("Latency", latency, "ms"),
+    ] {
+        // For latency the worst runs are the highest values.
+        let worst = if unit == "ms" { 0.9 } else { 0.1 };
+        let cell = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{v:.1} {unit}"));
+        _ = writeln!(
+            html,
+            r#"<tr><th>{label}</th><td class="num">{}</td><td class="num">{}</td><td class="num">{}</td><td class="num">{}</td></tr>"#,
+            cell(percentile(&values, 0.5)),
+            cell(percentile(&values, worst)),
+            cell(percentile(&values, 0.0)),
+            cell(percentile(&values, 1.0)),
+        );
+    }
+    _ = writeln!(html, "</table>");
+}
+
+fn render_runs(html: &mut String, results: &[SpeedTestResult]) {
+    _ = writeln!(
+        html,
+        "<h2>All runs</h2><table><tr><th>Time</th><th>Server</th><th>Download</th><th>Upload</th><th>Latency</th><th>Notes</th></tr>"
+    );
+    for result in results.iter().rev() {
+        let mbps = |bps: Option<f64>| bps.map_or("-".to_string(), |b| format!("{:.1}", b / 1e6));
+        let mut notes = result.tags.clone();
+        notes.extend(result.note.clone());
+        _ = writeln!(
+            html,
+            r#"<tr><td>{}</td><td>{} ({})</td><td class="num">{}</td><td class="num">{}</td><td class="num">{}</td><td>{}</td></tr>"#,
+            local_time(result.timestamp),
+            escape(&result.server.sponsor),
+            escape(&result.server.name),
+            mbps(result.download.map(|d| d.bits_per_second)),
+            mbps(result.upload.map(|u| u.bits_per_second)),
+            result
+                .idle_latency
+                .map_or("-".to_string(), |l| format!("{:.1} ms", l.avg_ms)),
+            escape(&notes.join(", ")),
+        );
+    }
+    _ = writeln!(html, "</table>");
+}
+
+/// Download and upload speed of every run as an SVG line chart.
+fn render_chart(results: &[SpeedTestResult]) -> String {
+    let times: Vec<i64> = results.iter().map(|r| r.timestamp.timestamp()).collect();
+    let (start, end) = match (times.first(), times.last()) {
+        (Some(&start), Some(&end)) => (start, end.max(start + 1)),
+        _ => return String::new(),
+    };
+    let max_mbps = results
+        .iter()
+        .flat_map(|r| [r.download, r.upload])
+        .flatten()
+        .map(|t| t.bits_per_second / 1e6)
+        .fold(1.0, f64::max);
+
+    let plot_width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+    let x = |time: i64| CHART_MARGIN + (time - start) as f64 / (end - start) as f64 * plot_width;
+    let y = |mbps: f64| CHART_MARGIN + plot_height * (1.0 - mbps / max_mbps);
+
+    let mut svg = String::new();
+    _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{CHART_WIDTH}" height="{CHART_HEIGHT}" font-family="sans-serif" font-size="11">"#
+    );
+    for fraction in [0.0, 0.5, 1.0] {
+        let mbps = max_mbps * fraction;
+        _ = writeln!(
+            svg,
+            r##"<line x1="{CHART_MARGIN}" x2="{}" y1="{y:.1}" y2="{y:.1}" stroke="#ddd"/><text x="{}" y="{:.1}" text-anchor="end">{mbps:.0}</text>"##,
+            CHART_WIDTH - CHART_MARGIN,
+            CHART_MARGIN - 4.0,
+            y(mbps) + 4.0,
+            y = y(mbps),
+        );
+    }
+    _ = writeln!(
+        svg,
+        r#"<text x="4" y="{}">Mbps</text><text x="{CHART_MARGIN}" y="{}">{}</text><text x="{}" y="{}" text-anchor="end">{}</text>"#,
+        CHART_MARGIN - 16.0,
+        CHART_HEIGHT - 16.0,
+        results
+            .first()
+            .map_or(String::new(), |r| local_time(r.timestamp)),
+        CHART_WIDTH - CHART_MARGIN,
+        CHART_HEIGHT - 16.0,
+        results
+            .last()
+            .map_or(String::new(), |r| local_time(r.timestamp)),
+    );
+
+    for (color, pick) in [
+        (
+            DOWNLOAD_COLOR,
+            (|r| r.download) as fn(&SpeedTestResult) -> _,
+        ),
+        (UPLOAD_COLOR, |r| r.upload),
+    ] {
+        let points: Vec<(f64, f64, f64)> = results
+            .iter()
+            .zip(&times)
+            .filter_map(|(r, &time)| {
+                let mbps = pick(r)?.bits_per_second / 1e6;
+                Some((x(time), y(mbps), mbps))
+            })
+            .collect();
+        let path: Vec<String> = points
+            .iter()
+            .map(|(x, y, _)| format!("{x:.1},{y:.1}"))
+            .collect();
+        _ = writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="1.5"/>"#,
+            path.join(" ")
+        );
+        for (x, y, mbps) in points {
+            _ = writeln!(
+                svg,
+                r#"<circle cx="{x:.1}" cy="{y:.1}" r="2.5" fill="{color}"><title>{mbps:.1} Mbps</title></circle>"#
+            );
+        }
+    }
+    _ = writeln!(svg, "</svg>");
+    svg
+}
+
+/// Nearest-rank percentile, `p` in `0.0..=1.0`.
+fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = (p * (sorted.len() - 1) as f64).round() as usize;
+    Some(sorted[rank])
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeDelta, Utc};
+    use speedtest_rs_core::result::{SpeedTestResult, TransferResult};
+
+    use crate::{
+        app::demo,
+        report::{percentile, render},
+    };
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 0.5), None);
+        assert_eq!(percentile(&[3.0, 1.0, 2.0], 0.5), Some(2.0));
+        assert_eq!(percentile(&[3.0, 1.0, 2.0], 0.0), Some(1.0));
+        assert_eq!(percentile(&[3.0, 1.0, 2.0], 1.0), Some(3.0));
+    }
+
+    #[test]
+    fn test_render() {
+        let now = Utc::now();
+        let servers = demo::servers();
+        let results: Vec<SpeedTestResult> = (0..3)
+            .map(|days_ago| {
+                SpeedTestResult::new(
+                    now - TimeDelta::days(3 - days_ago),
+                    &demo::client(),
+                    &servers[0].0,
+                )
+                .with_download(TransferResult::new(12_500_000, Duration::from_secs(1)))
+                .with_note(Some("<after> firmware update".to_string()))
+            })
+            .collect();
+
+        let html = render(&results, Duration::from_secs(30 * 24 * 60 * 60), now);
+        assert!(html.contains("3 runs from"));
+        assert!(html.contains("100.0 Mbps"));
+        assert!(html.contains("&lt;after&gt; firmware update"));
+        assert_eq!(html.matches("<svg").count(), 2);
+    }
+}
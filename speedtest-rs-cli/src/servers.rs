@@ -0,0 +1,271 @@
+//! Tracking of the server pool offered for the current location.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre;
+use speedtest_rs_core::{
+    model::{Server, group_by_sponsor},
+    speed_tester::SpeedTester,
+};
+
+use crate::{args::ServersCommand, sink::write_atomic};
+
+/// Servers that appeared, disappeared or changed between two fetches, matched by id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerDiff {
+    pub added: Vec<Server>,
+    pub removed: Vec<Server>,
+    pub changed: Vec<(Server, Server)>,
+}
+
+impl ServerDiff {
+    pub fn between(old: &[Server], new: &[Server]) -> Self {
+        let find = |servers: &[Server], id: &str| servers.iter().find(|s| s.id == id).cloned();
+
+        let mut diff = Self::default();
+        for server in new {
+            match find(old, &server.id) {
+                None => diff.added.push(server.clone()),
+                Some(previous) if previous != *server => {
+                    diff.changed.push((previous, server.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|s| find(new, &s.id).is_none())
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Names of the fields that differ between two versions of a server.
+pub fn changed_fields(old: &Server, new: &Server) -> Vec<&'static str> {
+    [
+        ("name", old.name != new.name),
+        ("sponsor", old.sponsor != new.sponsor),
+        ("country", old.country != new.country || old.cc != new.cc),
+        ("host", old.host != new.host),
+        ("url", old.url != new.url),
+        ("location", old.lat != new.lat || old.lon != new.lon),
+        ("extra", old.extra != new.extra),
+    ]
+    .into_iter()
+    .filter_map(|(field, changed)| changed.then_some(field))
+    .collect()
+}
+
+/// `$XDG_DATA_HOME/speedtest-rs/servers.json` or the platform equivalent.
+pub fn default_snapshot_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("speedtest-rs").join("servers.json"))
+}
+
+fn load_snapshot(path: &Path) -> eyre::Result<Option<Vec<Server>>> {
+    match std::fs::read(path) {
+        Ok(raw) => Ok(Some(serde_json::from_slice(&raw)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn describe(server: &Server) -> String {
+    format!(
+        "{:>6}  {} ({}, {})",
+        server.id, server.sponsor, server.name, server.cc
+    )
+}
+
+/// One line per server, with an indented block under a header line per sponsor when grouping.
+/// Sponsors are ordered by their first server.
+fn listing(servers: Vec<Server>, group: bool, line: impl Fn(&Server) -> String) -> Vec<String> {
+    if !group {
+        return servers.iter().map(line).collect();
+    }
+
+    let mut lines = vec![];
+    for group in group_by_sponsor(servers) {
+        let count = group.servers.len();
+        let plural = if count == 1 { "" } else { "s" };
+        lines.push(format!("{} ({count} server{plural})", group.sponsor));
+        lines.extend(
+            group
+                .servers
+                .iter()
+                .map(|server| format!("  {}", line(server))),
+        );
+    }
+    lines
+}
+
+/// Prints the servers near you, e.g. for `servers --group` without a subcommand.
+pub async fn list(speed_tester: &SpeedTester, group: bool) -> eyre::Result<()> {
+    let discovery = speed_tester
+        .discover()
+        .await
+        .map_err(|e| eyre::eyre!("{e}"))?;
+
+    for line in listing(discovery.servers, group, describe) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Races all servers like the TUI and prints the leaderboard.
+pub async fn ping(speed_tester: &SpeedTester, group: bool) -> eyre::Result<()> {
+    let discovery = speed_tester
+        .discover()
+        .await
+        .map_err(|e| eyre::eyre!("{e}"))?;
+
+    let ranked = speed_tester.rank_servers(discovery.servers).await;
+    let latencies: HashMap<String, _> = ranked
+        .iter()
+        .map(|(server, latency)| (server.id.clone(), *latency))
+        .collect();
+    let servers = ranked.into_iter().map(|(server, _)| server).collect();
+
+    println!("{:>8}  {:>8}  {:>7}  Server", "Latency", "Jitter", "Loss");
+    let line = |server: &Server| match latencies[&server.id] {
+        Some(latency) => format!(
+            "{:>5.1} ms  {:>5.1} ms  {:>7}  {}",
+            latency.avg_ms,
+            latency.jitter_ms,
+            format!("{}/{}", latency.lost, latency.samples + latency.lost),
+            describe(server)
+        ),
+        None => format!("{:>8}  {:>8}  {:>7}  {}", "-", "-", "all", describe(server)),
+    };
+    for line in listing(servers, group, line) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub async fn run(
+    command: ServersCommand,
+    speed_tester: &SpeedTester,
+    group: bool,
+) -> eyre::Result<()> {
+    match command {
+        ServersCommand::Diff {
+            snapshot,
+            no_update,
+        } => {
+            let path = snapshot
+                .or_else(default_snapshot_path)
+                .ok_or_else(|| eyre::eyre!("unable to determine the data directory"))?;
+
+            let discovery = speed_tester
+                .discover()
+                .await
+                .map_err(|e| eyre::eyre!("{e}"))?;
+            let current = discovery.servers;
+
+            match load_snapshot(&path)? {
+                None => println!(
+                    "No snapshot at {}, recording {} servers.",
+                    path.display(),
+                    current.len()
+                ),
+                Some(previous) => {
+                    let diff = ServerDiff::between(&previous, &current);
+                    if diff.is_empty() {
+                        println!("No changes ({} servers).", current.len());
+                    }
+                    for server in &diff.added {
+                        println!("+ {}", describe(server));
+                    }
+                    for server in &diff.removed {
+                        println!("- {}", describe(server));
+                    }
+                    for (old, new) in &diff.changed {
+                        println!(
+                            "~ {} [{}]",
+                            describe(new),
+                            changed_fields(old, new).join(", ")
+                        );
+                    }
+                }
+            }
+
+            if !no_update {
+                write_atomic(&path, &serde_json::to_vec_pretty(&current)?)?;
+            }
+        }
+        ServersCommand::Search { keyword } => {
+            let servers = speed_tester
+                .search_servers(Some(&keyword))
+                .await
+                .map_err(|e| eyre::eyre!("{e}"))?;
+
+            if servers.is_empty() {
+                println!("No servers matching '{keyword}'.");
+            }
+            for line in listing(servers, group, describe) {
+                println!("{line}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use speedtest_rs_core::model::Server;
+
+    use crate::servers::{ServerDiff, changed_fields, listing};
+
+    fn server(id: &str, sponsor: &str) -> Server {
+        let mut server = Server::from_url(&format!(
+            "http://{id}.example.com:8080/speedtest/upload.php"
+        ))
+        .unwrap();
+        server.lat = 22.0;
+        server.lon = 114.0;
+        server.name = "Hong Kong".to_string();
+        server.country = "Hong Kong".to_string();
+        server.cc = "HK".to_string();
+        server.sponsor = sponsor.to_string();
+        server.id = id.to_string();
+        server
+    }
+
+    #[test]
+    fn test_server_diff() {
+        let old = [server("1", "A"), server("2", "B"), server("3", "C")];
+        let new = [server("1", "A"), server("3", "C2"), server("4", "D")];
+
+        let diff = ServerDiff::between(&old, &new);
+
+        assert_eq!(diff.added, [server("4", "D")]);
+        assert_eq!(diff.removed, [server("2", "B")]);
+        assert_eq!(diff.changed, [(server("3", "C"), server("3", "C2"))]);
+        assert_eq!(
+            changed_fields(&diff.changed[0].0, &diff.changed[0].1),
+            ["sponsor"]
+        );
+
+        assert!(ServerDiff::between(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_listing() {
+        let servers = vec![server("1", "A"), server("2", "B"), server("3", "a")];
+        let id = |server: &Server| server.id.clone();
+
+        assert_eq!(listing(servers.clone(), false, id), ["1", "2", "3"]);
+        assert_eq!(
+            listing(servers, true, id),
+            ["A (2 servers)", "  1", "  3", "B (1 server)", "  2"]
+        );
+    }
+}
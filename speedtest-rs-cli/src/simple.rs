@@ -0,0 +1,371 @@
+//! `--simple`: the test without the TUI, for scripts, cron jobs and serial consoles. The transfers
+//! show their live rate on a status line updated in place at every throughput sample, or as a line
+//! per second when stdout is not a terminal.
+
+use std::{
+    io::{IsTerminal, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre;
+use speedtest_rs_core::{
+    redact::Redact,
+    result::SpeedTestResult,
+    speed_tester::{CancellationToken, SpeedTester},
+    stage::Stage,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    app::{App, RunOptions, demo, partial::PartialRun},
+    event::{AppEvent, Event, State, Status},
+    hooks::Hooks,
+    sink::Sinks,
+};
+
+/// Time between the lines of a transfer when stdout is not a terminal.
+const LINE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs the test printing its progress, returning the result if the run completed.
+pub async fn run(
+    speed_tester: SpeedTester,
+    options: RunOptions,
+    sinks: Sinks,
+    hooks: Hooks,
+    redact: bool,
+) -> eyre::Result<Option<SpeedTestResult>> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let downloaded = Arc::new(AtomicU64::new(0));
+    let uploaded = Arc::new(AtomicU64::new(0));
+    let test = tokio::spawn({
+        let speed_tester = speed_tester.clone();
+        let options = options.clone();
+        let downloaded = downloaded.clone();
+        let uploaded = uploaded.clone();
+        async move {
+            if options.demo {
+                demo::run(sender, downloaded, uploaded).await;
+            } else {
+                App::speedtest(speed_tester, options, sender, downloaded, uploaded).await;
+            }
+        }
+    });
+
+    let stdout = std::io::stdout();
+    let tty = stdout.is_terminal();
+    let mut line = StatusLine::new(stdout, tty);
+    let mut transfer: Option<Transfer> = None;
+    let mut result = None;
+    let mut pending_hooks = vec![];
+    let mut partial = PartialRun::default();
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        let event = tokio::select! {
+            // Keeps what was measured so far, marked aborted. No hooks run for it.
+            _ = &mut shutdown => {
+                test.abort();
+                let aborted = partial.result(
+                    downloaded.load(Ordering::SeqCst),
+                    uploaded.load(Ordering::SeqCst),
+                    Instant::now(),
+                );
+                if result.is_none()
+                    && let Some(aborted) = aborted
+                {
+                    let aborted = if redact { aborted.redact() } else { aborted };
+                    let aborted = options.annotate(aborted);
+                    if let Err(e) = sinks.write(&aborted) {
+                        tracing::warn!("failed to write aborted result: {}", e);
+                    }
+                    result = Some(aborted);
+                }
+                line.finish("Aborted")?;
+                break;
+            }
+            // Ends once the test dropped its sender.
+            event = receiver.recv() => match event {
+                Some(Event::App(event)) => event,
+                Some(_) => continue,
+                None => break,
+            },
+        };
+
+        match event {
+            AppEvent::SetState(state) => {
+                partial.apply(&state, Instant::now());
+                match state {
+                    State::RacingServers(Status::Ok(server)) => {
+                        let server = if redact { server.redact() } else { *server };
+                        line.finish(&format!(
+                            "Server: {}, {} ({})",
+                            server.sponsor, server.name, server.id
+                        ))?;
+                    }
+                    State::IdleLatency(Status::Ok(Some(latency))) => {
+                        line.finish(&format!("Idle latency: {:.0} ms", latency.avg_ms))?;
+                    }
+                    State::Download(Status::Start) | State::Upload(Status::Start) => {
+                        let (stage, counter) = match state {
+                            State::Download(_) => (Stage::Download, &downloaded),
+                            _ => (Stage::Upload, &uploaded),
+                        };
+                        let interval = if tty { Duration::ZERO } else { LINE_INTERVAL };
+                        transfer = Some(Transfer::new(stage, counter.clone(), interval));
+                    }
+                    State::Download(Status::Ok(_)) | State::Upload(Status::Ok(_)) => {
+                        if let Some(transfer) = transfer.take() {
+                            line.finish(&transfer.summary(Instant::now()))?;
+                        }
+                    }
+                    state if state.is_error() => {
+                        transfer = None;
+                        if let Some((stage, e)) = failure(&state) {
+                            line.finish(&format!("{stage} failed: {e}"))?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            AppEvent::Throughput(_) => {
+                if let Some(transfer) = &mut transfer
+                    && let Some(text) = transfer.sample(Instant::now())
+                {
+                    line.update(&text)?;
+                }
+            }
+            AppEvent::Finished(finished) => {
+                let finished = if redact { finished.redact() } else { *finished };
+                let finished = options.annotate(finished);
+                if let Err(e) = sinks.write(&finished) {
+                    tracing::warn!("failed to write result: {}", e);
+                }
+                pending_hooks = hooks.post_run(&finished);
+                result = Some(finished);
+            }
+            _ => {}
+        }
+    }
+
+    for hook in pending_hooks {
+        _ = hook.await;
+    }
+    Ok(result)
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM as sent by service managers, `timeout` and `kill`.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    _ = tokio::signal::ctrl_c().await;
+}
+
+/// A token cancelled by [`shutdown_signal`], for runs without the progress loop above.
+pub fn shutdown_token() -> CancellationToken {
+    let token = CancellationToken::new();
+    tokio::spawn({
+        let token = token.clone();
+        async move {
+            shutdown_signal().await;
+            token.cancel();
+        }
+    });
+    token
+}
+
+/// What a signal asks of `--daemon` between two runs.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonSignal {
+    /// SIGUSR1: run the test now.
+    Run,
+    /// SIGHUP: read the config file again.
+    Reload,
+}
+
+/// The signals of `--daemon`, registered once so none is lost while a run is in progress.
+#[cfg(unix)]
+pub struct DaemonSignals {
+    hangup: tokio::signal::unix::Signal,
+    user1: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl DaemonSignals {
+    pub fn new() -> std::io::Result<Self> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        Ok(Self {
+            hangup: signal(SignalKind::hangup())?,
+            user1: signal(SignalKind::user_defined1())?,
+        })
+    }
+
+    /// Waits for the next signal.
+    pub async fn next(&mut self) -> DaemonSignal {
+        tokio::select! {
+            _ = self.hangup.recv() => DaemonSignal::Reload,
+            _ = self.user1.recv() => DaemonSignal::Run,
+        }
+    }
+}
+
+/// Stage and message of a failed step.
+fn failure(state: &State) -> Option<(&'static str, &str)> {
+    let (stage, e) = match state {
+        State::FetchConfig(Status::Err(e)) | State::FetchServers(Status::Err(e)) => {
+            (Stage::Discovery, e)
+        }
+        State::RacingServers(Status::Err(e)) => (Stage::Racing, e),
+        State::IdleLatency(Status::Err(e)) => (Stage::Latency, e),
+        State::Download(Status::Err(e)) => (Stage::Download, e),
+        State::Upload(Status::Err(e)) => (Stage::Upload, e),
+        _ => return None,
+    };
+    Some((stage.display_name("en"), e))
+}
+
+/// A running transfer, sampled from the byte counter the tester adds to.
+struct Transfer {
+    stage: Stage,
+    counter: Arc<AtomicU64>,
+    interval: Duration,
+    start: Instant,
+    last: Instant,
+}
+
+impl Transfer {
+    fn new(stage: Stage, counter: Arc<AtomicU64>, interval: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            stage,
+            counter,
+            interval,
+            start: now,
+            last: now,
+        }
+    }
+
+    /// The status text at most once per interval, `None` in between.
+    fn sample(&mut self, now: Instant) -> Option<String> {
+        if now.duration_since(self.last) < self.interval {
+            return None;
+        }
+        self.last = now;
+        Some(self.summary(now))
+    }
+
+    /// Average rate and elapsed time, e.g. `Download: 93.4 Mbps (4.2s)`.
+    fn summary(&self, now: Instant) -> String {
+        let elapsed = now.duration_since(self.start).as_secs_f64();
+        let bytes = self.counter.load(Ordering::SeqCst);
+        let mbps = if elapsed > 0.0 {
+            bytes as f64 * 8.0 / elapsed / 1_000_000.0
+        } else {
+            0.0
+        };
+        format!(
+            "{}: {mbps:.1} Mbps ({elapsed:.1}s)",
+            self.stage.display_name("en")
+        )
+    }
+}
+
+/// A line rewritten in place with a carriage return on terminals, printed in full otherwise.
+struct StatusLine<W> {
+    out: W,
+    tty: bool,
+    /// Characters of the status line shown, to blank out the rest of a longer one.
+    shown: usize,
+}
+
+impl<W: Write> StatusLine<W> {
+    fn new(out: W, tty: bool) -> Self {
+        Self { out, tty, shown: 0 }
+    }
+
+    fn update(&mut self, text: &str) -> std::io::Result<()> {
+        if !self.tty {
+            return writeln!(self.out, "{text}");
+        }
+        let width = text.chars().count();
+        write!(self.out, "\r{text:<0$}", self.shown.max(width))?;
+        self.shown = width;
+        self.out.flush()
+    }
+
+    /// Replaces the status line with a permanent one.
+    fn finish(&mut self, text: &str) -> std::io::Result<()> {
+        if self.tty && self.shown > 0 {
+            write!(self.out, "\r{text:<0$}", self.shown)?;
+            self.shown = 0;
+            return writeln!(self.out);
+        }
+        writeln!(self.out, "{text}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, atomic::AtomicU64},
+        time::Duration,
+    };
+
+    use speedtest_rs_core::stage::Stage;
+
+    use crate::simple::{StatusLine, Transfer};
+
+    #[test]
+    fn test_status_line() {
+        let mut tty = StatusLine::new(vec![], true);
+        tty.update("Download: 10.0 Mbps (1.0s)").unwrap();
+        tty.update("Download: 9.5 Mbps (2.0s)").unwrap();
+        tty.finish("Download: 9.5 Mbps").unwrap();
+        tty.finish("Idle latency: 12 ms").unwrap();
+        assert_eq!(
+            String::from_utf8(tty.out).unwrap(),
+            "\rDownload: 10.0 Mbps (1.0s)\
+             \rDownload: 9.5 Mbps (2.0s) \
+             \rDownload: 9.5 Mbps       \n\
+             Idle latency: 12 ms\n"
+        );
+
+        let mut piped = StatusLine::new(vec![], false);
+        piped.update("Upload: 1.0 Mbps (1.0s)").unwrap();
+        piped.finish("Upload: 1.0 Mbps").unwrap();
+        assert_eq!(
+            String::from_utf8(piped.out).unwrap(),
+            "Upload: 1.0 Mbps (1.0s)\nUpload: 1.0 Mbps\n"
+        );
+    }
+
+    #[test]
+    fn test_transfer_sample() {
+        let counter = Arc::new(AtomicU64::new(12_500_000));
+        let mut transfer = Transfer::new(Stage::Download, counter, Duration::from_secs(1));
+        let start = transfer.start;
+
+        assert_eq!(transfer.sample(start + Duration::from_millis(500)), None);
+        assert_eq!(
+            transfer.sample(start + Duration::from_secs(1)).as_deref(),
+            Some("Download: 100.0 Mbps (1.0s)")
+        );
+        assert_eq!(transfer.sample(start + Duration::from_millis(1500)), None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre;
+use speedtest_rs_core::result::SpeedTestResult;
+
+use crate::history::History;
+
+/// Destinations the result of a finished run is written to.
+#[derive(Debug, Clone, Default)]
+pub struct Sinks {
+    /// Overwritten with every new result, so dashboards can poll a stable path.
+    pub latest_json: Option<PathBuf>,
+
+    /// SQLite history database.
+    pub history: Option<PathBuf>,
+}
+
+impl Sinks {
+    /// Writes the result everywhere, returning its row id in the history.
+    pub fn write(&self, result: &SpeedTestResult) -> eyre::Result<Option<i64>> {
+        self.write_latest(result)?;
+        self.history
+            .as_ref()
+            .map(|path| History::open(path)?.insert(result))
+            .transpose()
+    }
+
+    /// Rewrites a result whose note changed after it was written, without recording it twice.
+    pub fn update_note(
+        &self,
+        history_id: Option<i64>,
+        result: &SpeedTestResult,
+    ) -> eyre::Result<()> {
+        self.write_latest(result)?;
+        if let Some(path) = &self.history
+            && let Some(id) = history_id
+        {
+            History::open(path)?.set_note(id, result)?;
+        }
+        Ok(())
+    }
+
+    fn write_latest(&self, result: &SpeedTestResult) -> eyre::Result<()> {
+        if let Some(path) = &self.latest_json {
+            let json = serde_json::to_vec_pretty(result)?;
+            write_atomic(path, &json)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it into place, so readers never observe
+/// a partially written file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> eyre::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| eyre::eyre!("invalid output path {}", path.display()))?;
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        _ = std::fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! Colors and chart styles of the TUI.
+
+use clap::ValueEnum;
+use ratatui::{style::Color, symbols::Marker, widgets::GraphType};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Default,
+
+    /// Okabe-Ito colors distinguishable with red-green deficiency (deuteranopia).
+    Deuteranopia,
+
+    /// Like deuteranopia, avoiding reds that appear dark with protanopia.
+    Protanopia,
+}
+
+/// Symbols the charts are drawn with, braille renders poorly with some fonts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartMarker {
+    Braille,
+    #[value(name = "halfblock")]
+    #[serde(rename = "halfblock")]
+    HalfBlock,
+    Dot,
+}
+
+impl From<ChartMarker> for Marker {
+    fn from(value: ChartMarker) -> Self {
+        match value {
+            ChartMarker::Braille => Marker::Braille,
+            ChartMarker::HalfBlock => Marker::HalfBlock,
+            ChartMarker::Dot => Marker::Dot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub download: Color,
+    pub upload: Color,
+    pub ok: Color,
+    pub error: Color,
+
+    pub download_marker: Marker,
+    pub upload_marker: Marker,
+
+    /// Color-blind palettes also draw the upload as points, so the datasets differ in shape.
+    pub download_graph: GraphType,
+    pub upload_graph: GraphType,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(Palette::Default)
+    }
+}
+
+impl Theme {
+    pub fn new(palette: Palette) -> Self {
+        match palette {
+            Palette::Default => Self {
+                download: Color::Magenta,
+                upload: Color::Cyan,
+                ok: Color::Green,
+                error: Color::Red,
+                download_marker: Marker::Braille,
+                upload_marker: Marker::Braille,
+                download_graph: GraphType::Line,
+                upload_graph: GraphType::Line,
+            },
+            Palette::Deuteranopia => Self {
+                download: Color::Rgb(0xE6, 0x9F, 0x00),
+                upload: Color::Rgb(0x56, 0xB4, 0xE9),
+                ok: Color::Rgb(0x00, 0x72, 0xB2),
+                error: Color::Rgb(0xD5, 0x5E, 0x00),
+                ..Self::color_blind_shapes()
+            },
+            Palette::Protanopia => Self {
+                download: Color::Rgb(0xF0, 0xE4, 0x42),
+                upload: Color::Rgb(0x00, 0x72, 0xB2),
+                ok: Color::Rgb(0x56, 0xB4, 0xE9),
+                error: Color::Rgb(0xE6, 0x9F, 0x00),
+                ..Self::color_blind_shapes()
+            },
+        }
+    }
+
+    /// Draws all charts with the same marker.
+    pub fn with_marker(mut self, marker: Option<ChartMarker>) -> Self {
+        if let Some(marker) = marker {
+            self.download_marker = marker.into();
+            self.upload_marker = marker.into();
+        }
+        self
+    }
+
+    fn color_blind_shapes() -> Self {
+        Self {
+            download_marker: Marker::Braille,
+            upload_marker: Marker::Dot,
+            download_graph: GraphType::Line,
+            upload_graph: GraphType::Scatter,
+            ..Self::new(Palette::Default)
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Minimal reader for OpenWrt UCI config files (`/etc/config/<package>`).
+//!
+//! Only the subset needed for settings is supported: `config`, `option` and `list` statements,
+//! comments and single/double quoted or bare values.
+
+use std::path::Path;
+
+use color_eyre::eyre;
+
+/// Default location of the package config on OpenWrt.
+pub const DEFAULT_UCI_PATH: &str = "/etc/config/speedtest-rs";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UciSection {
+    pub kind: String,
+    pub name: Option<String>,
+    pub options: Vec<(String, String)>,
+}
+
+impl UciSection {
+    /// Returns the last value of an option, matching UCI semantics where later values win.
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns all values of a `list` option in declaration order.
+    pub fn list(&self, key: &str) -> impl Iterator<Item = &str> {
+        self.options
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UciConfig {
+    pub sections: Vec<UciSection>,
+}
+
+impl UciConfig {
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let raw = std::fs::read_to_string(path.as_ref())?;
+        Self::parse(&raw)
+    }
+
+    pub fn parse(raw: &str) -> eyre::Result<Self> {
+        let mut sections: Vec<UciSection> = vec![];
+
+        for (lineno, line) in raw.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let tokens = tokenize(line)
+                .ok_or_else(|| eyre::eyre!("line {}: unterminated quote", lineno + 1))?;
+
+            match tokens.as_slice() {
+                [keyword, kind, rest @ ..] if keyword == "config" && rest.len() <= 1 => {
+                    sections.push(UciSection {
+                        kind: kind.clone(),
+                        name: rest.first().cloned(),
+                        options: vec![],
+                    });
+                }
+                [keyword, key, value] if keyword == "option" || keyword == "list" => {
+                    let section = sections.last_mut().ok_or_else(|| {
+                        eyre::eyre!("line {}: '{keyword}' outside of a section", lineno + 1)
+                    })?;
+                    section.options.push((key.clone(), value.clone()));
+                }
+                _ => eyre::bail!("line {}: unsupported statement '{line}'", lineno + 1),
+            }
+        }
+
+        Ok(Self { sections })
+    }
+
+    /// Returns the first section of the given type.
+    pub fn section(&self, kind: &str) -> Option<&UciSection> {
+        self.sections.iter().find(|s| s.kind == kind)
+    }
+}
+
+fn tokenize(line: &str) -> Option<Vec<String>> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' => break,
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut token = String::new();
+                loop {
+                    match chars.next()? {
+                        q if q == c => break,
+                        ch => token.push(ch),
+                    }
+                }
+                tokens.push(token);
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() {
+                        break;
+                    }
+                    token.push(ch);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::uci::UciConfig;
+
+    const RAW_UCI: &str = r#"
+# speedtest-rs settings
+config speedtest 'main'
+	option interface 'wan'
+	option profile "desktop"
+	option max_concurrency 2 # routers have slow CPUs
+	list ignore '1234'
+	list ignore '5678'
+"#;
+
+    #[test]
+    fn test_parse_uci() {
+        let config = UciConfig::parse(RAW_UCI).unwrap();
+        let section = config.section("speedtest").unwrap();
+
+        assert_eq!(section.name.as_deref(), Some("main"));
+        assert_eq!(section.option("interface"), Some("wan"));
+        assert_eq!(section.option("profile"), Some("desktop"));
+        assert_eq!(section.option("max_concurrency"), Some("2"));
+        assert_eq!(section.list("ignore").collect::<Vec<_>>(), ["1234", "5678"]);
+    }
+
+    #[test]
+    fn test_parse_uci_errors() {
+        assert!(UciConfig::parse("option interface 'wan'").is_err());
+        assert!(UciConfig::parse("config speedtest 'main\n").is_err());
+    }
+}
@@ -1,42 +1,75 @@
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{DateTime, Local, Utc};
+
 use ratatui::{
     layout::{Alignment, Constraint, Layout, Margin},
     style::{Color, Style, Stylize},
-    symbols,
+    symbols::Marker,
     text::{Line, Span},
     widgets::{
-        Axis, Block, BorderType, Borders, Chart, Dataset, List, ListItem, Padding, Paragraph, Row,
-        Scrollbar, ScrollbarOrientation, ScrollbarState, Table, TableState, Widget,
+        Axis, Block, BorderType, Borders, Chart, Dataset, Gauge, GraphType, List, ListItem,
+        Padding, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table,
+        TableState, Widget, Wrap,
     },
 };
-use speedtest_rs_core::Humanize;
+use speedtest_rs_core::{Humanize, latency::Latency, model::Server, result::Methodology};
 
 use crate::{
-    app::{App, progress::Progress},
-    event::Status,
+    app::{App, MAX_RECORDS_LEN, progress::Progress, scale::AxisScale},
+    event::{NetworkIssue, Status},
 };
 
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
 impl Widget for &App {
     fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer)
     where
         Self: Sized,
     {
         let [
+            gauge_area,
             progresses_area,
             information_area,
             download_area,
             upload_area,
             footer_area,
-        ] = Layout::vertical([
-            Constraint::Length(9),
-            Constraint::Length(9),
-            Constraint::Length(12),
-            Constraint::Length(12),
-            Constraint::Length(1),
-        ])
-        .areas(area);
+        ] = if self.compact {
+            Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(8),
+                Constraint::Min(15),
+                Constraint::Length(9),
+                Constraint::Length(9),
+                Constraint::Length(1),
+            ])
+            .areas(area)
+        } else {
+            Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(10),
+                Constraint::Length(9),
+                Constraint::Length(12),
+                Constraint::Length(12),
+                Constraint::Length(1),
+            ])
+            .areas(area)
+        };
 
+        self.render_gauge(gauge_area, buf);
         self.render_progresses(progresses_area, buf);
-        self.render_information(information_area, buf);
+        let methodology = self
+            .result
+            .as_ref()
+            .and_then(|r| r.methodology.as_ref())
+            .filter(|_| self.show_methodology);
+        if self.show_debug {
+            self.render_debug(information_area, buf);
+        } else if let Some(methodology) = methodology {
+            self.render_methodology(methodology, information_area, buf);
+        } else {
+            self.render_information(information_area, buf);
+        }
         self.render_download(download_area, buf);
         self.render_upload(upload_area, buf);
         self.render_foot(footer_area, buf);
@@ -44,49 +77,224 @@ impl Widget for &App {
 }
 
 impl App {
+    fn render_gauge(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+        let ratio = self.overall_progress();
+        Gauge::default()
+            .gauge_style(Style::new().fg(self.theme.ok))
+            .ratio(ratio)
+            .label(format!("{:.0}%", ratio * 100.0))
+            .render(area, buf);
+    }
+
     fn render_progresses(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        let block = Block::new()
+        let mut block = Block::new()
             .title(Line::raw(" > Progress ").bold())
-            .padding(Padding::uniform(1))
+            .padding(self.padding())
             .borders(Borders::all())
             .border_type(BorderType::Thick);
 
+        if self.speed_tester().is_background() {
+            block = block.title(Line::raw(" background mode ").gray());
+        }
+
+        if let Some(issue) = self.network_issue {
+            let message = match issue {
+                NetworkIssue::NoConnectivity => " ⚠ No internet connectivity ",
+                NetworkIssue::CaptivePortal => " ⚠ Captive portal, sign in to the network first ",
+            };
+            block = block.title(
+                Line::from(message)
+                    .fg(self.theme.error)
+                    .bold()
+                    .right_aligned(),
+            );
+        } else if let Some(idle) = self
+            .idle_traffic
+            .filter(|idle| idle.is_contended() && self.result.is_none())
+        {
+            let rate = ((idle.rx_bits_per_second.max(idle.tx_bits_per_second) / 8.0) as usize)
+                .humanize_bitrate(1000);
+            block = block.title(
+                Line::from(format!(" ⚠ Background traffic {rate}, results may be low "))
+                    .yellow()
+                    .right_aligned(),
+            );
+        }
+
         let list = List::new([
-            ListItem::from(&self.fetch_config),
-            ListItem::from(&self.fetch_servers),
-            ListItem::from(&self.racing_servers),
-            ListItem::from(&self.download),
-            ListItem::from(&self.upload),
+            self.progress_item(&self.fetch_config),
+            self.progress_item(&self.fetch_servers),
+            self.progress_item(&self.racing_servers),
+            self.progress_item(&self.idle_latency),
+            self.progress_item(&self.download),
+            self.progress_item(&self.upload),
         ])
         .block(block);
 
         Widget::render(&list, area, buf);
     }
 
+    fn render_debug(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+        let block = Block::new()
+            .title(" > Debug ".bold())
+            .title(
+                Line::from(format!(
+                    " coalesced: {} · dropped ticks: {} ",
+                    self.event_stats.coalesced, self.event_stats.dropped_ticks
+                ))
+                .right_aligned(),
+            )
+            .borders(Borders::all())
+            .border_type(BorderType::Thick)
+            .border_style(Style::new().gray());
+
+        let lines = self
+            .logs
+            .tail(area.height.saturating_sub(2) as usize)
+            .into_iter()
+            .map(Line::from)
+            .collect::<Vec<_>>();
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    fn render_methodology(
+        &self,
+        methodology: &Methodology,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let join = |sizes: Vec<String>| sizes.join(", ");
+        let download_sizes = join(
+            methodology
+                .download_sizes
+                .iter()
+                .map(|size| format!("{size}x{size}"))
+                .collect(),
+        );
+        let upload_sizes = join(
+            methodology
+                .upload_sizes
+                .iter()
+                .map(|size| (*size as usize).humanize_bytes())
+                .collect(),
+        );
+        let protocol = match &methodology.http_version {
+            Some(version) => format!("{} ({version})", methodology.protocol),
+            None => methodology.protocol.clone(),
+        };
+        let rows = [
+            (
+                "Provider",
+                format!(
+                    "{} over {protocol}, v{}",
+                    methodology.provider, methodology.version
+                ),
+            ),
+            (
+                "Download",
+                format!(
+                    "{} connections, {} s, {download_sizes}",
+                    methodology.download_connections,
+                    methodology.download_duration_ms / 1000
+                ),
+            ),
+            (
+                "Upload",
+                format!(
+                    "{} connections, {} s, {upload_sizes}",
+                    methodology.upload_connections,
+                    methodology.upload_duration_ms / 1000
+                ),
+            ),
+            (
+                "Warm-up",
+                format!("{}, trimming {}", methodology.warm_up, methodology.trimming),
+            ),
+            (
+                "Background",
+                if methodology.background { "yes" } else { "no" }.to_string(),
+            ),
+        ]
+        .map(|(label, value)| Row::new([Span::from(label).bold().yellow(), Span::from(value)]));
+
+        Table::new(rows, [Constraint::Length(11), Constraint::Fill(1)])
+            .block(
+                Block::new()
+                    .title(" > Methodology ".bold())
+                    .title(Line::from(" 'm' to close ").right_aligned())
+                    .padding(self.padding())
+                    .borders(Borders::all())
+                    .border_type(BorderType::Thick)
+                    .border_style(Style::new().light_cyan()),
+            )
+            .render(area, buf);
+    }
+
     fn render_information(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        let [config_area, servers_area] =
-            Layout::horizontal([Constraint::Length(35), Constraint::Fill(1)]).areas(area);
+        let [config_area, servers_area] = if self.compact {
+            Layout::vertical([Constraint::Length(7), Constraint::Fill(1)]).areas(area)
+        } else {
+            Layout::horizontal([Constraint::Length(35), Constraint::Fill(1)]).areas(area)
+        };
 
         self.render_config(config_area, buf);
-        self.render_servers(servers_area, buf);
+        match self.result.as_ref().map(|r| &r.warnings) {
+            Some(warnings) if !warnings.is_empty() => {
+                let [servers_area, warnings_area] = Layout::vertical([
+                    Constraint::Fill(1),
+                    Constraint::Length(warnings.len() as u16 + 2),
+                ])
+                .areas(servers_area);
+                self.render_servers(servers_area, buf);
+                self.render_warnings(warnings_area, buf);
+            }
+            _ => self.render_servers(servers_area, buf),
+        }
+    }
+
+    fn render_warnings(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+        let lines = self
+            .result
+            .iter()
+            .flat_map(|r| &r.warnings)
+            .map(|warning| Line::from(format!("⚠ {}", warning.message)))
+            .collect::<Vec<_>>();
+
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .title(" > Warnings ".bold())
+                    .padding(Padding::horizontal(1))
+                    .borders(Borders::all())
+                    .border_type(BorderType::Thick),
+            )
+            .yellow()
+            .render(area, buf);
     }
 
     fn render_download(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        if matches!(self.download.status(), Status::Pending | Status::Canceled) {
+        if matches!(
+            self.download.status(),
+            Status::Pending | Status::Canceled | Status::Err(_)
+        ) {
             self.render_not_ok(area, buf, " > Download ", self.download.status());
             return;
         }
 
         let block = Block::new()
             .title(" > Download ".bold())
-            .padding(Padding::uniform(1))
+            .padding(self.padding())
             .borders(Borders::all())
             .border_type(BorderType::Thick)
-            .border_style(Style::default().magenta());
+            .border_style(Style::default().fg(self.theme.download));
 
         let inner = block.inner(area);
-        let [summary_area, chart_area] =
-            Layout::horizontal([Constraint::Length(30), Constraint::Fill(1)]).areas(inner);
+        let [summary_area, chart_area] = Layout::horizontal([
+            Constraint::Length(self.summary_width()),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
 
         self.render_download_summary(summary_area, buf);
         self.render_download_chart(chart_area, buf);
@@ -95,21 +303,27 @@ impl App {
     }
 
     fn render_upload(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        if matches!(self.upload.status(), Status::Pending | Status::Canceled) {
+        if matches!(
+            self.upload.status(),
+            Status::Pending | Status::Canceled | Status::Err(_)
+        ) {
             self.render_not_ok(area, buf, " > Upload ", self.upload.status());
             return;
         }
 
         let block = Block::new()
             .title(" > Upload ".bold())
-            .padding(Padding::uniform(1))
+            .padding(self.padding())
             .borders(Borders::all())
             .border_type(BorderType::Thick)
-            .border_style(Style::default().cyan());
+            .border_style(Style::default().fg(self.theme.upload));
 
         let inner = block.inner(area);
-        let [summary_area, chart_area] =
-            Layout::horizontal([Constraint::Length(30), Constraint::Fill(1)]).areas(inner);
+        let [summary_area, chart_area] = Layout::horizontal([
+            Constraint::Length(self.summary_width()),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
 
         self.render_upload_summary(summary_area, buf);
         self.render_upload_chart(chart_area, buf);
@@ -140,7 +354,7 @@ impl App {
                     .block(
                         Block::new()
                             .title(" > Config ".bold())
-                            .padding(Padding::uniform(1))
+                            .padding(self.padding())
                             .borders(Borders::all())
                             .border_type(BorderType::Thick)
                             .border_style(Style::new().light_cyan()),
@@ -159,24 +373,66 @@ impl App {
                 } else {
                     None
                 };
+                // Once the race is over, list the servers by their latency.
+                let mut servers: Vec<&Server> = servers.iter().collect();
+                if !matches!(self.racing_servers.status(), Status::Start) {
+                    let leaderboard = self.race_leaderboard();
+                    servers.sort_by_key(|server| {
+                        leaderboard
+                            .iter()
+                            .position(|(id, _)| *id == server.id)
+                            .unwrap_or(usize::MAX)
+                    });
+                }
+
+                // Grouped, every sponsor is listed with its best server, or the selected one, and
+                // the number of servers hidden behind it.
+                let entries: Vec<(&Server, usize)> = if self.group_servers {
+                    let mut entries: Vec<(&Server, usize)> = vec![];
+                    for server in servers {
+                        match entries
+                            .iter_mut()
+                            .find(|(listed, _)| listed.same_sponsor(server))
+                        {
+                            Some(entry) => {
+                                entry.1 += 1;
+                                if fastest.is_some_and(|v| *v == server.url) {
+                                    entry.0 = server;
+                                }
+                            }
+                            None => entries.push((server, 0)),
+                        }
+                    }
+                    entries
+                } else {
+                    servers.into_iter().map(|server| (server, 0)).collect()
+                };
+
                 let mut rows = vec![];
-                for server in servers {
+                for (server, hidden) in entries {
+                    let name = if hidden > 0 {
+                        format!("{} (+{hidden})", server.name)
+                    } else {
+                        server.name.clone()
+                    };
                     if fastest.is_some_and(|v| *v == server.url) {
                         rows.insert(
                             0,
                             Row::new([
-                                Span::from("🚀").green(),
-                                Span::from(server.name.clone()).green(),
-                                Span::from(server.country.clone()).green(),
-                                Span::from(server.url.clone()).green(),
+                                Span::from("🚀").fg(self.theme.ok),
+                                Span::from(name).fg(self.theme.ok),
+                                Span::from(server.country.clone()).fg(self.theme.ok),
+                                Span::from(self.race_detail(server)).fg(self.theme.ok),
+                                Span::from(self.server_detail(server)).fg(self.theme.ok),
                             ]),
                         );
                     } else {
                         rows.push(Row::new([
                             Span::from(" "),
-                            Span::from(server.name.clone()),
+                            Span::from(name),
                             Span::from(server.country.clone()),
-                            Span::from(server.url.clone()),
+                            Span::from(self.race_detail(server)),
+                            Span::from(self.server_detail(server)),
                         ]));
                     }
                 }
@@ -191,6 +447,7 @@ impl App {
                             Constraint::Length(2),
                             Constraint::Length(15),
                             Constraint::Length(15),
+                            Constraint::Length(12),
                             Constraint::Fill(1),
                         ],
                     )
@@ -199,7 +456,8 @@ impl App {
                             Span::from(""),
                             Span::from("Name"),
                             Span::from("Country"),
-                            Span::from("URL"),
+                            Span::from("Latency"),
+                            Span::from(if self.compact { "Sponsor" } else { "URL" }),
                         ])
                         .yellow()
                         .bold(),
@@ -207,8 +465,15 @@ impl App {
                     .block(
                         Block::new()
                             .title(" > Servers ".bold())
-                            .title(Line::from(" Use j k or ▲ ▼  to scroll ").right_aligned())
-                            .padding(Padding::uniform(1))
+                            .title(
+                                Line::from(if self.group_servers {
+                                    " Use j k or ▲ ▼  to scroll, g to expand "
+                                } else {
+                                    " Use j k or ▲ ▼  to scroll, g to group "
+                                })
+                                .right_aligned(),
+                            )
+                            .padding(self.padding())
                             .borders(Borders::all())
                             .border_type(BorderType::Thick)
                             .border_style(Style::new().light_cyan()),
@@ -229,8 +494,125 @@ impl App {
         };
     }
 
+    /// Live probe state of a server during racing, e.g. `⠹ 23ms ×2`.
+    fn race_detail(&self, server: &Server) -> String {
+        let racing = matches!(self.racing_servers.status(), Status::Start);
+        let spinner = if racing {
+            SPINNER[self.ticks % SPINNER.len()]
+        } else {
+            ' '
+        };
+
+        match self.racing.get(&server.id) {
+            Some(stats) => match stats.avg_ms() {
+                Some(avg) => format!("{spinner} {avg:.0}ms ×{}", stats.attempts),
+                None => format!("{spinner} ✗ ×{}", stats.attempts),
+            },
+            // Raced as another entry of the same machine.
+            None => match self.speed_tester().duplicate_of(&server.id) {
+                Some(id) => format!("{spinner} = {id}"),
+                None if racing => spinner.to_string(),
+                None => String::new(),
+            },
+        }
+    }
+
+    fn padding(&self) -> Padding {
+        if self.compact {
+            Padding::ZERO
+        } else {
+            Padding::uniform(1)
+        }
+    }
+
+    fn summary_width(&self) -> u16 {
+        if self.compact { 24 } else { 30 }
+    }
+
+    /// Last servers table column, the full URL does not fit on narrow terminals.
+    fn server_detail(&self, server: &Server) -> String {
+        if self.compact {
+            server.sponsor.clone()
+        } else {
+            server.url.clone()
+        }
+    }
+
+    /// Idle baseline and latency under load, with the difference caused by the load.
+    fn latency_rows(&self, loaded: &Status<Option<Latency>>) -> [Row<'static>; 2] {
+        let idle = match self.idle_latency.status() {
+            Status::Ok(latency) => *latency,
+            _ => None,
+        };
+        let loaded = match loaded {
+            Status::Ok(latency) => *latency,
+            _ => None,
+        };
+
+        let idle_text = idle.map_or("-".to_string(), |l| format!("{:.1} ms", l.avg_ms));
+        let loaded_text = match (loaded, idle) {
+            (Some(loaded), Some(idle)) => {
+                format!("{:.1} ms ({:+.1})", loaded.avg_ms, loaded.delta_ms(&idle))
+            }
+            (Some(loaded), None) => format!("{:.1} ms", loaded.avg_ms),
+            (None, _) => "-".to_string(),
+        };
+
+        [
+            Row::new([Span::from("Idle").bold().yellow(), Span::from(idle_text)]),
+            Row::new([
+                Span::from("Loaded").bold().yellow(),
+                Span::from(loaded_text),
+            ]),
+        ]
+    }
+
     fn render_foot(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        Paragraph::new("Press 'q' / 'esc' / 'Ctrl + D' / 'Ctrl + C' to quit")
+        if let Some(input) = &self.note_input {
+            Paragraph::new(Line::from(vec![
+                Span::from("Note: ").bold(),
+                Span::from(format!("{input}▏")),
+                Span::from(" 'enter' save 'esc' cancel").gray(),
+            ]))
+            .centered()
+            .render(area, buf);
+            return;
+        }
+
+        if let Some(notice) = &self.notice {
+            Paragraph::new(notice.as_str())
+                .yellow()
+                .centered()
+                .render(area, buf);
+            return;
+        }
+
+        if let Some(result) = &self.result {
+            let action = if self.copied {
+                " ✓ copied 'n' note 'm' details"
+            } else {
+                " 'y' copy 'n' note 'm' details"
+            };
+            Paragraph::new(Line::from(vec![
+                Span::from(result.summary_line()).bold().fg(self.theme.ok),
+                Span::from(action).gray(),
+            ]))
+            .centered()
+            .render(area, buf);
+            return;
+        }
+
+        let help = if self.compact {
+            "'q' / 'esc' to quit"
+        } else {
+            "Press 'q' / 'esc' / 'Ctrl + D' / 'Ctrl + C' to quit"
+        };
+        let mut spans = vec![];
+        if let Some(estimate) = self.usage_estimate {
+            spans.push(Span::from(format!("≈ {} of data · ", estimate.humanize_bytes())).gray());
+        }
+        spans.push(Span::from(help));
+        Paragraph::new(Line::from(spans))
             .centered()
             .render(area, buf);
     }
@@ -244,7 +626,7 @@ impl App {
     ) {
         let block = Block::new()
             .title(title.bold())
-            .padding(Padding::uniform(1))
+            .padding(self.padding())
             .borders(Borders::all())
             .border_type(BorderType::Thick);
 
@@ -259,9 +641,10 @@ impl App {
                 .block(block.yellow())
                 .render(area, buf),
             Status::Err(e) => Paragraph::new(e.to_string())
-                .red()
+                .fg(self.theme.error)
                 .alignment(Alignment::Center)
-                .block(block.red())
+                .wrap(Wrap { trim: true })
+                .block(block.fg(self.theme.error))
                 .render(area, buf),
             Status::Canceled => Paragraph::new("Canceled")
                 .alignment(Alignment::Center)
@@ -292,9 +675,11 @@ impl App {
             ]),
             Row::new([Span::from("Avg").bold().yellow(), Span::from(avg_data)]),
             Row::new([Span::from("Total").bold().yellow(), Span::from(total)]),
-        ];
+        ]
+        .into_iter()
+        .chain(self.latency_rows(self.download.status()));
 
-        Table::new(rows, [Constraint::Length(10), Constraint::Length(20)]).render(area, buf);
+        Table::new(rows, [Constraint::Length(7), Constraint::Fill(1)]).render(area, buf);
     }
 
     fn render_download_chart(
@@ -302,39 +687,16 @@ impl App {
         area: ratatui::prelude::Rect,
         buf: &mut ratatui::prelude::Buffer,
     ) {
-        let min_data = self.min_download_byte_ps();
-        let max_data = self.max_download_byte_ps();
-
-        let (max_data_f64, unit) = max_data.humanize();
-        let min_data_f64 = min_data as f64 / unit as f64;
-
-        let min_bound = min_data_f64 * 0.9;
-        let max_bound = max_data_f64 * 1.1;
-
-        let min_bound_str = ((min_data as f64 * 0.9) as usize).humanize_bitrate(1000);
-        let max_bound_str = ((max_data as f64 * 1.1) as usize).humanize_bitrate(1000);
-
-        let render_data: Vec<(f64, f64)> = self
-            .downloaded_data
-            .iter()
-            .enumerate()
-            .map(|(idx, v)| (idx as f64, *v as f64 / unit as f64))
-            .collect();
-
-        let dataset = Dataset::default()
-            .marker(symbols::Marker::Braille)
-            .style(Style::new().fg(Color::Magenta))
-            .graph_type(ratatui::widgets::GraphType::Line)
-            .data(&render_data);
-
-        Chart::new(vec![dataset])
-            .x_axis(Axis::default().bounds([0.0, 19.0]))
-            .y_axis(
-                Axis::default()
-                    .bounds([min_bound, max_bound])
-                    .labels([min_bound_str, max_bound_str]),
-            )
-            .render(area, buf);
+        let series = Series {
+            samples: &self.downloaded_data,
+            interval: self.sample_intervals().0,
+            avg_byte_ps: self.avg_download_byte_ps(),
+            scale: self.download_scale,
+            color: self.theme.download,
+            marker: self.theme.download_marker,
+            graph: self.theme.download_graph,
+        };
+        series.render(area, buf);
     }
 
     fn render_upload_summary(
@@ -358,9 +720,11 @@ impl App {
             ]),
             Row::new([Span::from("Avg").bold().yellow(), Span::from(avg_data)]),
             Row::new([Span::from("Total").bold().yellow(), Span::from(total)]),
-        ];
+        ]
+        .into_iter()
+        .chain(self.latency_rows(self.upload.status()));
 
-        Table::new(rows, [Constraint::Length(10), Constraint::Length(20)]).render(area, buf);
+        Table::new(rows, [Constraint::Length(7), Constraint::Fill(1)]).render(area, buf);
     }
 
     fn render_upload_chart(
@@ -368,46 +732,23 @@ impl App {
         area: ratatui::prelude::Rect,
         buf: &mut ratatui::prelude::Buffer,
     ) {
-        let min_data = self.min_upload_byte_ps();
-        let max_data = self.max_upload_byte_ps();
-
-        let (max_data_f64, unit) = max_data.humanize();
-        let min_data_f64 = min_data as f64 / unit as f64;
-
-        let min_bound = min_data_f64 * 0.9;
-        let max_bound = max_data_f64 * 1.1;
-
-        let min_bound_str = ((min_data as f64 * 0.9) as usize).humanize_bitrate(1000);
-        let max_bound_str = ((max_data as f64 * 1.1) as usize).humanize_bitrate(1000);
-
-        let render_data: Vec<(f64, f64)> = self
-            .uploaded_data
-            .iter()
-            .enumerate()
-            .map(|(idx, v)| (idx as f64, *v as f64 / unit as f64))
-            .collect();
-
-        let dataset = Dataset::default()
-            .marker(symbols::Marker::Braille)
-            .style(Style::new().fg(Color::Cyan))
-            .graph_type(ratatui::widgets::GraphType::Line)
-            .data(&render_data);
-
-        Chart::new(vec![dataset])
-            .x_axis(Axis::default().bounds([0.0, 19.0]))
-            .y_axis(
-                Axis::default()
-                    .bounds([min_bound, max_bound])
-                    .labels([min_bound_str, max_bound_str]),
-            )
-            .render(area, buf);
+        let series = Series {
+            samples: &self.uploaded_data,
+            interval: self.sample_intervals().1,
+            avg_byte_ps: self.avg_upload_byte_ps(),
+            scale: self.upload_scale,
+            color: self.theme.upload,
+            marker: self.theme.upload_marker,
+            graph: self.theme.upload_graph,
+        };
+        series.render(area, buf);
     }
 }
 
-impl<T> From<&Progress<T>> for ListItem<'_> {
-    fn from(value: &Progress<T>) -> Self {
+impl App {
+    fn progress_item<T>(&self, value: &Progress<T>) -> ListItem<'static> {
         let elapsed = value.elapsed().as_secs_f32();
-        let line = match value.status() {
+        let mut line = match value.status() {
             Status::Start => Line::from(vec![
                 Span::raw(format!("⏳ {:<15} ........ ", value.name())).bold(),
                 Span::raw(format!("Elapsed: {elapsed:.1?}s")),
@@ -415,14 +756,14 @@ impl<T> From<&Progress<T>> for ListItem<'_> {
             Status::Err(e) => Line::from(vec![
                 Span::raw(format!("❌ {:<15} Failed! ", value.name()))
                     .bold()
-                    .red(),
+                    .fg(self.theme.error),
                 Span::raw(format!("Elapsed: {elapsed:.1?}s")),
-                Span::raw(format!(" > {e}")).red(),
+                Span::raw(format!(" > {e}")).fg(self.theme.error),
             ]),
             Status::Ok(_) => Line::from(vec![
                 Span::raw(format!("🎉 {:<15} Success! ", value.name()))
                     .bold()
-                    .green(),
+                    .fg(self.theme.ok),
                 Span::raw(format!("Elapsed: {elapsed:.1?}s")),
             ]),
             Status::Pending => Line::from(vec![
@@ -434,6 +775,138 @@ impl<T> From<&Progress<T>> for ListItem<'_> {
                     .bold(),
             ]),
         };
+        if self.show_timings {
+            line.push_span(Span::from(timings(value)).gray());
+        }
         ListItem::new(line)
     }
 }
+
+/// Throughput samples of one transfer, in bytes per second every `interval`.
+struct Series<'a> {
+    samples: &'a VecDeque<u64>,
+    interval: Duration,
+    avg_byte_ps: usize,
+    scale: AxisScale,
+    color: Color,
+    marker: Marker,
+    graph: GraphType,
+}
+
+impl Series<'_> {
+    fn render(&self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+        let avg_bps = self.avg_byte_ps as f64 * 8.0;
+
+        let (ticks, unit, suffix) = self.scale.ticks();
+        let (y_min, y_max) = (ticks[0], ticks[ticks.len() - 1]);
+
+        let x_max = x_window(self.samples.len(), self.interval);
+
+        let data: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(idx, v)| {
+                (
+                    idx as f64 * self.interval.as_secs_f64(),
+                    *v as f64 * 8.0 / unit,
+                )
+            })
+            .collect();
+
+        // Horizontal guides at the inner ticks, drawn first so the data stays on top.
+        let grid: Vec<[(f64, f64); 2]> = ticks[1..ticks.len() - 1]
+            .iter()
+            .map(|y| [(0.0, *y), (x_max, *y)])
+            .collect();
+        let avg = [(0.0, avg_bps / unit), (x_max, avg_bps / unit)];
+
+        let mut datasets: Vec<Dataset> = grid
+            .iter()
+            .map(|line| {
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .style(Style::new().fg(Color::DarkGray))
+                    .graph_type(GraphType::Line)
+                    .data(line)
+            })
+            .collect();
+        if !self.samples.is_empty() {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Dot)
+                    .style(Style::new().fg(self.color).dim())
+                    .graph_type(GraphType::Line)
+                    .data(&avg),
+            );
+        }
+        datasets.push(
+            Dataset::default()
+                .marker(self.marker)
+                .style(Style::new().fg(self.color))
+                .graph_type(self.graph)
+                .data(&data),
+        );
+
+        let last = ticks.len() - 1;
+        let y_labels: Vec<String> = ticks
+            .iter()
+            .enumerate()
+            .map(|(idx, tick)| {
+                if idx == last {
+                    format!("{tick} {suffix}")
+                } else {
+                    tick.to_string()
+                }
+            })
+            .collect();
+        let x_labels = [0.0, x_max / 2.0, x_max].map(|secs| format!("{secs}s"));
+
+        Chart::new(datasets)
+            .x_axis(
+                Axis::default()
+                    .bounds([0.0, x_max])
+                    .labels(x_labels)
+                    .style(Style::new().dark_gray()),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([y_min, y_max])
+                    .labels(y_labels)
+                    .style(Style::new().dark_gray()),
+            )
+            .render(area, buf);
+    }
+}
+
+/// Local start and finish time of a stage, e.g. `  12:03:04.120 → 12:03:06.480`.
+fn timings<T>(progress: &Progress<T>) -> String {
+    let time = |at: DateTime<Utc>| at.with_timezone(&Local).format("%H:%M:%S%.3f").to_string();
+    match (progress.started_at(), progress.finished_at()) {
+        (Some(start), Some(end)) => format!("  {} → {}", time(start), time(end)),
+        (Some(start), None) => format!("  {} →", time(start)),
+        (None, _) => String::new(),
+    }
+}
+
+/// Seconds covered by the x-axis, at least [`MAX_RECORDS_LEN`] samples and rounded up to 5s.
+fn x_window(samples: usize, interval: Duration) -> f64 {
+    let secs = samples.max(MAX_RECORDS_LEN) as f64 * interval.as_secs_f64();
+    (secs / 5.0).ceil() * 5.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::ui::x_window;
+
+    #[test]
+    fn test_x_window() {
+        let interval = Duration::from_millis(500);
+        assert_eq!(x_window(0, interval), 10.0);
+        assert_eq!(x_window(23, interval), 15.0);
+        // A quick 5s test sampled every 100ms.
+        assert_eq!(x_window(50, Duration::from_millis(100)), 5.0);
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use speedtest_rs_core::speed_tester::SpeedTester;
+
+/// Latency-only monitor, composed from the individual stages.
+#[tokio::main]
+pub async fn main() {
+    let speed_tester = SpeedTester::default();
+
+    let discovery = speed_tester.discover().await.expect("discover failed");
+    let selection = speed_tester
+        .pick_server(&discovery)
+        .await
+        .expect("pick server failed");
+
+    println!(
+        "Server: {} ({})",
+        selection.server.sponsor, selection.server.name
+    );
+
+    loop {
+        match speed_tester
+            .measure_latency(&selection, Duration::from_secs(5))
+            .await
+        {
+            Some(latency) => println!(
+                "avg: {:.1}ms | jitter: {:.1}ms | lost: {}/{}",
+                latency.avg_ms,
+                latency.jitter_ms,
+                latency.lost,
+                latency.samples + latency.lost
+            ),
+            None => println!("server unreachable"),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! CPU time of the current process.
+//!
+//! Only Linux/Android (`/proc/self/stat`) is supported, like the interface counters.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+pub use speedtest_rs_types::result::CPU_BOUND_THRESHOLD;
+
+/// Unit of the `/proc` times, `USER_HZ` is 100 on every mainstream Linux build.
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+/// User and system CPU time consumed by the process so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuTime(pub Duration);
+
+impl CpuTime {
+    pub fn read() -> Option<Self> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let raw = std::fs::read_to_string("/proc/self/stat").ok()?;
+            Self::parse_proc_stat(&raw)
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            None
+        }
+    }
+
+    pub(crate) fn parse_proc_stat(raw: &str) -> Option<Self> {
+        // `pid (comm) state ...`, the command may contain spaces and parentheses.
+        let (_, stats) = raw.rsplit_once(')')?;
+        let fields: Vec<&str> = stats.split_whitespace().collect();
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        Some(Self(Duration::from_millis(
+            (utime + stime) * 1000 / CLOCK_TICKS_PER_SEC,
+        )))
+    }
+
+    /// Share of the CPU time of all cores used since `before`, over `wall` elapsed time.
+    pub fn usage_since(&self, before: &Self, wall: Duration) -> f64 {
+        let cores = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let available = wall.as_secs_f64() * cores as f64;
+        if available > 0.0 {
+            self.0.saturating_sub(before.0).as_secs_f64() / available
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::cpu::CpuTime;
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let raw = "4242 (speedtest (rs)) S 1 4242 4242 34816 4242 4194304 1200 0 0 0 250 50 0 0 \
+                   20 0 6 0 100 0 0";
+        assert_eq!(
+            CpuTime::parse_proc_stat(raw),
+            Some(CpuTime(Duration::from_secs(3)))
+        );
+        assert_eq!(CpuTime::parse_proc_stat("4242 (speedtest) S 1"), None);
+
+        let before = CpuTime(Duration::from_secs(1));
+        let usage = CpuTime(Duration::from_secs(1)).usage_since(&before, Duration::from_secs(1));
+        assert_eq!(usage, 0.0);
+    }
+}
@@ -0,0 +1,546 @@
+//! Name resolution of [`SpeedTester`](crate::speed_tester::SpeedTester): the system resolver, a
+//! DNS server of choice over UDP, or DNS over HTTPS (RFC 8484), restricted to an address family
+//! and timed per host.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    time::Instant,
+};
+
+use crate::{
+    error::{Error, Result},
+    http::{
+        HttpClient, HttpRequest,
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, HeaderValue},
+    },
+    net::{HostResolution, IpFamily},
+};
+
+/// Time a DNS server has to answer a query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Media type of DNS messages over HTTPS.
+const DNS_MESSAGE: &str = "application/dns-message";
+
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+
+/// Largest DNS answer over UDP without EDNS.
+const MAX_UDP_ANSWER: usize = 512;
+
+/// Resolves the host names of the servers. Other stacks such as hickory-resolver plug in with
+/// [`SpeedTester::with_resolver`](crate::speed_tester::SpeedTester::with_resolver).
+pub trait Resolver: fmt::Debug + Send + Sync {
+    /// Addresses of `host` of both families, in the order connections should try them.
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Vec<IpAddr>>>;
+}
+
+/// The resolver of the operating system, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Vec<IpAddr>>> {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host, 0)).await?;
+            Ok(addrs.map(|addr| addr.ip()).collect())
+        })
+    }
+}
+
+/// Asks a DNS server of choice over UDP, e.g. `1.1.1.1:53`, and over TCP if the answer does not
+/// fit into a datagram. IPv4 addresses come first.
+#[derive(Debug, Clone, Copy)]
+pub struct UdpResolver {
+    server: SocketAddr,
+}
+
+impl UdpResolver {
+    pub fn new(server: SocketAddr) -> Self {
+        Self { server }
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> Result<Vec<IpAddr>> {
+        let local: SocketAddr = match self.server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(self.server).await?;
+
+        let id = rand::random();
+        let message = query(id, host, record_type)?;
+        socket.send(&message).await?;
+        let mut buf = [0; MAX_UDP_ANSWER];
+        let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| Error::Dns(format!("{} did not answer", self.server)))??;
+        if !is_truncated(&buf[..len]) {
+            return parse_answer(id, host, &buf[..len]);
+        }
+
+        let answer = tokio::time::timeout(QUERY_TIMEOUT, self.query_tcp(&message))
+            .await
+            .map_err(|_| Error::Dns(format!("{} did not answer over TCP", self.server)))??;
+        parse_answer(id, host, &answer)
+    }
+
+    /// Sends `message` over TCP, each way prefixed with its length.
+    async fn query_tcp(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(self.server).await?;
+        let len = u16::try_from(message.len())
+            .map_err(|_| Error::Dns("DNS query too long".to_string()))?;
+        stream.write_all(&len.to_be_bytes()).await?;
+        stream.write_all(message).await?;
+
+        let mut len = [0; 2];
+        stream.read_exact(&mut len).await?;
+        let mut answer = vec![0; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut answer).await?;
+        Ok(answer)
+    }
+}
+
+impl Resolver for UdpResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Vec<IpAddr>>> {
+        Box::pin(async move {
+            let (v4, v6) = futures::join!(self.query(host, TYPE_A), self.query(host, TYPE_AAAA));
+            merge_answers(v4, v6)
+        })
+    }
+}
+
+/// DNS over HTTPS with a server of choice, e.g. `https://cloudflare-dns.com/dns-query`, for
+/// networks that tamper with plain DNS. IPv4 addresses come first.
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    url: String,
+    client: Arc<dyn HttpClient>,
+}
+
+impl DohResolver {
+    /// Sends the queries with a reqwest client of its own, which looks up the DoH server with the
+    /// system resolver.
+    #[cfg(feature = "reqwest")]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::new_with_http_client(url, reqwest::Client::new())
+    }
+
+    pub fn new_with_http_client(url: impl Into<String>, client: impl HttpClient + 'static) -> Self {
+        Self {
+            url: url.into(),
+            client: Arc::new(client),
+        }
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> Result<Vec<IpAddr>> {
+        // An id of zero keeps the answers cacheable by HTTP caches, as RFC 8484 recommends.
+        let message = Bytes::from(query(0, host, record_type)?);
+        let request = HttpRequest::post(self.url.clone())
+            .with_timeout(QUERY_TIMEOUT)
+            .with_header(CONTENT_TYPE, HeaderValue::from_static(DNS_MESSAGE))
+            .with_header(ACCEPT, HeaderValue::from_static(DNS_MESSAGE))
+            .with_header(CONTENT_LENGTH, HeaderValue::from(message.len()))
+            .with_body(Box::pin(stream::iter([Ok(message)])));
+
+        let resp = self.client.send(request).await?;
+        if !resp.status.is_success() {
+            return Err(Error::Dns(format!("{} answered {}", self.url, resp.status)));
+        }
+        parse_answer(0, host, &resp.bytes().await?)
+    }
+}
+
+impl Resolver for DohResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Vec<IpAddr>>> {
+        Box::pin(async move {
+            let (v4, v6) = futures::join!(self.query(host, TYPE_A), self.query(host, TYPE_AAAA));
+            merge_answers(v4, v6)
+        })
+    }
+}
+
+/// The lookups of a tester: a [`Resolver`] restricted to an address family, timing the first
+/// lookup of every host for the results. Clones share the timings.
+#[derive(Debug, Clone)]
+pub struct Dns {
+    resolver: Arc<dyn Resolver>,
+    family: Option<IpFamily>,
+    resolutions: Arc<Mutex<BTreeMap<String, HostResolution>>>,
+}
+
+impl Default for Dns {
+    fn default() -> Self {
+        Self::new(SystemResolver)
+    }
+}
+
+impl Dns {
+    pub fn new(resolver: impl Resolver + 'static) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            family: None,
+            resolutions: Arc::default(),
+        }
+    }
+
+    /// Keeps the addresses of `family` only, so connections never fall back to the other.
+    pub fn with_family(mut self, family: Option<IpFamily>) -> Self {
+        self.family = family;
+        self
+    }
+
+    pub fn family(&self) -> Option<IpFamily> {
+        self.family
+    }
+
+    /// Addresses of `host` to connect to on `port`. Addresses in place of the name are used as
+    /// they are. Fails if none of the family is left.
+    pub async fn lookup(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let ips = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => {
+                let start = Instant::now();
+                let resolved = self.resolver.resolve(host).await;
+                let resolution = HostResolution::new(
+                    host,
+                    start.elapsed(),
+                    resolved.as_deref().unwrap_or_default(),
+                );
+                self.resolutions
+                    .lock()
+                    .unwrap()
+                    .entry(host.to_string())
+                    .or_insert(resolution);
+                resolved?
+            }
+        };
+
+        let addrs: Vec<_> = ips
+            .into_iter()
+            .filter(|ip| self.family.is_none_or(|family| IpFamily::of(*ip) == family))
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+        match self.family {
+            _ if !addrs.is_empty() => Ok(addrs),
+            Some(family) => Err(Error::Dns(format!("{host} has no {family} address"))),
+            None => Err(Error::Dns(format!("{host} has no address"))),
+        }
+    }
+
+    /// First lookup of every host so far, by host name.
+    pub fn resolutions(&self) -> Vec<HostResolution> {
+        self.resolutions.lock().unwrap().values().cloned().collect()
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl reqwest::dns::Resolve for Dns {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let dns = self.clone();
+        Box::pin(async move {
+            // The connector sets the port of the URL.
+            let addrs = dns.lookup(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The answers of the A and AAAA queries, failing only if both failed.
+fn merge_answers(v4: Result<Vec<IpAddr>>, v6: Result<Vec<IpAddr>>) -> Result<Vec<IpAddr>> {
+    match (v4, v6) {
+        (Err(e), Err(_)) => Err(e),
+        (v4, v6) => Ok(v4
+            .unwrap_or_default()
+            .into_iter()
+            .chain(v6.unwrap_or_default())
+            .collect()),
+    }
+}
+
+/// A recursive query for the records of `record_type` of `host`.
+fn query(id: u16, host: &str, record_type: u16) -> Result<Vec<u8>> {
+    let mut message = Vec::with_capacity(18 + host.len());
+    message.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question.
+    message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(Error::Dns(format!("invalid host name '{host}'")));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&record_type.to_be_bytes());
+    // Class IN.
+    message.extend_from_slice(&[0, 1]);
+    Ok(message)
+}
+
+/// Whether the server cut the answer short to fit it into a datagram (the TC bit).
+fn is_truncated(message: &[u8]) -> bool {
+    message.get(2).is_some_and(|flags| flags & 0x02 != 0)
+}
+
+/// The addresses of the answer to query `id` for `host`. Names that do not exist have no
+/// addresses. Only records of `host` and of the names it is a CNAME of count, others could be
+/// planted by a spoofed answer.
+fn parse_answer(id: u16, host: &str, message: &[u8]) -> Result<Vec<IpAddr>> {
+    let truncated = || Error::Dns("truncated DNS answer".to_string());
+    let header = message.get(..12).ok_or_else(truncated)?;
+    if u16::from_be_bytes([header[0], header[1]]) != id || header[2] & 0x80 == 0 {
+        return Err(Error::Dns("unexpected DNS answer".to_string()));
+    }
+    if is_truncated(message) {
+        return Err(truncated());
+    }
+    match header[3] & 0x0f {
+        0 => {}
+        // NXDOMAIN
+        3 => return Ok(vec![]),
+        rcode => return Err(Error::Dns(format!("DNS server failed with rcode {rcode}"))),
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+
+    let host = normalize(host);
+    let mut pos = 12;
+    for _ in 0..questions {
+        let (name, end) = read_name(message, pos).ok_or_else(truncated)?;
+        if name != host {
+            return Err(Error::Dns(format!(
+                "DNS answer for {name} instead of {host}"
+            )));
+        }
+        // Type and class follow the name.
+        pos = end + 4;
+    }
+
+    let mut records = vec![];
+    for _ in 0..answers {
+        let (owner, end) = read_name(message, pos).ok_or_else(truncated)?;
+        let record = message.get(end..end + 10).ok_or_else(truncated)?;
+        let record_type = u16::from_be_bytes([record[0], record[1]]);
+        let len = u16::from_be_bytes([record[8], record[9]]) as usize;
+        let data = end + 10..end + 10 + len;
+        message.get(data.clone()).ok_or_else(truncated)?;
+        records.push((owner, record_type, data));
+        pos = end + 10 + len;
+    }
+
+    // Follow the CNAME chain from the host, whatever the order of the records.
+    let mut names = HashSet::from([host]);
+    loop {
+        let aliases: Vec<String> = records
+            .iter()
+            .filter(|(owner, record_type, _)| *record_type == TYPE_CNAME && names.contains(owner))
+            .filter_map(|(_, _, data)| read_name(message, data.start).map(|(name, _)| name))
+            .filter(|name| !names.contains(name))
+            .collect();
+        if aliases.is_empty() {
+            break;
+        }
+        names.extend(aliases);
+    }
+
+    let mut addrs = vec![];
+    for (owner, record_type, data) in records {
+        if !names.contains(&owner) {
+            continue;
+        }
+        let data = &message[data];
+        match record_type {
+            TYPE_A => addrs.extend(<[u8; 4]>::try_from(data).ok().map(IpAddr::from)),
+            TYPE_AAAA => addrs.extend(<[u8; 16]>::try_from(data).ok().map(IpAddr::from)),
+            _ => {}
+        }
+    }
+    Ok(addrs)
+}
+
+/// Host names compare without case and trailing dot.
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// The name at `pos` and the position after it, following compression pointers.
+fn read_name(message: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = vec![];
+    let mut end = None;
+    // Pointers only point backwards in valid messages, the limit stops loops in invalid ones.
+    for _ in 0..128 {
+        match *message.get(pos)? as usize {
+            0 => {
+                let name = normalize(&labels.join("."));
+                return Some((name, end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let target = (len & 0x3f) << 8 | *message.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            len => {
+                let label = message.get(pos + 1..pos + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv6Addr};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, UdpSocket},
+    };
+
+    use crate::{
+        dns::{Dns, TYPE_A, TYPE_AAAA, UdpResolver, parse_answer, query},
+        net::IpFamily,
+    };
+
+    const HOST: &str = "speedtest.example.com";
+
+    /// The answer to `query` as a resolver sends it: a CNAME of the name, then the record of the
+    /// type asked for of the CNAME target, with the names compressed.
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let mut message = query.to_vec();
+        message[2] |= 0x80;
+        message[7] = 2;
+        message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 6]);
+        let target = message.len() as u8;
+        message.extend_from_slice(&[3, b'c', b'd', b'n', 0xc0, 12]);
+
+        let record_type = query[query.len() - 3];
+        let address = match record_type as u16 {
+            TYPE_A => vec![192, 0, 2, 1],
+            _ => "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets().to_vec(),
+        };
+        message.extend_from_slice(&[0xc0, target, 0, record_type, 0, 1, 0, 0, 0, 60, 0]);
+        message.push(address.len() as u8);
+        message.extend_from_slice(&address);
+        message
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        let query = query(7, "speedtest.example.com.", TYPE_A).unwrap();
+        assert_eq!(&query[12..30], b"\x09speedtest\x07example");
+        assert_eq!(&query[query.len() - 4..], [0, 1, 0, 1]);
+        assert!(super::query(7, "bad..name", TYPE_A).is_err());
+
+        let addrs = parse_answer(7, HOST, &answer(&query)).unwrap();
+        assert_eq!(addrs, ["192.0.2.1".parse::<IpAddr>().unwrap()]);
+        let aaaa = super::query(7, "speedtest.example.com", TYPE_AAAA).unwrap();
+        let addrs = parse_answer(7, HOST, &answer(&aaaa)).unwrap();
+        assert_eq!(addrs, ["2001:db8::1".parse::<IpAddr>().unwrap()]);
+        assert!(parse_answer(8, HOST, &answer(&query)).is_err());
+        assert!(parse_answer(7, HOST, &answer(&query)[..40]).is_err());
+
+        let mut nxdomain = query.clone();
+        nxdomain[2] |= 0x80;
+        nxdomain[3] = 3;
+        assert!(parse_answer(7, HOST, &nxdomain).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_answer_names() {
+        let query = query(7, HOST, TYPE_A).unwrap();
+        assert_eq!(
+            parse_answer(7, "SpeedTest.Example.com.", &answer(&query)).unwrap(),
+            ["192.0.2.1".parse::<IpAddr>().unwrap()]
+        );
+        let e = parse_answer(7, "other.example.com", &answer(&query)).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "DNS answer for speedtest.example.com instead of other.example.com"
+        );
+
+        // A record of a name outside of the CNAME chain is ignored.
+        let mut planted = query.clone();
+        planted[2] |= 0x80;
+        planted[7] = 1;
+        planted.extend_from_slice(b"\x04evil\x07example\x00");
+        planted.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 66]);
+        assert!(parse_answer(7, HOST, &planted).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_udp_resolver() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = server.recv_from(&mut buf).await.unwrap();
+                server.send_to(&answer(&buf[..len]), peer).await.unwrap();
+            }
+        });
+
+        let dns = Dns::new(UdpResolver::new(address));
+        let addrs = dns.lookup("speedtest.example.com", 8080).await.unwrap();
+        assert_eq!(addrs[0], "192.0.2.1:8080".parse().unwrap());
+
+        let v6 = dns.clone().with_family(Some(IpFamily::V6));
+        let addrs = v6.lookup("speedtest.example.com", 443).await.unwrap();
+        assert_eq!(addrs, ["[2001:db8::1]:443".parse().unwrap()]);
+
+        // Addresses skip the resolver and are not timed.
+        let e = v6.lookup("127.0.0.1", 80).await.unwrap_err();
+        assert_eq!(e.to_string(), "127.0.0.1 has no IPv6 address");
+
+        let resolutions = dns.resolutions();
+        assert_eq!(resolutions.len(), 1);
+        assert_eq!(resolutions[0].host, "speedtest.example.com");
+        assert_eq!(resolutions[0].addresses, ["192.0.2.1", "2001:db8::1"]);
+    }
+
+    #[tokio::test]
+    async fn test_udp_resolver_truncated() {
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = tcp.local_addr().unwrap();
+        let udp = UdpSocket::bind(address).await.unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = udp.recv_from(&mut buf).await.unwrap();
+                let mut truncated = buf[..len].to_vec();
+                truncated[2] |= 0x82;
+                udp.send_to(&truncated, peer).await.unwrap();
+            }
+        });
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = tcp.accept().await.unwrap();
+                let len = stream.read_u16().await.unwrap();
+                let mut query = vec![0; len as usize];
+                stream.read_exact(&mut query).await.unwrap();
+                let answer = answer(&query);
+                stream.write_u16(answer.len() as u16).await.unwrap();
+                stream.write_all(&answer).await.unwrap();
+            }
+        });
+
+        let dns = Dns::new(UdpResolver::new(address));
+        let addrs = dns.lookup(HOST, 80).await.unwrap();
+        assert_eq!(addrs[0], "192.0.2.1:80".parse().unwrap());
+    }
+}
@@ -0,0 +1,166 @@
+//! The error of the core API. The checks before a run fail with typed causes, so frontends can tell
+//! the user what to fix instead of matching on messages.
+
+use std::fmt;
+
+pub use speedtest_rs_types::error::{
+    CaptivePortal, FetchFailed, FetchFailure, NoConnectivity, ParseError,
+};
+
+use crate::http::{HttpError, StatusCode};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The run was cancelled, see
+    /// [`SpeedTester::with_cancellation`](crate::speed_tester::SpeedTester::with_cancellation).
+    Canceled,
+    NoConnectivity(NoConnectivity),
+    CaptivePortal(CaptivePortal),
+    /// Every attempt of a discovery fetch failed.
+    FetchFailed(FetchFailed),
+    /// A request failed before the server answered.
+    Http(HttpError),
+    /// The server answered with an error status.
+    Status(StatusCode),
+    /// An answer, argument or config value could not be parsed.
+    Parse(String),
+    /// No server is left to test against, e.g. none matches the filter.
+    NoServers(String),
+    /// A host name did not resolve, or the DNS server answered something unusable.
+    Dns(String),
+    Io(std::io::Error),
+    /// Anything else, e.g. calling the methods of the tester in the wrong order.
+    Other(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Canceled => f.write_str("canceled"),
+            Error::NoConnectivity(e) => e.fmt(f),
+            Error::CaptivePortal(e) => e.fmt(f),
+            Error::FetchFailed(e) => e.fmt(f),
+            Error::Http(e) => e.fmt(f),
+            Error::Status(status) => write!(f, "status: {status}"),
+            Error::Parse(message)
+            | Error::NoServers(message)
+            | Error::Dns(message)
+            | Error::Other(message) => f.write_str(message),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NoConnectivity(e) => Some(e),
+            Error::CaptivePortal(e) => Some(e),
+            Error::FetchFailed(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<NoConnectivity> for Error {
+    fn from(e: NoConnectivity) -> Self {
+        Error::NoConnectivity(e)
+    }
+}
+
+impl From<CaptivePortal> for Error {
+    fn from(e: CaptivePortal) -> Self {
+        Error::CaptivePortal(e)
+    }
+}
+
+impl From<FetchFailed> for Error {
+    fn from(e: FetchFailed) -> Self {
+        Error::FetchFailed(e)
+    }
+}
+
+impl From<HttpError> for Error {
+    fn from(e: HttpError) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<quick_xml::DeError> for Error {
+    fn from(e: quick_xml::DeError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+#[cfg(feature = "ndt7")]
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::Other(e.to_string())
+    }
+}
+
+/// Lets [`Resolver`](crate::dns::Resolver) implementations built on anyhow use `?`.
+#[cfg(feature = "anyhow")]
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        Error::Other(format!("{e:#}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use crate::{
+        error::{Error, NoConnectivity, ParseError},
+        http::StatusCode,
+    };
+
+    #[test]
+    fn test_error() {
+        let offline = NoConnectivity {
+            reason: "cannot resolve example.com".to_string(),
+        };
+        let e = Error::from(offline.clone());
+        assert_eq!(e.to_string(), offline.to_string());
+        assert!(e.source().is_some());
+        assert!(matches!(e, Error::NoConnectivity(_)));
+
+        assert_eq!(
+            Error::Status(StatusCode::SERVICE_UNAVAILABLE).to_string(),
+            "status: 503 Service Unavailable"
+        );
+        let e = Error::from(ParseError::new("invalid size '1X'"));
+        assert_eq!(e.to_string(), "invalid size '1X'");
+        assert_eq!(Error::Canceled.to_string(), "canceled");
+    }
+}
@@ -0,0 +1,233 @@
+//! The HTTP transport of [`SpeedTester`](crate::speed_tester::SpeedTester).
+//!
+//! Requests go through the [`HttpClient`] trait, implemented for `reqwest::Client` with the
+//! default `reqwest` feature. Other stacks such as hyper or isahc, or a client replaying recorded
+//! responses in tests, plug in with
+//! [`SpeedTester::new_with_http_client`](crate::speed_tester::SpeedTester::new_with_http_client).
+
+use std::{fmt, pin::Pin, time::Duration};
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt, future::BoxFuture};
+
+pub use http::{HeaderMap, Method, StatusCode, Version, header};
+pub use url::Url;
+
+/// Request or response body, sent and received chunk by chunk.
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, HttpError>> + Send>>;
+
+/// Sends requests for the tests, following redirects.
+pub trait HttpClient: fmt::Debug + Send + Sync {
+    /// Resolves once the status and headers arrived, the body is read from the response.
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>>;
+}
+
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    /// Limit of the whole request including the body, `None` for transfers ended by the caller.
+    pub timeout: Option<Duration>,
+    pub body: Option<BodyStream>,
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            timeout: None,
+            body: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub fn head(url: impl Into<String>) -> Self {
+        Self::new(Method::HEAD, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_header(mut self, name: header::HeaderName, value: header::HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: BodyStream) -> Self {
+        self.body = Some(body);
+        self
+    }
+}
+
+impl fmt::Debug for HttpRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpRequest")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .field("timeout", &self.timeout)
+            .field("body", &self.body.is_some())
+            .finish()
+    }
+}
+
+pub struct HttpResponse {
+    pub status: StatusCode,
+    /// Protocol the response came over, which tells whether the transfers were multiplexed.
+    pub version: Version,
+    /// URL the response came from after following redirects.
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: BodyStream,
+}
+
+impl HttpResponse {
+    /// Value of a header, `None` if it is missing or not valid text.
+    pub fn header(&self, name: header::HeaderName) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// The next chunk of the body, `None` at the end.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, HttpError> {
+        self.body.next().await.transpose()
+    }
+
+    /// Reads the whole body.
+    pub async fn bytes(mut self) -> Result<Bytes, HttpError> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes.into())
+    }
+
+    pub async fn text(self) -> Result<String, HttpError> {
+        let bytes = self.bytes().await?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("version", &self.version)
+            .field("url", &self.url.as_str())
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// HTTP versions the transfers may use with the built-in client, see
+/// [`SpeedTester::with_http_version`](crate::speed_tester::SpeedTester::with_http_version).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/1.1 only, every concurrent transfer on a connection of its own.
+    #[default]
+    Http1,
+    /// HTTP/2 where the server offers it over TLS, multiplexing the concurrent transfers to a
+    /// host over one connection. Plain `http` URLs stay on HTTP/1.1.
+    Http2,
+}
+
+/// Why a request failed, coarse enough for the error summaries of a phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HttpErrorKind {
+    Timeout,
+    /// The connection could not be established, including DNS and TLS failures.
+    Connect,
+    /// The connection broke while the body was transferred.
+    Body,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    kind: HttpErrorKind,
+    message: String,
+}
+
+impl HttpError {
+    pub fn new(kind: HttpErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn kind(&self) -> HttpErrorKind {
+        self.kind
+    }
+
+    /// Short, stable description used to group the errors of a phase.
+    pub fn cause(&self) -> &'static str {
+        match self.kind {
+            HttpErrorKind::Timeout => "timed out",
+            HttpErrorKind::Connect => "connection failed",
+            HttpErrorKind::Body => "connection interrupted",
+            HttpErrorKind::Other => "request failed",
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for HttpError {
+    fn from(e: reqwest::Error) -> Self {
+        let kind = if e.is_timeout() {
+            HttpErrorKind::Timeout
+        } else if e.is_connect() {
+            HttpErrorKind::Connect
+        } else if e.is_body() || e.is_decode() {
+            HttpErrorKind::Body
+        } else {
+            HttpErrorKind::Other
+        };
+        Self::new(kind, e.to_string())
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpClient for reqwest::Client {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>> {
+        let mut builder = self
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(reqwest::Body::wrap_stream(body));
+        }
+
+        Box::pin(async move {
+            let resp = builder.send().await?;
+            Ok(HttpResponse {
+                status: resp.status(),
+                version: resp.version(),
+                url: resp.url().clone(),
+                headers: resp.headers().clone(),
+                body: Box::pin(resp.bytes_stream().map(|chunk| Ok(chunk?))),
+            })
+        })
+    }
+}
@@ -0,0 +1,183 @@
+//! Operating system network interface counters.
+//!
+//! Only Linux/Android (`/proc/net/dev`) is supported, other platforms and sandboxes without
+//! `/proc` report no counters rather than failing.
+
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use tokio::time::Instant;
+
+pub use speedtest_rs_types::ifstats::{
+    CONTENDED_TAG, CONTENDED_THRESHOLD_BPS, COUNTER_DISCREPANCY_THRESHOLD, CounterCheck,
+    IdleTraffic, LINK_CEILING_RATIO, LinkInfo, LinkKind,
+};
+
+/// Byte counters summed over all non-loopback interfaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl InterfaceCounters {
+    pub fn read() -> Option<Self> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let raw = std::fs::read_to_string("/proc/net/dev").ok()?;
+            Self::parse_proc_net_dev(&raw)
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            None
+        }
+    }
+
+    pub(crate) fn parse_proc_net_dev(raw: &str) -> Option<Self> {
+        let mut counters = Self::default();
+        let mut found = false;
+
+        // Two header lines, then `iface: rx_bytes rx_packets ... tx_bytes ...`.
+        for line in raw.lines().skip(2) {
+            let Some((name, stats)) = line.split_once(':') else {
+                continue;
+            };
+            if name.trim() == "lo" {
+                continue;
+            }
+
+            let fields: Vec<u64> = stats
+                .split_whitespace()
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            if fields.len() < 9 {
+                continue;
+            }
+
+            counters.rx_bytes += fields[0];
+            counters.tx_bytes += fields[8];
+            found = true;
+        }
+
+        found.then_some(counters)
+    }
+
+    pub fn delta(&self, earlier: &Self) -> Self {
+        Self {
+            rx_bytes: self.rx_bytes.saturating_sub(earlier.rx_bytes),
+            tx_bytes: self.tx_bytes.saturating_sub(earlier.tx_bytes),
+        }
+    }
+}
+
+/// Detects the link speed from sysfs. Most wireless interfaces and virtual devices do not
+/// report a speed and yield `None`.
+pub fn detect_link() -> Option<LinkInfo> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+        let interface = parse_default_route(&routes)?;
+        let sysfs = std::path::Path::new("/sys/class/net").join(&interface);
+        let speed = std::fs::read_to_string(sysfs.join("speed")).ok()?;
+        let speed_mbps = speed.trim().parse::<i64>().ok().filter(|v| *v > 0)? as u64;
+        let kind = link_kind(
+            sysfs.join("wireless").exists(),
+            sysfs.join("device").exists(),
+            std::fs::read_to_string(sysfs.join("type")).ok().as_deref(),
+        );
+
+        Some(LinkInfo::new(interface, speed_mbps).with_kind(kind))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        None
+    }
+}
+
+/// Tells the medium from the sysfs entries of an interface: a `wireless` directory, a backing
+/// `device` and the ARP hardware `type`, where `1` is Ethernet. Virtual devices like bridges and
+/// veths claim Ethernet as well but have no device, so they stay unknown.
+fn link_kind(wireless: bool, device: bool, arp_type: Option<&str>) -> Option<LinkKind> {
+    if wireless {
+        Some(LinkKind::Wireless)
+    } else if device && arp_type.map(str::trim) == Some("1") {
+        Some(LinkKind::Ethernet)
+    } else {
+        None
+    }
+}
+
+/// Returns the interface of the default route from `/proc/net/route`.
+fn parse_default_route(raw: &str) -> Option<String> {
+    raw.lines().skip(1).find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let iface = fields.next()?;
+        let destination = fields.next()?;
+        (destination == "00000000").then(|| iface.to_string())
+    })
+}
+
+/// Samples the interface counters for `duration`. Returns `None` when `/proc` is not available,
+/// e.g. in a sandbox. Linux only: Android restricts the counters of other apps, so the traffic
+/// they cause would go unnoticed.
+#[cfg(target_os = "linux")]
+pub async fn sample_idle_traffic(duration: Duration) -> Option<IdleTraffic> {
+    let before = InterfaceCounters::read()?;
+    let start = Instant::now();
+
+    tokio::time::sleep(duration).await;
+
+    let delta = InterfaceCounters::read()?.delta(&before);
+    let secs = start.elapsed().as_secs_f64();
+
+    Some(IdleTraffic::new(
+        delta.rx_bytes as f64 * 8.0 / secs,
+        delta.tx_bytes as f64 * 8.0 / secs,
+        start.elapsed().as_millis() as u64,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifstats::{InterfaceCounters, LinkKind, link_kind, parse_default_route};
+
+    const RAW_PROC_NET_DEV: &str = r#"Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  123456     100    0    0    0     0          0         0   123456     100    0    0    0     0       0          0
+  eth0: 1000000    2000    0    0    0     0          0         0   500000    1500    0    0    0     0       0          0
+ wlan0:    2000      20    0    0    0     0          0         0     3000      30    0    0    0     0       0          0
+"#;
+
+    #[test]
+    fn test_parse_default_route() {
+        let raw_route = "Iface\tDestination\tGateway\tFlags\n\
+                         eth0\t000200C0\t00000000\t0001\n\
+                         eth1\t00000000\t010200C0\t0003\n";
+        assert_eq!(parse_default_route(raw_route).as_deref(), Some("eth1"));
+    }
+
+    #[test]
+    fn test_link_kind() {
+        assert_eq!(link_kind(true, true, Some("1\n")), Some(LinkKind::Wireless));
+        assert_eq!(
+            link_kind(false, true, Some("1\n")),
+            Some(LinkKind::Ethernet)
+        );
+        assert_eq!(link_kind(false, false, Some("1\n")), None);
+        assert_eq!(link_kind(false, true, Some("65534\n")), None);
+        assert_eq!(link_kind(false, true, None), None);
+    }
+
+    #[test]
+    fn test_parse_proc_net_dev() {
+        let counters = InterfaceCounters::parse_proc_net_dev(RAW_PROC_NET_DEV).unwrap();
+
+        assert_eq!(counters.rx_bytes, 1_002_000);
+        assert_eq!(counters.tx_bytes, 503_000);
+
+        assert_eq!(InterfaceCounters::parse_proc_net_dev(""), None);
+    }
+}
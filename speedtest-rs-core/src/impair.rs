@@ -0,0 +1,247 @@
+//! Simulated network impairment for development, so slow links and flaky servers can be
+//! reproduced without a bad network. Enabled with the `impairment` feature through
+//! [`SpeedTester::with_impairment`](crate::speed_tester::SpeedTester).
+
+#![cfg_attr(not(feature = "impairment"), allow(dead_code))]
+
+use std::{
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{G_BITS_PER_SEC, K_BITS_PER_SEC, M_BITS_PER_SEC, error::ParseError};
+
+/// Impairment applied to every request, parsed from e.g.
+/// `latency=200ms,bandwidth=10M,failures=0.2,seed=7`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Impairment {
+    /// Added before every request.
+    pub latency: Duration,
+
+    /// Combined throughput cap of all transfers in bits per second.
+    pub bandwidth_bps: Option<u64>,
+
+    /// Probability in `0.0..=1.0` that a request fails.
+    pub failure_rate: f64,
+
+    /// The same seed fails the same requests, in the order they are sent.
+    pub seed: u64,
+}
+
+impl FromStr for Impairment {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut impairment = Self::default();
+
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| ParseError::new(format!("expected key=value, got '{pair}'")))?;
+            match key.trim() {
+                "latency" => impairment.latency = parse_duration(value)?,
+                "bandwidth" => impairment.bandwidth_bps = Some(parse_bitrate(value)?),
+                "failures" => {
+                    let rate: f64 = number(value)?;
+                    if !(0.0..=1.0).contains(&rate) {
+                        return Err(ParseError::new(format!(
+                            "failures must be between 0 and 1, got {rate}"
+                        )));
+                    }
+                    impairment.failure_rate = rate;
+                }
+                "seed" => impairment.seed = number(value)?,
+                other => return Err(ParseError::new(format!("unknown impairment '{other}'"))),
+            }
+        }
+        Ok(impairment)
+    }
+}
+
+fn number<T: FromStr>(value: &str) -> Result<T, ParseError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ParseError::new(format!("invalid number '{value}'")))
+}
+
+/// `200ms` or `1s`.
+fn parse_duration(value: &str) -> Result<Duration, ParseError> {
+    let value = value.trim();
+    if let Some(ms) = value.strip_suffix("ms") {
+        Ok(Duration::from_millis(number(ms)?))
+    } else if let Some(secs) = value.strip_suffix('s') {
+        Duration::try_from_secs_f64(number(secs)?)
+            .map_err(|_| ParseError::new(format!("invalid duration '{value}'")))
+    } else {
+        Err(ParseError::new(format!(
+            "invalid duration '{value}', expected e.g. 200ms"
+        )))
+    }
+}
+
+/// Bits per second with an optional decimal `K`, `M` or `G` suffix.
+fn parse_bitrate(value: &str) -> Result<u64, ParseError> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last() {
+        Some('K' | 'k') => (&value[..value.len() - 1], K_BITS_PER_SEC),
+        Some('M' | 'm') => (&value[..value.len() - 1], M_BITS_PER_SEC),
+        Some('G' | 'g') => (&value[..value.len() - 1], G_BITS_PER_SEC),
+        _ => (value, 1),
+    };
+    let bps = self::number::<f64>(number)? * multiplier as f64;
+    if bps < 1.0 {
+        return Err(ParseError::new(format!(
+            "bandwidth must be positive, got '{value}'"
+        )));
+    }
+    Ok(bps as u64)
+}
+
+/// Applies an [`Impairment`] to the requests of a [`SpeedTester`](crate::speed_tester::SpeedTester),
+/// a no-op unless configured.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Impairer {
+    state: Option<Arc<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    impairment: Impairment,
+    rng: AtomicU64,
+    /// When the bandwidth budget is free again.
+    next_free: Mutex<Instant>,
+}
+
+impl Impairer {
+    pub(crate) fn new(impairment: Impairment) -> Self {
+        Self {
+            state: Some(Arc::new(State {
+                impairment,
+                rng: AtomicU64::new(impairment.seed),
+                next_free: Mutex::new(Instant::now()),
+            })),
+        }
+    }
+
+    /// Delays the request and decides whether it fails.
+    pub(crate) async fn request(&self) -> Result<(), String> {
+        let Some(state) = &self.state else {
+            return Ok(());
+        };
+
+        tokio::time::sleep(state.impairment.latency).await;
+        if state.next_random() < state.impairment.failure_rate {
+            return Err("simulated failure".to_string());
+        }
+        Ok(())
+    }
+
+    /// Waits until `bytes` fit into the bandwidth cap shared by all transfers.
+    pub(crate) async fn throttle(&self, bytes: usize) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let Some(bps) = state.impairment.bandwidth_bps else {
+            return;
+        };
+
+        let wait = {
+            let mut next_free = state.next_free.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next_free).max(now);
+            *next_free = start + Duration::from_secs_f64(bytes as f64 * 8.0 / bps as f64);
+            *next_free - now
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+impl State {
+    /// SplitMix64, uniform in `0.0..1.0`.
+    fn next_random(&self) -> f64 {
+        let mut z = self
+            .rng
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use crate::impair::{Impairer, Impairment};
+
+    #[test]
+    fn test_parse_impairment() {
+        let impairment: Impairment = "latency=200ms, bandwidth=10M, failures=0.2, seed=7"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            impairment,
+            Impairment {
+                latency: Duration::from_millis(200),
+                bandwidth_bps: Some(10_000_000),
+                failure_rate: 0.2,
+                seed: 7,
+            }
+        );
+
+        assert!("failures=2".parse::<Impairment>().is_err());
+        assert!("jitter=5ms".parse::<Impairment>().is_err());
+        assert!("latency=5".parse::<Impairment>().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle() {
+        let impairer = Impairer::new(Impairment {
+            latency: Duration::from_millis(200),
+            bandwidth_bps: Some(80_000),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        impairer.request().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+
+        // 10 KB take a second at 80 kbps, the second transfer waits for the first one.
+        impairer.throttle(10_000).await;
+        impairer.throttle(10_000).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(2200));
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_deterministic() {
+        let failures = |seed| async move {
+            let impairer = Impairer::new(Impairment {
+                failure_rate: 0.5,
+                seed,
+                ..Default::default()
+            });
+            let mut failed = vec![];
+            for _ in 0..32 {
+                failed.push(impairer.request().await.is_err());
+            }
+            failed
+        };
+
+        let first = failures(1).await;
+        assert_eq!(first, failures(1).await);
+        assert_ne!(first, failures(2).await);
+        assert!(first.contains(&true) && first.contains(&false));
+
+        assert!(Impairer::default().request().await.is_ok());
+    }
+}
@@ -1,14 +1,77 @@
-use std::time::Duration;
+use std::{cmp::Ordering, collections::HashMap, fmt, str::FromStr, time::Duration};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
+
+use crate::{
+    error::{Error, ParseError, Result},
+    result::{ClientInfo, ServerInfo},
+    scoring::distance_km,
+    size::ByteSize,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Config {
     pub client: Client,
     #[serde(rename = "server-config")]
     pub server_config: ServerConfig,
     pub download: Download,
     pub upload: Upload,
+    /// Elements without typed support, `name` for the text of an element and `name/@attribute`
+    /// for its attributes, e.g. `odometer/@rate`. Nested elements are dropped.
+    #[serde(flatten, deserialize_with = "deserialize_extra_elements")]
+    pub extra: HashMap<String, String>,
+    /// Local settings of the download taking precedence over the values above.
+    #[serde(skip)]
+    pub download_overrides: PhaseOverrides,
+    #[serde(skip)]
+    pub upload_overrides: PhaseOverrides,
+    /// Local download sequence replacing the default one, side lengths of the images.
+    #[serde(skip)]
+    pub download_sizes: Option<Vec<usize>>,
+    /// Local upload sequence replacing the one derived from the upload ratio.
+    #[serde(skip)]
+    pub upload_sizes: Option<Vec<ByteSize>>,
+    /// Local number of upload requests replacing `maxchunkcount`.
+    #[serde(skip)]
+    pub max_chunk_count: Option<usize>,
+}
+
+/// Local settings merged over the fetched config, see [`ConfigOverrides::apply`]. `None` keeps
+/// the value of the remote config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigOverrides {
+    pub download: PhaseOverrides,
+    pub upload: PhaseOverrides,
+    /// Side lengths of the downloaded images in the order they are requested, an empty list keeps
+    /// the default sequence.
+    pub download_sizes: Option<Vec<usize>>,
+    /// Upload request sizes in the order they are sent, an empty list keeps the remote sequence.
+    pub upload_sizes: Option<Vec<ByteSize>>,
+    /// Upload requests of a phase at most, the upload ends early once they are sent.
+    pub max_chunk_count: Option<usize>,
+}
+
+/// Duration and connection count of a transfer phase, `None` keeps the value of the remote config.
+/// Asymmetric links are tested best with asymmetric settings, e.g. a long download with many
+/// connections and a short upload with few.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseOverrides {
+    pub duration: Option<Duration>,
+    /// Still capped by [`SpeedTester::with_max_concurrency`](crate::speed_tester::SpeedTester::with_max_concurrency).
+    pub threads: Option<usize>,
+}
+
+/// Start of a transfer phase left out of its reported rate, so the TCP slow start doesn't drag
+/// down short tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarmUp {
+    /// The first seconds of the phase.
+    Duration(Duration),
+    /// The first bytes of every connection, reached once the phase moved them times the
+    /// connection count.
+    Bytes(ByteSize),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,7 +86,194 @@ pub struct ServerList {
     pub servers: Vec<Server>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ServerList {
+    pub fn iter(&self) -> std::slice::Iter<'_, Server> {
+        self.servers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    /// The `n` servers nearest to the client by the distance the server list reports. Servers
+    /// without one keep their order after the others, the nearby list is already sorted by
+    /// distance.
+    pub fn closest(mut self, n: usize) -> Self {
+        self.servers
+            .sort_by(|a, b| match (a.distance_km(), b.distance_km()) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        self.servers.truncate(n);
+        self
+    }
+
+    /// The `n` servers nearest to a location.
+    pub fn closest_to(mut self, lat: f64, lon: f64, n: usize) -> Self {
+        let distance = |server: &Server| distance_km(lat, lon, server.lat, server.lon);
+        self.servers
+            .sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        self.servers.truncate(n);
+        self
+    }
+
+    /// Servers in the country with the ISO code `cc`, ignoring case.
+    pub fn in_country(self, cc: &str) -> Self {
+        self.into_iter()
+            .filter(|server| server.cc.eq_ignore_ascii_case(cc))
+            .collect()
+    }
+
+    pub fn exclude_ids<I>(self, ids: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let ids: Vec<I::Item> = ids.into_iter().collect();
+        self.into_iter()
+            .filter(|server| !ids.iter().any(|id| id.as_ref() == server.id))
+            .collect()
+    }
+
+    /// Servers run by `sponsor`, ignoring case and surrounding spaces.
+    pub fn by_sponsor(self, sponsor: &str) -> Self {
+        let sponsor = sponsor.trim().to_lowercase();
+        self.into_iter()
+            .filter(|server| server.sponsor.trim().to_lowercase() == sponsor)
+            .collect()
+    }
+
+    /// Servers matching `filter`, see [`filter_servers`].
+    pub fn filter(self, filter: &ServerFilter) -> Self {
+        filter_servers(self, filter).into_iter().collect()
+    }
+}
+
+/// Criteria narrowing down the candidate servers, all of which have to match. Unset criteria match
+/// any server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerFilter {
+    /// ISO code of the country, ignoring case.
+    pub cc: Option<String>,
+    /// Part of the sponsor, ignoring case.
+    pub sponsor: Option<String>,
+    /// Part of the name, usually the city, ignoring case.
+    pub name: Option<String>,
+}
+
+impl ServerFilter {
+    pub fn with_cc(mut self, cc: impl Into<String>) -> Self {
+        self.cc = Some(cc.into());
+        self
+    }
+
+    pub fn with_sponsor(mut self, sponsor: impl Into<String>) -> Self {
+        self.sponsor = Some(sponsor.into());
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cc.is_none() && self.sponsor.is_none() && self.name.is_none()
+    }
+
+    pub fn matches(&self, server: &Server) -> bool {
+        let contains = |value: &str, part: &Option<String>| {
+            part.as_ref()
+                .is_none_or(|part| value.to_lowercase().contains(&part.trim().to_lowercase()))
+        };
+        self.cc
+            .as_ref()
+            .is_none_or(|cc| server.cc.eq_ignore_ascii_case(cc.trim()))
+            && contains(&server.sponsor, &self.sponsor)
+            && contains(&server.name, &self.name)
+    }
+}
+
+impl std::fmt::Display for ServerFilter {
+    /// The set criteria, e.g. `cc JP, sponsor 'kddi'`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let criteria: Vec<String> = [
+            self.cc.as_ref().map(|cc| format!("cc {cc}")),
+            self.sponsor
+                .as_ref()
+                .map(|sponsor| format!("sponsor '{sponsor}'")),
+            self.name.as_ref().map(|name| format!("name '{name}'")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        write!(f, "{}", criteria.join(", "))
+    }
+}
+
+/// The servers matching `filter`, in the order they were passed.
+pub fn filter_servers(
+    servers: impl IntoIterator<Item = Server>,
+    filter: &ServerFilter,
+) -> Vec<Server> {
+    servers
+        .into_iter()
+        .filter(|server| filter.matches(server))
+        .collect()
+}
+
+impl FromIterator<Server> for ServerList {
+    fn from_iter<T: IntoIterator<Item = Server>>(iter: T) -> Self {
+        Self {
+            servers: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for ServerList {
+    type Item = Server;
+    type IntoIter = std::vec::IntoIter<Server>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.servers.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ServerList {
+    type Item = &'a Server;
+    type IntoIter = std::slice::Iter<'a, Server>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.servers.iter()
+    }
+}
+
+impl IntoIterator for Servers {
+    type Item = Server;
+    type IntoIter = std::vec::IntoIter<Server>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.servers.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Servers {
+    type Item = &'a Server;
+    type IntoIter = std::slice::Iter<'a, Server>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.servers.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Server {
     #[serde(rename = "@url")]
     pub url: String,
@@ -43,37 +293,277 @@ pub struct Server {
     pub id: String,
     #[serde(rename = "@host")]
     pub host: String,
+    /// Attributes without typed support by their name prefixed with `@`, e.g. `@distance`.
+    #[serde(flatten)]
+    pub extra: HashMap<String, String>,
+}
+
+/// Entry of the JSON server list (`/api/js/servers`), which reports the coordinates as strings.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct JsonServer {
+    url: String,
+    #[serde(default)]
+    lat: String,
+    #[serde(default)]
+    lon: String,
+    name: String,
+    country: String,
+    cc: String,
+    sponsor: String,
+    id: String,
+    host: String,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+impl From<JsonServer> for Server {
+    fn from(server: JsonServer) -> Self {
+        Self {
+            lat: server.lat.parse().unwrap_or_default(),
+            lon: server.lon.parse().unwrap_or_default(),
+            url: server.url,
+            name: server.name,
+            country: server.country,
+            cc: server.cc,
+            sponsor: server.sponsor,
+            id: server.id,
+            host: server.host,
+            extra: server
+                .extra
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(value) => value,
+                        value => value.to_string(),
+                    };
+                    (format!("@{key}"), value)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Client> for ClientInfo {
+    fn from(value: &Client) -> Self {
+        Self {
+            ip: value.ip.clone(),
+            lat: value.lat,
+            lon: value.lon,
+            isp: value.isp.clone(),
+            country: value.country.clone(),
+        }
+    }
+}
+
+impl From<&Server> for ServerInfo {
+    fn from(value: &Server) -> Self {
+        Self {
+            id: value.id.clone(),
+            name: value.name.clone(),
+            sponsor: value.sponsor.clone(),
+            country: value.country.clone(),
+            cc: value.cc.clone(),
+            host: value.host.clone(),
+            url: value.url.clone(),
+            lat: value.lat,
+            lon: value.lon,
+            extra: value.extra.clone(),
+        }
+    }
+}
+
+impl Server {
+    /// A self-hosted server given by the URL of its `upload.php`, e.g. of a speedtest-mini
+    /// install. The host with its port stands in for the id, name and sponsor.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let parsed = Url::parse(url)?;
+        let host = match (parsed.host_str(), parsed.port_or_known_default()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(Error::Parse(format!("server URL {url} has no host"))),
+        };
+        Ok(Self {
+            url: url.to_string(),
+            lat: 0.0,
+            lon: 0.0,
+            name: host.clone(),
+            country: String::new(),
+            cc: String::new(),
+            sponsor: host.clone(),
+            id: host.clone(),
+            host,
+            extra: HashMap::new(),
+        })
+    }
+
+    /// URL of the directory hosting the test files, i.e. `url` without `/upload.php`.
+    pub fn base_url(&self) -> &str {
+        match self.url.rsplit_once('/') {
+            Some((base, file)) if file.contains('.') => base,
+            _ => self.url.trim_end_matches('/'),
+        }
+    }
+
+    /// Tiny static file used for latency probes.
+    pub fn latency_url(&self) -> String {
+        format!("{}/latency.txt", self.base_url())
+    }
+
+    /// Attribute of the server list without typed support, e.g. `extra_attribute("distance")`.
+    pub fn extra_attribute(&self, name: &str) -> Option<&str> {
+        self.extra.get(&format!("@{name}")).map(String::as_str)
+    }
+
+    /// Distance from the client in kilometers, as reported by the JSON server list.
+    pub fn distance_km(&self) -> Option<f64> {
+        self.extra_attribute("distance")?.parse().ok()
+    }
+
+    /// Great-circle distance from the location of `client` in kilometers, computed from the
+    /// coordinates of both.
+    pub fn distance_from(&self, client: &Client) -> f64 {
+        distance_km(client.lat, client.lon, self.lat, self.lon)
+    }
+
+    /// Whether `id_or_host` is the id of the server or its host, with or without the port and
+    /// ignoring case.
+    pub fn matches(&self, id_or_host: &str) -> bool {
+        let id_or_host = id_or_host.trim();
+        let host = self.host.trim();
+        self.id == id_or_host
+            || host.eq_ignore_ascii_case(id_or_host)
+            || host
+                .rsplit_once(':')
+                .is_some_and(|(name, _)| name.eq_ignore_ascii_case(id_or_host))
+    }
+
+    /// Whether both servers are reached at the same host and port, ignoring case.
+    pub fn same_host(&self, other: &Server) -> bool {
+        self.host.trim().eq_ignore_ascii_case(other.host.trim())
+    }
+
+    /// Whether both servers are run by the same operator, ignoring case and surrounding spaces.
+    pub fn same_sponsor(&self, other: &Server) -> bool {
+        self.sponsor.trim().to_lowercase() == other.sponsor.trim().to_lowercase()
+    }
+}
+
+/// Servers run by the same operator, many of which list several identical hosts per city.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SponsorGroup {
+    pub sponsor: String,
+    /// In the order they were passed, so the first one is the best of a ranked list.
+    pub servers: Vec<Server>,
+}
+
+/// Groups servers by their sponsor, in the order of the first server of every sponsor.
+pub fn group_by_sponsor(servers: impl IntoIterator<Item = Server>) -> Vec<SponsorGroup> {
+    let mut groups: Vec<SponsorGroup> = vec![];
+    for server in servers {
+        match groups
+            .iter_mut()
+            .find(|group| group.servers[0].same_sponsor(&server))
+        {
+            Some(group) => group.servers.push(server),
+            None => groups.push(SponsorGroup {
+                sponsor: server.sponsor.trim().to_string(),
+                servers: vec![server],
+            }),
+        }
+    }
+    groups
 }
 
+/// Entries of the server list pointing at the same machine, only the first of which is raced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostGroup {
+    pub host: String,
+    /// In the order they were passed.
+    pub servers: Vec<Server>,
+}
+
+/// Groups servers by their host, in the order of the first server of every host.
+pub fn group_by_host(servers: impl IntoIterator<Item = Server>) -> Vec<HostGroup> {
+    let mut groups: Vec<HostGroup> = vec![];
+    for server in servers {
+        match groups
+            .iter_mut()
+            .find(|group| group.servers[0].same_host(&server))
+        {
+            Some(group) => group.servers.push(server),
+            None => groups.push(HostGroup {
+                host: server.host.trim().to_lowercase(),
+                servers: vec![server],
+            }),
+        }
+    }
+    groups
+}
+
+/// The stock settings of the speedtest.net config with an unknown client.
+const FALLBACK_CONFIG: &str = r#"<settings>
+<client ip="" lat="0" lon="0" isp="" isprating="0" rating="0" ispdlavg="0" ispulavg="0" loggedin="0" country=""/>
+<server-config threadcount="4" ignoreids="" notonmap="" forcepingid="" preferredserverid=""/>
+<download testlength="10" initialtest="250K" mintestsize="250K" threadsperurl="4"/>
+<upload testlength="10" ratio="5" initialtest="0" mintestsize="32K" threads="2" maxchunksize="512K" maxchunkcount="50" threadsperurl="4"/>
+</settings>"#;
+
 impl Config {
+    /// The stock settings, standing in for the config of speedtest.net when testing against static
+    /// servers in a network without access to it. The client is unknown.
+    pub fn fallback() -> Self {
+        quick_xml::de::from_str(FALLBACK_CONFIG).expect("fallback config is valid")
+    }
+
     pub fn client_info(&self) -> &Client {
         &self.client
     }
 
+    /// Element of the config without typed support, see [`Config::extra`].
+    pub fn extra_element(&self, path: &str) -> Option<&str> {
+        self.extra.get(path).map(String::as_str)
+    }
+
     pub fn ignore_servers(&self) -> impl Iterator<Item = &str> {
         self.server_config.ignoreids.split(',')
     }
 
-    pub fn upload_size_sequence(&self) -> Vec<usize> {
+    /// Body sizes of the upload requests, the ratio of the config skips the smallest ones.
+    pub fn upload_size_sequence(&self) -> Vec<ByteSize> {
+        if let Some(sizes) = &self.upload_sizes {
+            return sizes.clone();
+        }
         let mut seq = DefaultSequence::Upload.sequence();
 
         let ratio = self.upload.ratio as usize;
         if ratio > 0 && ratio < seq.len() {
             seq.drain(0..ratio - 1);
         }
-        seq
+        seq.into_iter().map(|size| ByteSize(size as u64)).collect()
+    }
+
+    /// Largest upload request the server accepts, `None` if the config value is malformed.
+    pub fn max_upload_chunk_size(&self) -> Option<ByteSize> {
+        self.upload.maxchunksize.parse().ok()
     }
 
     pub fn max_download_duration(&self) -> Duration {
-        Duration::from_secs(self.download.testlength as u64)
+        self.download_overrides
+            .duration
+            .unwrap_or_else(|| Duration::from_secs(self.download.testlength as u64))
     }
 
     pub fn max_upload_duration(&self) -> Duration {
-        Duration::from_secs(self.upload.testlength as u64)
+        self.upload_overrides
+            .duration
+            .unwrap_or_else(|| Duration::from_secs(self.upload.testlength as u64))
     }
 
+    /// Side lengths in pixels of the downloaded images, not byte sizes.
     pub fn download_size_sequence(&self) -> Vec<usize> {
-        DefaultSequence::Download.sequence()
+        self.download_sizes
+            .clone()
+            .unwrap_or_else(|| DefaultSequence::Download.sequence())
     }
 
     pub fn threads(&self) -> usize {
@@ -81,7 +571,9 @@ impl Config {
     }
 
     pub fn download_threads(&self) -> usize {
-        self.server_config.threadcount as usize * 2
+        self.download_overrides
+            .threads
+            .unwrap_or(self.server_config.threadcount as usize * 2)
     }
 
     pub fn download_count_per_url(&self) -> usize {
@@ -89,7 +581,9 @@ impl Config {
     }
 
     pub fn upload_threads(&self) -> usize {
-        self.upload.threads as usize
+        self.upload_overrides
+            .threads
+            .unwrap_or(self.upload.threads as usize)
     }
 
     pub fn upload_count_per_url(&self) -> usize {
@@ -97,11 +591,62 @@ impl Config {
     }
 
     pub fn max_upload_count(&self) -> usize {
-        self.upload.maxchunkcount as usize
+        self.max_chunk_count
+            .unwrap_or(self.upload.maxchunkcount as usize)
     }
 }
 
-pub enum DefaultSequence {
+impl ConfigOverrides {
+    /// Sets the local settings of `config`, replacing those of an earlier merge.
+    pub fn apply(&self, config: &mut Config) {
+        config.download_overrides = self.download;
+        config.upload_overrides = self.upload;
+        config.download_sizes = self
+            .download_sizes
+            .clone()
+            .filter(|sizes| !sizes.is_empty());
+        config.upload_sizes = self.upload_sizes.clone().filter(|sizes| !sizes.is_empty());
+        config.max_chunk_count = self.max_chunk_count;
+    }
+}
+
+impl fmt::Display for WarmUp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duration(duration) => write!(f, "{duration:?}"),
+            Self::Bytes(size) => write!(f, "{size} per connection"),
+        }
+    }
+}
+
+/// Parses a duration like `2s` or `500ms`, or a size like `1M` per connection.
+impl FromStr for WarmUp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || ParseError::new(format!("invalid warm-up '{s}', expected e.g. 2s or 1M"));
+        if let Some(ms) = s.strip_suffix("ms") {
+            return ms
+                .trim()
+                .parse()
+                .map(|ms| Self::Duration(Duration::from_millis(ms)))
+                .map_err(|_| invalid());
+        }
+        if let Some(secs) = s.strip_suffix('s') {
+            return secs
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .map(Self::Duration)
+                .ok_or_else(invalid);
+        }
+        s.parse().map(Self::Bytes).map_err(|_| invalid())
+    }
+}
+
+pub(crate) enum DefaultSequence {
     Upload,
     Download,
 }
@@ -125,6 +670,44 @@ impl DefaultSequence {
     }
 }
 
+/// Unknown element of the config, see [`Config::extra`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExtraElement {
+    Text(String),
+    /// Attributes by their name prefixed with `@`, the text as `$text`.
+    Element(HashMap<String, String>),
+    Nested(serde::de::IgnoredAny),
+}
+
+fn deserialize_extra_elements<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut extra = HashMap::new();
+    for (name, element) in HashMap::<String, ExtraElement>::deserialize(deserializer)? {
+        match element {
+            ExtraElement::Text(text) => {
+                extra.insert(name, text);
+            }
+            ExtraElement::Element(fields) if fields.is_empty() => {
+                extra.insert(name, String::new());
+            }
+            ExtraElement::Element(fields) => {
+                for (key, value) in fields {
+                    let path = match key.as_str() {
+                        "$text" => name.clone(),
+                        _ => format!("{name}/{key}"),
+                    };
+                    extra.insert(path, value);
+                }
+            }
+            ExtraElement::Nested(_) => {}
+        }
+    }
+    Ok(extra)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Client {
     #[serde(rename = "@ip")]
@@ -244,11 +827,104 @@ mod tests {
 
         println!("{:#?}", setting.upload_size_sequence());
 
+        assert_eq!(
+            setting.max_upload_chunk_size(),
+            Some(crate::size::ByteSize::kib(512))
+        );
+        assert_eq!(
+            setting.upload_size_sequence().first(),
+            Some(&crate::size::ByteSize::kib(512))
+        );
+
         println!(
             "{:#?} -> {:#?}",
             setting.max_upload_count(),
             setting.max_upload_duration()
         );
+
+        assert_eq!(
+            setting.extra_element("licensekey"),
+            Some("f7a45ced624d3a70-1df5b7cd427370f7-b91ee21d6cb22d7b")
+        );
+        assert_eq!(setting.extra_element("odometer/@rate"), Some("10"));
+        assert_eq!(setting.extra_element("times/@dl3"), Some("800000000"));
+        assert_eq!(setting.extra_element("download/@testlength"), None);
+    }
+
+    #[test]
+    fn test_phase_overrides() {
+        use std::time::Duration;
+
+        use crate::{
+            model::{Config, ConfigOverrides, PhaseOverrides},
+            size::ByteSize,
+        };
+
+        let mut config: Config = quick_xml::de::from_str(RAW_CONFIG).unwrap();
+        let (download_threads, upload_threads) =
+            (config.download_threads(), config.upload_threads());
+        let max_upload_count = config.max_upload_count();
+
+        let mut overrides = ConfigOverrides {
+            download: PhaseOverrides {
+                duration: Some(Duration::from_secs(15)),
+                threads: Some(16),
+            },
+            upload: PhaseOverrides {
+                duration: Some(Duration::from_millis(5500)),
+                threads: None,
+            },
+            download_sizes: Some(vec![]),
+            max_chunk_count: Some(8),
+            ..ConfigOverrides::default()
+        };
+        overrides.apply(&mut config);
+        assert_eq!(config.max_upload_count(), 8);
+        assert_eq!(config.download_sizes, None);
+        assert_eq!(config.max_download_duration(), Duration::from_secs(15));
+        assert_eq!(config.max_upload_duration(), Duration::from_millis(5500));
+        assert_eq!(config.download_threads(), 16);
+        assert_eq!(config.upload_threads(), upload_threads);
+        // Only the transfers change, the discovery keeps the thread count of the remote config.
+        assert_eq!(config.threads(), download_threads);
+
+        overrides.download_sizes = Some(vec![1000, 4000]);
+        overrides.upload_sizes = Some(vec![ByteSize::kib(256), ByteSize::mib(1)]);
+        overrides.max_chunk_count = None;
+        overrides.apply(&mut config);
+        assert_eq!(config.download_size_sequence(), [1000, 4000]);
+        assert_eq!(
+            config.upload_size_sequence(),
+            [ByteSize::kib(256), ByteSize::mib(1)]
+        );
+        assert_eq!(config.max_upload_count(), max_upload_count);
+    }
+
+    #[test]
+    fn test_warm_up() {
+        use std::time::Duration;
+
+        use crate::{model::WarmUp, size::ByteSize};
+
+        assert_eq!(
+            "2s".parse::<WarmUp>().unwrap(),
+            WarmUp::Duration(Duration::from_secs(2))
+        );
+        assert_eq!(
+            "500ms".parse::<WarmUp>().unwrap(),
+            WarmUp::Duration(Duration::from_millis(500))
+        );
+        assert_eq!(
+            "1M".parse::<WarmUp>().unwrap(),
+            WarmUp::Bytes(ByteSize::mib(1))
+        );
+        assert!("2x".parse::<WarmUp>().is_err());
+        assert!("-1s".parse::<WarmUp>().is_err());
+
+        assert_eq!(
+            WarmUp::Duration(Duration::from_millis(1500)).to_string(),
+            "1.5s"
+        );
     }
 
     #[test]
@@ -256,5 +932,158 @@ mod tests {
         use crate::model::Servers;
 
         let _servers: Servers = quick_xml::de::from_str(RAW_SERVERS).unwrap();
+
+        let raw = r#"<settings><servers>
+<server url="http://speed.example.com:8080/speedtest/upload.php" lat="22.2796" lon="114.1592" name="Hong Kong" country="Hong Kong" cc="HK" sponsor="Example" id="1" host="speed.example.com:8080" ipv6="yes"/>
+</servers></settings>"#;
+        let servers: Servers = quick_xml::de::from_str(raw).unwrap();
+        let server = &servers.servers.servers[0];
+        assert_eq!(server.lat, 22.2796);
+        assert_eq!(server.extra_attribute("ipv6"), Some("yes"));
+
+        // Kept in caches and snapshots written as JSON.
+        let cached: crate::model::Server =
+            serde_json::from_str(&serde_json::to_string(server).unwrap()).unwrap();
+        assert_eq!(&cached, server);
+    }
+
+    #[test]
+    fn test_deserialize_json_servers() {
+        use crate::model::{JsonServer, Server};
+
+        let raw = r#"[{"url":"http://kami.smartone.com:8080/speedtest/upload.php","lat":"22.2796",
+            "lon":"114.1592","distance":7,"name":"Hong Kong","country":"Hong Kong","cc":"HK",
+            "sponsor":"SmarTone","id":"35791","preferred":0,"https_functional":1,
+            "host":"kami.smartone.com:8080"}]"#;
+        let servers: Vec<JsonServer> = serde_json::from_str(raw).unwrap();
+        let server = Server::from(servers[0].clone());
+
+        assert_eq!(server.id, "35791");
+        assert_eq!(server.lat, 22.2796);
+        assert_eq!(server.host, "kami.smartone.com:8080");
+        assert_eq!(server.extra_attribute("distance"), Some("7"));
+        assert_eq!(server.extra_attribute("https_functional"), Some("1"));
+    }
+
+    #[test]
+    fn test_server_urls() {
+        use crate::model::Servers;
+
+        let servers: Servers = quick_xml::de::from_str(RAW_SERVERS).unwrap();
+        let server = &servers.servers.servers[0];
+
+        assert_eq!(server.base_url(), "http://kami.smartone.com:8080/speedtest");
+        assert_eq!(
+            server.latency_url(),
+            "http://kami.smartone.com:8080/speedtest/latency.txt"
+        );
+    }
+
+    #[test]
+    fn test_custom_server() {
+        use std::time::Duration;
+
+        use crate::model::{Config, Server};
+
+        let server = Server::from_url("http://speedtest.example.com/speedtest/upload.php").unwrap();
+        assert_eq!(server.host, "speedtest.example.com:80");
+        assert_eq!(server.id, server.host);
+        assert_eq!(server.base_url(), "http://speedtest.example.com/speedtest");
+        assert!(Server::from_url("speedtest.example.com").is_err());
+
+        let config = Config::fallback();
+        assert_eq!(config.client.ip, "");
+        assert_eq!(config.max_download_duration(), Duration::from_secs(10));
+        assert_eq!(config.threads(), 8);
+        assert_eq!(config.ignore_servers().collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn test_group_by_sponsor() {
+        use crate::model::{Servers, group_by_sponsor};
+
+        let mut servers: Vec<_> = quick_xml::de::from_str::<Servers>(RAW_SERVERS)
+            .unwrap()
+            .servers
+            .servers;
+        let mut duplicate = servers[2].clone();
+        duplicate.id = "65464".to_string();
+        duplicate.sponsor = "hkbn ".to_string();
+        servers.push(duplicate);
+
+        let groups = group_by_sponsor(servers.clone());
+        assert_eq!(groups.len(), servers.len() - 1);
+        assert_eq!(groups[2].sponsor, "HKBN");
+        let ids: Vec<&str> = groups[2].servers.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["65463", "65464"]);
+    }
+
+    #[test]
+    fn test_refine_server_list() {
+        use crate::model::{ServerFilter, Servers, filter_servers};
+
+        let servers: Servers = quick_xml::de::from_str(RAW_SERVERS).unwrap();
+        let ids = |list: &crate::model::ServerList| {
+            list.iter()
+                .map(|s| s.id.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let taiwan = servers.servers.clone().in_country("tw");
+        assert_eq!(ids(&taiwan), "8968,69301,18456");
+        assert_eq!(
+            ids(&taiwan
+                .clone()
+                .exclude_ids(["69301"])
+                .by_sponsor(" homeplus ")),
+            "8968"
+        );
+        let filter = ServerFilter::default().with_cc("tw").with_name("TAICHUNG");
+        assert_eq!(ids(&servers.servers.clone().filter(&filter)), "69301,18456");
+        let filter = filter.with_sponsor(" infini ");
+        assert_eq!(ids(&servers.servers.clone().filter(&filter)), "69301");
+        assert_eq!(
+            filter.to_string(),
+            "cc tw, sponsor ' infini ', name 'TAICHUNG'"
+        );
+        assert_eq!(
+            filter_servers(servers.servers.clone(), &ServerFilter::default()).len(),
+            servers.servers.len()
+        );
+        // Taichung is closer to Taipei than Kaohsiung.
+        assert_eq!(ids(&taiwan.closest_to(25.03, 121.56, 2)), "18456,69301");
+
+        let mut list = servers.servers.clone();
+        list.servers[2]
+            .extra
+            .insert("@distance".to_string(), "1.5".to_string());
+        list.servers[4]
+            .extra
+            .insert("@distance".to_string(), "0.8".to_string());
+        assert_eq!(ids(&list.closest(3)), "28912,65463,35791");
+
+        assert_eq!((&servers).into_iter().count(), servers.servers.len());
+        assert_eq!(servers.into_iter().filter(|s| s.cc == "HK").count(), 6);
+    }
+
+    #[test]
+    fn test_group_by_host() {
+        use crate::model::{Servers, group_by_host};
+
+        let mut servers: Vec<_> = quick_xml::de::from_str::<Servers>(RAW_SERVERS)
+            .unwrap()
+            .servers
+            .servers;
+        let mut duplicate = servers[1].clone();
+        duplicate.id = "99999".to_string();
+        duplicate.host = duplicate.host.to_uppercase();
+        servers.push(duplicate);
+
+        let groups = group_by_host(servers.clone());
+        assert_eq!(groups.len(), servers.len() - 1);
+        assert_eq!(groups[1].host, servers[1].host.to_lowercase());
+        let ids: Vec<&str> = groups[1].servers.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, [servers[1].id.as_str(), "99999"]);
     }
 }
@@ -0,0 +1,153 @@
+//! Carrier-grade NAT detection. The WAN address is asked from the router with NAT-PMP (RFC 6886),
+//! or is the address of the device itself when it holds the WAN address.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::net::UdpSocket;
+
+pub use speedtest_rs_types::nat::{NatCheck, WanSource, is_shared_address};
+
+use crate::trace;
+
+const NAT_PMP_PORT: u16 = 5351;
+
+/// Waits of the NAT-PMP retries, doubling from 250ms as in RFC 6886 but giving up early since
+/// most home routers do not answer at all.
+const NAT_PMP_TIMEOUTS: [Duration; 2] = [Duration::from_millis(250), Duration::from_millis(500)];
+
+/// Compares the WAN address of the local network with `public_ip`, the address the speedtest.net
+/// config reports. `None` when the WAN address is unknown, e.g. the router has no NAT-PMP, and for
+/// IPv6 connections which are not translated.
+pub async fn detect_nat(public_ip: &str) -> Option<NatCheck> {
+    let Ok(public @ IpAddr::V4(_)) = public_ip.parse::<IpAddr>() else {
+        return None;
+    };
+    let IpAddr::V4(local) = source_address(public).await? else {
+        return None;
+    };
+
+    if IpAddr::V4(local) == public || is_shared_address(local) {
+        return Some(NatCheck::new(local.into(), WanSource::Interface, public_ip));
+    }
+
+    let gateway = default_gateway().unwrap_or_else(|| guess_gateway(local));
+    match nat_pmp_external_address(gateway).await {
+        Some(wan) => Some(NatCheck::new(wan.into(), WanSource::NatPmp, public_ip)),
+        None => {
+            trace::debug!("no NAT-PMP answer from {}, WAN address unknown", gateway);
+            None
+        }
+    }
+}
+
+/// Local address the traffic to `target` leaves from. Connecting a UDP socket only selects the
+/// route, nothing is sent.
+pub async fn source_address(target: IpAddr) -> Option<IpAddr> {
+    let unspecified = match target {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind((unspecified, 0)).await.ok()?;
+    socket.connect((target, 80)).await.ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Gateway of the default IPv4 route from `/proc/net/route`, `None` on other platforms.
+pub fn default_gateway() -> Option<Ipv4Addr> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+        parse_default_gateway(&routes)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        None
+    }
+}
+
+/// Home routers usually take the first address of the /24 they hand out.
+fn guess_gateway(local: Ipv4Addr) -> Ipv4Addr {
+    let [a, b, c, _] = local.octets();
+    Ipv4Addr::new(a, b, c, 1)
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn parse_default_gateway(raw: &str) -> Option<Ipv4Addr> {
+    raw.lines().skip(1).find_map(|line| {
+        let mut fields = line.split_whitespace().skip(1);
+        let destination = fields.next()?;
+        let gateway = fields.next()?;
+        // Addresses are written in host byte order, little endian on all supported platforms.
+        let gateway = u32::from_str_radix(gateway, 16).ok()?;
+        (destination == "00000000" && gateway != 0).then(|| Ipv4Addr::from(gateway.swap_bytes()))
+    })
+}
+
+async fn nat_pmp_external_address(gateway: Ipv4Addr) -> Option<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    socket
+        .connect(SocketAddr::from((gateway, NAT_PMP_PORT)))
+        .await
+        .ok()?;
+
+    let mut buf = [0; 16];
+    for timeout in NAT_PMP_TIMEOUTS {
+        // Version 0, opcode 0: external address request.
+        socket.send(&[0, 0]).await.ok()?;
+        match tokio::time::timeout(timeout, socket.recv(&mut buf)).await {
+            Ok(Ok(len)) => return parse_nat_pmp_response(&buf[..len]),
+            Ok(Err(_)) => return None,
+            Err(_) => continue,
+        }
+    }
+    None
+}
+
+/// The external address of a successful external address response.
+fn parse_nat_pmp_response(response: &[u8]) -> Option<Ipv4Addr> {
+    match response {
+        [0, 128, 0, 0, _, _, _, _, a, b, c, d, ..] => Some(Ipv4Addr::new(*a, *b, *c, *d)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::nat::{guess_gateway, parse_default_gateway, parse_nat_pmp_response};
+
+    #[test]
+    fn test_parse_default_gateway() {
+        let raw_route = "Iface\tDestination\tGateway\tFlags\n\
+                         eth0\t000200C0\t00000000\t0001\n\
+                         eth1\t00000000\t010200C0\t0003\n";
+        assert_eq!(
+            parse_default_gateway(raw_route),
+            Some(Ipv4Addr::new(192, 0, 2, 1))
+        );
+        assert_eq!(parse_default_gateway("Iface\tDestination\tGateway\n"), None);
+        assert_eq!(
+            guess_gateway(Ipv4Addr::new(192, 168, 1, 23)),
+            Ipv4Addr::new(192, 168, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_parse_nat_pmp_response() {
+        let response = [0, 128, 0, 0, 0, 0, 0x1c, 0x20, 100, 72, 3, 9];
+        assert_eq!(
+            parse_nat_pmp_response(&response),
+            Some(Ipv4Addr::new(100, 72, 3, 9))
+        );
+
+        // Result code 3: the router has no WAN address yet.
+        let failed = [0, 128, 0, 3, 0, 0, 0x1c, 0x20, 0, 0, 0, 0];
+        assert_eq!(parse_nat_pmp_response(&failed), None);
+        assert_eq!(parse_nat_pmp_response(&[0, 128]), None);
+    }
+}
@@ -0,0 +1,623 @@
+//! The ndt7 protocol of M-Lab: a download and an upload over a WebSocket each, with the server
+//! reporting the TCP statistics of the connection as JSON measurements. WebSockets pass most
+//! middleboxes that interfere with plain HTTP transfers. See
+//! <https://github.com/m-lab/ndt-server/blob/main/spec/ndt7-protocol.md>.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt, stream::SplitSink};
+use serde::Deserialize;
+use tokio::{net::TcpStream, time::Instant};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream,
+    tungstenite::{self, Message, client::IntoClientRequest, http::HeaderValue},
+};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{
+    dns::Dns,
+    error::{Error, Result},
+    latency::Latency,
+    model::Server,
+    result::{Methodology, TransferResult},
+    trace,
+};
+
+/// Nearest servers of the ndt7 service, with access tokens in their URLs.
+pub const LOCATE_URL: &str = "https://locate.measurementlab.net/v2/nearest/ndt/ndt7";
+
+/// WebSocket subprotocol the servers require.
+pub const SUBPROTOCOL: &str = "net.measurementlab.ndt.v7";
+
+/// Sponsor of the servers in results.
+pub const SPONSOR: &str = "M-Lab";
+
+/// The server ends the download after about 10 seconds, the client gives up after 15.
+const MAX_DOWNLOAD_DURATION: Duration = Duration::from_secs(15);
+
+const UPLOAD_DURATION: Duration = Duration::from_secs(10);
+
+/// Initial size of the upload messages, doubled up to [`MAX_MESSAGE_SIZE`] while it is small
+/// compared with the bytes sent so far, as the spec recommends.
+const MIN_MESSAGE_SIZE: usize = 1 << 13;
+
+const MAX_MESSAGE_SIZE: usize = 1 << 24;
+
+/// A message grows once it is smaller than this fraction of the bytes sent.
+const SCALING_FRACTION: u64 = 16;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time to finish the closing handshake after a phase.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize)]
+struct Locate {
+    #[serde(default)]
+    results: Vec<LocateResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocateResult {
+    machine: String,
+    #[serde(default)]
+    location: Option<LocateLocation>,
+    #[serde(default)]
+    urls: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocateLocation {
+    #[serde(default)]
+    city: String,
+    #[serde(default)]
+    country: String,
+}
+
+/// A server offered by the locate API. The URLs carry access tokens which expire after a few
+/// minutes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ndt7Server {
+    pub machine: String,
+    pub city: String,
+    /// ISO code of the country.
+    pub country: String,
+    pub download_url: String,
+    pub upload_url: String,
+}
+
+/// Servers of a locate API response, nearest first, with the `wss` URLs or else the plain `ws`
+/// ones.
+pub fn parse_locate(body: &str) -> Result<Vec<Ndt7Server>> {
+    let locate: Locate = serde_json::from_str(body)?;
+    Ok(locate
+        .results
+        .into_iter()
+        .filter_map(|result| {
+            let url = |test: &str| {
+                ["wss", "ws"]
+                    .iter()
+                    .find_map(|scheme| result.urls.get(&format!("{scheme}:///ndt/v7/{test}")))
+                    .cloned()
+            };
+            let (download_url, upload_url) = (url("download")?, url("upload")?);
+            let location = result.location.as_ref();
+            Some(Ndt7Server {
+                city: location.map(|l| l.city.clone()).unwrap_or_default(),
+                country: location.map(|l| l.country.clone()).unwrap_or_default(),
+                machine: result.machine,
+                download_url,
+                upload_url,
+            })
+        })
+        .collect())
+}
+
+impl From<&Ndt7Server> for Server {
+    /// The server as it is recorded in results, the URL without its access token.
+    fn from(value: &Ndt7Server) -> Self {
+        let url = Url::parse(&value.download_url).ok();
+        Self {
+            url: url
+                .as_ref()
+                .map(|url| format!("{}://{}{}", url.scheme(), url.authority(), url.path()))
+                .unwrap_or_default(),
+            lat: 0.0,
+            lon: 0.0,
+            name: value.city.clone(),
+            country: value.country.clone(),
+            cc: value.country.clone(),
+            sponsor: SPONSOR.to_string(),
+            id: value.machine.clone(),
+            host: url
+                .as_ref()
+                .and_then(|url| url.host_str())
+                .unwrap_or(&value.machine)
+                .to_string(),
+            extra: HashMap::new(),
+        }
+    }
+}
+
+/// Measurement message of either side, only the fields used here.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Measurement {
+    pub app_info: Option<AppInfo>,
+    /// Only in the first message of the server.
+    pub connection_info: Option<ConnectionInfo>,
+    /// `client` or `server`.
+    pub origin: Option<String>,
+    #[serde(rename = "TCPInfo")]
+    pub tcp_info: Option<TcpInfo>,
+}
+
+/// Progress at the application level, times in microseconds since the start of the phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AppInfo {
+    pub elapsed_time: u64,
+    pub num_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConnectionInfo {
+    /// Address and port of the client as the server sees it.
+    pub client: String,
+    pub server: String,
+    #[serde(rename = "UUID")]
+    pub uuid: String,
+}
+
+/// `TCP_INFO` of the server side, times in microseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TcpInfo {
+    #[serde(rename = "RTT")]
+    pub rtt: Option<u64>,
+    #[serde(rename = "MinRTT")]
+    pub min_rtt: Option<u64>,
+    pub bytes_acked: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub elapsed_time: Option<u64>,
+}
+
+/// What a phase measured.
+#[derive(Debug, Clone)]
+pub struct Ndt7Phase {
+    pub transfer: TransferResult,
+    /// RTT of the connection sampled by the server under load.
+    pub latency: Option<Latency>,
+    /// Smallest RTT the server saw, the closest to the latency without load.
+    pub min_rtt: Option<Duration>,
+    /// Address of the client as the server sees it, without the port.
+    pub client_ip: Option<String>,
+    /// Last measurement of the server.
+    pub measurement: Option<Measurement>,
+}
+
+/// The measurements of the server during a phase.
+#[derive(Debug, Default)]
+struct Measurements {
+    rtts: Vec<Duration>,
+    min_rtt: Option<Duration>,
+    client_ip: Option<String>,
+    last: Option<Measurement>,
+}
+
+impl Measurements {
+    fn record(&mut self, text: &str) {
+        let Ok(measurement) = serde_json::from_str::<Measurement>(text) else {
+            trace::debug!("ignoring ndt7 message {}", text);
+            return;
+        };
+        if let Some(info) = &measurement.connection_info {
+            self.client_ip = Some(client_ip(&info.client));
+        }
+        if let Some(tcp_info) = measurement.tcp_info {
+            self.rtts.extend(tcp_info.rtt.map(Duration::from_micros));
+            if let Some(min_rtt) = tcp_info.min_rtt.map(Duration::from_micros) {
+                self.min_rtt = Some(self.min_rtt.map_or(min_rtt, |rtt| rtt.min(min_rtt)));
+            }
+        }
+        self.last = Some(measurement);
+    }
+
+    fn phase(self, transfer: TransferResult) -> Ndt7Phase {
+        Ndt7Phase {
+            transfer,
+            latency: Latency::from_samples(&self.rtts, 0),
+            min_rtt: self.min_rtt,
+            client_ip: self.client_ip,
+            measurement: self.last,
+        }
+    }
+
+    /// Bytes the server received and the time it took, more accurate for the upload than the
+    /// bytes the client handed to its socket.
+    fn received(&self) -> Option<TransferResult> {
+        let tcp_info = self.last.as_ref()?.tcp_info?;
+        Some(TransferResult::new(
+            tcp_info.bytes_received?,
+            Duration::from_micros(tcp_info.elapsed_time?),
+        ))
+    }
+}
+
+/// `1.2.3.4` of `1.2.3.4:5678`, or `::1` of `[::1]:5678`.
+fn client_ip(address: &str) -> String {
+    address
+        .parse::<SocketAddr>()
+        .map_or(address.to_string(), |address| address.ip().to_string())
+}
+
+/// Opens the WebSocket of a phase, looking up the server with `dns`.
+async fn connect(url: &str, dns: &Dns) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+    let mut request = url.into_client_request()?;
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static(SUBPROTOCOL),
+    );
+    let parsed = Url::parse(url)?;
+    let host = parsed.host_str().unwrap_or_default();
+    let port = parsed.port_or_known_default().unwrap_or(80);
+
+    let connect = async {
+        let addrs = dns.lookup(host, port).await?;
+        let stream = TcpStream::connect(addrs.as_slice()).await?;
+        Ok::<_, Error>(tokio_tungstenite::client_async_tls(request, stream).await?)
+    };
+    let (socket, _) = tokio::time::timeout(CONNECT_TIMEOUT, connect)
+        .await
+        .map_err(|_| Error::Other("connecting to the ndt7 server timed out".to_string()))??;
+    Ok(socket)
+}
+
+/// Receives until the server closes the connection, adding the bytes to `downloaded` as they
+/// arrive.
+pub async fn download(
+    url: &str,
+    dns: &Dns,
+    downloaded: Arc<AtomicU64>,
+    cancellation: &CancellationToken,
+) -> Result<Ndt7Phase> {
+    let mut socket = connect(url, dns).await?;
+    let mut measurements = Measurements::default();
+    let mut bytes = 0;
+    let start = Instant::now();
+    let deadline = tokio::time::sleep(MAX_DOWNLOAD_DURATION);
+    tokio::pin!(deadline);
+
+    loop {
+        let message = tokio::select! {
+            _ = &mut deadline => break,
+            _ = cancellation.cancelled() => break,
+            message = socket.next() => message,
+        };
+        let len = match message {
+            Some(Ok(Message::Binary(data))) => data.len(),
+            Some(Ok(Message::Text(text))) => {
+                measurements.record(text.as_str());
+                text.len()
+            }
+            Some(Ok(Message::Close(_))) | None => break,
+            Some(Ok(_)) => 0,
+            Some(Err(e)) if bytes == 0 => return Err(e.into()),
+            Some(Err(e)) => {
+                trace::debug!("ndt7 download ended early: {}", e);
+                break;
+            }
+        };
+        bytes += len as u64;
+        downloaded.fetch_add(len as u64, Ordering::SeqCst);
+    }
+    let elapsed = start.elapsed();
+    _ = tokio::time::timeout(CLOSE_TIMEOUT, socket.close(None)).await;
+
+    Ok(measurements.phase(TransferResult::new(bytes, elapsed)))
+}
+
+/// Sends for ten seconds, adding the bytes to `uploaded` as they are sent. The rate is the one the
+/// server measured if it reported one.
+pub async fn upload(
+    url: &str,
+    dns: &Dns,
+    uploaded: Arc<AtomicU64>,
+    cancellation: &CancellationToken,
+) -> Result<Ndt7Phase> {
+    let (mut sink, mut stream) = connect(url, dns).await?.split();
+    let mut measurements = Measurements::default();
+    let mut bytes = 0;
+    let start = Instant::now();
+    let deadline = tokio::time::sleep(UPLOAD_DURATION);
+    tokio::pin!(deadline);
+
+    let stop = CancellationToken::new();
+    let sent = {
+        // Polled next to the reads instead of created anew in the select, which would drop a
+        // message halfway through its send whenever a measurement arrives.
+        let send = send_messages(&mut sink, &uploaded, &mut bytes, &stop);
+        tokio::pin!(send);
+        let ended = loop {
+            tokio::select! {
+                _ = &mut deadline => break None,
+                _ = cancellation.cancelled() => break None,
+                sent = &mut send => break Some(sent),
+                message = stream.next() => match message {
+                    Some(Ok(Message::Text(text))) => measurements.record(text.as_str()),
+                    Some(Ok(Message::Close(_))) | None => break None,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        trace::debug!("ndt7 upload ended early: {}", e);
+                        break None;
+                    }
+                },
+            }
+        };
+        match ended {
+            Some(sent) => sent,
+            // Lets the message in flight finish, so the bytes counted are the bytes sent.
+            None => {
+                stop.cancel();
+                if let Ok(Err(e)) = tokio::time::timeout(CLOSE_TIMEOUT, send).await {
+                    trace::debug!("ndt7 upload ended early: {}", e);
+                }
+                Ok(())
+            }
+        }
+    };
+    match sent {
+        Err(e) if bytes == 0 => return Err(e.into()),
+        Err(e) => trace::debug!("ndt7 upload ended early: {}", e),
+        Ok(()) => {}
+    }
+    let elapsed = start.elapsed();
+    _ = tokio::time::timeout(CLOSE_TIMEOUT, sink.close()).await;
+
+    let transfer = measurements
+        .received()
+        .unwrap_or_else(|| TransferResult::new(bytes, elapsed));
+    Ok(measurements.phase(transfer))
+}
+
+/// Sends messages until `stop` is cancelled or the connection fails, doubling their size as the
+/// bytes sent grow.
+async fn send_messages(
+    sink: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    uploaded: &AtomicU64,
+    bytes: &mut u64,
+    stop: &CancellationToken,
+) -> std::result::Result<(), tungstenite::Error> {
+    let mut message = Bytes::from(vec![0; MIN_MESSAGE_SIZE]);
+    while !stop.is_cancelled() {
+        sink.send(Message::Binary(message.clone())).await?;
+        *bytes += message.len() as u64;
+        uploaded.fetch_add(message.len() as u64, Ordering::SeqCst);
+        if message.len() < MAX_MESSAGE_SIZE && (message.len() as u64) < *bytes / SCALING_FRACTION {
+            message = Bytes::from(vec![0; message.len() * 2]);
+        }
+    }
+    Ok(())
+}
+
+/// How the ndt7 phases measure, a single connection per phase.
+pub fn methodology(server: &Ndt7Server) -> Methodology {
+    let protocol =
+        Url::parse(&server.download_url).map_or("ws".to_string(), |url| url.scheme().to_string());
+    let mut methodology = Methodology::new(env!("CARGO_PKG_VERSION"), "mlab", protocol);
+    methodology.download_duration_ms = MAX_DOWNLOAD_DURATION.as_millis() as u64;
+    methodology.upload_duration_ms = UPLOAD_DURATION.as_millis() as u64;
+    methodology
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{
+        Message,
+        handshake::server::{ErrorResponse, Request, Response},
+    };
+    use tokio_util::sync::CancellationToken;
+
+    use crate::{
+        dns::Dns,
+        model::Server,
+        ndt7::{Measurements, SPONSOR, client_ip, download, parse_locate, upload},
+        net::IpFamily,
+    };
+
+    #[test]
+    fn test_parse_locate() {
+        let body = r#"{"results":[
+            {"machine":"mlab1-hkg01.mlab-oti.measurement-lab.org",
+             "location":{"city":"Hong Kong","country":"HK"},
+             "urls":{
+                "ws:///ndt/v7/download":"ws://ndt-mlab1-hkg01.mlab-oti.measurement-lab.org/ndt/v7/download?access_token=a",
+                "ws:///ndt/v7/upload":"ws://ndt-mlab1-hkg01.mlab-oti.measurement-lab.org/ndt/v7/upload?access_token=b",
+                "wss:///ndt/v7/download":"wss://ndt-mlab1-hkg01.mlab-oti.measurement-lab.org/ndt/v7/download?access_token=c",
+                "wss:///ndt/v7/upload":"wss://ndt-mlab1-hkg01.mlab-oti.measurement-lab.org/ndt/v7/upload?access_token=d"}},
+            {"machine":"mlab2-tpe01.mlab-oti.measurement-lab.org",
+             "urls":{"ws:///ndt/v7/download":"ws://ndt-mlab2-tpe01.mlab-oti.measurement-lab.org/ndt/v7/download?access_token=e"}}
+        ]}"#;
+
+        let servers = parse_locate(body).unwrap();
+        assert_eq!(servers.len(), 1);
+        let server = &servers[0];
+        assert_eq!(server.city, "Hong Kong");
+        assert!(server.download_url.starts_with("wss://"));
+        assert!(server.upload_url.ends_with("access_token=d"));
+
+        let recorded = Server::from(server);
+        assert_eq!(recorded.sponsor, SPONSOR);
+        assert_eq!(recorded.cc, "HK");
+        assert_eq!(
+            recorded.url,
+            "wss://ndt-mlab1-hkg01.mlab-oti.measurement-lab.org/ndt/v7/download"
+        );
+        assert_eq!(
+            recorded.host,
+            "ndt-mlab1-hkg01.mlab-oti.measurement-lab.org"
+        );
+
+        assert!(parse_locate("{}").unwrap().is_empty());
+        assert!(parse_locate("<html>").is_err());
+    }
+
+    /// Accepts the subprotocol the client asks for, the client refuses the connection otherwise.
+    #[allow(clippy::result_large_err)]
+    fn echo_subprotocol(
+        request: &Request,
+        mut response: Response,
+    ) -> Result<Response, ErrorResponse> {
+        let protocol = request.headers()["Sec-WebSocket-Protocol"].clone();
+        response
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", protocol);
+        Ok(response)
+    }
+
+    /// An ndt7 download server sending `chunks` messages of 64 KiB after a measurement.
+    async fn download_server(chunks: usize) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, echo_subprotocol)
+                .await
+                .unwrap();
+            let measurement = r#"{"ConnectionInfo":{"Client":"127.0.0.1:5","Server":"","UUID":""},"TCPInfo":{"RTT":5000,"MinRTT":4000}}"#;
+            socket.send(Message::text(measurement)).await.unwrap();
+            for _ in 0..chunks {
+                let chunk = Bytes::from(vec![0; 64 * 1024]);
+                socket.send(Message::Binary(chunk)).await.unwrap();
+            }
+            socket.close(None).await.unwrap();
+        });
+        format!("ws://{address}/ndt/v7/download")
+    }
+
+    #[tokio::test]
+    async fn test_download() {
+        let url = download_server(16).await;
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let token = CancellationToken::new();
+        let dns = Dns::default().with_family(Some(IpFamily::V4));
+        let phase = download(&url, &dns, downloaded.clone(), &token)
+            .await
+            .unwrap();
+
+        assert!(phase.transfer.bytes >= 16 * 64 * 1024);
+        assert_eq!(downloaded.load(Ordering::SeqCst), phase.transfer.bytes);
+        assert_eq!(phase.client_ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(phase.min_rtt, Some(Duration::from_millis(4)));
+
+        let token = CancellationToken::new();
+        let refused = download("ws://127.0.0.1:1/ndt/v7/download", &dns, downloaded, &token).await;
+        assert!(refused.is_err());
+
+        let url = download_server(1).await;
+        let dns = Dns::default().with_family(Some(IpFamily::V6));
+        let e = download(&url, &dns, Arc::default(), &token)
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "127.0.0.1 has no IPv6 address");
+    }
+
+    /// Stops the upload after a while and compares the bytes counted with the bytes the server
+    /// received, measurements arriving meanwhile must not cut a message short.
+    #[tokio::test]
+    async fn test_upload() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ndt/v7/upload", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, echo_subprotocol)
+                .await
+                .unwrap();
+            let mut received = 0;
+            let mut interval = tokio::time::interval(Duration::from_millis(5));
+            // Measurements the client has not read when it closes would reset the connection.
+            let mut measurements = 20;
+            loop {
+                tokio::select! {
+                    _ = interval.tick(), if measurements > 0 => {
+                        measurements -= 1;
+                        let measurement = r#"{"TCPInfo":{"RTT":5000,"MinRTT":4000}}"#;
+                        _ = socket.send(Message::text(measurement)).await;
+                    }
+                    message = socket.next() => match message {
+                        Some(Ok(Message::Binary(data))) => received += data.len() as u64,
+                        Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                        Some(Ok(_)) => {}
+                    },
+                }
+            }
+            received
+        });
+
+        let uploaded = Arc::new(AtomicU64::new(0));
+        let token = CancellationToken::new();
+        tokio::spawn({
+            let token = token.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                token.cancel();
+            }
+        });
+        let dns = Dns::default().with_family(Some(IpFamily::V4));
+        let phase = upload(&url, &dns, uploaded.clone(), &token).await.unwrap();
+
+        let received = server.await.unwrap();
+        assert!(received > 0);
+        assert_eq!(phase.transfer.bytes, received);
+        assert_eq!(uploaded.load(Ordering::SeqCst), received);
+        assert_eq!(phase.min_rtt, Some(Duration::from_millis(4)));
+    }
+
+    #[test]
+    fn test_measurements() {
+        let mut measurements = Measurements::default();
+        measurements.record(
+            r#"{"ConnectionInfo":{"Client":"[2001:db8::1]:51234","Server":"[2001:db8::2]:443","UUID":"x"},
+                "Origin":"server","Test":"upload",
+                "TCPInfo":{"RTT":24000,"MinRTT":12000,"BytesReceived":1000000,"ElapsedTime":1000000}}"#,
+        );
+        measurements.record("not json");
+        measurements.record(
+            r#"{"Origin":"server","Test":"upload",
+                "TCPInfo":{"RTT":30000,"MinRTT":11000,"BytesReceived":12500000,"ElapsedTime":10000000}}"#,
+        );
+
+        assert_eq!(measurements.client_ip.as_deref(), Some("2001:db8::1"));
+        assert_eq!(measurements.min_rtt, Some(Duration::from_millis(11)));
+        let received = measurements.received().unwrap();
+        assert_eq!(received.bits_per_second, 10_000_000.0);
+
+        let phase = measurements.phase(received);
+        let latency = phase.latency.unwrap();
+        assert_eq!((latency.samples, latency.avg_ms), (2, 27.0));
+
+        assert_eq!(client_ip("203.0.113.45:60000"), "203.0.113.45");
+        assert_eq!(client_ip("unknown"), "unknown");
+    }
+}
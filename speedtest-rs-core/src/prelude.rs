@@ -0,0 +1,21 @@
+//! The types most embedders need, `use speedtest_rs_core::prelude::*;`.
+
+pub use crate::{
+    Humanize,
+    error::Error,
+    http::HttpClient,
+    ifstats::{CounterCheck, IdleTraffic, LinkInfo},
+    latency::Latency,
+    model::{Client, Config, Server, ServerFilter},
+    redact::Redact,
+    result::{Diagnostics, SpeedTestResult, TransferErrors, TransferResult, UploadAccounting},
+    retry::RetryPolicy,
+    sampler::{Sampler, ThroughputSample},
+    scoring::Scoring,
+    size::ByteSize,
+    speed_tester::{CaptivePortal, FetchFailed, NoConnectivity, SpeedTester},
+    stage::{
+        Discovery, ProgressTracker, RaceProbe, Selection, ServerChoice, Stage, StageEvent, TestPlan,
+    },
+    urls::SpeedTestUrl,
+};
@@ -0,0 +1,33 @@
+pub use speedtest_rs_types::redact::{
+    REDACTED, Redact, redact_coordinate, redact_host, redact_ip, redact_url,
+};
+
+use crate::{
+    model::{Client, Server},
+    result::ServerInfo,
+};
+
+impl Redact for Client {
+    fn redact(&self) -> Self {
+        Self {
+            ip: redact_ip(&self.ip),
+            lat: redact_coordinate(self.lat),
+            lon: redact_coordinate(self.lon),
+            ..self.clone()
+        }
+    }
+}
+
+impl Redact for Server {
+    fn redact(&self) -> Self {
+        let info = ServerInfo::from(self).redact();
+        Self {
+            id: info.id,
+            name: info.name,
+            sponsor: info.sponsor,
+            host: info.host,
+            url: info.url,
+            ..self.clone()
+        }
+    }
+}
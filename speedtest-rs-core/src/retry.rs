@@ -0,0 +1,80 @@
+//! Retries of the discovery fetches, so a config host that hiccups once doesn't fail the run.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the config and server lists are fetched before giving up, see
+/// [`SpeedTester::with_retry_policy`](crate::speed_tester::SpeedTester::with_retry_policy). Every
+/// attempt is a pass over all URLs of the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// At least one.
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for every further one.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Time limit of one pass, `None` leaves it to the request timeout.
+    pub attempt_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+            attempt_timeout: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single pass without retries.
+    pub fn once() -> Self {
+        Self {
+            attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Backoff before retry `retry`, starting at 1. `jitter` in `0.0..1.0` picks a delay between
+    /// half and all of the exponential one, so clients failing together don't retry together.
+    pub fn delay(&self, retry: u32, jitter: f64) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        exponential.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Uniform in `0.0..1.0`, from the clock since retries are rare and need no real randomness.
+pub(crate) fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    // SplitMix64 finalizer, the low bits of the clock are often zero.
+    let mut z = u64::from(nanos).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::retry::{RetryPolicy, jitter};
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, 1.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(3, 0.5), Duration::from_millis(1500));
+        assert_eq!(policy.delay(10, 1.0), Duration::from_secs(8));
+        assert_eq!(policy.delay(u32::MAX, 0.0), Duration::from_secs(4));
+
+        assert!((0.0..1.0).contains(&jitter()));
+    }
+}
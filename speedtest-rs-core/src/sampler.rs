@@ -0,0 +1,175 @@
+//! Throughput of a running transfer, sampled from the byte counter passed to it, so all frontends
+//! chart the same numbers.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{sync::mpsc, time::Instant};
+
+use crate::stage::Stage;
+
+/// Rate of a transfer phase over the interval ending at `instant`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct ThroughputSample {
+    pub phase: Stage,
+    pub instant: Instant,
+    pub bytes_per_sec: f64,
+}
+
+/// Reads the byte counter of a transfer phase at a fixed interval, see
+/// [`SpeedTester::sample_interval`](crate::speed_tester::SpeedTester::sample_interval).
+#[derive(Debug)]
+pub struct Sampler {
+    phase: Stage,
+    counter: Arc<AtomicU64>,
+    interval: Duration,
+    last: Instant,
+    last_bytes: u64,
+    samples: Vec<ThroughputSample>,
+}
+
+impl Sampler {
+    /// Starts sampling from the current value of `counter`, so counters shared by several runs
+    /// need no reset.
+    pub fn new(phase: Stage, counter: Arc<AtomicU64>, interval: Duration) -> Self {
+        let last_bytes = counter.load(Ordering::SeqCst);
+        Self {
+            phase,
+            counter,
+            interval,
+            last: Instant::now(),
+            last_bytes,
+            samples: vec![],
+        }
+    }
+
+    /// The samples taken so far. The last one of a phase is left out if it covers less than half
+    /// an interval, too short to be representative.
+    pub fn samples(&self) -> &[ThroughputSample] {
+        &self.samples
+    }
+
+    /// The rate since the last sample once the interval passed, `None` in between.
+    pub fn sample(&mut self, now: Instant) -> Option<ThroughputSample> {
+        if now.duration_since(self.last) < self.interval {
+            return None;
+        }
+        self.finish(now)
+    }
+
+    /// The rate since the last sample regardless of the interval, e.g. at the end of the phase.
+    /// `None` if no time passed.
+    pub fn finish(&mut self, now: Instant) -> Option<ThroughputSample> {
+        let elapsed = now.duration_since(self.last);
+        if elapsed.is_zero() {
+            return None;
+        }
+
+        let bytes = self.counter.load(Ordering::SeqCst);
+        // Failed uploads are taken out of the counter again, so it can go backwards.
+        let moved = bytes.saturating_sub(self.last_bytes);
+        self.last = now;
+        self.last_bytes = bytes;
+        let sample = ThroughputSample {
+            phase: self.phase,
+            instant: now,
+            bytes_per_sec: moved as f64 / elapsed.as_secs_f64(),
+        };
+        if elapsed * 2 >= self.interval {
+            self.samples.push(sample);
+        }
+        Some(sample)
+    }
+
+    /// Runs `transfer`, sending a sample to `samples` every interval and a last one when it ends.
+    pub async fn run<F, T>(&mut self, samples: &mpsc::UnboundedSender<T>, transfer: F) -> F::Output
+    where
+        F: Future,
+        T: From<ThroughputSample>,
+    {
+        self.drive(transfer, |sample| _ = samples.send(sample.into()))
+            .await
+    }
+
+    /// Runs `transfer` sampling it every interval, for [`samples`](Self::samples) only.
+    pub async fn record<F: Future>(&mut self, transfer: F) -> F::Output {
+        self.drive(transfer, |_| {}).await
+    }
+
+    async fn drive<F: Future>(
+        &mut self,
+        transfer: F,
+        mut emit: impl FnMut(ThroughputSample),
+    ) -> F::Output {
+        let mut ticks = tokio::time::interval_at(self.last + self.interval, self.interval);
+        let mut transfer = std::pin::pin!(transfer);
+        loop {
+            tokio::select! {
+                output = &mut transfer => {
+                    if let Some(sample) = self.finish(Instant::now()) {
+                        emit(sample);
+                    }
+                    return output;
+                }
+                now = ticks.tick() => {
+                    if let Some(sample) = self.sample(now) {
+                        emit(sample);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::Duration,
+    };
+
+    use tokio::sync::mpsc;
+
+    use crate::{
+        sampler::{Sampler, ThroughputSample},
+        stage::Stage,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sampler() {
+        let counter = Arc::new(AtomicU64::new(5_000));
+        let mut sampler = Sampler::new(Stage::Upload, counter.clone(), Duration::from_millis(500));
+        let (sender, mut receiver) = mpsc::unbounded_channel::<ThroughputSample>();
+
+        let transfer = async {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            counter.fetch_add(1_000, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            counter.fetch_add(2_000, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            // A failed upload taken out again.
+            counter.fetch_sub(1_500, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            42
+        };
+        assert_eq!(sampler.run(&sender, transfer).await, 42);
+        drop(sender);
+
+        let mut rates = vec![];
+        while let Some(sample) = receiver.recv().await {
+            assert_eq!(sample.phase, Stage::Upload);
+            rates.push(sample.bytes_per_sec);
+        }
+        assert_eq!(rates, [2_000.0, 4_000.0, 0.0]);
+        assert_eq!(sampler.samples().len(), 3);
+    }
+}
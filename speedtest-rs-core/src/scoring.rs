@@ -0,0 +1,175 @@
+//! Weighted server selection combining latency and distance, more stable than the lowest ping
+//! alone when many servers answer within a few milliseconds of each other.
+
+use std::{str::FromStr, time::Duration};
+
+use crate::{
+    error::ParseError,
+    model::{Client, Server},
+};
+
+/// Distance adding as much to the score as the latency of the fastest server, at equal weights.
+const DISTANCE_UNIT_KM: f64 = 1000.0;
+
+/// Weights of the latency of a server, relative to the fastest candidate, and its distance in
+/// thousands of kilometers. The server with the lowest weighted sum wins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scoring {
+    pub latency_weight: f64,
+    pub distance_weight: f64,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            latency_weight: 1.0,
+            distance_weight: 0.5,
+        }
+    }
+}
+
+/// Parses weights like `latency=1,distance=0.5`, omitted weights keep their default.
+impl FromStr for Scoring {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scoring = Self::default();
+
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| ParseError::new(format!("expected key=value, got '{pair}'")))?;
+            let weight: f64 = value
+                .trim()
+                .parse()
+                .map_err(|_| ParseError::new(format!("invalid weight '{value}'")))?;
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(ParseError::new(format!(
+                    "weights must not be negative, got {weight}"
+                )));
+            }
+            match key.trim() {
+                "latency" => scoring.latency_weight = weight,
+                "distance" => scoring.distance_weight = weight,
+                other => return Err(ParseError::new(format!("unknown weight '{other}'"))),
+            }
+        }
+        Ok(scoring)
+    }
+}
+
+impl Scoring {
+    /// The best of the raced servers by their average latency.
+    pub fn pick(&self, client: &Client, raced: Vec<(Server, Duration)>) -> Option<Server> {
+        self.rank(client, raced).into_iter().next()
+    }
+
+    /// The raced servers ordered by their score, best first.
+    pub fn rank(&self, client: &Client, raced: Vec<(Server, Duration)>) -> Vec<Server> {
+        let candidates: Vec<(Server, f64, f64)> = raced
+            .into_iter()
+            .map(|(server, latency)| {
+                let distance = distance_km(client.lat, client.lon, server.lat, server.lon);
+                (server, latency.as_secs_f64(), distance)
+            })
+            .collect();
+
+        let fastest = candidates
+            .iter()
+            .map(|c| c.1)
+            .fold(f64::INFINITY, f64::min)
+            .max(f64::EPSILON);
+
+        let mut scored: Vec<(Server, f64)> = candidates
+            .into_iter()
+            .map(|(server, latency, distance)| {
+                let score = self.latency_weight * latency / fastest
+                    + self.distance_weight * distance / DISTANCE_UNIT_KM;
+                (server, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+        scored.into_iter().map(|(server, _)| server).collect()
+    }
+}
+
+/// Great-circle distance in kilometers.
+pub fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        model::{Client, Server},
+        scoring::{Scoring, distance_km},
+    };
+
+    fn server(id: &str, lat: f64, lon: f64) -> Server {
+        Server {
+            url: format!("http://{id}.example.com:8080/speedtest/upload.php"),
+            lat,
+            lon,
+            name: id.to_string(),
+            country: id.to_string(),
+            cc: "XX".to_string(),
+            sponsor: id.to_string(),
+            id: id.to_string(),
+            host: format!("{id}.example.com:8080"),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_scoring() {
+        assert_eq!(
+            "distance=2".parse::<Scoring>().unwrap(),
+            Scoring {
+                latency_weight: 1.0,
+                distance_weight: 2.0,
+            }
+        );
+        assert!("distance=-1".parse::<Scoring>().is_err());
+        assert!("capacity=1".parse::<Scoring>().is_err());
+
+        // Hong Kong to Taipei.
+        let distance = distance_km(22.28, 114.16, 25.03, 121.57);
+        assert!((distance - 810.0).abs() < 10.0, "{distance}");
+
+        let client = Client {
+            ip: "203.0.113.45".to_string(),
+            lat: 22.28,
+            lon: 114.16,
+            isp: "Example".to_string(),
+            isprating: 0.0,
+            rating: 0.0,
+            ispdlavg: 0.0,
+            ispulavg: 0.0,
+            loggedin: 0,
+            country: "HK".to_string(),
+        };
+        let raced = vec![
+            (server("near", 22.3, 114.2), Duration::from_millis(12)),
+            (server("far", 35.7, 139.7), Duration::from_millis(10)),
+        ];
+
+        let pick = |scoring: Scoring| scoring.pick(&client, raced.clone()).unwrap().id;
+        assert_eq!(pick(Scoring::default()), "near");
+        assert_eq!(
+            pick(Scoring {
+                latency_weight: 1.0,
+                distance_weight: 0.0,
+            }),
+            "far"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Registering results with speedtest.net, which answers with the id of a shareable result image.
+
+use crate::result::SpeedTestResult;
+
+/// Salt of the hash speedtest.net checks the submitted figures against.
+const SHARE_SALT: &str = "297aae72";
+
+/// Page the API expects submissions from.
+pub(crate) const SHARE_REFERER: &str = "http://c.speedtest.net/flash/speedtest.swf";
+
+/// Form body of the results API, rates in kbps and the latency in whole milliseconds. `None`
+/// unless both transfers ran.
+pub(crate) fn share_form(result: &SpeedTestResult) -> Option<String> {
+    let kbps = |bits_per_second: f64| (bits_per_second / 1000.0).round() as u64;
+    let download = result.download?;
+    let upload = result.upload?;
+    let (download_kbps, upload_kbps) =
+        (kbps(download.bits_per_second), kbps(upload.bits_per_second));
+    let ping = result
+        .idle_latency
+        .map_or(0, |latency| latency.avg_ms.round() as u64);
+    let hash = md5::compute(format!("{ping}-{upload_kbps}-{download_kbps}-{SHARE_SALT}"));
+    let server_id = &result.server.id;
+
+    Some(
+        [
+            format!("recommendedserverid={server_id}"),
+            format!("ping={ping}"),
+            "screenresolution=".to_string(),
+            "promo=".to_string(),
+            format!("download={download_kbps}"),
+            "screendpi=".to_string(),
+            format!("upload={upload_kbps}"),
+            "testmethod=http".to_string(),
+            format!("hash={hash:x}"),
+            "touchscreen=none".to_string(),
+            "startmode=pingselect".to_string(),
+            "accuracy=1".to_string(),
+            format!("bytesreceived={}", download.bytes),
+            format!("bytessent={}", upload.bytes),
+            format!("serverid={server_id}"),
+        ]
+        .join("&"),
+    )
+}
+
+/// The result id of an API answer like `resultid=17385920342&date=...`.
+pub(crate) fn parse_result_id(body: &str) -> Option<&str> {
+    body.trim()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("resultid="))
+        .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+/// Image of a shared result.
+pub fn result_image_url(result_id: &str) -> String {
+    format!("http://www.speedtest.net/result/{result_id}.png")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::{
+        latency::Latency,
+        result::{ClientInfo, ServerInfo, SpeedTestResult, TransferResult},
+        share::{parse_result_id, result_image_url, share_form},
+    };
+
+    #[test]
+    fn test_share_form() {
+        let client = ClientInfo {
+            ip: "203.0.113.45".to_string(),
+            lat: 22.3,
+            lon: 114.2,
+            isp: "Example ISP".to_string(),
+            country: "HK".to_string(),
+        };
+        let server = ServerInfo {
+            id: "1536".to_string(),
+            name: "Hong Kong".to_string(),
+            sponsor: "HKIX".to_string(),
+            country: "Hong Kong".to_string(),
+            cc: "HK".to_string(),
+            host: "speed.example.com:8080".to_string(),
+            url: "http://speed.example.com:8080/speedtest/upload.php".to_string(),
+            lat: 22.3,
+            lon: 114.2,
+            extra: Default::default(),
+        };
+        let mut result = SpeedTestResult::new(Utc::now(), client, server)
+            .with_download(TransferResult::new(125_000_000, Duration::from_secs(10)));
+        assert_eq!(share_form(&result), None);
+
+        result = result.with_upload(TransferResult::new(25_000_000, Duration::from_secs(10)));
+        result.idle_latency = Latency::from_samples(&[Duration::from_micros(8_600)], 0);
+        let form = share_form(&result).unwrap();
+        assert!(form.starts_with("recommendedserverid=1536&ping=9&"));
+        assert!(form.contains("&download=100000&"));
+        assert!(form.contains("&upload=20000&"));
+        assert!(form.contains(&format!(
+            "&hash={:x}&",
+            md5::compute("9-20000-100000-297aae72")
+        )));
+        assert!(form.ends_with("&bytesreceived=125000000&bytessent=25000000&serverid=1536"));
+    }
+
+    #[test]
+    fn test_parse_result_id() {
+        let body = "resultid=17385920342&date=1%2F7%2F2025&time=2%3A02+PM&rating=0\n";
+        assert_eq!(parse_result_id(body), Some("17385920342"));
+        assert_eq!(parse_result_id("error=Invalid+hash"), None);
+        assert_eq!(parse_result_id("resultid=&date=1"), None);
+        assert_eq!(
+            result_image_url("17385920342"),
+            "http://www.speedtest.net/result/17385920342.png"
+        );
+    }
+}
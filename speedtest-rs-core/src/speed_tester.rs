@@ -1,68 +1,243 @@
 use std::{
+    collections::HashMap,
     net::IpAddr,
     sync::{
-        Arc,
-        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
     },
-    time::{Duration, Instant},
+    time::Duration,
 };
 
 use bytes::Bytes;
-use futures::{Stream, StreamExt, TryStreamExt, stream};
-use reqwest::{IntoUrl, header::CONTENT_LENGTH};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::{Stream, StreamExt, stream};
 use serde::de::DeserializeOwned;
+use tokio::{sync::mpsc, time::Instant};
 
+pub use speedtest_rs_types::error::{CaptivePortal, FetchFailed, FetchFailure, NoConnectivity};
+pub use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "impairment")]
+use crate::impair::Impairment;
+use crate::{
+    Humanize,
+    dns::{Dns, Resolver},
+    error::{Error, Result},
+    http::{
+        HttpClient, HttpError, HttpRequest, HttpResponse, HttpVersion, StatusCode, Url, Version,
+        header::{CONTENT_LENGTH, CONTENT_TYPE, DATE, HeaderValue, REFERER},
+    },
+    impair::Impairer,
+    latency::Latency,
+    model::{
+        Client, Config, ConfigOverrides, JsonServer, PhaseOverrides, Server, ServerFilter,
+        ServerList, Servers, WarmUp, filter_servers, group_by_host, group_by_sponsor,
+    },
+    net::IpFamily,
+    result::{
+        CAPTIVE_PORTAL_SUSPICION, Methodology, RejectedServer, SpeedTestResult, TransferErrors,
+        TransferResult, UploadAccounting,
+    },
+    retry::{self, RetryPolicy},
+    sampler::Sampler,
+    scoring::Scoring,
+    share,
+    size::ByteSize,
+    stage::{self, Discovery, RaceProbe, Selection, ServerChoice, Stage, StageEvent, TestPlan},
+    throughput::ThroughputStats,
+    trace,
+    urls::{CAPTIVE_PORTAL_PROBE_URL, CONNECTIVITY_PROBE_URL, SpeedTestUrl},
+};
+#[cfg(feature = "ndt7")]
 use crate::{
-    model::{Config, Server, Servers},
-    urls::SpeedTestUrl,
+    ndt7::{self, Ndt7Server},
+    result::ClientInfo,
 };
 
 const UPLOAD_CHUNK: [u8; 1024 * 16] = [0; 1024 * 16];
 
+/// Concurrency multiplier once the download sequence is exhausted before the test duration.
+const SATURATION_FACTOR: usize = 4;
+
+/// Consecutive failures after which saturation stops, the server failing rather than being fast.
+const SATURATION_MAX_FAILURES: u32 = 6;
+
+/// Wait after a failed saturation fetch, doubled for every further consecutive failure.
+const SATURATION_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Concurrent transfers in background mode.
+const BACKGROUND_CONCURRENCY: usize = 2;
+
+/// Pause after every transfer request in background mode, leaving room for other traffic.
+const BACKGROUND_PAUSE: Duration = Duration::from_millis(250);
+
+/// Servers probed at the same time by [`SpeedTester::rank_servers`].
+const RANK_CONCURRENCY: usize = 8;
+
+/// Best candidates by latency compared by [`SpeedTester::with_capacity_probe`].
+const CAPACITY_CANDIDATES: usize = 3;
+
+/// Length of the download burst against each capacity candidate.
+const CAPACITY_PROBE_DURATION: Duration = Duration::from_secs(2);
+
+/// Concurrent downloads of a capacity burst.
+const CAPACITY_PROBE_CONNECTIONS: usize = 4;
+
+/// Side length of the images downloaded in a capacity burst, about 4 MB each.
+const CAPACITY_PROBE_SIZE: usize = 2000;
+
+/// Discovery URLs requested at the same time by default, enough to race all of them.
+const DISCOVERY_CONCURRENCY: usize = 4;
+
+/// Idle latency phase of [`SpeedTester::run_full_test`].
+const IDLE_LATENCY_DURATION: Duration = Duration::from_secs(2);
+
+/// Pause between two latency probes.
+const PING_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Selected servers checked before giving up when each fails validation.
+const VALIDATION_ATTEMPTS: usize = 3;
+
+/// Smallest plausible size of a download image, captive portals answer with short pages.
+const MIN_ASSET_BYTES: u64 = 1024;
+
+/// Timeout of the DNS lookup and of the request of the connectivity check, each.
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Timeout of the captive portal probe, the probe URL answers instantly on an open network.
+const CAPTIVE_PORTAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time between the checks of a transfer counter for the end of a warm-up by bytes.
+const WARM_UP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Side lengths of the download images each server was found to host, by server id.
+pub type AssetSizes = HashMap<String, Vec<usize>>;
+
 #[derive(Debug, Clone)]
 pub struct SpeedTester {
     urls: SpeedTestUrl,
-    client: reqwest::Client,
+    client: Arc<dyn HttpClient>,
     request_timeout: Duration,
     compare_times: usize,
     compare_interval: Duration,
+    max_concurrency: Option<usize>,
+    overrides: ConfigOverrides,
+    background: bool,
+    verify_upload: bool,
+    search: Option<String>,
+    country_code: Option<String>,
+    server_filter: Option<ServerFilter>,
+    respect_ignore_ids: bool,
+    scoring: Option<Scoring>,
+    per_sponsor: bool,
+    ip_dedup: bool,
+    closest_servers: Option<usize>,
+    server_choice: Option<ServerChoice>,
+    test_plan: TestPlan,
+    duplicates: Arc<Mutex<HashMap<String, String>>>,
+    sample_interval: Option<Duration>,
+    warm_up: Option<WarmUp>,
+    latency_probes: Option<usize>,
+    capacity_probe: bool,
+    asset_probe: bool,
+    asset_sizes: Arc<Mutex<AssetSizes>>,
+    /// Protocol of the last transfer response by server id.
+    negotiated: Arc<Mutex<HashMap<String, Version>>>,
+    validate_servers: bool,
+    connectivity_probe: Option<String>,
+    captive_portal_probe: Option<String>,
+    discovery_concurrency: usize,
+    retry_policy: RetryPolicy,
+    cancellation: CancellationToken,
+    progress: Option<mpsc::UnboundedSender<StageEvent>>,
+    impairer: Impairer,
+    dns: Dns,
+    http_version: HttpVersion,
+    /// Local address of the reqwest client the tester built itself, `None` for clients passed in.
+    #[cfg(feature = "reqwest")]
+    own_client: Option<Option<IpAddr>>,
 
     config: Option<Config>,
     server: Option<Server>,
 }
 
+#[cfg(feature = "reqwest")]
 impl Default for SpeedTester {
     fn default() -> Self {
-        Self::new(
-            reqwest::ClientBuilder::new()
-                .user_agent("SPEED-TESTER-RS")
-                .build()
-                .expect("build client failed"),
-        )
+        let dns = Dns::default();
+        Self {
+            own_client: Some(None),
+            dns: dns.clone(),
+            ..Self::new(reqwest_client(None, &dns, HttpVersion::default()))
+        }
     }
 }
 
 impl SpeedTester {
+    #[cfg(feature = "reqwest")]
     pub fn new(client: reqwest::Client) -> Self {
+        Self::new_with_http_client(client)
+    }
+
+    /// Sends the requests through another HTTP stack than reqwest.
+    pub fn new_with_http_client(client: impl HttpClient + 'static) -> Self {
         Self {
             urls: SpeedTestUrl::default(),
-            client,
+            client: Arc::new(client),
             config: None,
             server: None,
             request_timeout: Duration::from_secs(10),
             compare_times: 3,
             compare_interval: Duration::from_millis(200),
+            max_concurrency: None,
+            overrides: ConfigOverrides::default(),
+            background: false,
+            verify_upload: false,
+            search: None,
+            country_code: None,
+            server_filter: None,
+            respect_ignore_ids: true,
+            scoring: None,
+            per_sponsor: false,
+            ip_dedup: false,
+            closest_servers: None,
+            server_choice: None,
+            test_plan: TestPlan::default(),
+            duplicates: Arc::default(),
+            sample_interval: None,
+            warm_up: None,
+            latency_probes: None,
+            capacity_probe: false,
+            asset_probe: false,
+            asset_sizes: Arc::default(),
+            negotiated: Arc::default(),
+            validate_servers: true,
+            connectivity_probe: Some(CONNECTIVITY_PROBE_URL.to_string()),
+            captive_portal_probe: Some(CAPTIVE_PORTAL_PROBE_URL.to_string()),
+            discovery_concurrency: DISCOVERY_CONCURRENCY,
+            retry_policy: RetryPolicy::default(),
+            cancellation: CancellationToken::new(),
+            progress: None,
+            impairer: Impairer::default(),
+            dns: Dns::default(),
+            http_version: HttpVersion::default(),
+            #[cfg(feature = "reqwest")]
+            own_client: None,
         }
     }
 
+    #[cfg(feature = "reqwest")]
     pub fn new_with_local_addr(local_addr: IpAddr) -> Self {
-        let client = reqwest::Client::builder()
-            .local_address(local_addr)
-            .user_agent("SPEED-TESTER-RS")
-            .build()
-            .unwrap();
-
-        Self::new(client)
+        let dns = Dns::default();
+        Self {
+            own_client: Some(Some(local_addr)),
+            dns: dns.clone(),
+            ..Self::new(reqwest_client(
+                Some(local_addr),
+                &dns,
+                HttpVersion::default(),
+            ))
+        }
     }
 
     pub fn with_url(mut self, urls: SpeedTestUrl) -> Self {
@@ -81,352 +256,3101 @@ impl SpeedTester {
         self
     }
 
-    pub fn with_client(mut self, client: reqwest::Client) -> Self {
-        self.client = client;
-        self
+    #[cfg(feature = "reqwest")]
+    pub fn with_client(self, client: reqwest::Client) -> Self {
+        self.with_http_client(client)
     }
 
-    pub async fn initialize(&mut self) -> anyhow::Result<()> {
-        if self.config.is_some() && self.server.is_some() {
-            tracing::debug!("SpeedTester already initialized.");
-            return Ok(());
+    pub fn with_http_client(mut self, client: impl HttpClient + 'static) -> Self {
+        self.client = Arc::new(client);
+        #[cfg(feature = "reqwest")]
+        {
+            self.own_client = None;
         }
+        self
+    }
 
-        tracing::debug!("SpeedTester fetch config...");
-        let config = self.fetch_config().await?;
-
-        tracing::debug!("SpeedTester fetch config success {:?}", config);
-
-        tracing::debug!("SpeedTester fetch servers...");
-        let mut servers = self.fetch_servers(config.threads()).await?;
-        tracing::debug!("SpeedTester fetch servers success {:?}", servers);
+    /// Connects over IPv4 or IPv6 only, and resolves the hosts the tester looks up itself to that
+    /// family. The client of [`Default`] and [`new_with_local_addr`](Self::new_with_local_addr)
+    /// is rebuilt to skip the addresses of the other family, clients passed in have to be
+    /// restricted by the caller. The family is recorded in the methodology of the results.
+    pub fn with_ip_family(mut self, family: Option<IpFamily>) -> Self {
+        self.dns = self.dns.with_family(family);
+        self.rebuild_own_client();
+        self
+    }
 
-        self.filter_ignored_servers(&mut servers.servers.servers, &config);
+    /// Resolves the host names with `resolver` instead of the system resolver, e.g. a
+    /// [`DohResolver`](crate::dns::DohResolver) on networks that tamper with DNS. Like
+    /// [`with_ip_family`](Self::with_ip_family), clients passed in keep resolving on their own.
+    /// The first lookup of every host is timed in the diagnostics of the results.
+    pub fn with_resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.dns = Dns::new(resolver).with_family(self.dns.family());
+        self.rebuild_own_client();
+        self
+    }
 
-        self.config = Some(config);
+    /// Allows HTTP/2 for the transfers, which multiplexes the concurrent requests of a phase over
+    /// one connection instead of opening one per request, and measures a different throughput
+    /// than the HTTP/1.1 default. Like [`with_ip_family`](Self::with_ip_family), clients passed
+    /// in negotiate on their own. The protocol the transfers used is recorded in the methodology
+    /// of the results.
+    pub fn with_http_version(mut self, version: HttpVersion) -> Self {
+        self.http_version = version;
+        self.rebuild_own_client();
+        self
+    }
 
-        let fastest_server = self.select_fastest_server(servers.servers.servers).await?;
-        tracing::debug!(
-            "SpeedTester select fastest server success: {:?}",
-            fastest_server.url
-        );
+    /// Applies a changed resolver or HTTP version to the reqwest client the tester built itself.
+    fn rebuild_own_client(&mut self) {
+        #[cfg(feature = "reqwest")]
+        if let Some(local_addr) = self.own_client {
+            self.client = Arc::new(reqwest_client(local_addr, &self.dns, self.http_version));
+        }
+    }
 
-        self.server = Some(fastest_server);
+    /// Caps the number of concurrent download/upload requests, regardless of the thread counts
+    /// suggested by the remote config. Useful on constrained devices such as phones and routers.
+    pub fn with_max_concurrency(mut self, max_concurrency: Option<usize>) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
 
-        Ok(())
+    /// All local settings merged over the fetched config at once, replacing those set before.
+    pub fn with_config_overrides(mut self, overrides: ConfigOverrides) -> Self {
+        self.overrides = overrides;
+        self
     }
 
-    pub async fn do_download(&mut self, downloaded: Arc<AtomicU64>) -> anyhow::Result<()> {
-        self.initialize().await?;
+    /// Duration and connection count of the download, over the values of the remote config.
+    pub fn with_download_overrides(mut self, overrides: PhaseOverrides) -> Self {
+        self.overrides.download = overrides;
+        self
+    }
 
-        let config = self.get_config()?;
-        let server = self.get_server()?;
+    /// Duration and connection count of the upload, over the values of the remote config.
+    pub fn with_upload_overrides(mut self, overrides: PhaseOverrides) -> Self {
+        self.overrides.upload = overrides;
+        self
+    }
 
-        self.download(config, server, downloaded).await;
-        Ok(())
+    /// Side lengths of the downloaded images in the order they are requested, over the default
+    /// sequence. `None` or an empty list keeps the default.
+    pub fn with_download_sizes(mut self, sizes: Option<Vec<usize>>) -> Self {
+        self.overrides.download_sizes = sizes;
+        self
     }
 
-    pub async fn do_upload(&mut self, uploaded: Arc<AtomicU64>) -> anyhow::Result<()> {
-        self.initialize().await?;
+    /// Upload request sizes in the order they are sent, over the sequence of the remote config.
+    /// `None` or an empty list keeps the remote one.
+    pub fn with_upload_sizes(mut self, sizes: Option<Vec<ByteSize>>) -> Self {
+        self.overrides.upload_sizes = sizes;
+        self
+    }
 
-        let config = self.get_config()?;
-        let server = self.get_server()?;
+    /// Low-priority mode for scheduled runs on shared connections: fewer connections and a pause
+    /// after every request. Results under-report the peak capacity.
+    pub fn with_background(mut self, background: bool) -> Self {
+        self.background = background;
+        self
+    }
 
-        self.upload(config, server, uploaded).await;
-        Ok(())
+    pub fn is_background(&self) -> bool {
+        self.background
     }
 
-    pub async fn fetch_config(&self) -> anyhow::Result<Config> {
-        for url in self.urls.config_urls() {
-            match self.get_xml(url).await {
-                Ok(settings) => return Ok(settings),
-                Err(e) => tracing::debug!("failed to fetch config: {}", e),
-            }
-        }
+    /// Stages measured by [`run_full_test`](Self::run_full_test) and the other complete runs,
+    /// e.g. a download-only run that never sets up an upload. Skipped stages are `None` in the
+    /// result.
+    pub fn with_test_plan(mut self, plan: TestPlan) -> Self {
+        self.test_plan = plan;
+        self
+    }
 
-        anyhow::bail!("all fetch config failed")
+    pub fn test_plan(&self) -> TestPlan {
+        self.test_plan
     }
 
-    pub async fn fetch_servers(&self, threads: usize) -> anyhow::Result<Servers> {
-        let urls = self.urls.clone().threads(threads);
-        for url in urls.server_urls() {
-            match self.get_xml(url).await {
-                Ok(servers) => return Ok(servers),
-                Err(e) => tracing::debug!("failed to fetch servers: {}", e),
-            }
-        }
-        anyhow::bail!("all fetch servers failed")
+    /// Checks that the server reports to have received every upload completely, and counts
+    /// incomplete uploads as failed.
+    pub fn with_upload_verification(mut self, verify: bool) -> Self {
+        self.verify_upload = verify;
+        self
     }
 
-    pub async fn select_fastest_server(&self, servers: Vec<Server>) -> anyhow::Result<Server> {
-        if servers.is_empty() {
-            anyhow::bail!("no servers");
-        }
+    /// Takes the candidate servers from a search by city, sponsor or host name instead of the
+    /// servers near the client, which are often far off for distant clients.
+    pub fn with_search(mut self, keyword: Option<String>) -> Self {
+        self.search = keyword;
+        self
+    }
 
-        let times = self.compare_times;
-        let interval = self.compare_interval;
-        let timeout = self.request_timeout;
-        let (tx, mut rx) = tokio::sync::mpsc::channel(servers.len());
-        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    /// Only considers servers in the country with this ISO code, e.g. `JP`.
+    pub fn with_country_code(mut self, cc: Option<String>) -> Self {
+        self.country_code = cc;
+        self
+    }
 
-        for server in servers {
-            let client = self.client.clone();
-            let tx = tx.clone();
-            let mut shutdown = shutdown_rx.clone();
+    /// Only considers the servers matching the filter, e.g. of one sponsor in a large country. An
+    /// empty filter is the same as none.
+    pub fn with_server_filter(mut self, filter: Option<ServerFilter>) -> Self {
+        self.server_filter = filter.filter(|filter| !filter.is_empty());
+        self
+    }
 
-            tokio::spawn(async move {
-                let mut delay = Duration::default();
-                for i in 0..times {
-                    tokio::select! {
-                        _ = shutdown.changed() => {
-                            return;
-                        }
-                        current_delay = SpeedTester::get_server_delay(&client, &server, timeout) => {
-                            delay += current_delay;
-                        }
-                    }
-                    if i < times - 1 {
-                        tokio::time::sleep(interval).await;
-                    }
-                }
-                _ = tx.send((server, delay)).await;
-            });
-        }
+    /// Whether to drop the servers the remote config lists in `ignoreids`, enabled by default.
+    pub fn with_ignore_ids(mut self, respect: bool) -> Self {
+        self.respect_ignore_ids = respect;
+        self
+    }
 
-        let (server, delay) = rx.recv().await.unwrap();
+    /// Selects the server by latency and distance instead of the lowest latency alone.
+    pub fn with_scoring(mut self, scoring: Option<Scoring>) -> Self {
+        self.scoring = scoring;
+        self
+    }
 
-        if delay < timeout * 2 {
-            _ = shutdown_tx.send(true);
-            return Ok(server);
-        }
-        let mut server_delays = vec![];
-        server_delays.push((server, delay));
+    /// Keeps only the best server of every sponsor after ranking, so the capacity probe compares
+    /// different operators and a server failing validation is replaced by another operator's.
+    pub fn with_per_sponsor(mut self, per_sponsor: bool) -> Self {
+        self.per_sponsor = per_sponsor;
+        self
+    }
 
-        while let Some((server, delay)) = rx.recv().await {
-            server_delays.push((server, delay));
-        }
+    /// Races servers whose hosts resolve to the same address only once, on top of the servers
+    /// listed with the same host which are always raced once.
+    pub fn with_ip_dedup(mut self, ip_dedup: bool) -> Self {
+        self.ip_dedup = ip_dedup;
+        self
+    }
 
-        server_delays.sort_by(|a, b| a.1.cmp(&b.1));
+    /// Id of the server raced in place of `server_id` because both point at the same machine.
+    pub fn duplicate_of(&self, server_id: &str) -> Option<String> {
+        self.duplicates.lock().unwrap().get(server_id).cloned()
+    }
 
-        if server_delays[0].1 < timeout * 2 * times as u32 {
-            return Ok(server_delays[0].0.clone());
-        }
-        anyhow::bail!("all servers failed")
+    /// Tests against this server instead of racing, for reproducible benchmarks. A server looked up
+    /// by id or host fails [`pick_server`](Self::pick_server) if it is not in the server list.
+    pub fn with_server(mut self, choice: Option<ServerChoice>) -> Self {
+        self.server_choice = choice;
+        self
     }
 
-    pub async fn download(&self, config: &Config, server: &Server, downloaded: Arc<AtomicU64>) {
-        let seq = config.download_size_sequence();
+    /// Races only the `n` servers nearest to the client, like the official client, instead of all
+    /// candidates. Far servers rarely win and slow the race down on long lists.
+    pub fn with_closest_servers(mut self, n: Option<usize>) -> Self {
+        self.closest_servers = n;
+        self
+    }
 
-        let max_download_count = config.download_count_per_url() * seq.len();
+    /// Fixed interval frontends sample the transfer counters at, `None` adapts it to the transfer
+    /// duration, see [`sample_interval`](crate::stage::sample_interval).
+    pub fn with_sample_interval(mut self, interval: Option<Duration>) -> Self {
+        self.sample_interval = interval;
+        self
+    }
 
-        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    /// Interval to sample the counters of a transfer lasting `transfer` at.
+    pub fn sample_interval(&self, transfer: Duration) -> Duration {
+        self.sample_interval
+            .unwrap_or_else(|| stage::sample_interval(transfer))
+    }
 
-        let tasks = stream::iter(0..max_download_count).for_each_concurrent(
-            config.download_threads(),
-            |i| {
-                let size = seq[i % seq.len()];
-                let url = format!("{}/random{}x{}.jpg", server.url, size, size);
-                let client = self.client.clone();
-                let downloaded = downloaded.clone();
-                let shutdown = shutdown_rx.clone();
+    /// Start of every transfer phase left out of its reported rate, see
+    /// [`TransferResult::with_warm_up`]. `None` counts the bytes from the first request on.
+    pub fn with_warm_up(mut self, warm_up: Option<WarmUp>) -> Self {
+        self.warm_up = warm_up;
+        self
+    }
 
-                async move { Self::single_download(client, url, downloaded, shutdown).await }
-            },
-        );
+    /// Number of probes of the idle latency phase, `None` probes for the duration the caller asks
+    /// for.
+    pub fn with_latency_probes(mut self, probes: Option<usize>) -> Self {
+        self.latency_probes = probes;
+        self
+    }
 
-        tokio::select! {
-            biased;
-            _ = tokio::time::sleep(config.max_download_duration()) => {
-                _ = shutdown_tx.send(true);
-            }
-            _ = tasks => {
-            }
+    /// Expected time of the idle latency phase: `duration`, or the probes at their interval.
+    pub fn idle_latency_duration(&self, duration: Duration) -> Duration {
+        match self.latency_probes {
+            Some(probes) => PING_INTERVAL * probes as u32,
+            None => duration,
         }
     }
 
-    pub async fn upload(&self, config: &Config, server: &Server, uploaded: Arc<AtomicU64>) {
-        let seq = config.upload_size_sequence();
+    /// Compares the best few servers by latency with a short download burst each and picks the
+    /// one with the highest throughput, since the lowest latency does not imply the most capacity.
+    pub fn with_capacity_probe(mut self, capacity_probe: bool) -> Self {
+        self.capacity_probe = capacity_probe;
+        self
+    }
 
-        let max_upload_count = config.max_upload_count();
-        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    /// Checks which download images the server hosts before downloading and skips the missing
+    /// ones, for nonstandard servers. The result is cached per server id.
+    pub fn with_asset_probe(mut self, asset_probe: bool) -> Self {
+        self.asset_probe = asset_probe;
+        self
+    }
 
-        let tasks =
-            stream::iter(0..max_upload_count).for_each_concurrent(config.upload_threads(), |i| {
-                let size = seq[i % seq.len()];
-                let url = server.url.clone();
-                let client = self.client.clone();
-                let uploaded = uploaded.clone();
-                let shutdown = shutdown_rx.clone();
+    /// Checks the selected server with a HEAD request before the transfers and falls back to the
+    /// next best server if it fails, enabled by default.
+    pub fn with_server_validation(mut self, validate: bool) -> Self {
+        self.validate_servers = validate;
+        self
+    }
 
-                async move { Self::single_upload(client, url, size, uploaded, shutdown).await }
-            });
+    /// Number of config and server list URLs requested at the same time, the first answer wins
+    /// and the other requests are cancelled. `1` tries them one after the other.
+    pub fn with_discovery_concurrency(mut self, concurrency: usize) -> Self {
+        self.discovery_concurrency = concurrency.max(1);
+        self
+    }
 
-        tokio::select! {
-            biased;
-            _ = tokio::time::sleep(config.max_upload_duration()) => {
-                _ = shutdown_tx.send(true);
-            }
-            _ = tasks => {
-            }
-        }
+    /// Retries of the config and server list fetches, three attempts with jittered exponential
+    /// backoff by default.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
     }
 
-    pub fn get_config(&self) -> anyhow::Result<&Config> {
-        self.config.as_ref().ok_or(anyhow::anyhow!(
-            "config is empty. maybe call initialize first"
-        ))
+    /// URL resolved and requested before the discovery to fail fast when offline, `None` skips the
+    /// connectivity check. Defaults to [`CONNECTIVITY_PROBE_URL`].
+    pub fn with_connectivity_probe(mut self, url: Option<String>) -> Self {
+        self.connectivity_probe = url;
+        self
     }
 
-    pub fn get_server(&self) -> anyhow::Result<&Server> {
-        self.server.as_ref().ok_or(anyhow::anyhow!(
-            "server is empty. maybe call initialize first"
-        ))
+    /// URL expected to answer `204 No Content` before the discovery, `None` skips the captive
+    /// portal check. Defaults to [`CAPTIVE_PORTAL_PROBE_URL`].
+    pub fn with_captive_portal_probe(mut self, url: Option<String>) -> Self {
+        self.captive_portal_probe = url;
+        self
     }
 
-    pub fn filter_ignored_servers(&self, servers: &mut Vec<Server>, config: &Config) {
-        let ignore_ids = config.ignore_servers().collect::<Vec<_>>();
+    /// Seeds the asset probe cache, e.g. with the sizes of a previous process.
+    pub fn with_asset_sizes(self, asset_sizes: AssetSizes) -> Self {
+        *self.asset_sizes.lock().unwrap() = asset_sizes;
+        self
+    }
 
-        servers.retain(|s| !ignore_ids.contains(&s.id.as_str()));
+    /// The download sizes found by the asset probe so far, by server id.
+    pub fn asset_sizes(&self) -> AssetSizes {
+        self.asset_sizes.lock().unwrap().clone()
     }
 
-    async fn get_xml<T, U>(&self, url: U) -> anyhow::Result<T>
-    where
-        T: DeserializeOwned,
-        U: IntoUrl,
-    {
-        let resp = self
-            .client
-            .get(url)
-            .timeout(self.request_timeout)
-            .send()
-            .await?;
-        let status = resp.status();
+    /// Remembers the protocol a transfer to `server` used, for the methodology.
+    fn note_version(&self, server: &Server, version: Version) {
+        self.negotiated
+            .lock()
+            .unwrap()
+            .insert(server.id.clone(), version);
+    }
 
-        if status.is_success() {
-            let xml = resp.text().await?;
-            let ret: T = quick_xml::de::from_str(xml.as_str())?;
-            Ok(ret)
+    pub fn upload_accounting(&self) -> UploadAccounting {
+        if self.verify_upload {
+            UploadAccounting::Verified
         } else {
-            anyhow::bail!("status: {}", status);
+            UploadAccounting::Acknowledged
         }
     }
 
-    async fn get_server_delay(
-        client: &reqwest::Client,
-        server: &Server,
-        timeout: Duration,
-    ) -> Duration {
-        let start = Instant::now();
-
-        match client.get(&server.url).timeout(timeout).send().await {
-            Ok(resp) => {
-                if resp.bytes().await.is_ok() {
-                    return start.elapsed();
-                }
-            }
-            Err(e) => {
-                tracing::debug!("get server delay for {} failed: {}", server.url, e);
-            }
+    /// Describes how [`SpeedTester::download`] and [`SpeedTester::upload`] measure against
+    /// `server` with `config`.
+    pub async fn methodology(&self, config: &Config, server: &Server) -> Methodology {
+        let download_sizes = self.download_sequence(config, server).await;
+        let protocol = download_sizes
+            .first()
+            .and_then(|size| Url::parse(&self.urls.download_url(server, *size)).ok())
+            .map_or("http".to_string(), |url| url.scheme().to_string());
+
+        let mut methodology = Methodology::new(env!("CARGO_PKG_VERSION"), "ookla", protocol);
+        methodology.download_connections = self.concurrency(config.download_threads());
+        methodology.upload_connections = self.concurrency(config.upload_threads());
+        methodology.download_sizes = download_sizes;
+        methodology.upload_sizes = config
+            .upload_size_sequence()
+            .into_iter()
+            .map(|size| size.0)
+            .collect();
+        methodology.download_duration_ms = config.max_download_duration().as_millis() as u64;
+        methodology.upload_duration_ms = config.max_upload_duration().as_millis() as u64;
+        if let Some(warm_up) = self.warm_up {
+            methodology.warm_up = format!("excluded, first {warm_up}");
         }
-        timeout * 2
+        methodology.background = self.background;
+        methodology.ip_family = match self.dns.family() {
+            Some(family) => Some(family),
+            None => resolve(&self.dns, &server.host).await.map(IpFamily::of),
+        };
+        methodology.http_version = self
+            .negotiated
+            .lock()
+            .unwrap()
+            .get(&server.id)
+            .copied()
+            .map(version_name);
+        methodology
     }
 
-    async fn single_download(
-        client: reqwest::Client,
-        url: String,
-        downloaded: Arc<AtomicU64>,
-        mut shutdown: tokio::sync::watch::Receiver<bool>,
-    ) {
-        let mut resp = match client.get(&url).send().await {
-            Ok(resp) => resp,
-            Err(e) => {
-                tracing::debug!("download {} failed: {}", url, e);
-                return;
-            }
-        };
+    /// Ends the running phase early once `token` is cancelled: the race stops with the servers
+    /// probed so far, the latency with the probes sent so far, and the transfers keep the bytes
+    /// counted so far. Clones of the tester share the token.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
 
-        tokio::select! {
-            biased;
-            _ = shutdown.changed() => {
-            }
-            _ = async {
-                while let Ok(Some(chunk)) = resp.chunk().await {
-                    _ = downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
-                }
-            } => {}
-        }
+    /// Reports the stages to `events` as they run, see [`stage::report`]: the discovery from
+    /// [`fetch_config`](Self::fetch_config) to [`fetch_servers`](Self::fetch_servers), the race,
+    /// the idle latency and the transfers. Clones of the tester share the channel.
+    pub fn with_progress(mut self, events: Option<mpsc::UnboundedSender<StageEvent>>) -> Self {
+        self.progress = events;
+        self
     }
 
-    async fn single_upload(
-        client: reqwest::Client,
-        url: String,
-        size: usize,
-        uploaded: Arc<AtomicU64>,
-        mut shutdown: tokio::sync::watch::Receiver<bool>,
-    ) {
-        let body = Self::create_zero_stream(size, uploaded);
+    fn emit(&self, event: StageEvent) {
+        if let Some(events) = &self.progress {
+            _ = events.send(event);
+        }
+    }
 
-        tokio::select! {
-            biased;
-            _ = shutdown.changed() => {
-            }
-            _ = client
-                .post(url)
-                .body(reqwest::Body::wrap_stream(body))
-                .header(CONTENT_LENGTH, size)
-                .send() => {
-                }
+    /// Runs `future` as `stage`, reported to the channel of [`with_progress`](Self::with_progress).
+    async fn report<F: Future>(
+        &self,
+        stage: Stage,
+        counter: Option<&AtomicU64>,
+        future: F,
+    ) -> F::Output {
+        match &self.progress {
+            Some(events) => stage::report(stage, counter, events, future).await,
+            None => future.await,
         }
-        // client.post(url).body(body)
     }
 
-    fn create_zero_stream(
-        size: usize,
-        uploaded: Arc<AtomicU64>,
-    ) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
-        stream::unfold(size, |remaining| async move {
-            if remaining == 0 {
-                None
-            } else {
-                let chunk_size = remaining.min(UPLOAD_CHUNK.len());
+    /// Simulates a slow or flaky network for development, see [`Impairment`].
+    #[cfg(feature = "impairment")]
+    pub fn with_impairment(mut self, impairment: Impairment) -> Self {
+        self.impairer = Impairer::new(impairment);
+        self
+    }
 
-                let chunk = Bytes::from_static(&UPLOAD_CHUNK[..chunk_size]);
+    pub async fn initialize(&mut self) -> Result<()> {
+        if self.config.is_some() && self.server.is_some() {
+            trace::debug!("SpeedTester already initialized.");
+            return Ok(());
+        }
 
-                let next_state = remaining - chunk_size;
+        let discovery = self.discover().await?;
+        let selection = self.pick_server(&discovery).await?;
 
-                Some((Ok(chunk), next_state))
-            }
-        })
-        .inspect_ok(move |chunk| {
-            uploaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
-        })
-    }
+        self.config = Some(selection.config);
+        self.server = Some(selection.server);
+
+        Ok(())
+    }
+
+    /// Fetches the remote config and the candidate servers.
+    pub async fn discover(&self) -> Result<Discovery> {
+        self.check_connectivity().await?;
+        self.check_captive_portal().await?;
+
+        trace::debug!("SpeedTester fetch config...");
+        let config = self.fetch_config().await?;
+        trace::debug!("SpeedTester fetch config success {:?}", config);
+
+        trace::debug!("SpeedTester fetch servers...");
+        let mut servers = self.fetch_servers(config.threads()).await?;
+        trace::debug!("SpeedTester fetch servers success {:?}", servers);
+
+        let ignored = self.filter_ignored_servers(&mut servers.servers.servers, &config);
+
+        Ok(Discovery {
+            config,
+            servers: servers.servers.servers,
+            ignored,
+        })
+    }
+
+    /// Races the discovered servers and picks the fastest one.
+    pub async fn pick_server(&self, discovery: &Discovery) -> Result<Selection> {
+        if let Some(server) = self.chosen_server(&discovery.servers)? {
+            trace::debug!("testing against server {} without a race", server.id);
+            return Ok(Selection::new(discovery.config.clone(), server));
+        }
+
+        let (server, _) = self
+            .select_valid_server_with_probes(&discovery.config, discovery.servers.clone(), None)
+            .await?;
+        trace::debug!(
+            "SpeedTester select fastest server success: {:?}",
+            server.url
+        );
+
+        Ok(Selection::new(discovery.config.clone(), server))
+    }
+
+    /// Probes the latency of the selected server for `duration` without any load.
+    pub async fn measure_latency(
+        &self,
+        selection: &Selection,
+        duration: Duration,
+    ) -> Option<Latency> {
+        self.idle_latency(&selection.server, duration).await
+    }
+
+    /// Runs every stage from discovery to upload, with the latency probed idle and under load.
+    /// Frontends showing progress compose the stages themselves.
+    pub async fn run_full_test(&self) -> Result<SpeedTestResult> {
+        let discovery = self.discover().await?;
+        let selection = self.pick_server(&discovery).await?;
+        Ok(self.test_selection(&discovery, selection).await)
+    }
+
+    /// Runs the full test against the `n` servers of the lowest latency one after the other, e.g.
+    /// to compare the routes to several points of presence. The results are in the order of the
+    /// latency ranking; unreachable servers are left out.
+    pub async fn run_multi_server_test(&self, n: usize) -> Result<Vec<SpeedTestResult>> {
+        let discovery = self.discover().await?;
+        let candidates = self.race_candidates(&discovery.config.client, discovery.servers.clone());
+        let servers: Vec<Server> = self
+            .rank_servers(candidates)
+            .await
+            .into_iter()
+            .filter(|(_, latency)| latency.is_some())
+            .take(n)
+            .map(|(server, _)| server)
+            .collect();
+        if servers.is_empty() {
+            return Err(Error::NoServers("no reachable servers".to_string()));
+        }
+
+        let mut results = Vec::with_capacity(servers.len());
+        for server in servers {
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+            trace::debug!("testing against server {} of {}", results.len() + 1, n);
+            let selection = Selection::new(discovery.config.clone(), server);
+            results.push(self.test_selection(&discovery, selection).await);
+        }
+        Ok(results)
+    }
+
+    /// Runs the latency and transfer stages of the test plan against a selected server.
+    async fn test_selection(&self, discovery: &Discovery, selection: Selection) -> SpeedTestResult {
+        let timestamp = Utc::now();
+        let Selection { config, server } = selection;
+        let mut result = SpeedTestResult::new(timestamp, &config.client, &server)
+            .with_background(self.background);
+        if self.test_plan.latency {
+            result.idle_latency = self.idle_latency(&server, IDLE_LATENCY_DURATION).await;
+        }
+
+        if self.test_plan.download {
+            let downloaded = Arc::new(AtomicU64::new(0));
+            let ((download_errors, download), download_latency) = self
+                .loaded_latency(
+                    &server,
+                    self.measure_transfer(
+                        &config,
+                        Stage::Download,
+                        &downloaded,
+                        self.download(&config, &server, downloaded.clone()),
+                    ),
+                )
+                .await;
+            result = result.with_download(download);
+            result.download_latency = download_latency;
+            result.diagnostics.download_errors = download_errors;
+        }
+
+        if self.test_plan.upload {
+            let uploaded = Arc::new(AtomicU64::new(0));
+            let (((upload_errors, upload_size_cap), upload), upload_latency) = self
+                .loaded_latency(
+                    &server,
+                    self.measure_transfer(
+                        &config,
+                        Stage::Upload,
+                        &uploaded,
+                        self.upload(&config, &server, uploaded.clone()),
+                    ),
+                )
+                .await;
+            result = result.with_upload(upload);
+            result.upload_latency = upload_latency;
+            result.diagnostics.upload_errors = upload_errors;
+            result.diagnostics.upload_size_cap = upload_size_cap;
+            result.diagnostics.upload_accounting = Some(self.upload_accounting());
+        }
+
+        result.methodology = Some(self.methodology(&config, &server).await);
+        result.diagnostics.ignored_servers = discovery.ignored.iter().map(Into::into).collect();
+        result.diagnostics.resolutions = self.dns.resolutions();
+        result.warnings = result.detect_warnings();
+        result
+    }
+
+    /// Runs a download phase against the selected server.
+    pub async fn measure_download(&self, selection: &Selection) -> TransferResult {
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let download = self.download(&selection.config, &selection.server, downloaded.clone());
+
+        self.measure_transfer(&selection.config, Stage::Download, &downloaded, download)
+            .await
+            .1
+    }
+
+    /// Runs an upload phase against the selected server.
+    pub async fn measure_upload(&self, selection: &Selection) -> TransferResult {
+        let uploaded = Arc::new(AtomicU64::new(0));
+        let upload = self.upload(&selection.config, &selection.server, uploaded.clone());
+
+        self.measure_transfer(&selection.config, Stage::Upload, &uploaded, upload)
+            .await
+            .1
+    }
+
+    pub async fn do_download(&mut self, downloaded: Arc<AtomicU64>) -> Result<()> {
+        self.initialize().await?;
+
+        let config = self.get_config()?;
+        let server = self.get_server()?;
+
+        self.download(config, server, downloaded).await;
+        Ok(())
+    }
+
+    pub async fn do_upload(&mut self, uploaded: Arc<AtomicU64>) -> Result<()> {
+        self.initialize().await?;
+
+        let config = self.get_config()?;
+        let server = self.get_server()?;
+
+        _ = self.upload(config, server, uploaded).await;
+        Ok(())
+    }
+
+    /// Fails with [`NoConnectivity`] within seconds if the host of the probe URL does not resolve
+    /// or does not answer, instead of timing out on every discovery URL.
+    pub async fn check_connectivity(&self) -> Result<()> {
+        let Some(url) = &self.connectivity_probe else {
+            return Ok(());
+        };
+        let parsed = Url::parse(url)?;
+        let host = parsed.host_str().unwrap_or_default();
+        let port = parsed.port_or_known_default().unwrap_or(80);
+        let offline = |reason: String| Err(NoConnectivity { reason }.into());
+
+        match tokio::time::timeout(CONNECTIVITY_TIMEOUT, self.dns.lookup(host, port)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return offline(format!("cannot resolve {host} ({e})")),
+            Err(_) => return offline(format!("resolving {host} timed out")),
+        }
+
+        // Any status will do, even a captive portal proves a route to the network.
+        if let Err(e) = self
+            .client
+            .send(HttpRequest::head(url).with_timeout(CONNECTIVITY_TIMEOUT))
+            .await
+        {
+            return offline(format!("{host} {}", e.cause()));
+        }
+        Ok(())
+    }
+
+    /// Fails with [`CaptivePortal`] if the probe URL is redirected or answers anything but an
+    /// empty `204`. Unreachable probes pass, the discovery reports connectivity problems.
+    pub async fn check_captive_portal(&self) -> Result<()> {
+        let Some(url) = &self.captive_portal_probe else {
+            return Ok(());
+        };
+
+        let resp = match self
+            .client
+            .send(HttpRequest::get(url).with_timeout(CAPTIVE_PORTAL_TIMEOUT))
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                trace::debug!("captive portal probe failed: {}", e);
+                return Ok(());
+            }
+        };
+
+        if Url::parse(url).ok().as_ref() != Some(&resp.url) {
+            let location = resp.url.host_str().map(str::to_string);
+            return Err(CaptivePortal { location }.into());
+        }
+        if resp.status != StatusCode::NO_CONTENT {
+            trace::debug!("captive portal probe answered {}", resp.status);
+            return Err(CaptivePortal { location: None }.into());
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, name = "fetch_config")
+    )]
+    pub async fn fetch_config(&self) -> Result<Config> {
+        self.emit(StageEvent::Started {
+            stage: Stage::Discovery,
+        });
+        // Static servers work without speedtest.net, e.g. in air-gapped networks, so a missing
+        // config is not worth waiting for.
+        let fallback = !self.urls.static_servers().is_empty();
+        let policy = if fallback {
+            RetryPolicy::once()
+        } else {
+            self.retry_policy
+        };
+        let fetched = self
+            .fetch_with_retry("config", policy, self.urls.config_urls(), |url| {
+                self.get_xml::<Config>(url)
+            })
+            .await;
+        let mut config = match fetched {
+            Ok(config) => config,
+            Err(e @ Error::FetchFailed(_)) if fallback => {
+                trace::debug!("{}, using the stock config", e);
+                Config::fallback()
+            }
+            Err(e) => return Err(e),
+        };
+        self.overrides.apply(&mut config);
+        Ok(config)
+    }
+
+    /// Fetches the candidate servers: the static servers of the URLs, the servers near the client
+    /// or the results of the configured search, limited to the configured country and filter.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, name = "fetch_servers")
+    )]
+    pub async fn fetch_servers(&self, threads: usize) -> Result<Servers> {
+        let static_servers = self.urls.static_servers();
+        let mut servers = match &self.search {
+            _ if !static_servers.is_empty() => static_servers.to_vec(),
+            Some(keyword) => self.search_servers(Some(keyword)).await?,
+            None => self.fetch_nearby_servers(threads).await?,
+        };
+
+        if let Some(cc) = &self.country_code {
+            servers.retain(|s| s.cc.eq_ignore_ascii_case(cc));
+            // The nearby servers of a distant client rarely include the wanted country.
+            if servers.is_empty() && self.search.is_none() && static_servers.is_empty() {
+                servers = self.search_servers(Some(cc)).await?;
+                servers.retain(|s| s.cc.eq_ignore_ascii_case(cc));
+            }
+        }
+
+        if let Some(filter) = &self.server_filter
+            && !servers.is_empty()
+        {
+            servers = filter_servers(servers, filter);
+            if servers.is_empty() {
+                return Err(Error::NoServers(format!(
+                    "no servers matching the filter: {filter}"
+                )));
+            }
+        }
+
+        if servers.is_empty() {
+            let reason = match (&self.search, &self.country_code) {
+                (None, None) => "no servers found".to_string(),
+                (Some(search), None) => format!("no servers matching '{search}'"),
+                (None, Some(cc)) => format!("no servers in country {cc}"),
+                (Some(search), Some(cc)) => {
+                    format!("no servers matching '{search}' in country {cc}")
+                }
+            };
+            return Err(Error::NoServers(reason));
+        }
+        self.emit(StageEvent::Finished {
+            stage: Stage::Discovery,
+        });
+        Ok(Servers {
+            servers: ServerList { servers },
+        })
+    }
+
+    /// Servers near the client, falling back to the JSON list when the XML lists are unavailable.
+    async fn fetch_nearby_servers(&self, threads: usize) -> Result<Vec<Server>> {
+        let urls = self.urls.clone().threads(threads);
+        let fetched = self
+            .fetch_with_retry("servers", self.retry_policy, urls.server_urls(), |url| {
+                self.get_xml::<Servers>(url)
+            })
+            .await;
+        let mut failed = match fetched {
+            Ok(servers) => return Ok(servers.servers.servers),
+            Err(Error::FetchFailed(failed)) => failed,
+            Err(e) => return Err(e),
+        };
+
+        let error = match self.search_servers(None).await {
+            Ok(servers) if !servers.is_empty() => return Ok(servers),
+            Ok(_) => "no servers".to_string(),
+            Err(e) => e.to_string(),
+        };
+        failed.failures.push(FetchFailure {
+            attempt: failed.attempts,
+            url: self.urls.server_json_url(None),
+            error,
+        });
+        Err(failed.into())
+    }
+
+    /// Passes over `urls` until one answers, as often as `policy` allows, see [`first_success`].
+    /// Fails with [`Error::FetchFailed`], or [`Error::Canceled`] when cancelled between passes.
+    async fn fetch_with_retry<T, F, Fut>(
+        &self,
+        what: &str,
+        policy: RetryPolicy,
+        urls: impl IntoIterator<Item = String>,
+        fetch: F,
+    ) -> Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let urls: Vec<String> = urls.into_iter().collect();
+        let attempts = policy.attempts.max(1);
+        let mut failures = vec![];
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                let delay = policy.delay(attempt - 1, retry::jitter());
+                trace::debug!("fetch {} failed, retrying in {:?}", what, delay);
+                tokio::select! {
+                    _ = self.cancellation.cancelled() => return Err(Error::Canceled),
+                    _ = tokio::time::sleep(delay) => {}
+                }
+            }
+
+            let mut errors = vec![];
+            let pass = first_success(
+                urls.iter().cloned(),
+                self.discovery_concurrency,
+                &fetch,
+                &mut errors,
+            );
+            let fetched = match policy.attempt_timeout {
+                Some(limit) => match tokio::time::timeout(limit, pass).await {
+                    Ok(fetched) => fetched,
+                    Err(_) => {
+                        for url in &urls {
+                            if !errors.iter().any(|(failed, _)| failed == url) {
+                                errors.push((
+                                    url.clone(),
+                                    format!("attempt timed out after {limit:?}"),
+                                ));
+                            }
+                        }
+                        None
+                    }
+                },
+                None => pass.await,
+            };
+            if let Some(value) = fetched {
+                return Ok(value);
+            }
+            failures.extend(errors.into_iter().map(|(url, error)| FetchFailure {
+                attempt,
+                url,
+                error,
+            }));
+        }
+
+        Err(FetchFailed {
+            what: what.to_string(),
+            attempts,
+            failures,
+        }
+        .into())
+    }
+
+    /// Fetches the JSON server list, optionally only the servers matching `keyword` anywhere
+    /// in the world, e.g. a city or sponsor name.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, name = "search_servers")
+    )]
+    pub async fn search_servers(&self, keyword: Option<&str>) -> Result<Vec<Server>> {
+        self.impairer.request().await.map_err(Error::Other)?;
+        let resp = self
+            .client
+            .send(
+                HttpRequest::get(self.urls.server_json_url(keyword))
+                    .with_timeout(self.request_timeout),
+            )
+            .await?;
+        let status = resp.status;
+        if !status.is_success() {
+            return Err(Error::Status(status));
+        }
+
+        let servers: Vec<JsonServer> = serde_json::from_str(&resp.text().await?)?;
+        Ok(servers.into_iter().map(Server::from).collect())
+    }
+
+    /// Keeps the first server of every host, see [`with_ip_dedup`](Self::with_ip_dedup) and
+    /// [`duplicate_of`](Self::duplicate_of).
+    pub async fn dedup_servers(&self, servers: Vec<Server>) -> Vec<Server> {
+        self.group_duplicates(servers)
+            .await
+            .into_iter()
+            .filter_map(|group| group.into_iter().next())
+            .collect()
+    }
+
+    /// Groups the servers pointing at the same machine and remembers all but the first server of
+    /// every group as its duplicates.
+    async fn group_duplicates(&self, servers: Vec<Server>) -> Vec<Vec<Server>> {
+        let count = servers.len();
+        let mut groups: Vec<Vec<Server>> = group_by_host(servers)
+            .into_iter()
+            .map(|group| group.servers)
+            .collect();
+
+        if self.ip_dedup {
+            let addrs = futures::future::join_all(
+                groups
+                    .iter()
+                    .map(|group| resolve(&self.dns, &group[0].host)),
+            )
+            .await;
+            let mut merged: Vec<(Option<IpAddr>, Vec<Server>)> = vec![];
+            for (addr, group) in addrs.into_iter().zip(groups) {
+                // Unresolved hosts stay apart, the race reports them as unreachable.
+                match merged
+                    .iter_mut()
+                    .find(|(a, _)| addr.is_some() && *a == addr)
+                {
+                    Some((_, servers)) => servers.extend(group),
+                    None => merged.push((addr, group)),
+                }
+            }
+            groups = merged.into_iter().map(|(_, group)| group).collect();
+        }
+
+        if groups.len() < count {
+            trace::debug!(
+                "racing {} of {} servers, the others point at the same machines",
+                groups.len(),
+                count
+            );
+        }
+        let mut duplicates = self.duplicates.lock().unwrap();
+        for group in &groups {
+            for duplicate in &group[1..] {
+                duplicates.insert(duplicate.id.clone(), group[0].id.clone());
+            }
+        }
+        groups
+    }
+
+    pub async fn select_fastest_server(&self, servers: Vec<Server>) -> Result<Server> {
+        self.select_fastest_server_with_probes(servers, None).await
+    }
+
+    /// Probes the latency of every server without transferring bulk data, fastest first and
+    /// unreachable servers last. Servers pointing at the same machine are probed once and share
+    /// the latency.
+    pub async fn rank_servers(&self, servers: Vec<Server>) -> Vec<(Server, Option<Latency>)> {
+        let groups = self.group_duplicates(servers).await;
+        let mut ranked: Vec<(Server, Option<Latency>)> = stream::iter(groups)
+            .map(|group| async move {
+                let server = &group[0];
+                let mut samples = vec![];
+                let mut lost = 0;
+                for i in 0..self.compare_times {
+                    match self.ping(server).await {
+                        Some(rtt) => samples.push(rtt),
+                        None => lost += 1,
+                    }
+                    if i < self.compare_times - 1 {
+                        tokio::time::sleep(self.compare_interval).await;
+                    }
+                }
+                let latency = Latency::from_samples(&samples, lost);
+                stream::iter(group.into_iter().map(move |server| (server, latency)))
+            })
+            .buffer_unordered(self.concurrency(RANK_CONCURRENCY))
+            .flatten()
+            .collect()
+            .await;
+
+        ranked.sort_by(|a, b| match (a.1, b.1) {
+            (Some(a), Some(b)) => a.avg_ms.total_cmp(&b.avg_ms),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        ranked
+    }
+
+    /// The server set with [`with_server`](Self::with_server), looked up in `servers`.
+    fn chosen_server(&self, servers: &[Server]) -> Result<Option<Server>> {
+        match &self.server_choice {
+            None => Ok(None),
+            Some(ServerChoice::Server(server)) => Ok(Some(*server.clone())),
+            Some(ServerChoice::IdOrHost(id_or_host)) => servers
+                .iter()
+                .find(|server| server.matches(id_or_host))
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| {
+                    Error::NoServers(format!("server {id_or_host} is not in the server list"))
+                }),
+        }
+    }
+
+    /// The servers with their distance from `client` in kilometers, nearest first.
+    pub fn rank_servers_by_distance(
+        &self,
+        client: &Client,
+        servers: Vec<Server>,
+    ) -> Vec<(Server, f64)> {
+        let mut ranked: Vec<(Server, f64)> = servers
+            .into_iter()
+            .map(|server| {
+                let distance = server.distance_from(client);
+                (server, distance)
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+        ranked
+    }
+
+    /// The candidates to race, limited to the nearest ones if set with
+    /// [`with_closest_servers`](Self::with_closest_servers).
+    fn race_candidates(&self, client: &Client, servers: Vec<Server>) -> Vec<Server> {
+        let Some(n) = self.closest_servers else {
+            return servers;
+        };
+        self.rank_servers_by_distance(client, servers)
+            .into_iter()
+            .take(n)
+            .map(|(server, _)| server)
+            .collect()
+    }
+
+    /// Same as [`SpeedTester::select_fastest_server`], reporting every single probe to `probes`
+    /// as it completes, e.g. to animate the race.
+    pub async fn select_fastest_server_with_probes(
+        &self,
+        servers: Vec<Server>,
+        probes: Option<tokio::sync::mpsc::UnboundedSender<RaceProbe>>,
+    ) -> Result<Server> {
+        let mut raced = self.race(servers, probes, true).await?;
+        raced.sort_by_key(|a| a.1);
+
+        match raced.into_iter().next() {
+            Some((server, delay))
+                if delay < self.request_timeout * 2 * self.compare_times as u32 =>
+            {
+                Ok(server)
+            }
+            _ => Err(Error::NoServers("all servers failed".to_string())),
+        }
+    }
+
+    /// Picks the server to test against: the first to answer all probes, or the best by the
+    /// [`Scoring`] set with [`with_scoring`](Self::with_scoring) or the
+    /// [capacity probe](Self::with_capacity_probe), which race all servers to the end.
+    pub async fn select_server_with_probes(
+        &self,
+        client: &Client,
+        servers: Vec<Server>,
+        probes: Option<tokio::sync::mpsc::UnboundedSender<RaceProbe>>,
+    ) -> Result<Server> {
+        if self.scoring.is_none() && !self.capacity_probe {
+            return self
+                .select_fastest_server_with_probes(servers, probes)
+                .await;
+        }
+
+        let failed = self.request_timeout * 2 * self.compare_times as u32;
+        let mut reachable: Vec<(Server, Duration)> = self
+            .race(servers, probes, false)
+            .await?
+            .into_iter()
+            .filter(|(_, delay)| *delay < failed)
+            .map(|(server, delay)| (server, delay / self.compare_times as u32))
+            .collect();
+
+        let mut ranked = match self.scoring {
+            Some(scoring) => scoring.rank(client, reachable),
+            None => {
+                reachable.sort_by_key(|(_, delay)| *delay);
+                reachable.into_iter().map(|(server, _)| server).collect()
+            }
+        };
+        if self.per_sponsor {
+            ranked = group_by_sponsor(ranked)
+                .into_iter()
+                .filter_map(|group| group.servers.into_iter().next())
+                .collect();
+        }
+        if !self.capacity_probe {
+            return ranked
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::NoServers("all servers failed".to_string()));
+        }
+
+        let mut best: Option<(Server, u64)> = None;
+        for server in ranked.into_iter().take(CAPACITY_CANDIDATES) {
+            let bytes = self.burst_download(&server).await;
+            trace::debug!(
+                "capacity probe of {}: {}",
+                server.id,
+                ByteSize(bytes).humanize_bitrate(CAPACITY_PROBE_DURATION.as_millis() as u64)
+            );
+            // Ties keep the better ranked server.
+            if best.as_ref().is_none_or(|(_, most)| bytes > *most) {
+                best = Some((server, bytes));
+            }
+        }
+        best.map(|(server, _)| server)
+            .ok_or_else(|| Error::NoServers("all servers failed".to_string()))
+    }
+
+    /// Selects a server like [`Self::select_server_with_probes`] and validates it, replacing
+    /// servers that fail by the best of the remaining ones. Returns the rejected servers too.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, name = "racing")
+    )]
+    pub async fn select_valid_server_with_probes(
+        &self,
+        config: &Config,
+        mut servers: Vec<Server>,
+        probes: Option<tokio::sync::mpsc::UnboundedSender<RaceProbe>>,
+    ) -> Result<(Server, Vec<RejectedServer>)> {
+        let mut rejected = vec![];
+        servers = self.race_candidates(&config.client, servers);
+        self.report(Stage::Racing, None, async move {
+            loop {
+                let server = self
+                    .select_server_with_probes(&config.client, servers.clone(), probes.clone())
+                    .await?;
+                let Err(reason) = self.validate_server(config, &server).await else {
+                    return Ok((server, rejected));
+                };
+
+                trace::warning!("server {} failed validation: {}", server.id, reason);
+                if self.per_sponsor {
+                    servers.retain(|s| !s.same_sponsor(&server));
+                } else {
+                    servers.retain(|s| s.id != server.id);
+                }
+                rejected.push(RejectedServer::new(&server, &reason));
+                if rejected.len() >= VALIDATION_ATTEMPTS || servers.is_empty() {
+                    return Err(Error::NoServers(format!(
+                        "{} selected servers failed validation, the last one {reason}",
+                        rejected.len()
+                    )));
+                }
+            }
+        })
+        .await
+    }
+
+    /// Checks with a HEAD request for the smallest download image that `server` serves real
+    /// assets rather than a captive portal page, returning why it does not. Always passes when
+    /// validation is disabled, and for servers that do not support HEAD requests.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "validate_server",
+            fields(server = %server.id),
+        )
+    )]
+    pub async fn validate_server(&self, config: &Config, server: &Server) -> Result<(), String> {
+        if !self.validate_servers {
+            return Ok(());
+        }
+        let size = self
+            .download_sequence(config, server)
+            .await
+            .into_iter()
+            .min()
+            .unwrap_or_default();
+        let url = self.urls.download_url(server, size);
+        let requested_host = Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+
+        let resp = self
+            .client
+            .send(HttpRequest::head(&url).with_timeout(self.request_timeout))
+            .await
+            .map_err(|e| format!("is unreachable ({})", e.cause()))?;
+
+        let host = resp.url.host_str().map(str::to_string);
+        if host != requested_host {
+            return Err(format!(
+                "redirected to {}, {CAPTIVE_PORTAL_SUSPICION}",
+                host.unwrap_or_default()
+            ));
+        }
+        if matches!(
+            resp.status,
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        ) {
+            return Ok(());
+        }
+        if !resp.status.is_success() {
+            return Err(format!("answered HTTP {}", resp.status));
+        }
+        let header = |name| resp.header(name);
+        if header(CONTENT_TYPE).is_some_and(|content_type| content_type.starts_with("text/html")) {
+            return Err(format!(
+                "answered with an HTML page, {CAPTIVE_PORTAL_SUSPICION}"
+            ));
+        }
+        // HEAD responses have no body, the length is only in the header.
+        if let Some(length) = header(CONTENT_LENGTH).and_then(|length| length.parse::<u64>().ok())
+            && length < MIN_ASSET_BYTES
+        {
+            return Err(format!(
+                "announced only {length} bytes for a download image"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Downloads from `server` over a few connections for a moment, returning the bytes received.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "capacity_probe",
+            fields(server = %server.id),
+        )
+    )]
+    async fn burst_download(&self, server: &Server) -> u64 {
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let url = self.urls.download_url(server, CAPACITY_PROBE_SIZE);
+
+        let connections = (0..self.concurrency(CAPACITY_PROBE_CONNECTIONS)).map(|_| {
+            let downloaded = downloaded.clone();
+            let shutdown = shutdown_rx.clone();
+            let url = url.clone();
+            async move {
+                // A failing server ends its connection instead of retrying in a tight loop.
+                while !*shutdown.borrow() {
+                    let result = Self::single_download(
+                        self.client.clone(),
+                        self.impairer.clone(),
+                        url.clone(),
+                        downloaded.clone(),
+                        shutdown.clone(),
+                    )
+                    .await;
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        tokio::select! {
+            biased;
+            _ = tokio::time::sleep(CAPACITY_PROBE_DURATION) => {
+                _ = shutdown_tx.send(true);
+            }
+            _ = futures::future::join_all(connections) => {}
+        }
+        downloaded.load(Ordering::Relaxed)
+    }
+
+    /// Probes every server `compare_times` times, returning the summed delays in the order the
+    /// servers finished. With `first_wins` the race stops at the first server answering in time.
+    async fn race(
+        &self,
+        servers: Vec<Server>,
+        probes: Option<tokio::sync::mpsc::UnboundedSender<RaceProbe>>,
+        first_wins: bool,
+    ) -> Result<Vec<(Server, Duration)>> {
+        let servers = self.dedup_servers(servers).await;
+        if servers.is_empty() {
+            return Err(Error::NoServers("no servers".to_string()));
+        }
+
+        let times = self.compare_times;
+        let interval = self.compare_interval;
+        let timeout = self.request_timeout;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(servers.len());
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+        for server in servers {
+            let client = self.client.clone();
+            let impairer = self.impairer.clone();
+            let tx = tx.clone();
+            let mut shutdown = shutdown_rx.clone();
+            let probes = probes.clone();
+
+            tokio::spawn(async move {
+                let mut delay = Duration::default();
+                for i in 0..times {
+                    tokio::select! {
+                        _ = shutdown.changed() => {
+                            return;
+                        }
+                        current_delay = SpeedTester::get_server_delay(client.as_ref(), &impairer, &server, timeout) => {
+                            delay += current_delay;
+                            if let Some(probes) = &probes {
+                                _ = probes.send(RaceProbe::new(
+                                    server.id.clone(),
+                                    i + 1,
+                                    (current_delay < timeout * 2).then_some(current_delay),
+                                ));
+                            }
+                        }
+                    }
+                    if i < times - 1 {
+                        tokio::time::sleep(interval).await;
+                    }
+                }
+                _ = tx.send((server, delay)).await;
+            });
+        }
+        // Only the racing tasks hold senders now, the channel closes once all of them finished.
+        drop(tx);
+
+        let mut raced = vec![];
+        loop {
+            let (server, delay) = tokio::select! {
+                _ = self.cancellation.cancelled() => {
+                    _ = shutdown_tx.send(true);
+                    if raced.is_empty() {
+                        return Err(Error::Canceled);
+                    }
+                    break;
+                }
+                raced = rx.recv() => match raced {
+                    Some(raced) => raced,
+                    None => break,
+                },
+            };
+            if first_wins && delay < timeout * 2 {
+                _ = shutdown_tx.send(true);
+                return Ok(vec![(server, delay)]);
+            }
+            raced.push((server, delay));
+        }
+        Ok(raced)
+    }
+
+    /// Downloads until the configured duration is over, returning the failed requests.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "download",
+            fields(server = %server.id),
+        )
+    )]
+    pub async fn download(
+        &self,
+        config: &Config,
+        server: &Server,
+        downloaded: Arc<AtomicU64>,
+    ) -> TransferErrors {
+        let errors = Arc::new(Mutex::new(TransferErrors::default()));
+        let seq = self.download_sequence(config, server).await;
+        let largest = seq.iter().copied().max().unwrap_or_default();
+
+        let max_download_count = config.download_count_per_url() * seq.len();
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+        let fetch = |size: usize| {
+            let url = self.urls.download_url(server, size);
+            let client = self.client.clone();
+            let downloaded = downloaded.clone();
+            let shutdown = shutdown_rx.clone();
+            let impairer = self.impairer.clone();
+            let pause = self.pause();
+            let errors = errors.clone();
+
+            async move {
+                let result =
+                    Self::single_download(client, impairer, url, downloaded, shutdown).await;
+                let ok = result.is_ok();
+                match result {
+                    Ok(version) => self.note_version(server, version),
+                    Err(cause) => errors.lock().unwrap().record(cause),
+                }
+                tokio::time::sleep(pause).await;
+                ok
+            }
+        };
+
+        let tasks = async {
+            stream::iter(0..max_download_count)
+                .for_each_concurrent(self.concurrency(config.download_threads()), |i| {
+                    let size = seq[i % seq.len()];
+                    async move {
+                        fetch(size).await;
+                    }
+                })
+                .await;
+
+            // The stock sequence finished before the deadline, the link is faster than the
+            // assets are large (multi-gigabit). Keep it saturated with many concurrent fetches of
+            // the largest asset until the duration is over.
+            if downloaded.load(Ordering::Relaxed) == 0 {
+                // Nothing was received, the server is failing rather than fast.
+                return;
+            }
+            trace::debug!("download sequence exhausted early, saturating with largest asset");
+            // A server failing fast now would be fetched in a tight loop, back off and give up.
+            let failures = &AtomicU32::new(0);
+            stream::repeat(largest)
+                .take_while(|_| {
+                    futures::future::ready(
+                        failures.load(Ordering::Relaxed) < SATURATION_MAX_FAILURES,
+                    )
+                })
+                .for_each_concurrent(
+                    self.concurrency(config.download_threads().max(1) * SATURATION_FACTOR),
+                    |size| async move {
+                        if fetch(size).await {
+                            failures.store(0, Ordering::Relaxed);
+                            return;
+                        }
+                        let failed = failures.fetch_add(1, Ordering::Relaxed) + 1;
+                        if failed < SATURATION_MAX_FAILURES {
+                            tokio::time::sleep(SATURATION_BACKOFF * (1 << (failed - 1))).await;
+                        }
+                    },
+                )
+                .await;
+            trace::debug!(
+                "saturation stopped after {} failures",
+                SATURATION_MAX_FAILURES
+            );
+        };
+
+        tokio::select! {
+            biased;
+            _ = tokio::time::sleep(config.max_download_duration()) => {
+                _ = shutdown_tx.send(true);
+            }
+            _ = self.cancellation.cancelled() => {
+                _ = shutdown_tx.send(true);
+            }
+            _ = tasks => {
+            }
+        }
+
+        let mut errors = errors.lock().unwrap();
+        std::mem::take(&mut *errors)
+    }
+
+    /// The download sequence of the config, limited to the images the server hosts when probing
+    /// is enabled.
+    async fn download_sequence(&self, config: &Config, server: &Server) -> Vec<usize> {
+        let seq = config.download_size_sequence();
+        if !self.asset_probe {
+            return seq;
+        }
+
+        let cached = self.asset_sizes.lock().unwrap().get(&server.id).cloned();
+        let available = match cached {
+            Some(available) => available,
+            None => {
+                let available = self.probe_download_sizes(server, &seq).await;
+                self.asset_sizes
+                    .lock()
+                    .unwrap()
+                    .insert(server.id.clone(), available.clone());
+                available
+            }
+        };
+
+        let filtered: Vec<usize> = seq
+            .iter()
+            .copied()
+            .filter(|size| available.contains(size))
+            .collect();
+        if filtered.is_empty() { seq } else { filtered }
+    }
+
+    /// Checks with HEAD requests which of the download images in `sizes` the server hosts. All
+    /// sizes are kept if none answers, since some servers reject HEAD requests altogether.
+    pub async fn probe_download_sizes(&self, server: &Server, sizes: &[usize]) -> Vec<usize> {
+        let mut distinct = sizes.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        let available: Vec<usize> = stream::iter(distinct.iter().copied())
+            .map(|size| async move {
+                let url = self.urls.download_url(server, size);
+                let found = match self
+                    .client
+                    .send(HttpRequest::head(&url).with_timeout(self.request_timeout))
+                    .await
+                {
+                    Ok(resp) => resp.status.is_success(),
+                    Err(e) => {
+                        trace::debug!("probing {} failed: {}", url, e);
+                        false
+                    }
+                };
+                found.then_some(size)
+            })
+            .buffer_unordered(distinct.len().max(1))
+            .filter_map(|size| async move { size })
+            .collect()
+            .await;
+
+        if available.is_empty() {
+            trace::debug!(
+                "server {} answered no asset probe, keeping all sizes",
+                server.id
+            );
+            return distinct;
+        }
+        let missing: Vec<usize> = distinct
+            .iter()
+            .copied()
+            .filter(|size| !available.contains(size))
+            .collect();
+        if !missing.is_empty() {
+            trace::info!("server {} does not host the sizes {:?}", server.id, missing);
+        }
+        available
+    }
+
+    /// Uploads until the configured duration is over, returning the failed requests and the size
+    /// uploads were capped at after the server rejected larger ones.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "upload",
+            fields(server = %server.id),
+        )
+    )]
+    pub async fn upload(
+        &self,
+        config: &Config,
+        server: &Server,
+        uploaded: Arc<AtomicU64>,
+    ) -> (TransferErrors, Option<ByteSize>) {
+        let errors = Arc::new(Mutex::new(TransferErrors::default()));
+        let seq = config.upload_size_sequence();
+        let size_cap = Arc::new(AtomicU64::new(u64::MAX));
+
+        let max_upload_count = config.max_upload_count();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+        let tasks = stream::iter(0..max_upload_count).for_each_concurrent(
+            self.concurrency(config.upload_threads()),
+            |i| {
+                let mut size = seq[i % seq.len()].min(ByteSize(size_cap.load(Ordering::Relaxed)));
+                let seq = &seq;
+                let size_cap = size_cap.clone();
+                let url = server.url.clone();
+                let uploaded = uploaded.clone();
+                let shutdown = shutdown_rx.clone();
+                let verify = self.verify_upload;
+                let pause = self.pause();
+                let errors = errors.clone();
+
+                async move {
+                    loop {
+                        let result = Self::single_upload(
+                            self.client.clone(),
+                            self.impairer.clone(),
+                            url.clone(),
+                            size,
+                            verify,
+                            uploaded.clone(),
+                            shutdown.clone(),
+                        )
+                        .await;
+
+                        match result {
+                            Ok(version) => {
+                                if let Some(version) = version {
+                                    self.note_version(server, version);
+                                }
+                                break;
+                            }
+                            // Some servers limit the body size below the sizes of the sequence,
+                            // retry smaller and keep later requests below the limit as well.
+                            Err(UploadError::Rejected(status))
+                                if let Some(smaller) = next_smaller_size(seq, size) =>
+                            {
+                                trace::debug!(
+                                    "upload of {} rejected by {}: {}, retrying with {}",
+                                    size,
+                                    url,
+                                    status,
+                                    smaller
+                                );
+                                size_cap.fetch_min(smaller.bytes(), Ordering::Relaxed);
+                                size = smaller;
+                            }
+                            Err(e) => {
+                                errors.lock().unwrap().record(e.cause());
+                                break;
+                            }
+                        }
+                    }
+                    tokio::time::sleep(pause).await;
+                }
+            },
+        );
+
+        tokio::select! {
+            biased;
+            _ = tokio::time::sleep(config.max_upload_duration()) => {
+                _ = shutdown_tx.send(true);
+            }
+            _ = self.cancellation.cancelled() => {
+                _ = shutdown_tx.send(true);
+            }
+            _ = tasks => {
+            }
+        }
+
+        let size_cap = match size_cap.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            cap => Some(ByteSize(cap)),
+        };
+        let errors = std::mem::take(&mut *errors.lock().unwrap());
+        (errors, size_cap)
+    }
+
+    /// Sends a single latency probe, returning `None` on failure or timeout.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "ping",
+            fields(server = %server.id),
+        )
+    )]
+    pub async fn ping(&self, server: &Server) -> Option<Duration> {
+        let start = Instant::now();
+        self.impairer.request().await.ok()?;
+
+        match self
+            .client
+            .send(HttpRequest::get(server.latency_url()).with_timeout(self.request_timeout))
+            .await
+        {
+            Ok(resp) if resp.status.is_success() => {
+                resp.bytes().await.ok().map(|_| start.elapsed())
+            }
+            Ok(resp) => {
+                trace::debug!("ping {} failed: {}", server.url, resp.status);
+                None
+            }
+            Err(e) => {
+                trace::debug!("ping {} failed: {}", server.url, e);
+                None
+            }
+        }
+    }
+
+    /// Local clock minus the clock of `server` from the `Date` header of a request, `None` if it
+    /// sends no date. The header has a resolution of one second.
+    pub async fn clock_skew(&self, server: &Server) -> Option<TimeDelta> {
+        let sent = Utc::now();
+        let resp = self
+            .client
+            .send(HttpRequest::head(server.latency_url()).with_timeout(self.request_timeout))
+            .await
+            .ok()?;
+        let received = Utc::now();
+
+        let date = resp.header(DATE)?;
+        let server_time = DateTime::parse_from_rfc2822(date).ok()?;
+        Some(sent + (received - sent) / 2 - server_time.with_timezone(&Utc))
+    }
+
+    /// Registers a result with speedtest.net and returns the URL of its result image. Only results
+    /// with both transfers can be shared.
+    pub async fn share_result(&self, result: &SpeedTestResult) -> Result<String> {
+        let form = share::share_form(result).ok_or_else(|| {
+            Error::Other("only results with download and upload can be shared".to_string())
+        })?;
+        let request = HttpRequest::post(self.urls.share_url())
+            .with_timeout(self.request_timeout)
+            .with_header(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            )
+            .with_header(REFERER, HeaderValue::from_static(share::SHARE_REFERER))
+            .with_header(CONTENT_LENGTH, HeaderValue::from(form.len()))
+            .with_body(Box::pin(stream::iter([Ok(Bytes::from(form))])));
+
+        let resp = self.client.send(request).await?;
+        if !resp.status.is_success() {
+            return Err(Error::Other(format!(
+                "share result failed: {}",
+                resp.status
+            )));
+        }
+        let body = resp.text().await?;
+        let result_id = share::parse_result_id(&body)
+            .ok_or_else(|| Error::Other(format!("share result failed: {}", body.trim())))?;
+        Ok(share::result_image_url(result_id))
+    }
+
+    /// The M-Lab ndt7 servers nearest to the client, from the locate API.
+    #[cfg(feature = "ndt7")]
+    pub async fn locate_ndt7_servers(&self) -> Result<Vec<Ndt7Server>> {
+        let resp = self
+            .client
+            .send(HttpRequest::get(ndt7::LOCATE_URL.to_string()).with_timeout(self.request_timeout))
+            .await?;
+        if !resp.status.is_success() {
+            return Err(Error::Other(format!(
+                "locate ndt7 servers failed: {}",
+                resp.status
+            )));
+        }
+        ndt7::parse_locate(&resp.text().await?)
+    }
+
+    /// Runs the ndt7 download and upload against the nearest M-Lab server instead of the
+    /// speedtest.net servers. The idle latency is the smallest RTT the server saw. The WebSockets
+    /// connect directly, not through the HTTP client of the tester.
+    #[cfg(feature = "ndt7")]
+    pub async fn run_ndt7_test(&self) -> Result<SpeedTestResult> {
+        let timestamp = Utc::now();
+        let servers = self.locate_ndt7_servers().await?;
+        let server = servers
+            .first()
+            .ok_or_else(|| Error::NoServers("no ndt7 servers found".to_string()))?;
+        trace::debug!("testing against ndt7 server {}", server.machine);
+
+        let download = ndt7::download(
+            &server.download_url,
+            &self.dns,
+            Arc::default(),
+            &self.cancellation,
+        )
+        .await?;
+        let upload = ndt7::upload(
+            &server.upload_url,
+            &self.dns,
+            Arc::default(),
+            &self.cancellation,
+        )
+        .await?;
+
+        // The address the server saw tells the family the connections used.
+        let client_ip = download.client_ip.clone().or(upload.client_ip.clone());
+        let ip_family = client_ip
+            .as_deref()
+            .and_then(|ip| ip.parse().ok())
+            .map(IpFamily::of)
+            .or(self.dns.family());
+        let client = ClientInfo {
+            ip: client_ip.unwrap_or_default(),
+            lat: 0.0,
+            lon: 0.0,
+            isp: String::new(),
+            country: String::new(),
+        };
+        let mut result = SpeedTestResult::new(timestamp, client, &Server::from(server))
+            .with_download(download.transfer)
+            .with_upload(upload.transfer);
+        let min_rtt = [download.min_rtt, upload.min_rtt]
+            .into_iter()
+            .flatten()
+            .min();
+        result.idle_latency = min_rtt.and_then(|rtt| Latency::from_samples(&[rtt], 0));
+        result.download_latency = download.latency;
+        result.upload_latency = upload.latency;
+        let mut methodology = ndt7::methodology(server);
+        methodology.ip_family = ip_family;
+        result.methodology = Some(methodology);
+        result.diagnostics.resolutions = self.dns.resolutions();
+        result.warnings = result.detect_warnings();
+        Ok(result)
+    }
+
+    /// Measures the idle latency by probing the server for `duration` before any load, or with
+    /// the number of probes set with [`with_latency_probes`](Self::with_latency_probes).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "latency",
+            fields(server = %server.id),
+        )
+    )]
+    pub async fn idle_latency(&self, server: &Server, duration: Duration) -> Option<Latency> {
+        self.report(Stage::Latency, None, async {
+            if let Some(probes) = self.latency_probes {
+                return self.probe_latency(server, probes).await;
+            }
+            let idle = async {
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => {}
+                    _ = self.cancellation.cancelled() => {}
+                }
+            };
+            self.loaded_latency(server, idle).await.1
+        })
+        .await
+    }
+
+    /// Sends `probes` latency probes one after another, the min/avg/max and jitter of those that
+    /// succeeded.
+    pub async fn probe_latency(&self, server: &Server, probes: usize) -> Option<Latency> {
+        let mut samples = vec![];
+        let mut lost = 0;
+        for i in 0..probes {
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+            if i > 0 {
+                tokio::time::sleep(PING_INTERVAL).await;
+            }
+            match self.ping(server).await {
+                Some(rtt) => samples.push(rtt),
+                None => lost += 1,
+            }
+        }
+        Latency::from_samples(&samples, lost)
+    }
+
+    /// Probes the server latency while `load` is running, e.g. a download or upload phase. Sends
+    /// no probes if the test plan leaves out the latency.
+    pub async fn loaded_latency<F: Future>(
+        &self,
+        server: &Server,
+        load: F,
+    ) -> (F::Output, Option<Latency>) {
+        if !self.test_plan.latency {
+            return (load.await, None);
+        }
+        let mut samples = vec![];
+        let mut lost = 0;
+
+        let probes = async {
+            loop {
+                match self.ping(server).await {
+                    Some(rtt) => samples.push(rtt),
+                    None => lost += 1,
+                }
+                tokio::time::sleep(PING_INTERVAL).await;
+            }
+        };
+
+        let output = tokio::select! {
+            output = load => output,
+            _ = probes => unreachable!(),
+        };
+
+        (output, Latency::from_samples(&samples, lost))
+    }
+
+    /// Totals `transfer`, the download or upload `stage` adding the bytes it moves to `counter`,
+    /// with the warm-up of [`with_warm_up`](Self::with_warm_up) left out of the rate and of the
+    /// statistics of the throughput samples.
+    pub async fn measure_transfer<F: Future>(
+        &self,
+        config: &Config,
+        stage: Stage,
+        counter: &Arc<AtomicU64>,
+        transfer: F,
+    ) -> (F::Output, TransferResult) {
+        let (threads, duration) = match stage {
+            Stage::Upload => (config.upload_threads(), config.max_upload_duration()),
+            _ => (config.download_threads(), config.max_download_duration()),
+        };
+        let connections = self.concurrency(threads);
+        let mut sampler = Sampler::new(stage, counter.clone(), self.sample_interval(duration));
+        let start = Instant::now();
+        let mut warm_up = None;
+
+        let output = {
+            let mut transfer =
+                std::pin::pin!(self.report(stage, Some(counter), sampler.record(transfer)));
+            tokio::select! {
+                output = &mut transfer => output,
+                end = self.warm_up_end(counter, connections, start) => {
+                    warm_up = end;
+                    transfer.await
+                }
+            }
+        };
+
+        let steady_start = start + warm_up.map_or(Duration::ZERO, |(_, elapsed)| elapsed);
+        let rates: Vec<f64> = sampler
+            .samples()
+            .iter()
+            .filter(|sample| sample.instant > steady_start)
+            .map(|sample| sample.bytes_per_sec * 8.0)
+            .collect();
+
+        let total = TransferResult::new(counter.load(Ordering::SeqCst), start.elapsed())
+            .with_stats(ThroughputStats::from_samples(&rates));
+        let total = match warm_up {
+            Some((bytes, elapsed)) => total.with_warm_up(bytes, elapsed),
+            None => total,
+        };
+        (output, total)
+    }
+
+    /// Bytes and time at the end of the warm-up of a transfer started at `start`, `None` right
+    /// away without one.
+    async fn warm_up_end(
+        &self,
+        counter: &AtomicU64,
+        connections: usize,
+        start: Instant,
+    ) -> Option<(u64, Duration)> {
+        match self.warm_up? {
+            WarmUp::Duration(duration) => tokio::time::sleep_until(start + duration).await,
+            WarmUp::Bytes(size) => {
+                let bytes = size.bytes() * connections as u64;
+                while counter.load(Ordering::Relaxed) < bytes {
+                    tokio::time::sleep(WARM_UP_POLL_INTERVAL).await;
+                }
+            }
+        }
+        Some((counter.load(Ordering::SeqCst), start.elapsed()))
+    }
+
+    pub fn get_config(&self) -> Result<&Config> {
+        self.config.as_ref().ok_or(Error::Other(
+            "config is empty. maybe call initialize first".to_string(),
+        ))
+    }
+
+    pub fn get_server(&self) -> Result<&Server> {
+        self.server.as_ref().ok_or(Error::Other(
+            "server is empty. maybe call initialize first".to_string(),
+        ))
+    }
+
+    /// Removes the servers the config lists in `ignoreids`, unless disabled with
+    /// [`with_ignore_ids`](Self::with_ignore_ids), and returns them.
+    pub fn filter_ignored_servers(
+        &self,
+        servers: &mut Vec<Server>,
+        config: &Config,
+    ) -> Vec<Server> {
+        if !self.respect_ignore_ids {
+            return vec![];
+        }
+        let ignore_ids = config.ignore_servers().collect::<Vec<_>>();
+
+        let (ignored, kept) = std::mem::take(servers)
+            .into_iter()
+            .partition(|s| ignore_ids.contains(&s.id.as_str()));
+        *servers = kept;
+
+        if !ignored.is_empty() {
+            trace::info!(
+                "ignored {} of {} servers listed in ignoreids: {}",
+                ignored.len(),
+                ignored.len() + servers.len(),
+                ignored
+                    .iter()
+                    .map(|s| s.id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        ignored
+    }
+
+    fn concurrency(&self, threads: usize) -> usize {
+        let max = match (self.max_concurrency, self.background) {
+            (Some(max), true) => Some(max.min(BACKGROUND_CONCURRENCY)),
+            (None, true) => Some(BACKGROUND_CONCURRENCY),
+            (max, false) => max,
+        };
+        match max {
+            Some(max) if threads == 0 => max,
+            Some(max) => threads.min(max),
+            None => threads,
+        }
+    }
+
+    fn pause(&self) -> Duration {
+        if self.background {
+            BACKGROUND_PAUSE
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "get",
+            fields(url = %url),
+        )
+    )]
+    async fn get_xml<T>(&self, url: String) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.impairer.request().await.map_err(Error::Other)?;
+        let resp = self
+            .client
+            .send(HttpRequest::get(url).with_timeout(self.request_timeout))
+            .await?;
+        let status = resp.status;
+
+        if status.is_success() {
+            let xml = resp.text().await?;
+            let ret: T = quick_xml::de::from_str(xml.as_str())?;
+            Ok(ret)
+        } else {
+            Err(Error::Status(status))
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "server_delay",
+            fields(server = %server.id),
+        )
+    )]
+    async fn get_server_delay(
+        client: &dyn HttpClient,
+        impairer: &Impairer,
+        server: &Server,
+        timeout: Duration,
+    ) -> Duration {
+        let start = Instant::now();
+        if impairer.request().await.is_err() {
+            return timeout * 2;
+        }
+
+        match client
+            .send(HttpRequest::get(&server.url).with_timeout(timeout))
+            .await
+        {
+            Ok(resp) => {
+                if resp.bytes().await.is_ok() {
+                    return start.elapsed();
+                }
+            }
+            Err(e) => {
+                trace::debug!("get server delay for {} failed: {}", server.url, e);
+            }
+        }
+        timeout * 2
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "download_request",
+            fields(url = %url),
+        )
+    )]
+    async fn single_download(
+        client: Arc<dyn HttpClient>,
+        impairer: Impairer,
+        url: String,
+        downloaded: Arc<AtomicU64>,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+    ) -> Result<Version, String> {
+        impairer.request().await?;
+        let mut resp = match client.send(HttpRequest::get(&url)).await {
+            Ok(resp) if resp.status.is_success() => resp,
+            Ok(resp) => {
+                trace::debug!("download {} failed: {}", url, resp.status);
+                return Err(format!("HTTP {}", resp.status));
+            }
+            Err(e) => {
+                trace::debug!("download {} failed: {}", url, e);
+                return Err(e.cause().to_string());
+            }
+        };
+
+        let version = resp.version;
+        tokio::select! {
+            biased;
+            _ = shutdown.changed() => Ok(version),
+            result = async {
+                while let Some(chunk) = resp.chunk().await? {
+                    impairer.throttle(chunk.len()).await;
+                    _ = downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                }
+                Ok::<_, HttpError>(version)
+            } => result.map_err(|e| {
+                trace::debug!("download {} interrupted: {}", url, e);
+                e.cause().to_string()
+            }),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            name = "upload_request",
+            fields(url = %url, size = size.bytes()),
+        )
+    )]
+    async fn single_upload(
+        client: Arc<dyn HttpClient>,
+        impairer: Impairer,
+        url: String,
+        size: ByteSize,
+        verify: bool,
+        uploaded: Arc<AtomicU64>,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+    ) -> Result<Option<Version>, UploadError> {
+        impairer.request().await?;
+
+        // Bytes are credited when the connection pulls them from the body rather than when they
+        // are generated, and taken out again if the request fails.
+        let sent = Arc::new(AtomicU64::new(0));
+        let body = Self::create_zero_stream(size.as_usize()).then({
+            let sent = sent.clone();
+            let uploaded = uploaded.clone();
+            move |chunk| {
+                let impairer = impairer.clone();
+                let sent = sent.clone();
+                let uploaded = uploaded.clone();
+                async move {
+                    if let Ok(chunk) = &chunk {
+                        impairer.throttle(chunk.len()).await;
+                        sent.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                        uploaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                    }
+                    chunk
+                }
+            }
+        });
+
+        let result = tokio::select! {
+            biased;
+            _ = shutdown.changed() => return Ok(None),
+            result = client.send(
+                HttpRequest::post(&url)
+                    .with_header(CONTENT_LENGTH, HeaderValue::from(size.bytes()))
+                    .with_body(Box::pin(body)),
+            ) => match result {
+                    Ok(resp) if resp.status.is_success() && verify => {
+                        let version = resp.version;
+                        Self::verify_upload(resp, size)
+                            .await
+                            .map(|()| Some(version))
+                            .map_err(UploadError::Failed)
+                    }
+                    Ok(resp) if resp.status.is_success() => Ok(Some(resp.version)),
+                    Ok(resp) => {
+                        trace::debug!("upload {} failed: {}", url, resp.status);
+                        let status = resp.status;
+                        if status == StatusCode::PAYLOAD_TOO_LARGE || status.is_server_error() {
+                            Err(UploadError::Rejected(status))
+                        } else {
+                            Err(UploadError::Failed(format!("HTTP {status}")))
+                        }
+                    }
+                    Err(e) => {
+                        trace::debug!("upload {} failed: {}", url, e);
+                        Err(UploadError::Failed(e.cause().to_string()))
+                    }
+                }
+        };
+
+        if result.is_err() {
+            uploaded.fetch_sub(sent.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Compares the size the server reports to have received with the size sent. Servers that
+    /// do not report a size are trusted on their status code.
+    async fn verify_upload(resp: HttpResponse, size: ByteSize) -> Result<(), String> {
+        let url = resp.url.clone();
+        let body = resp.text().await.map_err(|e| e.cause().to_string())?;
+
+        match received_size(&body) {
+            Some(received) if received < size.bytes() => {
+                trace::debug!(
+                    "upload {} incomplete: {} of {}",
+                    url,
+                    received,
+                    size.bytes()
+                );
+                Err("incomplete upload".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn create_zero_stream(size: usize) -> impl Stream<Item = Result<Bytes, HttpError>> {
+        stream::unfold(size, |remaining| async move {
+            if remaining == 0 {
+                None
+            } else {
+                let chunk_size = remaining.min(UPLOAD_CHUNK.len());
+
+                let chunk = Bytes::from_static(&UPLOAD_CHUNK[..chunk_size]);
+
+                let next_state = remaining - chunk_size;
+
+                Some((Ok(chunk), next_state))
+            }
+        })
+    }
+}
+
+/// Why a single upload request failed.
+enum UploadError {
+    /// The server refused the request, possibly because of its size, e.g. with
+    /// `413 Payload Too Large` or a 5xx status of a proxy in front of it.
+    Rejected(StatusCode),
+    Failed(String),
+}
+
+impl UploadError {
+    fn cause(self) -> String {
+        match self {
+            Self::Rejected(status) => format!("HTTP {status}"),
+            Self::Failed(cause) => cause,
+        }
+    }
+}
+
+impl From<String> for UploadError {
+    fn from(cause: String) -> Self {
+        Self::Failed(cause)
+    }
+}
+
+/// First address `host` resolves to with `dns`, connections try its family first. `None` if it
+/// does not resolve in time.
+async fn resolve(dns: &Dns, host: &str) -> Option<IpAddr> {
+    let name = host.rsplit_once(':').map_or(host, |(name, _)| name);
+    tokio::time::timeout(CONNECTIVITY_TIMEOUT, dns.lookup(name, 0))
+        .await
+        .ok()?
+        .ok()?
+        .first()
+        .map(|addr| addr.ip())
+}
+
+/// The client of [`SpeedTester::default`], bound to `local_addr` when given and resolving with
+/// `dns`.
+#[cfg(feature = "reqwest")]
+fn reqwest_client(local_addr: Option<IpAddr>, dns: &Dns, version: HttpVersion) -> reqwest::Client {
+    let builder = reqwest::Client::builder()
+        .local_address(local_addr)
+        .dns_resolver(Arc::new(dns.clone()))
+        .user_agent("SPEED-TESTER-RS");
+    match version {
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Http2 => builder,
+    }
+    .build()
+    .expect("build client failed")
+}
+
+/// Name of `version` as in the status line, e.g. `HTTP/2`.
+fn version_name(version: Version) -> String {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => return format!("{version:?}"),
+    }
+    .to_string()
+}
+
+/// Largest size of the sequence below `size`, to retry a rejected upload with.
+fn next_smaller_size(seq: &[ByteSize], size: ByteSize) -> Option<ByteSize> {
+    seq.iter().copied().filter(|s| *s < size).max()
+}
+
+/// Received size in the response of `upload.php`, e.g. `size=524288`.
+fn received_size(body: &str) -> Option<u64> {
+    body.trim()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("size="))
+        .and_then(|size| size.parse().ok())
+}
+
+/// Runs `fetch` for `urls`, at most `concurrency` at a time, and returns the first success.
+/// Dropping the stream cancels the requests still running. `None` if every request failed, the
+/// failed URLs and their errors are added to `errors`.
+async fn first_success<T, F, Fut>(
+    urls: impl IntoIterator<Item = String>,
+    concurrency: usize,
+    fetch: F,
+    errors: &mut Vec<(String, String)>,
+) -> Option<T>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = stream::iter(urls)
+        .map(|url| {
+            let attempt = fetch(url.clone());
+            async move { (url, attempt.await) }
+        })
+        .buffer_unordered(concurrency);
+    while let Some((url, result)) = attempts.next().await {
+        match result {
+            Ok(value) => return Some(value),
+            Err(e) => {
+                trace::debug!("failed to fetch {}: {}", url, e);
+                errors.push((url, e.to_string()));
+            }
+        }
+    }
+    None
 }
 
-#[cfg(test)]
+// Most tests build the tester with the reqwest client of `SpeedTester::default`.
+#[cfg(all(test, feature = "reqwest"))]
 mod tests {
 
-    use std::sync::{
-        Arc,
-        atomic::{AtomicU64, Ordering},
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU64, AtomicUsize, Ordering},
+        },
+        time::Duration,
     };
 
-    use futures::StreamExt;
+    use bytes::Bytes;
+    use futures::{StreamExt, future::BoxFuture, stream};
+    use tokio::time::Instant;
+
+    use crate::{
+        error::Error,
+        http::{
+            HeaderMap, HttpClient, HttpError, HttpErrorKind, HttpRequest, HttpResponse,
+            HttpVersion, StatusCode, Url, Version,
+        },
+        model::{Config, Server, WarmUp},
+        retry::RetryPolicy,
+        size::ByteSize,
+        speed_tester::{
+            CancellationToken, CaptivePortal, NoConnectivity, SATURATION_BACKOFF,
+            SATURATION_MAX_FAILURES, SpeedTester, first_success, next_smaller_size, received_size,
+        },
+        stage::{Discovery, Selection, ServerChoice, Stage, TestPlan},
+        urls::SpeedTestUrl,
+    };
+
+    #[test]
+    fn test_concurrency() {
+        let speed_tester = SpeedTester::default();
+        assert_eq!(speed_tester.concurrency(8), 8);
+
+        let speed_tester = speed_tester.with_max_concurrency(Some(4));
+        assert_eq!(speed_tester.concurrency(8), 4);
+        assert_eq!(speed_tester.concurrency(0), 4);
+
+        let speed_tester = speed_tester.with_background(true);
+        assert_eq!(speed_tester.concurrency(8), 2);
+    }
+
+    const CONFIG_XML: &str = r#"<settings>
+<client ip="1.1.1.1" lat="22.3" lon="114.2" isp="Value" isprating="3.7" rating="0" ispdlavg="0" ispulavg="0" loggedin="0" country="HK"/>
+<server-config threadcount="4" ignoreids="2,3" notonmap="" forcepingid="" preferredserverid=""/>
+<download testlength="10" initialtest="250K" mintestsize="250K" threadsperurl="4"/>
+<upload testlength="10" ratio="5" initialtest="0" mintestsize="32K" threads="2" maxchunksize="512K" maxchunkcount="50" threadsperurl="4"/>
+</settings>"#;
+
+    fn config() -> Config {
+        quick_xml::de::from_str(CONFIG_XML).unwrap()
+    }
+
+    #[test]
+    fn test_filter_ignored_servers() {
+        let config = config();
+        let server = |id: &str| Server {
+            url: format!("http://{id}.example.com:8080/speedtest/upload.php"),
+            lat: 22.0,
+            lon: 114.0,
+            name: "Hong Kong".to_string(),
+            country: "Hong Kong".to_string(),
+            cc: "HK".to_string(),
+            sponsor: id.to_string(),
+            id: id.to_string(),
+            host: format!("{id}.example.com:8080"),
+            extra: Default::default(),
+        };
+
+        let mut servers = vec![server("1"), server("2"), server("4")];
+        let ignored = SpeedTester::default().filter_ignored_servers(&mut servers, &config);
+        assert_eq!(servers, [server("1"), server("4")]);
+        assert_eq!(ignored, [server("2")]);
+
+        let mut servers = vec![server("1"), server("2")];
+        let ignored = SpeedTester::default()
+            .with_ignore_ids(false)
+            .filter_ignored_servers(&mut servers, &config);
+        assert_eq!(servers.len(), 2);
+        assert!(ignored.is_empty());
+    }
+
+    #[test]
+    fn test_next_smaller_size() {
+        let seq = [256, 512, 1024, 512, 256].map(ByteSize::kib);
+        assert_eq!(
+            next_smaller_size(&seq, ByteSize::kib(1024)),
+            Some(ByteSize::kib(512))
+        );
+        assert_eq!(
+            next_smaller_size(&seq, ByteSize::kib(300)),
+            Some(ByteSize::kib(256))
+        );
+        assert_eq!(next_smaller_size(&seq, ByteSize::kib(256)), None);
+    }
+
+    #[test]
+    fn test_received_size() {
+        assert_eq!(received_size("size=524288"), Some(524288));
+        assert_eq!(received_size("size=1024&id=7\n"), Some(1024));
+        assert_eq!(received_size("OK"), None);
+    }
+
+    /// Answers every request with the status line and headers `respond` returns for its request
+    /// line, returning the address.
+    async fn http_server(respond: fn(&str) -> String) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0; 1024];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let head = respond(request.lines().next().unwrap_or_default());
+                _ = socket
+                    .write_all(format!("HTTP/1.1 {head}\r\nconnection: close\r\n\r\n").as_bytes())
+                    .await;
+            }
+        });
+        addr.to_string()
+    }
+
+    fn test_server(host: String) -> Server {
+        Server {
+            url: format!("http://{host}/speedtest/upload.php"),
+            lat: 22.0,
+            lon: 114.0,
+            name: "Hong Kong".to_string(),
+            country: "Hong Kong".to_string(),
+            cc: "HK".to_string(),
+            sponsor: "Example".to_string(),
+            id: "1".to_string(),
+            host,
+            extra: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_download_sizes() {
+        let speed_tester = SpeedTester::default();
+
+        let host = http_server(|request| {
+            if request.contains("/random350x350.jpg ") || request.contains("/random1000x1000.jpg ")
+            {
+                "200 OK\r\ncontent-length: 0".to_string()
+            } else {
+                "404 Not Found\r\ncontent-length: 0".to_string()
+            }
+        })
+        .await;
+        let mut sizes = speed_tester
+            .probe_download_sizes(&test_server(host), &[350, 500, 1000, 350])
+            .await;
+        sizes.sort_unstable();
+        assert_eq!(sizes, [350, 1000]);
+
+        // A server answering no probe keeps every size.
+        let host = http_server(|_| "404 Not Found\r\ncontent-length: 0".to_string()).await;
+        let mut sizes = speed_tester
+            .probe_download_sizes(&test_server(host), &[500, 350])
+            .await;
+        sizes.sort_unstable();
+        assert_eq!(sizes, [350, 500]);
+    }
+
+    #[test]
+    fn test_chosen_server() {
+        let servers: Vec<Server> = ["speed.example.com:8080", "speed.example.net:8080"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, host)| Server {
+                id: i.to_string(),
+                ..test_server(host.to_string())
+            })
+            .collect();
+        let chosen = |choice: ServerChoice| {
+            SpeedTester::default()
+                .with_server(Some(choice))
+                .chosen_server(&servers)
+                .map(|server| server.map(|s| s.id))
+        };
+
+        assert_eq!(
+            chosen(ServerChoice::IdOrHost("1".into())).unwrap(),
+            Some("1".into())
+        );
+        assert_eq!(
+            chosen(ServerChoice::IdOrHost("Speed.Example.NET".into())).unwrap(),
+            Some("1".into())
+        );
+        assert_eq!(
+            chosen(ServerChoice::IdOrHost("speed.example.com:8080".into())).unwrap(),
+            Some("0".into())
+        );
+        assert!(chosen(ServerChoice::IdOrHost("42".into())).is_err());
+
+        let own = Server {
+            id: "self-hosted".to_string(),
+            ..test_server("10.0.0.2:8080".to_string())
+        };
+        assert_eq!(
+            chosen(ServerChoice::Server(Box::new(own))).unwrap(),
+            Some("self-hosted".into())
+        );
+        assert_eq!(
+            SpeedTester::default().chosen_server(&servers).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rank_servers_by_distance() {
+        let client = config().client;
+        let servers: Vec<Server> = [("far", 10.0), ("here", 0.0), ("near", 1.0)]
+            .into_iter()
+            .map(|(id, offset)| Server {
+                id: id.to_string(),
+                lat: client.lat + offset,
+                lon: client.lon,
+                ..test_server("speed.example.com:8080".to_string())
+            })
+            .collect();
+
+        let speed_tester = SpeedTester::default();
+        let ranked = speed_tester.rank_servers_by_distance(&client, servers.clone());
+        let ids = ranked
+            .iter()
+            .map(|(s, _)| s.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["here", "near", "far"]);
+        assert_eq!(ranked[0].1, 0.0);
+        // One degree of latitude is about 111 km.
+        assert!((ranked[1].1 - 111.0).abs() < 1.0, "{}", ranked[1].1);
+
+        let ids = |servers: Vec<Server>| servers.into_iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(speed_tester.race_candidates(&client, servers.clone())),
+            ["far", "here", "near"]
+        );
+        let speed_tester = speed_tester.with_closest_servers(Some(2));
+        assert_eq!(
+            ids(speed_tester.race_candidates(&client, servers)),
+            ["here", "near"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dedup_servers() {
+        let mut servers = vec![
+            test_server("127.0.0.1:8080".to_string()),
+            test_server("127.0.0.1:8080".to_string()),
+            test_server("127.0.0.1:8081".to_string()),
+        ];
+        for (i, server) in servers.iter_mut().enumerate() {
+            server.id = i.to_string();
+        }
+
+        let speed_tester = SpeedTester::default();
+        let ids = |servers: Vec<Server>| servers.into_iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(speed_tester.dedup_servers(servers.clone()).await),
+            ["0", "2"]
+        );
+        assert_eq!(speed_tester.duplicate_of("1").as_deref(), Some("0"));
+        assert_eq!(speed_tester.duplicate_of("2"), None);
+
+        let speed_tester = speed_tester.with_ip_dedup(true);
+        assert_eq!(ids(speed_tester.dedup_servers(servers).await), ["0"]);
+        assert_eq!(speed_tester.duplicate_of("2").as_deref(), Some("0"));
+    }
+
+    #[tokio::test]
+    async fn test_methodology() {
+        let config = config();
+        let server = test_server("speed.example.com:8080".to_string());
+        let methodology = SpeedTester::default()
+            .with_max_concurrency(Some(2))
+            .methodology(&config, &server)
+            .await;
+
+        assert_eq!(methodology.provider, "ookla");
+        assert_eq!(methodology.protocol, "http");
+        assert_eq!(methodology.warm_up, "included");
+        assert_eq!(methodology.download_connections, 2);
+        assert_eq!(methodology.download_sizes, config.download_size_sequence());
+        assert_eq!(
+            methodology.upload_duration_ms,
+            config.max_upload_duration().as_millis() as u64
+        );
+        assert_eq!(methodology.http_version, None);
+    }
+
+    #[tokio::test]
+    async fn test_http_version() {
+        let config = config();
+        let server = test_server(http_server(|_| "200 OK\r\ncontent-length: 0".to_string()).await);
+        let speed_tester = SpeedTester::default().with_http_version(HttpVersion::Http2);
+
+        // HTTP/2 is only negotiated over TLS, the plain HTTP test server stays on HTTP/1.1.
+        let errors = speed_tester
+            .download(&config, &server, Arc::new(AtomicU64::new(0)))
+            .await;
+        assert_eq!(errors.total(), 0);
+        let methodology = speed_tester.methodology(&config, &server).await;
+        assert_eq!(methodology.http_version.as_deref(), Some("HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_clock_skew() {
+        let addr =
+            http_server(|_| "200 OK\r\ndate: Tue, 15 Nov 1994 08:12:31 GMT".to_string()).await;
+        let skew = SpeedTester::default()
+            .clock_skew(&test_server(addr))
+            .await
+            .unwrap();
+        assert!(skew.num_days() > 365 * 30);
+
+        let addr = http_server(|_| "200 OK".to_string()).await;
+        assert_eq!(
+            SpeedTester::default().clock_skew(&test_server(addr)).await,
+            None
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_first_success() {
+        let fetch = |url: String| async move {
+            match url.as_str() {
+                "slow" => {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    Ok(url)
+                }
+                "fail" => Err(Error::Other("failed".to_string())),
+                _ => Ok(url),
+            }
+        };
+        let urls = |urls: &[&str]| urls.iter().map(|url| url.to_string()).collect::<Vec<_>>();
+
+        // The slow request is cancelled rather than awaited.
+        let mut errors = vec![];
+        let started = Instant::now();
+        let first = first_success(urls(&["slow", "fail", "fast"]), 3, fetch, &mut errors).await;
+        assert_eq!(first.as_deref(), Some("fast"));
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        let first = first_success(urls(&["fail", "first", "second"]), 1, fetch, &mut errors).await;
+        assert_eq!(first.as_deref(), Some("first"));
+
+        errors.clear();
+        let first = first_success(urls(&["fail", "fail"]), 2, fetch, &mut errors).await;
+        assert_eq!(first, None);
+        assert_eq!(errors, vec![("fail".to_string(), "failed".to_string()); 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fetch_with_retry() {
+        let calls = AtomicU64::new(0);
+        let fetch = |url: String| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match (url.as_str(), call) {
+                    ("slow", _) => {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        Ok(url)
+                    }
+                    // Answers in the third pass only.
+                    ("flaky", 4..) => Ok(url),
+                    _ => Err(Error::Status(StatusCode::SERVICE_UNAVAILABLE)),
+                }
+            }
+        };
+        let urls = || ["flaky".to_string(), "slow".to_string()];
+        let policy = RetryPolicy {
+            attempt_timeout: Some(Duration::from_secs(5)),
+            ..RetryPolicy::default()
+        };
+        let speed_tester = SpeedTester::default().with_retry_policy(policy);
+
+        let started = Instant::now();
+        let fetched = speed_tester
+            .fetch_with_retry("config", policy, urls(), fetch)
+            .await;
+        assert_eq!(fetched.unwrap(), "flaky");
+        // Two timed out passes and backoffs of 250ms to 500ms, then 500ms to 1s.
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(10_750), "{elapsed:?}");
+        assert!(elapsed <= Duration::from_millis(11_500), "{elapsed:?}");
+
+        let Err(Error::FetchFailed(failed)) = speed_tester
+            .fetch_with_retry("config", RetryPolicy::once(), ["fail".to_string()], fetch)
+            .await
+        else {
+            panic!("expected a failed fetch");
+        };
+        assert_eq!(failed.attempts, 1);
+        assert_eq!(failed.failures.len(), 1);
+        assert_eq!(
+            failed.to_string(),
+            "all fetch config failed: fail (status: 503 Service Unavailable)"
+        );
+
+        let Err(Error::FetchFailed(failed)) = speed_tester
+            .fetch_with_retry("servers", policy, urls(), |url| {
+                fetch(url.replace("flaky", "down"))
+            })
+            .await
+        else {
+            panic!("expected a failed fetch");
+        };
+        assert_eq!(failed.attempts, 3);
+        assert_eq!(
+            failed
+                .failures
+                .iter()
+                .map(|f| f.attempt)
+                .collect::<Vec<_>>(),
+            [1, 1, 2, 2, 3, 3]
+        );
+        assert_eq!(failed.failures[1].url, "slow");
+        assert_eq!(failed.failures[1].error, "attempt timed out after 5s");
+
+        // Cancelling during the backoff ends the retries right away.
+        let cancellation = CancellationToken::new();
+        let speed_tester = speed_tester.with_cancellation(cancellation.clone());
+        let canceled = async {
+            // The first pass times out after 5s, the backoff takes at least 250ms.
+            tokio::time::sleep(Duration::from_millis(5_100)).await;
+            cancellation.cancel();
+        };
+        let started = Instant::now();
+        let (fetched, _) = tokio::join!(
+            speed_tester.fetch_with_retry("servers", policy, urls(), |url| {
+                fetch(url.replace("flaky", "down"))
+            }),
+            canceled
+        );
+        assert!(matches!(fetched, Err(Error::Canceled)), "{fetched:?}");
+        assert_eq!(started.elapsed(), Duration::from_millis(5_100));
+    }
+
+    #[tokio::test]
+    async fn test_check_connectivity() {
+        let check = |url: String| async move {
+            SpeedTester::default()
+                .with_connectivity_probe(Some(url))
+                .check_connectivity()
+                .await
+        };
+
+        let addr = http_server(|_| "404 Not Found".to_string()).await;
+        assert!(check(format!("http://{addr}/")).await.is_ok());
+
+        let e = check("http://127.0.0.1:1/".to_string()).await.unwrap_err();
+        let Error::NoConnectivity(NoConnectivity { reason }) = e else {
+            panic!("{e}");
+        };
+        assert_eq!(reason, "127.0.0.1 connection failed");
+
+        let e = check("http://speedtest-rs.invalid/".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::NoConnectivity(_)), "{e}");
+    }
+
+    #[tokio::test]
+    async fn test_check_captive_portal() {
+        let check = |respond| async move {
+            let addr = http_server(respond).await;
+            SpeedTester::default()
+                .with_captive_portal_probe(Some(format!("http://{addr}/generate_204")))
+                .check_captive_portal()
+                .await
+        };
 
-    use crate::speed_tester::SpeedTester;
+        let open = |_: &str| "204 No Content".to_string();
+        assert!(check(open).await.is_ok());
+
+        let login_page = |_: &str| "200 OK\r\ncontent-type: text/html".to_string();
+        let e = check(login_page).await.unwrap_err();
+        assert!(
+            matches!(e, Error::CaptivePortal(CaptivePortal { location: None })),
+            "{e}"
+        );
+
+        let redirect = |request: &str| {
+            if request.contains("generate_204") {
+                "302 Found\r\nlocation: /login".to_string()
+            } else {
+                "204 No Content".to_string()
+            }
+        };
+        let e = check(redirect).await.unwrap_err();
+        let Error::CaptivePortal(CaptivePortal { location }) = e else {
+            panic!("{e}");
+        };
+        assert_eq!(location.as_deref(), Some("127.0.0.1"));
+
+        let unreachable = SpeedTester::default()
+            .with_captive_portal_probe(Some("http://127.0.0.1:1/generate_204".to_string()));
+        assert!(unreachable.check_captive_portal().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_server() {
+        let config = config();
+        let speed_tester = SpeedTester::default();
+        let validate = |respond| {
+            let config = config.clone();
+            let speed_tester = speed_tester.clone();
+            async move {
+                let server = test_server(http_server(respond).await);
+                speed_tester.validate_server(&config, &server).await
+            }
+        };
+
+        let image =
+            |_: &str| "200 OK\r\ncontent-type: image/jpeg\r\ncontent-length: 245388".to_string();
+        assert_eq!(validate(image).await, Ok(()));
+        let unsupported = |_: &str| "405 Method Not Allowed\r\ncontent-length: 0".to_string();
+        assert_eq!(validate(unsupported).await, Ok(()));
+
+        let portal =
+            |_: &str| "200 OK\r\ncontent-type: text/html\r\ncontent-length: 5120".to_string();
+        assert!(
+            validate(portal)
+                .await
+                .unwrap_err()
+                .contains("captive portal")
+        );
+        let tiny = |_: &str| "200 OK\r\ncontent-length: 12".to_string();
+        assert!(validate(tiny).await.unwrap_err().contains("12 bytes"));
+        let missing = |_: &str| "404 Not Found\r\ncontent-length: 0".to_string();
+        assert!(validate(missing).await.unwrap_err().contains("404"));
+
+        let disabled = speed_tester.clone().with_server_validation(false);
+        let server = test_server(http_server(tiny).await);
+        assert_eq!(disabled.validate_server(&config, &server).await, Ok(()));
+    }
+
+    /// Replays canned bodies instead of going to the network.
+    #[derive(Debug)]
+    struct RecordedClient;
+
+    impl HttpClient for RecordedClient {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>> {
+            Box::pin(async move {
+                let body = if request.url.ends_with("/speedtest-config.php") {
+                    CONFIG_XML
+                } else if request.url.contains("/latency.txt") {
+                    "test=test"
+                } else {
+                    return Err(HttpError::new(HttpErrorKind::Timeout, "not recorded"));
+                };
+                Ok(HttpResponse {
+                    status: StatusCode::OK,
+                    version: Version::HTTP_11,
+                    url: Url::parse(&request.url).unwrap(),
+                    headers: HeaderMap::new(),
+                    body: Box::pin(stream::iter([Ok(Bytes::from_static(body.as_bytes()))])),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_static_servers() {
+        let missing = |_: &str| "404 Not Found\r\ncontent-length: 0".to_string();
+        let mirror = http_server(missing).await;
+        let server = test_server("speed.example.com:8080".to_string());
+        let urls = SpeedTestUrl::new().hosts([mirror.clone()]);
+
+        let speed_tester =
+            SpeedTester::default().with_url(urls.clone().servers(vec![server.clone()]));
+        let config = speed_tester.fetch_config().await.unwrap();
+        assert_eq!(config.client.ip, "");
+        let servers = speed_tester.fetch_servers(config.threads()).await.unwrap();
+        assert_eq!(servers.servers.servers, [server]);
+
+        // Without static servers there is nothing to test against.
+        let speed_tester = SpeedTester::default()
+            .with_url(urls)
+            .with_retry_policy(RetryPolicy::once());
+        assert!(speed_tester.fetch_config().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_client() {
+        let speed_tester = SpeedTester::new_with_http_client(RecordedClient);
+        let config = speed_tester.fetch_config().await.unwrap();
+        assert_eq!(config.client.ip, "1.1.1.1");
+
+        let server = test_server("speed.example.com:8080".to_string());
+        assert!(speed_tester.ping(&server).await.is_some());
+        assert_eq!(
+            speed_tester.validate_server(&config, &server).await,
+            Err("is unreachable (timed out)".to_string())
+        );
+    }
+
+    /// Never answers, like a server that stalls mid-transfer.
+    #[derive(Debug)]
+    struct StalledClient;
+
+    impl HttpClient for StalledClient {
+        fn send(&self, _: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>> {
+            Box::pin(futures::future::pending())
+        }
+    }
+
+    /// Answers the first request with 1 KB and fails every later one right away.
+    #[derive(Debug)]
+    struct FailingClient(Arc<AtomicUsize>);
+
+    impl HttpClient for FailingClient {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>> {
+            let first = self.0.fetch_add(1, Ordering::SeqCst) == 0;
+            Box::pin(async move {
+                if !first {
+                    return Err(HttpError::new(HttpErrorKind::Connect, "refused"));
+                }
+                Ok(HttpResponse {
+                    status: StatusCode::OK,
+                    version: Version::HTTP_11,
+                    url: Url::parse(&request.url).unwrap(),
+                    headers: HeaderMap::new(),
+                    body: Box::pin(stream::iter([Ok(Bytes::from_static(&[0; 1024]))])),
+                })
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_saturation_failures() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let speed_tester = SpeedTester::new_with_http_client(FailingClient(requests.clone()))
+            .with_max_concurrency(Some(2));
+        let config = config();
+        let server = test_server("speed.example.com:8080".to_string());
+
+        let start = Instant::now();
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let errors = speed_tester
+            .download(&config, &server, downloaded.clone())
+            .await;
+        assert_eq!(downloaded.load(Ordering::SeqCst), 1024);
+
+        // Saturation backs off and gives up instead of fetching until the duration is over.
+        let requests = requests.load(Ordering::SeqCst);
+        let sequence = config.download_count_per_url() * config.download_size_sequence().len();
+        assert!(requests <= sequence + SATURATION_MAX_FAILURES as usize + 1);
+        assert!(start.elapsed() >= SATURATION_BACKOFF * 16);
+        assert!(start.elapsed() < config.max_download_duration());
+        assert_eq!(errors.total(), requests as u64 - 1);
+        assert_eq!(errors.dominant().unwrap().0, "connection failed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_measure_transfer() {
+        // 0.5 MB in each of the first two seconds, 1 MB in each of the eight after.
+        async fn ramp(counter: &AtomicU64) {
+            for second in 0..10 {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let bytes = if second < 2 { 500_000 } else { 1_000_000 };
+                counter.fetch_add(bytes, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
+        let config = config();
+        let speed_tester = SpeedTester::default()
+            .with_max_concurrency(Some(1))
+            .with_sample_interval(Some(Duration::from_secs(1)));
+
+        let counter = Arc::new(AtomicU64::new(0));
+        let (_, raw) = speed_tester
+            .measure_transfer(&config, Stage::Download, &counter, ramp(&counter))
+            .await;
+        assert!(raw.warm_up.is_none());
+        assert_eq!(raw.bits_per_second, 7_200_000.0);
+        let stats = raw.stats.unwrap();
+        assert_eq!(stats.samples, 10);
+        assert_eq!((stats.min_bps, stats.p50_bps), (4_000_000.0, 8_000_000.0));
+
+        let speed_tester =
+            speed_tester.with_warm_up(Some(WarmUp::Duration(Duration::from_secs(2))));
+        let counter = Arc::new(AtomicU64::new(0));
+        let (_, steady) = speed_tester
+            .measure_transfer(&config, Stage::Download, &counter, ramp(&counter))
+            .await;
+        let warm_up = steady.warm_up.unwrap();
+        assert_eq!(
+            (warm_up.warm_up_bytes, warm_up.warm_up_ms),
+            (1_000_000, 2000)
+        );
+        assert_eq!(warm_up.raw_bits_per_second, 7_200_000.0);
+        assert_eq!(steady.bits_per_second, 8_000_000.0);
+        // The samples of the warm-up are left out as well.
+        let stats = steady.stats.unwrap();
+        assert_eq!(
+            (stats.samples, stats.min_bps, stats.cv),
+            (8, 8_000_000.0, 0.0)
+        );
+
+        let speed_tester = speed_tester.with_warm_up(Some(WarmUp::Bytes(ByteSize::b(1_000_000))));
+        let counter = Arc::new(AtomicU64::new(0));
+        let (_, steady) = speed_tester
+            .measure_transfer(&config, Stage::Upload, &counter, ramp(&counter))
+            .await;
+        let warm_up = steady.warm_up.unwrap();
+        assert_eq!(warm_up.warm_up_bytes, 1_000_000);
+        assert!((1500..=1510).contains(&warm_up.warm_up_ms));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_test_plan() {
+        let speed_tester =
+            SpeedTester::new_with_http_client(StalledClient).with_test_plan(TestPlan {
+                latency: false,
+                download: false,
+                upload: true,
+            });
+        let config = config();
+        let server = test_server("192.0.2.1:8080".to_string());
+        let discovery = Discovery {
+            config: config.clone(),
+            servers: vec![server.clone()],
+            ignored: vec![],
+        };
+
+        let start = Instant::now();
+        let result = speed_tester
+            .test_selection(&discovery, Selection::new(config.clone(), server))
+            .await;
+        // Only the upload ran, without latency probes.
+        assert_eq!(start.elapsed(), config.max_upload_duration());
+        assert!(result.download.is_none());
+        assert_eq!(result.upload.unwrap().bytes, 0);
+        assert_eq!(result.idle_latency, None);
+        assert_eq!(result.upload_latency, None);
+
+        assert!(TestPlan::default().includes(Stage::Latency));
+        assert!(!speed_tester.test_plan().includes(Stage::Download));
+        assert!(speed_tester.test_plan().includes(Stage::Discovery));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancellation() {
+        let token = CancellationToken::new();
+        let speed_tester =
+            SpeedTester::new_with_http_client(StalledClient).with_cancellation(token.clone());
+        let config = config();
+        let server = test_server("speed.example.com:8080".to_string());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            token.cancel();
+        });
+
+        let start = Instant::now();
+        let downloaded = Arc::new(AtomicU64::new(0));
+        speed_tester.download(&config, &server, downloaded).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(config.max_download_duration() > Duration::from_secs(1));
+
+        // Later phases end right away once cancelled.
+        let uploaded = Arc::new(AtomicU64::new(0));
+        speed_tester.upload(&config, &server, uploaded).await;
+        assert_eq!(
+            speed_tester
+                .idle_latency(&server, Duration::from_secs(2))
+                .await,
+            None
+        );
+        let raced = speed_tester.select_fastest_server(vec![server]).await;
+        assert_eq!(raced.unwrap_err().to_string(), "canceled");
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_probe_latency() {
+        let speed_tester = SpeedTester::new_with_http_client(RecordedClient);
+        let server = test_server("speed.example.com:8080".to_string());
+        let latency = speed_tester.probe_latency(&server, 3).await.unwrap();
+        assert_eq!((latency.samples, latency.lost), (3, 0));
+
+        // A probe count replaces the duration of the idle latency phase.
+        let speed_tester = speed_tester.with_latency_probes(Some(5));
+        let start = Instant::now();
+        let latency = speed_tester
+            .idle_latency(&server, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(latency.samples, 5);
+        assert!(start.elapsed() < Duration::from_secs(60));
+        assert_eq!(
+            speed_tester.idle_latency_duration(Duration::from_secs(2)),
+            Duration::from_secs(1)
+        );
+
+        assert_eq!(speed_tester.probe_latency(&server, 0).await, None);
+    }
 
     #[tokio::test]
     async fn test_create_zero_stream() {
         let size = 16 * 16 * 1025;
-        let recorded = Arc::new(AtomicU64::new(0));
         let mut total = 0;
-        let mut bytes_stream = Box::pin(SpeedTester::create_zero_stream(size, recorded.clone()));
+        let mut bytes_stream = Box::pin(SpeedTester::create_zero_stream(size));
 
         while let Some(Ok(chunk)) = bytes_stream.next().await {
+            assert!(chunk.len() <= 16 * 1024);
             total += chunk.len();
         }
 
         assert_eq!(total, size);
-        assert_eq!(recorded.load(Ordering::Relaxed), size as u64);
     }
 }
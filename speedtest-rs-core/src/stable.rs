@@ -0,0 +1,27 @@
+//! The API frontends and bindings can rely on, `use speedtest_rs_core::stable::*;`.
+//!
+//! Items re-exported here follow semver: they are not removed or changed incompatibly within a
+//! major version. A replaced item stays for at least one minor release marked `#[deprecated]`,
+//! naming its successor. Everything else of the crate may change with any release, and the
+//! experimental modules are only compiled with the `unstable` feature.
+
+pub use crate::{
+    G_BITS_PER_SEC, GB, Humanize, K_BITS_PER_SEC, KB, M_BITS_PER_SEC, MB, T_BITS_PER_SEC, TB,
+    bitrate_unit,
+    error::Error,
+    http::{HttpClient, HttpError, HttpErrorKind, HttpRequest, HttpResponse, HttpVersion},
+    latency::Latency,
+    model::{
+        Client, Config, ConfigOverrides, PhaseOverrides, Server, ServerFilter, ServerList, Servers,
+        WarmUp,
+    },
+    redact::Redact,
+    result::{ClientInfo, Diagnostics, SpeedTestResult, TransferResult, WarmUpSplit},
+    retry::RetryPolicy,
+    scoring::Scoring,
+    size::ByteSize,
+    speed_tester::{CancellationToken, FetchFailed, FetchFailure, SpeedTester},
+    stage::{Discovery, ProgressTracker, Selection, ServerChoice, Stage, StageEvent, TestPlan},
+    throughput::ThroughputStats,
+    urls::SpeedTestUrl,
+};
@@ -0,0 +1,192 @@
+//! Typed outputs of the individual test stages.
+//!
+//! Each stage of [`SpeedTester`](crate::speed_tester::SpeedTester) can be run on its own, so
+//! embedders are free to compose custom pipelines, e.g. a latency-only monitor:
+//!
+//! ```no_run
+//! # #[cfg(feature = "reqwest")]
+//! # async fn monitor() -> speedtest_rs_core::error::Result<()> {
+//! use std::time::Duration;
+//!
+//! use speedtest_rs_core::prelude::*;
+//!
+//! let speed_tester = SpeedTester::default();
+//! let discovery = speed_tester.discover().await?;
+//! let selection = speed_tester.pick_server(&discovery).await?;
+//! let latency = speed_tester
+//!     .measure_latency(&selection, Duration::from_secs(5))
+//!     .await;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+pub use speedtest_rs_types::stage::{
+    ProgressTracker, RaceProbe, Stage, StageEvent, sample_interval, time_fraction,
+};
+use tokio::{sync::mpsc, time::Instant};
+
+use crate::model::{Config, Server};
+
+/// Interval of the [`StageEvent::Progress`] events of [`report`].
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Output of [`SpeedTester::discover`](crate::speed_tester::SpeedTester::discover): the remote
+/// config and the candidate servers, with the servers ignored by the config already removed.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Discovery {
+    pub config: Config,
+    pub servers: Vec<Server>,
+    /// Servers removed because the config lists them in `ignoreids`.
+    pub ignored: Vec<Server>,
+}
+
+/// Output of [`SpeedTester::pick_server`](crate::speed_tester::SpeedTester::pick_server): the
+/// server all measurements run against.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Selection {
+    pub config: Config,
+    pub server: Server,
+}
+
+/// Server to test against without a race, see
+/// [`SpeedTester::with_server`](crate::speed_tester::SpeedTester::with_server).
+#[derive(Debug, Clone)]
+pub enum ServerChoice {
+    /// Looked up in the discovered servers by its id or host, see [`Server::matches`].
+    IdOrHost(String),
+    /// Used as is, e.g. a self-hosted server missing from the server list.
+    Server(Box<Server>),
+}
+
+/// Runs `future` as `stage`, sending [`StageEvent::Started`] to `events`, then a
+/// [`StageEvent::Progress`] every [`PROGRESS_INTERVAL`] with the bytes `counter` moved, and
+/// [`StageEvent::Finished`] once it completes.
+pub async fn report<F, T>(
+    stage: Stage,
+    counter: Option<&AtomicU64>,
+    events: &mpsc::UnboundedSender<T>,
+    future: F,
+) -> F::Output
+where
+    F: Future,
+    T: From<StageEvent>,
+{
+    let bytes = || counter.map_or(0, |counter| counter.load(Ordering::SeqCst));
+    let start = Instant::now();
+    let start_bytes = bytes();
+    _ = events.send(StageEvent::Started { stage }.into());
+
+    let mut ticks = tokio::time::interval_at(start + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+    let mut future = std::pin::pin!(future);
+    let output = loop {
+        tokio::select! {
+            output = &mut future => break output,
+            _ = ticks.tick() => {
+                let progress = StageEvent::Progress {
+                    stage,
+                    elapsed: start.elapsed(),
+                    // Failed uploads are taken out of the counter again.
+                    bytes: bytes().saturating_sub(start_bytes),
+                };
+                _ = events.send(progress.into());
+            }
+        }
+    };
+    _ = events.send(StageEvent::Finished { stage }.into());
+    output
+}
+
+impl Selection {
+    /// Skips server racing, e.g. to always test against the same server.
+    pub fn new(config: Config, server: Server) -> Self {
+        Self { config, server }
+    }
+}
+
+/// Stages of a run to measure, see
+/// [`SpeedTester::with_test_plan`](crate::speed_tester::SpeedTester::with_test_plan). The
+/// discovery and the server selection always run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestPlan {
+    /// Idle latency before the transfers and loaded latency during them.
+    pub latency: bool,
+    pub download: bool,
+    pub upload: bool,
+}
+
+impl Default for TestPlan {
+    fn default() -> Self {
+        Self {
+            latency: true,
+            download: true,
+            upload: true,
+        }
+    }
+}
+
+impl TestPlan {
+    pub fn includes(&self, stage: Stage) -> bool {
+        match stage {
+            Stage::Latency => self.latency,
+            Stage::Download => self.download,
+            Stage::Upload => self.upload,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    use tokio::sync::mpsc;
+
+    use crate::stage::{Stage, StageEvent, report};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_report() {
+        let (events, mut rx) = mpsc::unbounded_channel::<StageEvent>();
+        let counter = AtomicU64::new(1_000);
+        let transfer = async {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            counter.fetch_add(500, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            42
+        };
+        let output = report(Stage::Download, Some(&counter), &events, transfer).await;
+        assert_eq!(output, 42);
+
+        let stage = Stage::Download;
+        let mut received = vec![];
+        while let Ok(event) = rx.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            [
+                StageEvent::Started { stage },
+                StageEvent::Progress {
+                    stage,
+                    elapsed: Duration::from_millis(100),
+                    bytes: 0,
+                },
+                StageEvent::Progress {
+                    stage,
+                    elapsed: Duration::from_millis(200),
+                    bytes: 500,
+                },
+                StageEvent::Finished { stage },
+            ]
+        );
+    }
+}
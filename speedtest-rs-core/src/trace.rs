@@ -0,0 +1,34 @@
+//! Log macros forwarding to `tracing`, or compiling to nothing without the `tracing` feature.
+
+#[cfg(feature = "tracing")]
+macro_rules! debug {
+    ($($arg:tt)*) => { tracing::debug!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! info {
+    ($($arg:tt)*) => { tracing::info!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! warning {
+    ($($arg:tt)*) => { tracing::warn!($($arg)*) };
+}
+
+// The arguments are still type checked, so both configurations fail to build on the same mistakes.
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {{ _ = format_args!($($arg)*); }};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! info {
+    ($($arg:tt)*) => {{ _ = format_args!($($arg)*); }};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! warning {
+    ($($arg:tt)*) => {{ _ = format_args!($($arg)*); }};
+}
+
+pub(crate) use {debug, info, warning};
@@ -1,8 +1,39 @@
+use std::collections::HashMap;
+
+use crate::model::Server;
+
+/// Download path of Ookla servers.
+///
+/// Placeholders: `{base}` (server URL without `upload.php`), `{url}` (full server URL), `{host}`
+/// and `{size}`.
+pub const DEFAULT_DOWNLOAD_TEMPLATE: &str = "{base}/random{size}x{size}.jpg";
+
+/// Any HTTP answer from it proves the internet is reachable.
+pub const CONNECTIVITY_PROBE_URL: &str = "http://www.speedtest.net/";
+
+/// Answers `204 No Content` with an empty body on an open network, captive portals redirect it
+/// or answer with their login page.
+pub const CAPTIVE_PORTAL_PROBE_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+
+/// Servers requested from the JSON server list.
+const JSON_SERVER_LIMIT: usize = 20;
+
 #[derive(Debug, Default, Clone)]
 pub struct SpeedTestUrl {
     use_tls: bool,
 
     threads: usize,
+
+    download_template: Option<String>,
+
+    /// Templates of individual servers by server id.
+    server_download_templates: HashMap<String, String>,
+
+    /// Hosts serving the config and server lists instead of the speedtest.net ones.
+    hosts: Vec<String>,
+
+    /// Servers tested instead of the fetched server lists.
+    servers: Vec<Server>,
 }
 
 impl SpeedTestUrl {
@@ -20,30 +51,130 @@ impl SpeedTestUrl {
         self
     }
 
-    pub fn config_urls(&self) -> impl Iterator<Item = String> {
-        SpeedTestHost::all().into_iter().map(|host| {
-            if self.use_tls {
-                format!("https://{}{}", host.host(), SpeedTestPath::Config.path())
-            } else {
-                format!("http://{}{}", host.host(), SpeedTestPath::Config.path())
-            }
-        })
+    /// Replaces [`DEFAULT_DOWNLOAD_TEMPLATE`], e.g. `{base}/download?size={size}` for self-hosted
+    /// servers.
+    pub fn download_template(mut self, template: impl Into<String>) -> Self {
+        self.download_template = Some(template.into());
+        self
+    }
+
+    /// Overrides the download template of a single server.
+    pub fn server_download_template(
+        mut self,
+        server_id: impl Into<String>,
+        template: impl Into<String>,
+    ) -> Self {
+        self.server_download_templates
+            .insert(server_id.into(), template.into());
+        self
+    }
+
+    /// Fetches the config and the server lists from these hosts, e.g. a corporate mirror as
+    /// `speedtest.example.com:8080`, instead of www.speedtest.net and c.speedtest.net. No hosts
+    /// keeps the defaults.
+    pub fn hosts<I>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.hosts = hosts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Tests against these servers, e.g. self-hosted speedtest-mini installs, instead of fetching
+    /// the server lists. The stock config stands in when no config host answers, so the test also
+    /// works in networks without access to speedtest.net.
+    pub fn servers(mut self, servers: Vec<Server>) -> Self {
+        self.servers = servers;
+        self
+    }
+
+    /// The servers set with [`servers`](Self::servers).
+    pub fn static_servers(&self) -> &[Server] {
+        &self.servers
+    }
+
+    /// The custom hosts, or else the speedtest.net ones.
+    fn host_names(&self) -> Vec<&str> {
+        if self.hosts.is_empty() {
+            SpeedTestHost::all()
+                .iter()
+                .map(SpeedTestHost::host)
+                .collect()
+        } else {
+            self.hosts.iter().map(String::as_str).collect()
+        }
+    }
+
+    pub fn download_url(&self, server: &Server, size: usize) -> String {
+        let template = self
+            .server_download_templates
+            .get(&server.id)
+            .or(self.download_template.as_ref())
+            .map_or(DEFAULT_DOWNLOAD_TEMPLATE, String::as_str);
+
+        template
+            .replace("{base}", server.base_url())
+            .replace("{url}", &server.url)
+            .replace("{host}", &server.host)
+            .replace("{size}", &size.to_string())
+    }
+
+    pub(crate) fn config_urls(&self) -> impl Iterator<Item = String> {
+        let scheme = if self.use_tls { "https" } else { "http" };
+        self.host_names()
+            .into_iter()
+            .map(move |host| format!("{}://{}{}", scheme, host, SpeedTestPath::Config.path()))
+    }
+
+    /// The JSON server list, an alternative to the XML lists, optionally filtered by a keyword
+    /// matching e.g. the sponsor or city.
+    pub(crate) fn server_json_url(&self, search: Option<&str>) -> String {
+        let scheme = if self.use_tls { "https" } else { "http" };
+        let mut url = format!(
+            "{}://{}{}?engine=js&limit={}",
+            scheme,
+            self.host_names()[0],
+            SpeedTestPath::JsonServers.path(),
+            JSON_SERVER_LIMIT
+        );
+        if let Some(search) = search {
+            url.push_str("&search=");
+            url.extend(search.bytes().map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            }));
+        }
+        url
+    }
+
+    /// The API results are registered with, see [`share`](crate::share).
+    pub(crate) fn share_url(&self) -> String {
+        let scheme = if self.use_tls { "https" } else { "http" };
+        format!(
+            "{}://{}{}",
+            scheme,
+            SpeedTestHost::Main.host(),
+            SpeedTestPath::Share.path()
+        )
     }
 
-    pub fn server_urls(&self) -> impl Iterator<Item = String> {
-        SpeedTestHost::all().into_iter().flat_map(move |host| {
+    pub(crate) fn server_urls(&self) -> impl Iterator<Item = String> {
+        let scheme = if self.use_tls { "https" } else { "http" };
+        self.host_names().into_iter().flat_map(move |host| {
             SpeedTestPath::servers().into_iter().map(move |path| {
-                let scheme = if self.use_tls { "https" } else { "http" };
                 if self.threads > 0 {
                     format!(
                         "{}://{}{}?threads={}",
                         scheme,
-                        host.host(),
+                        host,
                         path.path(),
                         self.threads
                     )
                 } else {
-                    format!("{}://{}{}", scheme, host.host(), path.path())
+                    format!("{}://{}{}", scheme, host, path.path())
                 }
             })
         })
@@ -51,7 +182,7 @@ impl SpeedTestUrl {
 }
 
 #[derive(Debug, Clone, Copy)]
-pub enum SpeedTestHost {
+pub(crate) enum SpeedTestHost {
     Main,
     Backup,
 }
@@ -70,10 +201,12 @@ impl SpeedTestHost {
 }
 
 #[derive(Debug, Clone, Copy)]
-pub enum SpeedTestPath {
+pub(crate) enum SpeedTestPath {
     Config,
     Server,
     ServerStatic,
+    JsonServers,
+    Share,
 }
 
 impl SpeedTestPath {
@@ -86,13 +219,49 @@ impl SpeedTestPath {
             SpeedTestPath::Config => "/speedtest-config.php",
             SpeedTestPath::Server => "/speedtest-servers.php",
             SpeedTestPath::ServerStatic => "/speedtest-servers-static.php",
+            SpeedTestPath::JsonServers => "/api/js/servers",
+            SpeedTestPath::Share => "/api/api.php",
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::urls::SpeedTestUrl;
+    use crate::{model::Server, urls::SpeedTestUrl};
+
+    #[test]
+    fn test_download_url() {
+        let server = Server {
+            url: "http://speed.example.com:8080/speedtest/upload.php".to_string(),
+            lat: 0.0,
+            lon: 0.0,
+            name: "Example".to_string(),
+            country: "Example".to_string(),
+            cc: "EX".to_string(),
+            sponsor: "Example".to_string(),
+            id: "42".to_string(),
+            host: "speed.example.com:8080".to_string(),
+            extra: Default::default(),
+        };
+
+        let urls = SpeedTestUrl::new();
+        assert_eq!(
+            urls.download_url(&server, 350),
+            "http://speed.example.com:8080/speedtest/random350x350.jpg"
+        );
+
+        let urls = urls.download_template("{base}/download?size={size}");
+        assert_eq!(
+            urls.download_url(&server, 350),
+            "http://speed.example.com:8080/speedtest/download?size=350"
+        );
+
+        let urls = urls.server_download_template("42", "https://{host}/files/{size}.bin");
+        assert_eq!(
+            urls.download_url(&server, 350),
+            "https://speed.example.com:8080/files/350.bin"
+        );
+    }
 
     #[test]
     fn test_config_urls() {
@@ -107,6 +276,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_custom_hosts() {
+        let urls = SpeedTestUrl::new().hosts(["speedtest.example.com:8080"]);
+
+        assert_eq!(
+            urls.config_urls().collect::<Vec<_>>(),
+            ["http://speedtest.example.com:8080/speedtest-config.php"]
+        );
+        assert_eq!(
+            urls.server_urls().collect::<Vec<_>>(),
+            [
+                "http://speedtest.example.com:8080/speedtest-servers.php",
+                "http://speedtest.example.com:8080/speedtest-servers-static.php",
+            ]
+        );
+        assert!(
+            urls.server_json_url(None)
+                .starts_with("http://speedtest.example.com:8080/api/js/servers")
+        );
+        assert_eq!(
+            SpeedTestUrl::new()
+                .hosts(Vec::<String>::new())
+                .config_urls()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_server_json_url() {
+        let urls = SpeedTestUrl::new().use_tls(true);
+
+        assert_eq!(
+            urls.server_json_url(None),
+            "https://www.speedtest.net/api/js/servers?engine=js&limit=20"
+        );
+        assert_eq!(
+            urls.server_json_url(Some("Hong Kong&x")),
+            "https://www.speedtest.net/api/js/servers?engine=js&limit=20&search=Hong%20Kong%26x"
+        );
+    }
+
     #[test]
     fn test_servers_urls() {
         let urls: Vec<_> = SpeedTestUrl::new()
@@ -0,0 +1,624 @@
+//! Snapshot of the public API of the core and its types crate with the default features, in the
+//! style of `cargo public-api`: every public item, field, variant, method and trait impl, one per
+//! line in `tests/public-api.txt`. A change of the snapshot is a change of the API, reviewed with
+//! the diff and checked with `cargo semver-checks` before a release.
+//!
+//! The items are read from the rustdoc JSON, which needs a nightly toolchain; without one the test
+//! is skipped. `UPDATE_PUBLIC_API=1 cargo test -p speedtest-rs-core --test public_api` rewrites
+//! the snapshot after an intended change.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde_json::Value;
+
+const CRATES: [(&str, &str); 2] = [
+    ("speedtest-rs-types", "speedtest_rs_types"),
+    ("speedtest-rs-core", "speedtest_rs_core"),
+];
+
+/// Auto and marker traits of the nightly toolchain, not nameable by users on stable.
+const UNSTABLE_TRAITS: [&str; 3] = [
+    "core::marker::Freeze",
+    "core::marker::StructuralPartialEq",
+    "core::marker::UnsafeUnpin",
+];
+
+#[test]
+fn test_public_api() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("../target/public-api");
+    let snapshot_path = manifest_dir.join("tests/public-api.txt");
+
+    let mut api = String::new();
+    for (package, name) in CRATES {
+        let Some(json) = rustdoc_json(package, name, &target_dir) else {
+            eprintln!("skipped: `cargo +nightly rustdoc` is not available");
+            return;
+        };
+        for line in public_api(&json) {
+            api.push_str(&line);
+            api.push('\n');
+        }
+    }
+
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        std::fs::write(&snapshot_path, &api).unwrap();
+        return;
+    }
+    let snapshot = std::fs::read_to_string(&snapshot_path).unwrap_or_default();
+    if snapshot != api {
+        let old: BTreeSet<&str> = snapshot.lines().collect();
+        let new: BTreeSet<&str> = api.lines().collect();
+        let removed = old.difference(&new).map(|line| format!("-{line}"));
+        let added = new.difference(&old).map(|line| format!("+{line}"));
+        panic!(
+            "the public API changed, rerun with UPDATE_PUBLIC_API=1 if intended:\n{}",
+            removed.chain(added).collect::<Vec<_>>().join("\n")
+        );
+    }
+}
+
+/// The rustdoc JSON of a package, built in a target directory of its own so the build does not
+/// wait for the lock of the running `cargo test`.
+fn rustdoc_json(package: &str, name: &str, target_dir: &Path) -> Option<Value> {
+    let status = Command::new("cargo")
+        .args([
+            "+nightly",
+            "rustdoc",
+            "--quiet",
+            "--package",
+            package,
+            "--target-dir",
+        ])
+        .arg(target_dir)
+        .args(["--", "-Z", "unstable-options", "--output-format", "json"])
+        // Set by rustup and cargo for the stable toolchain running this test.
+        .env_remove("RUSTUP_TOOLCHAIN")
+        .env_remove("RUSTC")
+        .env_remove("RUSTDOC")
+        .env_remove("RUSTC_WRAPPER")
+        .status()
+        .ok()?;
+    if !status.success() {
+        return None;
+    }
+    let path: PathBuf = target_dir.join(format!("doc/{name}.json"));
+    Some(serde_json::from_str(&std::fs::read_to_string(path).ok()?).unwrap())
+}
+
+/// The sorted lines of the public items, walking the modules from the crate root.
+fn public_api(json: &Value) -> BTreeSet<String> {
+    let mut api = Api {
+        json,
+        lines: BTreeSet::new(),
+        visited: HashSet::new(),
+    };
+    let root = &json["root"];
+    let name = json["index"][id(root)]["name"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    api.module(root, &name);
+    api.lines
+}
+
+fn id(value: &Value) -> String {
+    value.to_string()
+}
+
+struct Api<'a> {
+    json: &'a Value,
+    lines: BTreeSet<String>,
+    visited: HashSet<String>,
+}
+
+impl Api<'_> {
+    fn item(&self, id: &Value) -> Option<&Value> {
+        self.json["index"].get(self::id(id))
+    }
+
+    fn module(&mut self, id: &Value, path: &str) {
+        if !self.visited.insert(format!("{path}#{id}")) {
+            return;
+        }
+        self.lines.insert(format!("pub mod {path}"));
+        let Some(module) = self.item(id) else {
+            return;
+        };
+        for child in module["inner"]["module"]["items"]
+            .as_array()
+            .unwrap()
+            .clone()
+        {
+            self.child(&child, path);
+        }
+    }
+
+    fn child(&mut self, id: &Value, parent: &str) {
+        let Some(item) = self.item(id).cloned() else {
+            return;
+        };
+        if item["visibility"] != "public" {
+            return;
+        }
+        let name = item["name"].as_str().unwrap_or_default();
+        let path = format!("{parent}::{name}");
+        let attrs = attrs(&item);
+        let (kind, inner) = kind(&item);
+        match kind {
+            "module" => self.module(id, &path),
+            "use" => {
+                let source = inner["source"].as_str().unwrap();
+                if inner["is_glob"] == true {
+                    self.lines.insert(format!("pub use {parent}::{source}::*"));
+                } else {
+                    let name = inner["name"].as_str().unwrap();
+                    self.lines
+                        .insert(format!("pub use {parent}::{name} = {source}"));
+                }
+            }
+            "struct" => {
+                let generics = self.generics(&inner["generics"]);
+                self.lines
+                    .insert(format!("{attrs}pub struct {path}{generics}"));
+                match &inner["kind"] {
+                    Value::Object(kind) if kind.contains_key("plain") => {
+                        for field in kind["plain"]["fields"].as_array().unwrap() {
+                            self.field(field, &path);
+                        }
+                    }
+                    Value::Object(kind) if kind.contains_key("tuple") => {
+                        for (i, field) in kind["tuple"].as_array().unwrap().iter().enumerate() {
+                            if !field.is_null() {
+                                let ty = self.field_type(field);
+                                self.lines.insert(format!("pub field {path}::{i}: {ty}"));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                self.impls(&inner["impls"], &path);
+            }
+            "enum" => {
+                let generics = self.generics(&inner["generics"]);
+                self.lines
+                    .insert(format!("{attrs}pub enum {path}{generics}"));
+                for variant in inner["variants"].as_array().unwrap() {
+                    self.variant(variant, &path);
+                }
+                self.impls(&inner["impls"], &path);
+            }
+            "trait" => {
+                let generics = self.generics(&inner["generics"]);
+                let bounds = self.bounds(&inner["bounds"]);
+                let bounds = if bounds.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {bounds}")
+                };
+                self.lines
+                    .insert(format!("{attrs}pub trait {path}{generics}{bounds}"));
+                for item in inner["items"].as_array().unwrap() {
+                    self.assoc(item, &path);
+                }
+            }
+            "function" => {
+                let function = self.function(&path, &inner);
+                self.lines.insert(format!("{attrs}pub {function}"));
+            }
+            "constant" => {
+                let ty = self.ty(&inner["type"]);
+                self.lines.insert(format!("pub const {path}: {ty}"));
+            }
+            "static" => {
+                let ty = self.ty(&inner["type"]);
+                self.lines.insert(format!("pub static {path}: {ty}"));
+            }
+            "type_alias" => {
+                let generics = self.generics(&inner["generics"]);
+                let ty = self.ty(&inner["type"]);
+                self.lines
+                    .insert(format!("pub type {path}{generics} = {ty}"));
+            }
+            other => {
+                self.lines.insert(format!("pub {other} {path}"));
+            }
+        }
+    }
+
+    fn field(&mut self, id: &Value, parent: &str) {
+        let Some(field) = self.item(id).cloned() else {
+            return;
+        };
+        let name = field["name"].as_str().unwrap();
+        let ty = self.ty(&field["inner"]["struct_field"]);
+        self.lines
+            .insert(format!("pub field {parent}::{name}: {ty}"));
+    }
+
+    fn field_type(&self, id: &Value) -> String {
+        self.item(id)
+            .map(|field| self.ty(&field["inner"]["struct_field"]))
+            .unwrap_or_default()
+    }
+
+    fn variant(&mut self, id: &Value, parent: &str) {
+        let Some(variant) = self.item(id).cloned() else {
+            return;
+        };
+        let name = variant["name"].as_str().unwrap();
+        let kind = &variant["inner"]["variant"]["kind"];
+        let fields = match kind {
+            Value::Object(kind) if kind.contains_key("tuple") => {
+                let fields: Vec<String> = kind["tuple"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter(|field| !field.is_null())
+                    .map(|field| self.field_type(field))
+                    .collect();
+                format!("({})", fields.join(", "))
+            }
+            Value::Object(kind) if kind.contains_key("struct") => {
+                let fields: Vec<String> = kind["struct"]["fields"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter_map(|field| self.item(field))
+                    .map(|field| {
+                        let name = field["name"].as_str().unwrap();
+                        format!("{name}: {}", self.ty(&field["inner"]["struct_field"]))
+                    })
+                    .collect();
+                format!(" {{ {} }}", fields.join(", "))
+            }
+            _ => String::new(),
+        };
+        self.lines
+            .insert(format!("pub variant {parent}::{name}{fields}"));
+    }
+
+    /// Inherent methods and trait impls, except the blanket impls of the standard library.
+    fn impls(&mut self, impls: &Value, parent: &str) {
+        for id in impls.as_array().unwrap() {
+            let Some(item) = self.item(id).cloned() else {
+                continue;
+            };
+            let inner = &item["inner"]["impl"];
+            if !inner["blanket_impl"].is_null() {
+                continue;
+            }
+            let generics = self.generics(&inner["generics"]);
+            let for_ = self.ty(&inner["for"]);
+            if inner["trait"].is_null() {
+                for id in inner["items"].as_array().unwrap() {
+                    self.assoc(id, parent);
+                }
+            } else {
+                let negative = if inner["is_negative"] == true {
+                    "!"
+                } else {
+                    ""
+                };
+                let trait_ = self.path(&inner["trait"]);
+                if UNSTABLE_TRAITS.contains(&trait_.as_str()) {
+                    continue;
+                }
+                self.lines
+                    .insert(format!("impl{generics} {negative}{trait_} for {for_}"));
+            }
+        }
+    }
+
+    fn assoc(&mut self, id: &Value, parent: &str) {
+        let Some(item) = self.item(id).cloned() else {
+            return;
+        };
+        // Trait items have no visibility of their own.
+        if item["visibility"] != "public" && item["visibility"] != "default" {
+            return;
+        }
+        let name = item["name"].as_str().unwrap();
+        let attrs = attrs(&item);
+        let (kind, inner) = kind(&item);
+        let line = match kind {
+            "function" => format!(
+                "{attrs}pub {}",
+                self.function(&format!("{parent}::{name}"), &inner)
+            ),
+            "constant" => format!("pub const {parent}::{name}: {}", self.ty(&inner["type"])),
+            "assoc_const" => format!("pub const {parent}::{name}: {}", self.ty(&inner["type"])),
+            "assoc_type" => format!("pub type {parent}::{name}"),
+            other => format!("pub {other} {parent}::{name}"),
+        };
+        self.lines.insert(line);
+    }
+
+    fn function(&self, path: &str, inner: &Value) -> String {
+        let header = &inner["header"];
+        let mut qualifiers = String::new();
+        for (key, qualifier) in [
+            ("is_const", "const "),
+            ("is_async", "async "),
+            ("is_unsafe", "unsafe "),
+        ] {
+            if header[key] == true {
+                qualifiers.push_str(qualifier);
+            }
+        }
+        let sig = &inner["sig"];
+        let inputs: Vec<String> = sig["inputs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|input| {
+                let name = input[0].as_str().unwrap();
+                let ty = self.ty(&input[1]);
+                match ty.strip_suffix("Self") {
+                    Some(reference) if name == "self" => format!("{reference}self"),
+                    _ => format!("{name}: {ty}"),
+                }
+            })
+            .collect();
+        let output = if sig["output"].is_null() {
+            String::new()
+        } else {
+            format!(" -> {}", self.ty(&sig["output"]))
+        };
+        let generics = self.generics(&inner["generics"]);
+        let bounds = self.where_clause(&inner["generics"]);
+        format!(
+            "{qualifiers}fn {path}{generics}({}){output}{bounds}",
+            inputs.join(", ")
+        )
+    }
+
+    /// `<...>` of the declared parameters, leaving out those of `impl Trait` arguments.
+    fn generics(&self, generics: &Value) -> String {
+        let params: Vec<String> = generics["params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|param| {
+                let name = param["name"].as_str().unwrap();
+                let kind = &param["kind"];
+                if let Some(ty) = kind.get("type") {
+                    if ty["is_synthetic"] == true {
+                        return None;
+                    }
+                    let bounds = self.bounds(&ty["bounds"]);
+                    return Some(if bounds.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("{name}: {bounds}")
+                    });
+                }
+                if let Some(constant) = kind.get("const") {
+                    return Some(format!("const {name}: {}", self.ty(&constant["type"])));
+                }
+                Some(name.to_string())
+            })
+            .collect();
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", params.join(", "))
+        }
+    }
+
+    fn where_clause(&self, generics: &Value) -> String {
+        let predicates: Vec<String> = generics["where_predicates"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|predicate| {
+                let bound = predicate.get("bound_predicate")?;
+                Some(format!(
+                    "{}: {}",
+                    self.ty(&bound["type"]),
+                    self.bounds(&bound["bounds"])
+                ))
+            })
+            .collect();
+        if predicates.is_empty() {
+            String::new()
+        } else {
+            format!(" where {}", predicates.join(", "))
+        }
+    }
+
+    fn bounds(&self, bounds: &Value) -> String {
+        let bounds: Vec<String> = bounds
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bound| {
+                if let Some(trait_bound) = bound.get("trait_bound") {
+                    let modifier = if trait_bound["modifier"] == "maybe" {
+                        "?"
+                    } else {
+                        ""
+                    };
+                    format!("{modifier}{}", self.path(&trait_bound["trait"]))
+                } else if let Some(lifetime) = bound.get("outlives") {
+                    lifetime.as_str().unwrap().to_string()
+                } else {
+                    "use<..>".to_string()
+                }
+            })
+            .collect();
+        bounds.join(" + ")
+    }
+
+    /// A path by the canonical name of its item where rustdoc knows it, as written otherwise.
+    fn path(&self, path: &Value) -> String {
+        let name = self.json["paths"]
+            .get(id(&path["id"]))
+            .and_then(|summary| summary["path"].as_array())
+            .map(|segments| {
+                let segments: Vec<&str> = segments.iter().filter_map(Value::as_str).collect();
+                segments.join("::")
+            })
+            .unwrap_or_else(|| path["path"].as_str().unwrap_or_default().to_string());
+        format!("{name}{}", self.args(&path["args"]))
+    }
+
+    fn args(&self, args: &Value) -> String {
+        if let Some(angle) = args.get("angle_bracketed") {
+            let mut rendered: Vec<String> = angle["args"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|arg| match arg {
+                    Value::Object(arg) if arg.contains_key("type") => self.ty(&arg["type"]),
+                    Value::Object(arg) if arg.contains_key("lifetime") => {
+                        arg["lifetime"].as_str().unwrap().to_string()
+                    }
+                    Value::Object(arg) if arg.contains_key("const") => {
+                        arg["const"]["expr"].as_str().unwrap_or("_").to_string()
+                    }
+                    _ => "_".to_string(),
+                })
+                .collect();
+            for constraint in angle["constraints"].as_array().unwrap() {
+                let name = constraint["name"].as_str().unwrap();
+                let binding = &constraint["binding"];
+                rendered.push(match binding.get("equality") {
+                    Some(equality) => format!("{name} = {}", self.ty(&equality["type"])),
+                    None => format!("{name}: {}", self.bounds(&binding["constraint"])),
+                });
+            }
+            if rendered.is_empty() {
+                String::new()
+            } else {
+                format!("<{}>", rendered.join(", "))
+            }
+        } else if let Some(parenthesized) = args.get("parenthesized") {
+            let inputs: Vec<String> = parenthesized["inputs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|input| self.ty(input))
+                .collect();
+            let output = if parenthesized["output"].is_null() {
+                String::new()
+            } else {
+                format!(" -> {}", self.ty(&parenthesized["output"]))
+            };
+            format!("({}){output}", inputs.join(", "))
+        } else {
+            String::new()
+        }
+    }
+
+    fn ty(&self, ty: &Value) -> String {
+        let Value::Object(ty) = ty else {
+            return "_".to_string();
+        };
+        let Some((kind, inner)) = ty.iter().next() else {
+            return "_".to_string();
+        };
+        match kind.as_str() {
+            "resolved_path" => self.path(inner),
+            "generic" | "primitive" => inner.as_str().unwrap().to_string(),
+            "borrowed_ref" => {
+                let lifetime = match inner["lifetime"].as_str() {
+                    Some(lifetime) => format!("{lifetime} "),
+                    None => String::new(),
+                };
+                let mutable = if inner["is_mutable"] == true {
+                    "mut "
+                } else {
+                    ""
+                };
+                format!("&{lifetime}{mutable}{}", self.ty(&inner["type"]))
+            }
+            "raw_pointer" => {
+                let mutable = if inner["is_mutable"] == true {
+                    "mut"
+                } else {
+                    "const"
+                };
+                format!("*{mutable} {}", self.ty(&inner["type"]))
+            }
+            "slice" => format!("[{}]", self.ty(inner)),
+            "array" => format!(
+                "[{}; {}]",
+                self.ty(&inner["type"]),
+                inner["len"].as_str().unwrap_or("_")
+            ),
+            "tuple" => {
+                let types: Vec<String> = inner
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|ty| self.ty(ty))
+                    .collect();
+                format!("({})", types.join(", "))
+            }
+            "impl_trait" => format!("impl {}", self.bounds(inner)),
+            "dyn_trait" => {
+                let traits: Vec<String> = inner["traits"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|bound| self.path(&bound["trait"]))
+                    .chain(inner["lifetime"].as_str().map(str::to_string))
+                    .collect();
+                format!("dyn {}", traits.join(" + "))
+            }
+            "qualified_path" => {
+                let self_type = self.ty(&inner["self_type"]);
+                let name = inner["name"].as_str().unwrap();
+                match inner["trait"].is_null() {
+                    true => format!("{self_type}::{name}"),
+                    false => format!("<{self_type} as {}>::{name}", self.path(&inner["trait"])),
+                }
+            }
+            "function_pointer" => {
+                let sig = &inner["sig"];
+                let inputs: Vec<String> = sig["inputs"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|input| self.ty(&input[1]))
+                    .collect();
+                let output = if sig["output"].is_null() {
+                    String::new()
+                } else {
+                    format!(" -> {}", self.ty(&sig["output"]))
+                };
+                format!("fn({}){output}", inputs.join(", "))
+            }
+            _ => "_".to_string(),
+        }
+    }
+}
+
+fn kind(item: &Value) -> (&str, Value) {
+    let (kind, inner) = item["inner"].as_object().unwrap().iter().next().unwrap();
+    (kind.as_str(), inner.clone())
+}
+
+/// `#[non_exhaustive]` and `#[deprecated]`, the attributes that make a difference to users.
+fn attrs(item: &Value) -> String {
+    let mut attrs = String::new();
+    let has = |name: &str| {
+        item["attrs"].as_array().is_some_and(|attrs| {
+            attrs
+                .iter()
+                .any(|attr| attr == name || attr.get(name).is_some())
+        })
+    };
+    if has("non_exhaustive") {
+        attrs.push_str("#[non_exhaustive] ");
+    }
+    if !item["deprecation"].is_null() {
+        attrs.push_str("#[deprecated] ");
+    }
+    attrs
+}
@@ -0,0 +1,64 @@
+//! Compile-time check of the `stable` API: a signature change that breaks this file breaks
+//! downstream crates too, and needs a deprecation first. `cargo semver-checks` covers the rest of
+//! the public API before a release.
+
+use std::time::Duration;
+
+use speedtest_rs_core::stable::*;
+
+/// Type checked only, never run: the stage pipeline embedders compose.
+#[allow(dead_code)]
+async fn pipeline(speed_tester: SpeedTester) -> Result<SpeedTestResult, Error> {
+    let discovery: Discovery = speed_tester.discover().await?;
+    let selection: Selection = speed_tester.pick_server(&discovery).await?;
+    let latency: Option<Latency> = speed_tester
+        .measure_latency(&selection, Duration::from_secs(5))
+        .await;
+    let download: TransferResult = speed_tester.measure_download(&selection).await;
+    let upload: TransferResult = speed_tester.measure_upload(&selection).await;
+
+    let config: &Config = &selection.config;
+    let server: &Server = &selection.server;
+    let _: &Client = &config.client;
+    let _: &[Server] = &discovery.servers;
+
+    let mut result =
+        SpeedTestResult::new(chrono::Utc::now(), ClientInfo::from(&config.client), server)
+            .with_download(download)
+            .with_upload(upload);
+    result.idle_latency = latency;
+    let _: SpeedTestResult = speed_tester.run_full_test().await?;
+    let _: Vec<SpeedTestResult> = speed_tester.run_multi_server_test(3).await?;
+    Ok(result.redact())
+}
+
+#[test]
+fn test_stable_api() {
+    let _: fn() -> SpeedTester = SpeedTester::default;
+    let _: fn(SpeedTester, Duration) -> SpeedTester = SpeedTester::with_timeout;
+    let _: fn(SpeedTester, PhaseOverrides) -> SpeedTester = SpeedTester::with_download_overrides;
+    let _: fn(SpeedTester, ConfigOverrides) -> SpeedTester = SpeedTester::with_config_overrides;
+    let _: fn(&ConfigOverrides, &mut Config) = ConfigOverrides::apply;
+    let _: fn(SpeedTester, Option<Scoring>) -> SpeedTester = SpeedTester::with_scoring;
+    let _: fn(SpeedTester, SpeedTestUrl) -> SpeedTester = SpeedTester::with_url;
+    let _: fn(SpeedTester, TestPlan) -> SpeedTester = SpeedTester::with_test_plan;
+    let _: fn(SpeedTester, RetryPolicy) -> SpeedTester = SpeedTester::with_retry_policy;
+    let _: fn(SpeedTester, HttpVersion) -> SpeedTester = SpeedTester::with_http_version;
+    let _: fn(&RetryPolicy, u32, f64) -> Duration = RetryPolicy::delay;
+
+    let _: fn(Stage) -> &'static str = Stage::as_str;
+    let _: fn(Stage, &str) -> &'static str = Stage::display_name;
+    let _: fn(Stage, f64) -> f64 = Stage::overall;
+
+    let mut tracker = ProgressTracker::new();
+    let (stage, fraction, _) = tracker.update(&StageEvent::Started {
+        stage: Stage::Download,
+    });
+    assert_eq!((stage.as_str(), fraction), ("download", 0.0));
+
+    let transfer = TransferResult::new(12_500_000, Duration::from_secs(1));
+    assert_eq!(transfer.bits_per_second, 100_000_000.0);
+    assert_eq!(M_BITS_PER_SEC, 1_000_000);
+    assert_eq!(bitrate_unit(transfer.bits_per_second), (1e6, "Mbps"));
+    assert_eq!(ByteSize(KB as u64).to_string(), "1.00 KBytes");
+}
@@ -0,0 +1,138 @@
+//! Errors of the checks before a run, for telling the user what to fix.
+
+use serde::{Deserialize, Serialize};
+
+/// The network intercepts plain HTTP requests, usually until the user signs in on a login page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptivePortal {
+    /// Host the probe was redirected to, if it was redirected.
+    pub location: Option<String>,
+}
+
+impl std::fmt::Display for CaptivePortal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "captive portal detected (redirected to {location})")?,
+            None => write!(f, "captive portal detected")?,
+        }
+        write!(f, ", sign in to the network and try again")
+    }
+}
+
+impl std::error::Error for CaptivePortal {}
+
+/// The internet cannot be reached at all, e.g. the cable is unplugged or DNS is down.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoConnectivity {
+    pub reason: String,
+}
+
+impl std::fmt::Display for NoConnectivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no internet connectivity: {}, check the network connection",
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for NoConnectivity {}
+
+/// Every attempt to fetch the config or the server lists failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchFailed {
+    /// What was fetched, e.g. `"config"`.
+    pub what: String,
+    pub attempts: u32,
+    /// The failed requests of all attempts, in the order they failed.
+    pub failures: Vec<FetchFailure>,
+}
+
+/// A request of a discovery fetch that failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchFailure {
+    /// Starting at 1.
+    pub attempt: u32,
+    pub url: String,
+    pub error: String,
+}
+
+/// Lists the last error of every URL, the earlier attempts usually failed the same way.
+impl std::fmt::Display for FetchFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "all fetch {} failed", self.what)?;
+        if self.attempts > 1 {
+            write!(f, " after {} attempts", self.attempts)?;
+        }
+
+        let mut last: Vec<&FetchFailure> = vec![];
+        for failure in &self.failures {
+            match last.iter_mut().find(|f| f.url == failure.url) {
+                Some(previous) => *previous = failure,
+                None => last.push(failure),
+            }
+        }
+        for (i, failure) in last.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(f, "{separator}{} ({})", failure.url, failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FetchFailed {}
+
+/// A value of an argument, a config file or an answer that could not be parsed, e.g. an invalid
+/// size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl ParseError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{FetchFailed, FetchFailure};
+
+    #[test]
+    fn test_fetch_failed() {
+        let failure = |attempt, url: &str, error: &str| FetchFailure {
+            attempt,
+            url: url.to_string(),
+            error: error.to_string(),
+        };
+        let failed = FetchFailed {
+            what: "config".to_string(),
+            attempts: 2,
+            failures: vec![
+                failure(1, "http://a/config", "status: 503"),
+                failure(1, "http://b/config", "timed out"),
+                failure(2, "http://a/config", "status: 502"),
+            ],
+        };
+        assert_eq!(
+            failed.to_string(),
+            "all fetch config failed after 2 attempts: http://a/config (status: 502); \
+             http://b/config (timed out)"
+        );
+
+        let failed = FetchFailed {
+            attempts: 1,
+            failures: vec![],
+            ..failed
+        };
+        assert_eq!(failed.to_string(), "all fetch config failed");
+    }
+}
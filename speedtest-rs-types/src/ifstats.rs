@@ -0,0 +1,174 @@
+//! Interface level figures of a run: background traffic, counter checks and the link speed.
+
+use serde::{Deserialize, Serialize};
+
+/// Background traffic above this rate makes a result unreliable.
+pub const CONTENDED_THRESHOLD_BPS: f64 = 1_000_000.0;
+
+/// Tag attached to results measured while other traffic was present.
+pub const CONTENDED_TAG: &str = "contended";
+
+/// Relative difference between interface and application byte counts considered suspicious.
+/// Interface counters include TCP/IP and TLS overhead, which is typically below 5%.
+pub const COUNTER_DISCREPANCY_THRESHOLD: f64 = 0.1;
+
+/// Traffic observed on the host while the test was idle.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct IdleTraffic {
+    pub rx_bits_per_second: f64,
+    pub tx_bits_per_second: f64,
+    pub sample_ms: u64,
+}
+
+/// Bytes seen by the HTTP layer compared with the OS interface counters for one phase.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CounterCheck {
+    pub app_bytes: u64,
+    pub interface_bytes: u64,
+    /// `(interface_bytes - app_bytes) / app_bytes`.
+    pub discrepancy: f64,
+}
+
+impl CounterCheck {
+    pub fn new(app_bytes: u64, interface_bytes: u64) -> Self {
+        let discrepancy = if app_bytes > 0 {
+            (interface_bytes as f64 - app_bytes as f64) / app_bytes as f64
+        } else {
+            0.0
+        };
+
+        Self {
+            app_bytes,
+            interface_bytes,
+            discrepancy,
+        }
+    }
+
+    /// The interface moved notably fewer bytes than reported (e.g. a proxy or compression in
+    /// between) or notably more (other traffic during the test).
+    pub fn is_suspicious(&self) -> bool {
+        self.discrepancy.abs() > COUNTER_DISCREPANCY_THRESHOLD
+    }
+}
+
+/// Medium of a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    Ethernet,
+    Wireless,
+}
+
+impl LinkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Ethernet => "Ethernet",
+            LinkKind::Wireless => "Wi-Fi",
+        }
+    }
+}
+
+/// Negotiated speed of the interface carrying the default route.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LinkInfo {
+    pub interface: String,
+    pub speed_mbps: u64,
+    /// `None` when the medium is unknown, e.g. a virtual device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<LinkKind>,
+}
+
+/// Share of the link speed above which a result is considered capped by the link itself.
+/// TCP/IP overhead limits goodput to about 94% of the line rate.
+pub const LINK_CEILING_RATIO: f64 = 0.9;
+
+impl LinkInfo {
+    pub fn new(interface: String, speed_mbps: u64) -> Self {
+        Self {
+            interface,
+            speed_mbps,
+            kind: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: Option<LinkKind>) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// The speed followed by the medium when known, e.g. "1 Gbps Ethernet" or "100 Mbps".
+    pub fn label(&self) -> String {
+        let speed = if self.speed_mbps >= 1000 && self.speed_mbps.is_multiple_of(1000) {
+            format!("{} Gbps", self.speed_mbps / 1000)
+        } else if self.speed_mbps >= 1000 {
+            format!("{:.1} Gbps", self.speed_mbps as f64 / 1000.0)
+        } else {
+            format!("{} Mbps", self.speed_mbps)
+        };
+        match self.kind {
+            Some(kind) => format!("{speed} {}", kind.as_str()),
+            None => speed,
+        }
+    }
+
+    /// Whether a measured rate is pinned at the ceiling of this link.
+    pub fn is_at_ceiling(&self, bits_per_second: f64) -> bool {
+        bits_per_second >= self.speed_mbps as f64 * 1_000_000.0 * LINK_CEILING_RATIO
+    }
+
+    /// A hint like "result limited by 100 Mbps Ethernet link" when the rate hits the ceiling.
+    pub fn ceiling_hint(&self, bits_per_second: f64) -> Option<String> {
+        self.is_at_ceiling(bits_per_second)
+            .then(|| format!("result limited by {} link", self.label()))
+    }
+}
+
+impl IdleTraffic {
+    pub fn new(rx_bits_per_second: f64, tx_bits_per_second: f64, sample_ms: u64) -> Self {
+        Self {
+            rx_bits_per_second,
+            tx_bits_per_second,
+            sample_ms,
+        }
+    }
+
+    pub fn is_contended(&self) -> bool {
+        self.rx_bits_per_second > CONTENDED_THRESHOLD_BPS
+            || self.tx_bits_per_second > CONTENDED_THRESHOLD_BPS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifstats::{CounterCheck, LinkInfo, LinkKind};
+
+    #[test]
+    fn test_link_ceiling() {
+        let link = LinkInfo::new("eth1".to_string(), 100).with_kind(Some(LinkKind::Ethernet));
+        assert_eq!(
+            link.ceiling_hint(94_100_000.0).as_deref(),
+            Some("result limited by 100 Mbps Ethernet link")
+        );
+        assert_eq!(link.ceiling_hint(60_000_000.0), None);
+
+        let link = LinkInfo::new("wlan0".to_string(), 2400).with_kind(Some(LinkKind::Wireless));
+        assert_eq!(link.label(), "2.4 Gbps Wi-Fi");
+        assert_eq!(
+            LinkInfo::new("veth0".to_string(), 10_000).label(),
+            "10 Gbps"
+        );
+    }
+
+    #[test]
+    fn test_counter_check() {
+        let check = CounterCheck::new(1_000_000, 1_040_000);
+        assert!((check.discrepancy - 0.04).abs() < 1e-9);
+        assert!(!check.is_suspicious());
+
+        assert!(CounterCheck::new(1_000_000, 500_000).is_suspicious());
+        assert!(!CounterCheck::new(0, 500).is_suspicious());
+    }
+}
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Round-trip time statistics of a series of HTTP probes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Latency {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    /// Mean absolute difference between consecutive samples.
+    pub jitter_ms: f64,
+    pub samples: usize,
+    /// Probes that failed or timed out.
+    pub lost: usize,
+}
+
+impl Latency {
+    /// Returns `None` if no probe succeeded.
+    pub fn from_samples(samples: &[Duration], lost: usize) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+
+        let min_ms = ms.iter().copied().fold(f64::INFINITY, f64::min);
+        let max_ms = ms.iter().copied().fold(0.0, f64::max);
+        let avg_ms = ms.iter().sum::<f64>() / ms.len() as f64;
+        let jitter_ms = if ms.len() > 1 {
+            ms.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (ms.len() - 1) as f64
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            min_ms,
+            avg_ms,
+            max_ms,
+            jitter_ms,
+            samples: samples.len(),
+            lost,
+        })
+    }
+
+    /// Extra delay under load compared with an idle baseline (bufferbloat).
+    pub fn delta_ms(&self, idle: &Latency) -> f64 {
+        self.avg_ms - idle.avg_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::latency::Latency;
+
+    #[test]
+    fn test_latency_from_samples() {
+        let samples = [10, 14, 12, 20].map(Duration::from_millis);
+        let latency = Latency::from_samples(&samples, 1).unwrap();
+
+        assert_eq!(latency.min_ms, 10.0);
+        assert_eq!(latency.max_ms, 20.0);
+        assert_eq!(latency.avg_ms, 14.0);
+        assert!((latency.jitter_ms - 14.0 / 3.0).abs() < 1e-9);
+        assert_eq!(latency.samples, 4);
+        assert_eq!(latency.lost, 1);
+
+        assert_eq!(Latency::from_samples(&[], 3), None);
+    }
+}
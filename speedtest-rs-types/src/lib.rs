@@ -0,0 +1,119 @@
+//! Result, event and error types of speedtest-rs.
+//!
+//! Frontends that only read or display results can depend on this crate without pulling in the
+//! HTTP client and the async runtime of `speedtest-rs-core`, which re-exports everything here.
+
+pub mod error;
+pub mod ifstats;
+pub mod latency;
+pub mod nat;
+pub mod net;
+pub mod redact;
+pub mod result;
+pub mod size;
+pub mod stage;
+pub mod throughput;
+
+// Bytes
+pub const KB: usize = 1024;
+pub const MB: usize = 1024 * KB;
+pub const GB: usize = 1024 * MB;
+pub const TB: usize = 1024 * GB;
+
+// Bitrate
+pub const K_BITS_PER_SEC: usize = 1000;
+pub const M_BITS_PER_SEC: usize = 1000 * K_BITS_PER_SEC;
+pub const G_BITS_PER_SEC: usize = 1000 * M_BITS_PER_SEC;
+pub const T_BITS_PER_SEC: usize = 1000 * G_BITS_PER_SEC;
+
+/// Decimal unit to display `bits_per_sec` in, as the divisor and its suffix.
+pub fn bitrate_unit(bits_per_sec: f64) -> (f64, &'static str) {
+    if bits_per_sec < K_BITS_PER_SEC as f64 {
+        (1.0, "bps")
+    } else if bits_per_sec < M_BITS_PER_SEC as f64 {
+        (K_BITS_PER_SEC as f64, "Kbps")
+    } else if bits_per_sec < G_BITS_PER_SEC as f64 {
+        (M_BITS_PER_SEC as f64, "Mbps")
+    } else if bits_per_sec < T_BITS_PER_SEC as f64 {
+        (G_BITS_PER_SEC as f64, "Gbps")
+    } else {
+        (T_BITS_PER_SEC as f64, "Tbps")
+    }
+}
+
+pub trait Humanize {
+    fn humanize_bytes(&self) -> String;
+
+    fn humanize_bitrate(&self, duration_millis: u64) -> String;
+
+    fn humanize(&self) -> (f64, usize);
+}
+
+impl Humanize for usize {
+    fn humanize_bytes(&self) -> String {
+        let bytes = *self;
+        if bytes < KB {
+            format!("{bytes} Bytes")
+        } else if bytes < MB {
+            format!("{:.2} KBytes", bytes as f64 / KB as f64)
+        } else if bytes < GB {
+            format!("{:.2} MBytes", bytes as f64 / MB as f64)
+        } else if bytes < TB {
+            format!("{:.2} GBytes", bytes as f64 / GB as f64)
+        } else {
+            format!("{:.2} TBytes", bytes as f64 / TB as f64)
+        }
+    }
+
+    fn humanize_bitrate(&self, duration_millis: u64) -> String {
+        let bytes = *self;
+        // For higher accuracy we are getting the actual millis of the duration rather than the
+        // rounded seconds.
+        let bits = bytes * 8;
+        // rate as fraction in seconds;
+        let rate = (bits as f64 / duration_millis as f64) * 1000f64;
+        if rate < K_BITS_PER_SEC as f64 {
+            format!("{rate} Bits/sec")
+        } else if rate < M_BITS_PER_SEC as f64 {
+            format!("{:.2} Kbits/sec", rate / K_BITS_PER_SEC as f64)
+        } else if rate < G_BITS_PER_SEC as f64 {
+            format!("{:.2} Mbits/sec", rate / M_BITS_PER_SEC as f64)
+        } else if rate < T_BITS_PER_SEC as f64 {
+            format!("{:.2} Gbits/sec", rate / G_BITS_PER_SEC as f64)
+        } else {
+            format!("{:.2} Tbits/sec", rate / T_BITS_PER_SEC as f64)
+        }
+    }
+
+    fn humanize(&self) -> (f64, usize) {
+        let bytes = *self;
+        if bytes < KB {
+            (bytes as f64, 1)
+        } else if bytes < MB {
+            (bytes as f64 / KB as f64, KB)
+        } else if bytes < GB {
+            (bytes as f64 / MB as f64, MB)
+        } else if bytes < TB {
+            (bytes as f64 / GB as f64, GB)
+        } else {
+            (bytes as f64 / TB as f64, TB)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Humanize, bitrate_unit};
+
+    #[test]
+    fn test_humanize_bitrate() {
+        assert_eq!(100usize.humanize_bitrate(1000), "800 Bits/sec");
+        assert_eq!(12_500usize.humanize_bitrate(1000), "100.00 Kbits/sec");
+        assert_eq!(12_500_000usize.humanize_bitrate(1000), "100.00 Mbits/sec");
+        assert_eq!(250_000_000usize.humanize_bitrate(1000), "2.00 Gbits/sec");
+
+        assert_eq!(bitrate_unit(999.0), (1.0, "bps"));
+        assert_eq!(bitrate_unit(100e6), (1e6, "Mbps"));
+        assert_eq!(bitrate_unit(2.5e9), (1e9, "Gbps"));
+    }
+}
@@ -0,0 +1,84 @@
+//! Carrier-grade NAT detection: the WAN address of the local network compared with the public
+//! address the servers see.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde::{Deserialize, Serialize};
+
+/// Where the WAN address came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WanSource {
+    /// The router answered a NAT-PMP query.
+    NatPmp,
+    /// The device holds the WAN address itself, e.g. a phone on mobile data or a router.
+    Interface,
+}
+
+/// WAN address of the local network, and whether the provider translates it once more.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct NatCheck {
+    pub wan_ip: String,
+    pub source: WanSource,
+    /// The WAN address is private and differs from the public address, the provider shares the
+    /// public address with other customers.
+    pub cgnat: bool,
+}
+
+impl NatCheck {
+    pub fn new(wan_ip: IpAddr, source: WanSource, public_ip: &str) -> Self {
+        let translated = public_ip
+            .parse::<IpAddr>()
+            .is_ok_and(|public| public != wan_ip);
+        let cgnat = translated
+            && match wan_ip {
+                IpAddr::V4(v4) => v4.is_private() || is_shared_address(v4),
+                IpAddr::V6(_) => false,
+            };
+
+        Self {
+            wan_ip: wan_ip.to_string(),
+            source,
+            cgnat,
+        }
+    }
+}
+
+/// The shared address space of RFC 6598, 100.64.0.0/10, reserved for carrier-grade NAT.
+pub fn is_shared_address(ip: Ipv4Addr) -> bool {
+    let [a, b, _, _] = ip.octets();
+    a == 100 && (b & 0b1100_0000) == 64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::nat::{NatCheck, WanSource, is_shared_address};
+
+    #[test]
+    fn test_is_shared_address() {
+        assert!(is_shared_address(Ipv4Addr::new(100, 64, 0, 1)));
+        assert!(is_shared_address(Ipv4Addr::new(100, 127, 255, 254)));
+        assert!(!is_shared_address(Ipv4Addr::new(100, 128, 0, 1)));
+        assert!(!is_shared_address(Ipv4Addr::new(100, 63, 255, 255)));
+    }
+
+    #[test]
+    fn test_nat_check() {
+        let wan = |ip: [u8; 4]| IpAddr::from(ip);
+
+        let cgnat = NatCheck::new(wan([100, 72, 3, 9]), WanSource::NatPmp, "203.0.113.45");
+        assert!(cgnat.cgnat);
+        assert_eq!(cgnat.wan_ip, "100.72.3.9");
+
+        // A private WAN address behind another NAT, as on some fiber and cable plans.
+        assert!(NatCheck::new(wan([10, 20, 0, 5]), WanSource::NatPmp, "203.0.113.45").cgnat);
+
+        assert!(!NatCheck::new(wan([203, 0, 113, 45]), WanSource::NatPmp, "203.0.113.45").cgnat);
+        // A VPN or proxy changes the public address without a provider NAT.
+        assert!(!NatCheck::new(wan([198, 51, 100, 7]), WanSource::Interface, "203.0.113.45").cgnat);
+        assert!(!NatCheck::new(wan([100, 72, 3, 9]), WanSource::Interface, "unknown").cgnat);
+    }
+}
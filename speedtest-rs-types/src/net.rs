@@ -0,0 +1,100 @@
+//! IP address families and host name lookups, to force a test over one family and to report
+//! how the servers were reached.
+
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IpFamily {
+    #[serde(rename = "ipv4")]
+    V4,
+    #[serde(rename = "ipv6")]
+    V6,
+}
+
+impl IpFamily {
+    pub fn of(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => Self::V4,
+            IpAddr::V6(_) => Self::V6,
+        }
+    }
+
+    pub fn matches(self, addr: &SocketAddr) -> bool {
+        Self::of(addr.ip()) == self
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V4 => "IPv4",
+            Self::V6 => "IPv6",
+        })
+    }
+}
+
+/// Parses `ipv4`/`ipv6` in any case, or the bare version `4`/`6`.
+impl FromStr for IpFamily {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "4" | "ipv4" => Ok(Self::V4),
+            "6" | "ipv6" => Ok(Self::V6),
+            _ => Err(ParseError::new(format!(
+                "invalid IP family '{s}', expected ipv4 or ipv6"
+            ))),
+        }
+    }
+}
+
+/// A lookup of a host name the test connected to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct HostResolution {
+    pub host: String,
+    pub elapsed_ms: f64,
+    /// Addresses of the answer, empty if the lookup failed.
+    pub addresses: Vec<String>,
+}
+
+impl HostResolution {
+    pub fn new(host: impl Into<String>, elapsed: Duration, addresses: &[IpAddr]) -> Self {
+        Self {
+            host: host.into(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            addresses: addresses.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use crate::net::IpFamily;
+
+    #[test]
+    fn test_ip_family() {
+        assert_eq!("IPv6".parse::<IpFamily>().unwrap(), IpFamily::V6);
+        assert_eq!("4".parse::<IpFamily>().unwrap(), IpFamily::V4);
+        assert!("ipv5".parse::<IpFamily>().is_err());
+
+        let v6: SocketAddr = "[2001:db8::1]:8080".parse().unwrap();
+        assert!(IpFamily::V6.matches(&v6));
+        assert!(!IpFamily::V4.matches(&v6));
+        assert_eq!(IpFamily::of("192.0.2.1".parse().unwrap()), IpFamily::V4);
+
+        assert_eq!(serde_json::to_string(&IpFamily::V4).unwrap(), "\"ipv4\"");
+        assert_eq!(IpFamily::V6.to_string(), "IPv6");
+    }
+}
@@ -0,0 +1,248 @@
+use std::net::IpAddr;
+
+use crate::{
+    net::HostResolution,
+    result::{ClientInfo, RejectedServer, ServerInfo, SpeedTestResult},
+};
+
+/// Placeholder for values that are hidden entirely.
+pub const REDACTED: &str = "<redacted>";
+
+/// Removes personally identifying details so results can be shared publicly.
+///
+/// Country and ISP information is kept, precise location data is masked.
+pub trait Redact {
+    fn redact(&self) -> Self;
+}
+
+impl Redact for ClientInfo {
+    fn redact(&self) -> Self {
+        Self {
+            ip: redact_ip(&self.ip),
+            lat: redact_coordinate(self.lat),
+            lon: redact_coordinate(self.lon),
+            ..self.clone()
+        }
+    }
+}
+
+/// Self-hosted servers, e.g. at home or at work, are named after their host, so names containing
+/// it are hidden along with the host and URL.
+impl Redact for ServerInfo {
+    fn redact(&self) -> Self {
+        let hostname = strip_port(&self.host);
+        let hide = |value: &str| {
+            if !hostname.is_empty() && value.contains(hostname) {
+                REDACTED.to_string()
+            } else {
+                value.to_string()
+            }
+        };
+        Self {
+            id: hide(&self.id),
+            name: hide(&self.name),
+            sponsor: hide(&self.sponsor),
+            host: redact_host(&self.host),
+            url: redact_url(&self.url),
+            ..self.clone()
+        }
+    }
+}
+
+impl Redact for RejectedServer {
+    fn redact(&self) -> Self {
+        let hostname = strip_port(&self.server.host);
+        let reason = if hostname.is_empty() {
+            self.reason.clone()
+        } else {
+            self.reason.replace(hostname, REDACTED)
+        };
+        Self {
+            server: self.server.redact(),
+            reason,
+        }
+    }
+}
+
+impl Redact for HostResolution {
+    fn redact(&self) -> Self {
+        Self {
+            host: redact_host(&self.host),
+            addresses: self.addresses.iter().map(|ip| redact_ip(ip)).collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Redact for SpeedTestResult {
+    fn redact(&self) -> Self {
+        let mut diagnostics = self.diagnostics.clone();
+        // Without a provider NAT the WAN address is the public one.
+        if let Some(nat) = &mut diagnostics.nat {
+            nat.wan_ip = redact_ip(&nat.wan_ip);
+        }
+        diagnostics.ignored_servers = diagnostics
+            .ignored_servers
+            .iter()
+            .map(Redact::redact)
+            .collect();
+        diagnostics.rejected_servers = diagnostics
+            .rejected_servers
+            .iter()
+            .map(Redact::redact)
+            .collect();
+        diagnostics.resolutions = diagnostics.resolutions.iter().map(Redact::redact).collect();
+        Self {
+            client: self.client.redact(),
+            server: self.server.redact(),
+            diagnostics,
+            ..self.clone()
+        }
+    }
+}
+
+/// Masks the host part of an address, keeping only the first two IPv4 octets or the first two
+/// IPv6 groups (roughly the provider allocation).
+pub fn redact_ip(ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            let [a, b, _, _] = v4.octets();
+            format!("{a}.{b}.x.x")
+        }
+        Ok(IpAddr::V6(v6)) => {
+            let segments = v6.segments();
+            format!("{:x}:{:x}:x:x:x:x:x:x", segments[0], segments[1])
+        }
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/// Hides a host name entirely and masks an address like [`redact_ip`], dropping the port.
+pub fn redact_host(host: &str) -> String {
+    let host = strip_port(host);
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => redact_ip(&ip.to_string()),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/// Replaces the host of a URL with [`redact_host`], keeping the scheme and path.
+pub fn redact_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return REDACTED.to_string();
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    // Credentials before the host are dropped as well.
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    format!("{scheme}://{}{path}", redact_host(host))
+}
+
+/// `host` without a trailing `:port`, IPv6 addresses keep their brackets.
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if port.chars().all(|c| c.is_ascii_digit())
+                && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
+
+/// Rounds a coordinate to whole degrees (~100km), enough to keep the region but not the address.
+pub fn redact_coordinate(value: f64) -> f64 {
+    value.round()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::{
+        net::HostResolution,
+        redact::{Redact, redact_coordinate, redact_host, redact_ip, redact_url},
+        result::{ClientInfo, RejectedServer, ServerInfo, SpeedTestResult},
+    };
+
+    #[test]
+    fn test_redact_ip() {
+        assert_eq!(redact_ip("203.0.113.45"), "203.0.x.x");
+        assert_eq!(redact_ip("2001:db8::1"), "2001:db8:x:x:x:x:x:x");
+        assert_eq!(redact_ip("not an ip"), "<redacted>");
+    }
+
+    #[test]
+    fn test_redact_coordinate() {
+        assert_eq!(redact_coordinate(22.2796), 22.0);
+        assert_eq!(redact_coordinate(-114.6592), -115.0);
+    }
+
+    #[test]
+    fn test_redact_host() {
+        assert_eq!(redact_host("speed.example.com:8080"), "<redacted>");
+        assert_eq!(redact_host("203.0.113.45:8080"), "203.0.x.x");
+        assert_eq!(redact_host("[2001:db8::1]:443"), "2001:db8:x:x:x:x:x:x");
+        assert_eq!(
+            redact_url("http://user@speed.example.com:8080/speedtest/upload.php"),
+            "http://<redacted>/speedtest/upload.php"
+        );
+        assert_eq!(redact_url("speed.example.com"), "<redacted>");
+    }
+
+    #[test]
+    fn test_redact_result() {
+        // A self-hosted server, named after its host.
+        let server = |host: &str| ServerInfo {
+            id: format!("{host}:8080"),
+            name: format!("{host}:8080"),
+            sponsor: format!("{host}:8080"),
+            country: String::new(),
+            cc: String::new(),
+            host: format!("{host}:8080"),
+            url: format!("http://{host}:8080/speedtest/upload.php"),
+            lat: 0.0,
+            lon: 0.0,
+            extra: Default::default(),
+        };
+        let client = ClientInfo {
+            ip: "203.0.113.45".to_string(),
+            lat: 22.2796,
+            lon: 114.1887,
+            isp: "HKBN".to_string(),
+            country: "HK".to_string(),
+        };
+        let timestamp = Utc.with_ymd_and_hms(2025, 1, 7, 14, 2, 30).unwrap();
+        let mut result = SpeedTestResult::new(timestamp, client, server("nas.home.example"));
+        let diagnostics = &mut result.diagnostics;
+        diagnostics.ignored_servers = vec![server("ignored.home.example")];
+        diagnostics.rejected_servers = vec![RejectedServer::new(
+            server("office.example"),
+            "office.example answered 404",
+        )];
+        diagnostics.resolutions = vec![HostResolution::new(
+            "nas.home.example",
+            Duration::from_millis(3),
+            &["192.168.1.10".parse().unwrap()],
+        )];
+
+        let redacted = result.redact();
+        let json = serde_json::to_string(&redacted).unwrap();
+        let text = redacted.summary_line();
+        for host in ["nas.home.example", "ignored.home.example", "office.example"] {
+            assert!(!json.contains(host), "{host} in {json}");
+            assert!(!text.contains(host), "{host} in {text}");
+        }
+        assert!(!json.contains("192.168.1.10"));
+        assert_eq!(
+            redacted.server.url,
+            "http://<redacted>/speedtest/upload.php"
+        );
+    }
+}
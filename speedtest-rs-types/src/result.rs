@@ -0,0 +1,816 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Humanize,
+    ifstats::{CONTENDED_TAG, COUNTER_DISCREPANCY_THRESHOLD, CounterCheck, IdleTraffic, LinkInfo},
+    latency::Latency,
+    nat::NatCheck,
+    net::{HostResolution, IpFamily},
+    size::ByteSize,
+    throughput::ThroughputStats,
+};
+
+/// Tag of results measured in low-priority background mode.
+pub const BACKGROUND_TAG: &str = "background";
+
+/// Tag of partial results of runs quit before they completed.
+pub const ABORTED_TAG: &str = "aborted";
+
+/// Offset from the server clock above which timestamps are reported as unreliable.
+pub const CLOCK_SKEW_THRESHOLD_MS: i64 = 60_000;
+
+/// Share of the available CPU time above which a run was likely limited by this device rather
+/// than the network, e.g. TLS on a router.
+pub const CPU_BOUND_THRESHOLD: f64 = 0.9;
+
+/// Ending of the reasons of servers rejected because of a captive portal.
+pub const CAPTIVE_PORTAL_SUSPICION: &str = "likely a captive portal";
+
+/// Outcome of a speed test run, suitable for exporting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SpeedTestResult {
+    /// When the run started.
+    pub timestamp: DateTime<Utc>,
+
+    pub client: ClientInfo,
+
+    pub server: ServerInfo,
+
+    pub download: Option<TransferResult>,
+
+    pub upload: Option<TransferResult>,
+
+    /// Latency measured before any load, the reference for bufferbloat.
+    #[serde(default)]
+    pub idle_latency: Option<Latency>,
+
+    /// Latency measured while the download was running.
+    #[serde(default)]
+    pub download_latency: Option<Latency>,
+
+    /// Latency measured while the upload was running.
+    #[serde(default)]
+    pub upload_latency: Option<Latency>,
+
+    /// Traffic of other applications measured before the test, if the pre-check ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_traffic: Option<IdleTraffic>,
+
+    /// Free-form labels, e.g. `contended` when background traffic was detected or `background`
+    /// for low-priority runs.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Free-text annotation, e.g. "after router firmware update".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+
+    #[serde(default)]
+    pub diagnostics: Diagnostics,
+
+    /// How the figures were measured, `None` for results of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methodology: Option<Methodology>,
+
+    /// Conditions that may make the figures misleading, see [`SpeedTestResult::detect_warnings`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+
+    /// Result image on speedtest.net, if the result was shared there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_url: Option<String>,
+}
+
+/// Details that help explaining unexpected results.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Diagnostics {
+    /// Downloaded bytes compared with the received bytes of the OS interfaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_counters: Option<CounterCheck>,
+
+    /// Uploaded bytes compared with the transmitted bytes of the OS interfaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_counters: Option<CounterCheck>,
+
+    /// Local link speed, if it could be detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkInfo>,
+
+    #[serde(default, skip_serializing_if = "TransferErrors::is_empty")]
+    pub download_errors: TransferErrors,
+
+    #[serde(default, skip_serializing_if = "TransferErrors::is_empty")]
+    pub upload_errors: TransferErrors,
+
+    /// Nearby servers left out because the remote config lists them in `ignoreids`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignored_servers: Vec<ServerInfo>,
+
+    /// Largest upload size the server accepted after rejecting larger requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_size_cap: Option<ByteSize>,
+
+    /// How the uploaded bytes were counted, `None` for results of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_accounting: Option<UploadAccounting>,
+
+    /// Selected servers replaced by the next best one because they failed validation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected_servers: Vec<RejectedServer>,
+
+    /// Local clock minus the clock of the server, from its `Date` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_skew_ms: Option<i64>,
+
+    /// Share of the CPU time of all cores the process used during the transfers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_usage: Option<f64>,
+
+    /// WAN address of the local network, `None` if the router does not report it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nat: Option<NatCheck>,
+
+    /// First lookup of every host name, by name. Lookups of clients passed to the tester, which
+    /// resolve on their own, are missing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolutions: Vec<HostResolution>,
+}
+
+/// How a result was measured, so results of different versions and settings can be compared
+/// honestly. The upload byte count is described by [`Diagnostics::upload_accounting`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Methodology {
+    /// Version of `speedtest-rs-core` that measured the result.
+    pub version: String,
+
+    /// Server network, `ookla`.
+    pub provider: String,
+
+    /// `http` or `https`.
+    pub protocol: String,
+
+    /// Concurrent requests of the download phase.
+    pub download_connections: usize,
+
+    /// Concurrent requests of the upload phase.
+    pub upload_connections: usize,
+
+    /// Side lengths of the download images in the order they are requested, repeated until the
+    /// duration is over.
+    pub download_sizes: Vec<usize>,
+
+    /// Sizes of the upload bodies in bytes, in the order they are sent.
+    pub upload_sizes: Vec<u64>,
+
+    pub download_duration_ms: u64,
+
+    pub upload_duration_ms: u64,
+
+    /// Handling of the TCP ramp-up at the start of a phase, `included`: bytes count from the
+    /// first request on, or `excluded` and the warm-up left out, see [`WarmUpSplit`].
+    pub warm_up: String,
+
+    /// Samples left out of the rates, `none`: the rates are the total bytes over the total time.
+    pub trimming: String,
+
+    /// Fewer connections and pauses between requests, see `--background`.
+    pub background: bool,
+
+    /// Address family the transfers ran over, `None` if it is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_family: Option<IpFamily>,
+
+    /// Protocol the transfers negotiated, e.g. `HTTP/1.1`, or `HTTP/2` with multiplexed
+    /// transfers. `None` if no transfer got a response, or for WebSocket transfers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_version: Option<String>,
+}
+
+/// A condition that may make a result misleading, for triage by people and scripts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+/// Stable identifier of a [`Warning`], serialized in snake case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    /// The local clock is off, timestamps of the result are unreliable.
+    ClockSkew,
+
+    /// A server was rejected as a likely captive portal, the network may intercept traffic.
+    CaptivePortal,
+
+    /// The device used nearly all of its CPU time, results may be limited by it.
+    CpuBound,
+
+    /// Other applications were transferring data before the test.
+    BackgroundTraffic,
+
+    /// The result is capped by the speed of the local link.
+    LinkCeiling,
+
+    /// The interface received far fewer bytes than downloaded, traffic is likely compressed.
+    Compression,
+
+    /// The monthly data cap is nearly used up, only the latency was measured.
+    DataCap,
+
+    /// The provider shares the public address through carrier-grade NAT.
+    Cgnat,
+}
+
+impl WarningCode {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            WarningCode::ClockSkew => "clock_skew",
+            WarningCode::CaptivePortal => "captive_portal",
+            WarningCode::CpuBound => "cpu_bound",
+            WarningCode::BackgroundTraffic => "background_traffic",
+            WarningCode::LinkCeiling => "link_ceiling",
+            WarningCode::Compression => "compression",
+            WarningCode::DataCap => "data_cap",
+            WarningCode::Cgnat => "cgnat",
+        }
+    }
+}
+
+impl Methodology {
+    /// A single connection per phase without fixed sizes or durations, bytes counted from the
+    /// first request on.
+    pub fn new(
+        version: impl Into<String>,
+        provider: impl Into<String>,
+        protocol: impl Into<String>,
+    ) -> Self {
+        Self {
+            version: version.into(),
+            provider: provider.into(),
+            protocol: protocol.into(),
+            download_connections: 1,
+            upload_connections: 1,
+            download_sizes: vec![],
+            upload_sizes: vec![],
+            download_duration_ms: 0,
+            upload_duration_ms: 0,
+            warm_up: "included".to_string(),
+            trimming: "none".to_string(),
+            background: false,
+            ip_family: None,
+            http_version: None,
+        }
+    }
+}
+
+impl Warning {
+    pub fn new(code: WarningCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A server that failed the validation before the transfers, e.g. behind a captive portal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedServer {
+    pub server: ServerInfo,
+    pub reason: String,
+}
+
+impl RejectedServer {
+    pub fn new(server: impl Into<ServerInfo>, reason: impl Into<String>) -> Self {
+        Self {
+            server: server.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Methodology of the upload byte count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadAccounting {
+    /// Bytes count once they are handed to the connection and are taken out again if their
+    /// request fails. Requests still running at the end of the phase count with the bytes sent
+    /// so far.
+    Acknowledged,
+
+    /// Like [`Acknowledged`](Self::Acknowledged), and requests additionally only count if the
+    /// server reports to have received the full size.
+    Verified,
+}
+
+/// Failed requests of a download or upload phase, counted by cause.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TransferErrors {
+    pub causes: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub ip: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub isp: String,
+    pub country: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub id: String,
+    pub name: String,
+    pub sponsor: String,
+    pub country: String,
+    pub cc: String,
+    pub host: String,
+    pub url: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Attributes of the server list without typed support, by their name prefixed with `@`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra: HashMap<String, String>,
+}
+
+/// Totals of a single download or upload phase.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TransferResult {
+    pub bytes: u64,
+    pub elapsed_ms: u64,
+    /// Reported rate of the phase, after the warm-up if one was left out.
+    pub bits_per_second: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_up: Option<WarmUpSplit>,
+    /// Spread of the rate over the phase, `None` if it was not sampled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ThroughputStats>,
+}
+
+/// A phase split at the end of its warm-up, e.g. the TCP slow start of short tests. `bytes` and
+/// `elapsed_ms` of the phase still include the warm-up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct WarmUpSplit {
+    pub warm_up_bytes: u64,
+    pub warm_up_ms: u64,
+    /// Rate of the whole phase, warm-up included.
+    pub raw_bits_per_second: f64,
+    /// Rate after the warm-up.
+    pub steady_bits_per_second: f64,
+}
+
+impl SpeedTestResult {
+    pub fn new(
+        timestamp: DateTime<Utc>,
+        client: impl Into<ClientInfo>,
+        server: impl Into<ServerInfo>,
+    ) -> Self {
+        Self {
+            timestamp,
+            client: client.into(),
+            server: server.into(),
+            download: None,
+            upload: None,
+            idle_latency: None,
+            download_latency: None,
+            upload_latency: None,
+            idle_traffic: None,
+            tags: vec![],
+            note: None,
+            diagnostics: Diagnostics::default(),
+            methodology: None,
+            warnings: vec![],
+            share_url: None,
+        }
+    }
+
+    pub fn with_download(mut self, download: TransferResult) -> Self {
+        self.download = Some(download);
+        self
+    }
+
+    pub fn with_upload(mut self, upload: TransferResult) -> Self {
+        self.upload = Some(upload);
+        self
+    }
+
+    pub fn with_idle_traffic(mut self, idle_traffic: IdleTraffic) -> Self {
+        if idle_traffic.is_contended() {
+            self.tags.push(CONTENDED_TAG.to_string());
+        }
+        self.idle_traffic = Some(idle_traffic);
+        self
+    }
+
+    /// Marks a run in background mode, which under-reports the peak capacity.
+    pub fn with_background(mut self, background: bool) -> Self {
+        if background {
+            self.tags.push(BACKGROUND_TAG.to_string());
+        }
+        self
+    }
+
+    /// Attaches a note, blank notes are dropped.
+    pub fn with_note(mut self, note: Option<String>) -> Self {
+        self.note = note
+            .map(|note| note.trim().to_string())
+            .filter(|note| !note.is_empty());
+        self
+    }
+
+    pub fn is_background(&self) -> bool {
+        self.tags.iter().any(|tag| tag == BACKGROUND_TAG)
+    }
+
+    /// Marks the partial result of a run quit before it completed.
+    pub fn with_aborted(mut self, aborted: bool) -> Self {
+        if aborted {
+            self.tags.push(ABORTED_TAG.to_string());
+        }
+        self
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.tags.iter().any(|tag| tag == ABORTED_TAG)
+    }
+
+    pub fn is_contended(&self) -> bool {
+        self.tags.iter().any(|tag| tag == CONTENDED_TAG)
+    }
+
+    /// Explains a download or upload rate capped by the local link speed.
+    pub fn link_ceiling_hint(&self) -> Option<String> {
+        let link = self.diagnostics.link.as_ref()?;
+        let fastest = [self.download, self.upload]
+            .iter()
+            .flatten()
+            .map(|t| t.bits_per_second)
+            .fold(0.0, f64::max);
+        link.ceiling_hint(fastest)
+    }
+
+    /// Warnings derived from the diagnostics and the idle traffic, in a stable order.
+    pub fn detect_warnings(&self) -> Vec<Warning> {
+        let mut warnings = vec![];
+        let diagnostics = &self.diagnostics;
+
+        if let Some(skew) = diagnostics
+            .clock_skew_ms
+            .filter(|skew| skew.abs() > CLOCK_SKEW_THRESHOLD_MS)
+        {
+            let direction = if skew > 0 { "ahead of" } else { "behind" };
+            warnings.push(Warning::new(
+                WarningCode::ClockSkew,
+                format!(
+                    "Local clock is {} s {direction} the server, timestamps may be off",
+                    skew.abs() / 1000
+                ),
+            ));
+        }
+        if diagnostics
+            .rejected_servers
+            .iter()
+            .any(|rejected| rejected.reason.ends_with(CAPTIVE_PORTAL_SUSPICION))
+        {
+            warnings.push(Warning::new(
+                WarningCode::CaptivePortal,
+                "A server was rejected as a likely captive portal",
+            ));
+        }
+        if let Some(usage) = diagnostics
+            .cpu_usage
+            .filter(|usage| *usage >= CPU_BOUND_THRESHOLD)
+        {
+            warnings.push(Warning::new(
+                WarningCode::CpuBound,
+                format!(
+                    "CPU usage {:.0}% during the transfers, results may be limited by this device",
+                    usage * 100.0
+                ),
+            ));
+        }
+        if let Some(idle) = self.idle_traffic.filter(IdleTraffic::is_contended) {
+            let rate = ((idle.rx_bits_per_second.max(idle.tx_bits_per_second) / 8.0) as usize)
+                .humanize_bitrate(1000);
+            warnings.push(Warning::new(
+                WarningCode::BackgroundTraffic,
+                format!("Background traffic {rate} before the test, results may be low"),
+            ));
+        }
+        if let Some(hint) = self.link_ceiling_hint() {
+            warnings.push(Warning::new(WarningCode::LinkCeiling, hint));
+        }
+        if let Some(check) = diagnostics
+            .download_counters
+            .filter(|check| check.discrepancy < -COUNTER_DISCREPANCY_THRESHOLD)
+        {
+            warnings.push(Warning::new(
+                WarningCode::Compression,
+                format!(
+                    "Interface received {:.0}% fewer bytes than downloaded, traffic is likely \
+                     compressed",
+                    -check.discrepancy * 100.0
+                ),
+            ));
+        }
+        if diagnostics.nat.as_ref().is_some_and(|nat| nat.cgnat) {
+            warnings.push(Warning::new(
+                WarningCode::Cgnat,
+                "Behind carrier-grade NAT, uploads and latency under load may suffer",
+            ));
+        }
+        warnings
+    }
+}
+
+impl SpeedTestResult {
+    /// One-line summary for sharing, in the local time zone, e.g.
+    /// `↓ 214.3 Mbps ↑ 38.1 Mbps · 9 ms · HKIX, Hong Kong · 2025-01-07 14:02`.
+    pub fn summary_line(&self) -> String {
+        self.summary_line_in(&Local)
+    }
+
+    pub fn summary_line_in<Tz: TimeZone>(&self, tz: &Tz) -> String
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        let mbps = |transfer: Option<TransferResult>| {
+            transfer.map_or("-".to_string(), |t| {
+                format!("{:.1} Mbps", t.bits_per_second / 1_000_000.0)
+            })
+        };
+
+        let mut parts = vec![format!("↓ {} ↑ {}", mbps(self.download), mbps(self.upload))];
+        if let Some(latency) = self.idle_latency {
+            parts.push(format!("{:.0} ms", latency.avg_ms));
+        }
+        parts.push(format!("{}, {}", self.server.sponsor, self.server.name));
+        if self.is_background() {
+            parts.push("background mode".to_string());
+        }
+        if self.is_aborted() {
+            parts.push("aborted".to_string());
+        }
+        parts.push(
+            self.timestamp
+                .with_timezone(tz)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+        );
+        parts.extend(self.share_url.clone());
+        parts.join(" · ")
+    }
+}
+
+impl TransferErrors {
+    pub fn record(&mut self, cause: impl Into<String>) {
+        *self.causes.entry(cause.into()).or_default() += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.causes.is_empty()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.causes.values().sum()
+    }
+
+    /// The most frequent cause and its count.
+    pub fn dominant(&self) -> Option<(&str, u64)> {
+        self.causes
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(cause, count)| (cause.as_str(), *count))
+    }
+
+    /// Explains a phase that transferred nothing, e.g.
+    /// `No data transferred, 12 requests failed: timed out (10)`.
+    pub fn explain_zero(&self) -> String {
+        match self.dominant() {
+            Some((cause, count)) => format!(
+                "No data transferred, {} requests failed: {} ({})",
+                self.total(),
+                cause,
+                count
+            ),
+            None => "No data transferred".to_string(),
+        }
+    }
+}
+
+impl TransferResult {
+    pub fn new(bytes: u64, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let bits_per_second = if secs > 0.0 {
+            bytes as f64 * 8.0 / secs
+        } else {
+            0.0
+        };
+
+        Self {
+            bytes,
+            elapsed_ms: elapsed.as_millis() as u64,
+            bits_per_second,
+            warm_up: None,
+            stats: None,
+        }
+    }
+
+    pub fn with_stats(mut self, stats: Option<ThroughputStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Leaves the first `bytes` moved within `elapsed` out of the reported rate. A phase that
+    /// ended during its warm-up keeps the raw rate.
+    pub fn with_warm_up(mut self, bytes: u64, elapsed: Duration) -> Self {
+        let warm_up_ms = elapsed.as_millis() as u64;
+        if bytes >= self.bytes || warm_up_ms >= self.elapsed_ms {
+            return self;
+        }
+
+        let steady = Self::new(
+            self.bytes - bytes,
+            Duration::from_millis(self.elapsed_ms - warm_up_ms),
+        );
+        self.warm_up = Some(WarmUpSplit {
+            warm_up_bytes: bytes,
+            warm_up_ms,
+            raw_bits_per_second: self.bits_per_second,
+            steady_bits_per_second: steady.bits_per_second,
+        });
+        self.bits_per_second = steady.bits_per_second;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::{
+        ifstats::CounterCheck,
+        latency::Latency,
+        nat::{NatCheck, WanSource},
+        result::{
+            ClientInfo, RejectedServer, ServerInfo, SpeedTestResult, TransferErrors,
+            TransferResult, WarningCode,
+        },
+    };
+
+    fn client() -> ClientInfo {
+        ClientInfo {
+            ip: "203.0.113.45".to_string(),
+            lat: 22.2796,
+            lon: 114.1887,
+            isp: "HKBN".to_string(),
+            country: "HK".to_string(),
+        }
+    }
+
+    fn server() -> ServerInfo {
+        ServerInfo {
+            url: "http://hkix.example.com:8080/speedtest/upload.php".to_string(),
+            lat: 22.25,
+            lon: 114.17,
+            name: "Hong Kong".to_string(),
+            country: "Hong Kong".to_string(),
+            cc: "HK".to_string(),
+            sponsor: "HKIX".to_string(),
+            id: "1".to_string(),
+            host: "hkix.example.com:8080".to_string(),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_transfer_errors() {
+        let mut errors = TransferErrors::default();
+        assert_eq!(errors.explain_zero(), "No data transferred");
+
+        errors.record("timed out");
+        errors.record("HTTP 404 Not Found");
+        errors.record("timed out");
+        assert_eq!(errors.total(), 3);
+        assert_eq!(errors.dominant(), Some(("timed out", 2)));
+        assert_eq!(
+            errors.explain_zero(),
+            "No data transferred, 3 requests failed: timed out (2)"
+        );
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let timestamp = Utc.with_ymd_and_hms(2025, 1, 7, 14, 2, 30).unwrap();
+
+        let mut result = SpeedTestResult::new(timestamp, client(), server())
+            .with_download(TransferResult::new(267_875_000, Duration::from_secs(10)))
+            .with_upload(TransferResult::new(47_625_000, Duration::from_secs(10)));
+        result.idle_latency = Latency::from_samples(&[Duration::from_millis(9)], 0);
+
+        assert_eq!(
+            result.summary_line_in(&Utc),
+            "↓ 214.3 Mbps ↑ 38.1 Mbps · 9 ms · HKIX, Hong Kong · 2025-01-07 14:02"
+        );
+
+        result.share_url = Some("http://www.speedtest.net/result/17385920342.png".to_string());
+        assert_eq!(
+            result.summary_line_in(&Utc),
+            "↓ 214.3 Mbps ↑ 38.1 Mbps · 9 ms · HKIX, Hong Kong · 2025-01-07 14:02 · \
+             http://www.speedtest.net/result/17385920342.png"
+        );
+
+        result.share_url = None;
+        result.upload = None;
+        let aborted = result.with_aborted(true);
+        assert!(aborted.is_aborted());
+        assert_eq!(
+            aborted.summary_line_in(&Utc),
+            "↓ 214.3 Mbps ↑ - · 9 ms · HKIX, Hong Kong · aborted · 2025-01-07 14:02"
+        );
+    }
+
+    #[test]
+    fn test_detect_warnings() {
+        let mut result = SpeedTestResult::new(Utc::now(), client(), server());
+        assert!(result.detect_warnings().is_empty());
+
+        result.diagnostics.clock_skew_ms = Some(-90_000);
+        result.diagnostics.cpu_usage = Some(0.95);
+        result.diagnostics.rejected_servers = vec![RejectedServer::new(
+            server(),
+            "answered with an HTML page, likely a captive portal",
+        )];
+        result.diagnostics.download_counters = Some(CounterCheck::new(1_000_000, 400_000));
+        result.diagnostics.nat = Some(NatCheck::new(
+            [100, 72, 3, 9].into(),
+            WanSource::NatPmp,
+            "203.0.113.45",
+        ));
+
+        let warnings = result.detect_warnings();
+        let codes: Vec<&str> = warnings.iter().map(|w| w.code.as_str()).collect();
+        assert_eq!(
+            codes,
+            [
+                "clock_skew",
+                "captive_portal",
+                "cpu_bound",
+                "compression",
+                "cgnat"
+            ]
+        );
+        assert_eq!(
+            warnings[0].message,
+            "Local clock is 90 s behind the server, timestamps may be off"
+        );
+        assert_eq!(
+            serde_json::to_value(warnings[3].code).unwrap(),
+            WarningCode::Compression.as_str()
+        );
+    }
+
+    #[test]
+    fn test_transfer_result() {
+        let result = TransferResult::new(12_500_000, Duration::from_secs(10));
+
+        assert_eq!(result.elapsed_ms, 10_000);
+        assert_eq!(result.bits_per_second, 10_000_000.0);
+
+        assert_eq!(
+            TransferResult::new(100, Duration::ZERO).bits_per_second,
+            0.0
+        );
+
+        let split = result.with_warm_up(2_500_000, Duration::from_secs(5));
+        assert_eq!(split.bytes, 12_500_000);
+        assert_eq!(split.bits_per_second, 16_000_000.0);
+        let warm_up = split.warm_up.unwrap();
+        assert_eq!(
+            (warm_up.warm_up_bytes, warm_up.warm_up_ms),
+            (2_500_000, 5000)
+        );
+        assert_eq!(warm_up.raw_bits_per_second, 10_000_000.0);
+        assert_eq!(warm_up.steady_bits_per_second, split.bits_per_second);
+
+        // Ended during the warm-up.
+        let short = result.with_warm_up(12_500_000, Duration::from_secs(10));
+        assert!(short.warm_up.is_none());
+        assert_eq!(short.bits_per_second, 10_000_000.0);
+    }
+}
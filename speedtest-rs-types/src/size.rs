@@ -0,0 +1,99 @@
+//! Byte quantities with the unit carried in the type.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{GB, Humanize, KB, MB, error::ParseError};
+
+/// A number of bytes, e.g. the size of an upload request.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub const fn b(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn kib(kib: u64) -> Self {
+        Self(kib * KB as u64)
+    }
+
+    pub const fn mib(mib: u64) -> Self {
+        Self(mib * MB as u64)
+    }
+
+    pub const fn bytes(self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.humanize_bytes())
+    }
+}
+
+impl Humanize for ByteSize {
+    fn humanize_bytes(&self) -> String {
+        self.as_usize().humanize_bytes()
+    }
+
+    fn humanize_bitrate(&self, duration_millis: u64) -> String {
+        self.as_usize().humanize_bitrate(duration_millis)
+    }
+
+    fn humanize(&self) -> (f64, usize) {
+        self.as_usize().humanize()
+    }
+}
+
+/// Parses the sizes of the remote config, e.g. `512K`, `1M` or plain bytes.
+impl FromStr for ByteSize {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, unit) = match s.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+            Some((idx, _)) => s.split_at(idx),
+            None => (s, ""),
+        };
+
+        let multiplier = match unit.to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "K" | "KB" => KB,
+            "M" | "MB" => MB,
+            "G" | "GB" => GB,
+            _ => return Err(ParseError::new(format!("invalid size unit in '{s}'"))),
+        };
+        let number: u64 = number
+            .trim()
+            .parse()
+            .map_err(|_| ParseError::new(format!("invalid size '{s}'")))?;
+
+        Ok(Self(number * multiplier as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::size::ByteSize;
+
+    #[test]
+    fn test_byte_size() {
+        assert_eq!("512K".parse::<ByteSize>().unwrap(), ByteSize::kib(512));
+        assert_eq!("1M".parse::<ByteSize>().unwrap(), ByteSize::mib(1));
+        assert_eq!("250".parse::<ByteSize>().unwrap(), ByteSize::b(250));
+        assert!("12X".parse::<ByteSize>().is_err());
+        assert!("K".parse::<ByteSize>().is_err());
+
+        assert_eq!(ByteSize::kib(32).to_string(), "32.00 KBytes");
+    }
+}
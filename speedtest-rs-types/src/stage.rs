@@ -0,0 +1,334 @@
+//! Progress events of a run.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ParseError;
+
+/// A single latency probe of a server while racing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RaceProbe {
+    pub server_id: String,
+    /// 1-based number of the probe for this server.
+    pub attempt: usize,
+    /// `None` if the probe failed or timed out.
+    pub delay: Option<Duration>,
+}
+
+impl RaceProbe {
+    pub fn new(server_id: String, attempt: usize, delay: Option<Duration>) -> Self {
+        Self {
+            server_id,
+            attempt,
+            delay,
+        }
+    }
+}
+
+/// Phases of a complete run, weighted by their typical share of the total duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Stage {
+    /// Fetching the config and the server list.
+    Discovery,
+    Racing,
+    Latency,
+    Download,
+    Upload,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Discovery,
+        Stage::Racing,
+        Stage::Latency,
+        Stage::Download,
+        Stage::Upload,
+    ];
+
+    /// Share of the whole run, the weights of all stages add up to 1.
+    pub fn weight(self) -> f64 {
+        match self {
+            Stage::Discovery => 0.05,
+            Stage::Racing => 0.1,
+            Stage::Latency => 0.05,
+            Stage::Download => 0.4,
+            Stage::Upload => 0.4,
+        }
+    }
+
+    /// Overall progress in `0.0..=1.0` when this stage is `fraction` complete.
+    pub fn overall(self, fraction: f64) -> f64 {
+        let done: f64 = Self::ALL
+            .iter()
+            .take_while(|stage| **stage != self)
+            .map(|stage| stage.weight())
+            .sum();
+
+        (done + self.weight() * fraction.clamp(0.0, 1.0)).min(1.0)
+    }
+
+    /// Stable identifier for scripts and GUIs, the same as the serialized form.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Discovery => "discovery",
+            Stage::Racing => "racing",
+            Stage::Latency => "latency",
+            Stage::Download => "download",
+            Stage::Upload => "upload",
+        }
+    }
+
+    /// Name to show to users, for a locale such as `de`, `de_DE.UTF-8` or `zh-CN`. Falls back to
+    /// English for unknown languages.
+    pub fn display_name(self, locale: &str) -> &'static str {
+        let language = locale
+            .split(['_', '-', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match (language.as_str(), self) {
+            ("de", Stage::Discovery) => "Serversuche",
+            ("de", Stage::Racing) => "Serverauswahl",
+            ("de", Stage::Latency) => "Latenz",
+            ("de", Stage::Download) => "Download",
+            ("de", Stage::Upload) => "Upload",
+            ("zh", Stage::Discovery) => "获取服务器",
+            ("zh", Stage::Racing) => "选择服务器",
+            ("zh", Stage::Latency) => "延迟",
+            ("zh", Stage::Download) => "下载",
+            ("zh", Stage::Upload) => "上传",
+            (_, Stage::Discovery) => "Discovery",
+            (_, Stage::Racing) => "Server selection",
+            (_, Stage::Latency) => "Latency",
+            (_, Stage::Download) => "Download",
+            (_, Stage::Upload) => "Upload",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Stage {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|stage| stage.as_str() == s.trim())
+            .ok_or_else(|| ParseError::new(format!("unknown stage '{s}'")))
+    }
+}
+
+/// What a frontend learns about a running test, e.g. translated from the byte counters passed to
+/// the transfers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum StageEvent {
+    Started {
+        stage: Stage,
+    },
+    /// Time spent in the stage and bytes transferred so far, the latter zero outside transfers.
+    Progress {
+        stage: Stage,
+        elapsed: Duration,
+        bytes: u64,
+    },
+    Finished {
+        stage: Stage,
+    },
+}
+
+/// Turns [`StageEvent`]s into `(stage, fraction, rate)` tuples with the completed fraction of the
+/// stage in `0.0..=1.0` and the average rate in bits per second, for transfers only. Feed the
+/// stage and fraction to [`Stage::overall`] for a single progress bar.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressTracker {
+    expected: Vec<(Stage, Duration)>,
+    current: Option<(Stage, f64, Option<f64>)>,
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expected duration of a stage, e.g. the transfer durations of the remote config. Stages
+    /// without one stay at zero until they finish.
+    pub fn with_expected(mut self, stage: Stage, duration: Duration) -> Self {
+        self.expected.retain(|(s, _)| *s != stage);
+        self.expected.push((stage, duration));
+        self
+    }
+
+    pub fn update(&mut self, event: &StageEvent) -> (Stage, f64, Option<f64>) {
+        let current = match *event {
+            StageEvent::Started { stage } => (stage, 0.0, None),
+            StageEvent::Progress {
+                stage,
+                elapsed,
+                bytes,
+            } => {
+                let fraction = self
+                    .expected
+                    .iter()
+                    .find(|(s, _)| *s == stage)
+                    .map_or(0.0, |(_, expected)| time_fraction(elapsed, *expected));
+                let transfer = matches!(stage, Stage::Download | Stage::Upload);
+                let rate = (transfer && !elapsed.is_zero())
+                    .then(|| bytes as f64 * 8.0 / elapsed.as_secs_f64());
+                (stage, fraction, rate)
+            }
+            StageEvent::Finished { stage } => {
+                // Keep the last rate of the stage as its final value.
+                let rate = self
+                    .current
+                    .filter(|(s, _, _)| *s == stage)
+                    .and_then(|(_, _, rate)| rate);
+                (stage, 1.0, rate)
+            }
+        };
+        self.current = Some(current);
+        current
+    }
+
+    /// The tuple after the last event, `None` before the first one.
+    pub fn current(&self) -> Option<(Stage, f64, Option<f64>)> {
+        self.current
+    }
+
+    pub fn track<I>(mut self, events: I) -> impl Iterator<Item = (Stage, f64, Option<f64>)>
+    where
+        I: IntoIterator<Item = StageEvent>,
+    {
+        events.into_iter().map(move |event| self.update(&event))
+    }
+}
+
+/// Completed fraction of a stage bounded by time, such as the transfers.
+pub fn time_fraction(elapsed: Duration, expected: Duration) -> f64 {
+    if expected.is_zero() {
+        return 1.0;
+    }
+    (elapsed.as_secs_f64() / expected.as_secs_f64()).min(1.0)
+}
+
+/// Throughput sampling interval for a transfer of the `expected` duration: about 50 samples, in
+/// steps of 50ms between 50ms for quick tests and 500ms for long soak runs.
+pub fn sample_interval(expected: Duration) -> Duration {
+    let steps = (expected.as_millis() / 50 / 50).clamp(1, 10);
+    Duration::from_millis(steps as u64 * 50)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::stage::{ProgressTracker, Stage, StageEvent, sample_interval, time_fraction};
+
+    #[test]
+    fn test_stage_progress() {
+        let total: f64 = Stage::ALL.iter().map(|s| s.weight()).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        assert_eq!(Stage::Discovery.overall(0.0), 0.0);
+        assert!((Stage::Download.overall(0.5) - 0.4).abs() < 1e-9);
+        assert!((Stage::Upload.overall(1.0) - 1.0).abs() < 1e-9);
+
+        assert_eq!(
+            time_fraction(Duration::from_secs(5), Duration::from_secs(10)),
+            0.5
+        );
+        assert_eq!(
+            time_fraction(Duration::from_secs(20), Duration::from_secs(10)),
+            1.0
+        );
+        assert_eq!(time_fraction(Duration::from_secs(1), Duration::ZERO), 1.0);
+    }
+
+    #[test]
+    fn test_sample_interval() {
+        let ms = |secs| sample_interval(Duration::from_secs(secs)).as_millis();
+        assert_eq!(sample_interval(Duration::ZERO).as_millis(), 50);
+        assert_eq!(ms(2), 50);
+        assert_eq!(ms(5), 100);
+        assert_eq!(ms(10), 200);
+        assert_eq!(ms(15), 300);
+        assert_eq!(ms(60), 500);
+    }
+
+    #[test]
+    fn test_stage_names() {
+        for stage in Stage::ALL {
+            assert_eq!(stage.to_string().parse::<Stage>().unwrap(), stage);
+            let json = serde_json::to_string(&stage).unwrap();
+            assert_eq!(json, format!("\"{}\"", stage.as_str()));
+        }
+        assert_eq!(Stage::Download.as_str(), "download");
+        assert!("downloading".parse::<Stage>().is_err());
+
+        assert_eq!(
+            Stage::Racing.display_name("en_US.UTF-8"),
+            "Server selection"
+        );
+        assert_eq!(Stage::Latency.display_name("de_DE.UTF-8"), "Latenz");
+        assert_eq!(Stage::Upload.display_name("zh-CN"), "上传");
+        assert_eq!(Stage::Upload.display_name("C"), "Upload");
+    }
+
+    #[test]
+    fn test_progress_tracker() {
+        let events = vec![
+            StageEvent::Started {
+                stage: Stage::Latency,
+            },
+            StageEvent::Progress {
+                stage: Stage::Latency,
+                elapsed: Duration::from_secs(1),
+                bytes: 0,
+            },
+            StageEvent::Started {
+                stage: Stage::Download,
+            },
+            StageEvent::Progress {
+                stage: Stage::Download,
+                elapsed: Duration::from_secs(5),
+                bytes: 12_500_000,
+            },
+            StageEvent::Finished {
+                stage: Stage::Download,
+            },
+        ];
+        let tuples: Vec<_> = ProgressTracker::new()
+            .with_expected(Stage::Download, Duration::from_secs(10))
+            .track(events)
+            .collect();
+
+        assert_eq!(
+            tuples,
+            vec![
+                (Stage::Latency, 0.0, None),
+                // No expected duration for the latency.
+                (Stage::Latency, 0.0, None),
+                (Stage::Download, 0.0, None),
+                (Stage::Download, 0.5, Some(20_000_000.0)),
+                (Stage::Download, 1.0, Some(20_000_000.0)),
+            ]
+        );
+
+        let json = serde_json::to_string(&StageEvent::Finished {
+            stage: Stage::Upload,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"event":"finished","stage":"upload"}"#);
+    }
+}
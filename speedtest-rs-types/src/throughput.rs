@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+/// Distribution of the throughput samples of a transfer phase, to tell a steady link from a
+/// flaky one with the same average. Rates are in bits per second.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ThroughputStats {
+    pub min_bps: f64,
+    pub avg_bps: f64,
+    pub max_bps: f64,
+    pub p50_bps: f64,
+    pub p90_bps: f64,
+    pub p99_bps: f64,
+    /// Population standard deviation.
+    pub std_dev_bps: f64,
+    /// Standard deviation relative to the average, `0.0` if nothing was transferred.
+    pub cv: f64,
+    pub samples: usize,
+}
+
+impl ThroughputStats {
+    /// Returns `None` without samples.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len() as f64;
+
+        let avg_bps = sorted.iter().sum::<f64>() / n;
+        let variance = sorted.iter().map(|s| (s - avg_bps).powi(2)).sum::<f64>() / n;
+        let std_dev_bps = variance.sqrt();
+        let cv = if avg_bps > 0.0 {
+            std_dev_bps / avg_bps
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            min_bps: sorted[0],
+            avg_bps,
+            max_bps: sorted[sorted.len() - 1],
+            p50_bps: percentile(&sorted, 50.0),
+            p90_bps: percentile(&sorted, 90.0),
+            p99_bps: percentile(&sorted, 99.0),
+            std_dev_bps,
+            cv,
+            samples: sorted.len(),
+        })
+    }
+}
+
+/// Nearest-rank percentile of ascending `sorted` samples.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::throughput::ThroughputStats;
+
+    #[test]
+    fn test_throughput_stats() {
+        let samples: Vec<f64> = (1..=10).rev().map(|mbps| mbps as f64 * 1e6).collect();
+        let stats = ThroughputStats::from_samples(&samples).unwrap();
+
+        assert_eq!(stats.min_bps, 1e6);
+        assert_eq!(stats.max_bps, 10e6);
+        assert_eq!(stats.avg_bps, 5.5e6);
+        assert_eq!(stats.p50_bps, 5e6);
+        assert_eq!(stats.p90_bps, 9e6);
+        assert_eq!(stats.p99_bps, 10e6);
+        assert!((stats.std_dev_bps - 8.25f64.sqrt() * 1e6).abs() < 1e-3);
+        assert!((stats.cv - stats.std_dev_bps / 5.5e6).abs() < 1e-12);
+        assert_eq!(stats.samples, 10);
+
+        let idle = ThroughputStats::from_samples(&[0.0, 0.0]).unwrap();
+        assert_eq!((idle.std_dev_bps, idle.cv), (0.0, 0.0));
+        assert_eq!(ThroughputStats::from_samples(&[]), None);
+    }
+}